@@ -0,0 +1,138 @@
+// Compiles the shaders listed in res/shaders.manifest, and generates the table src/shaders.rs embeds them with.
+//
+// The compiler is picked by the SHADER_COMPILER environment variable:
+//     d3dcompiler     D3DCompile, the default when building for Windows on Windows
+//     precompiled     Read res/precompiled/{name}.dxbc, the default when not building for Windows.  Building for
+//                     Windows elsewhere (cross compiling from Linux) must opt in to it explicitly.
+//     fake            Embed nothing, leaving shaders to the runtime fallback.  The default when cross compiling for
+//                     Windows, and an error in release builds.
+//
+// Next to the bytecode, $OUT_DIR/shaders/sources.txt records what each shader was compiled from.  precompiled fails if
+// res/precompiled/sources.txt doesn't match the current sources.
+
+// Build scripts run before the package's library is built, so can't depend on it:  these std-only modules are
+// compiled in directly instead.
+#[path = "src/com/d3d/compile_options.rs"] #[allow(dead_code)] mod compile_options;
+#[path = "src/shader_manifest.rs"] #[allow(dead_code)] mod shader_manifest;
+
+use shader_manifest::{Compiler, Fake, Precompiled};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main () {
+    let res = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("res");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let bytecode_dir = out_dir.join("shaders");
+    println!("cargo:rerun-if-changed=res");
+    println!("cargo:rerun-if-env-changed=SHADER_COMPILER");
+
+    let manifest_path = res.join("shaders.manifest");
+    let manifest = fs::read_to_string(&manifest_path).unwrap_or_else(|e| panic!("{}: {}", manifest_path.display(), e));
+    let entries = shader_manifest::parse(&manifest).unwrap_or_else(|e| panic!("{}", e));
+
+    let release = env::var("PROFILE").is_ok_and(|p| p == "release");
+    let compiler = select_compiler(&res, release);
+
+    fs::create_dir_all(&bytecode_dir).unwrap();
+    let mut hashes = Vec::new();
+    for entry in entries.iter() {
+        let source_path = res.join(&entry.source);
+        let source = fs::read(&source_path).unwrap_or_else(|e| panic!("{}: {}", source_path.display(), e));
+        let bytecode = compiler.compile(entry, &source_path, &source[..]).unwrap_or_else(|e| panic!("{}", e));
+        fs::write(bytecode_dir.join(&entry.name).with_extension("dxbc"), bytecode).unwrap();
+        hashes.push((&entry.name[..], shader_manifest::source_hash(entry, &source[..])));
+    }
+    fs::write(bytecode_dir.join("sources.txt"), shader_manifest::sources_txt(&hashes[..])).unwrap();
+
+    fs::write(out_dir.join("embedded_shaders.rs"), shader_manifest::embed(&entries[..], &bytecode_dir)).unwrap();
+}
+
+fn select_compiler (res: &Path, release: bool) -> Box<dyn Compiler> {
+    // cfg!(windows) is the host this script runs on, which is where D3DCompile has to be.  What's being built for is
+    // CARGO_CFG_TARGET_OS.
+    let target_windows = env::var("CARGO_CFG_TARGET_OS").is_ok_and(|os| os == "windows");
+    // res/precompiled isn't fxc output (see its README.md), so executables only ship it when asked to.
+    let precompiled = res.join("precompiled");
+    let default = if target_windows { if cfg!(windows) { "d3dcompiler" } else { "fake" } } else if precompiled.exists() { "precompiled" } else { "fake" };
+    match env::var("SHADER_COMPILER").as_ref().map_or(default, |c| &c[..]) {
+        #[cfg(windows)]
+        "d3dcompiler"   => Box::new(d3dcompiler::D3DCompiler { release }),
+        "precompiled"   => Box::new(Precompiled { dir: precompiled }),
+        "fake" if release => panic!("SHADER_COMPILER=fake:  release builds don't compile shaders at runtime, so must embed them (build on Windows for d3dcompiler, or set SHADER_COMPILER=precompiled)"),
+        "fake"          => Box::new(Fake),
+        other           => panic!("SHADER_COMPILER={:?} isn't supported on this host", other),
+    }
+}
+
+#[cfg(windows)]
+mod d3dcompiler {
+    use super::*;
+    use super::compile_options::CompileOptions;
+    use super::shader_manifest::Entry;
+    use std::ffi::CString;
+    use std::ptr::{null, null_mut};
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::um::d3dcommon::{ID3DBlob, D3D_SHADER_MACRO};
+    use winapi::um::d3dcompiler::{D3DCompile, D3D_COMPILE_STANDARD_FILE_INCLUDE};
+
+    pub struct D3DCompiler {
+        pub release: bool,
+    }
+
+    /// Applies `entry.options` on top of the build profile's preset.
+    fn options (entry: &Entry, release: bool) -> Result<CompileOptions, String> {
+        let mut options = if release { CompileOptions::release() } else { CompileOptions::debug() };
+        for switch in entry.options.iter() {
+            if !options.apply_switch(switch) { return Err(format!("{}: unknown option {:?}", entry.name, switch)); }
+        }
+        Ok(options)
+    }
+
+    unsafe fn blob_string (blob: *mut ID3DBlob) -> String {
+        if blob.is_null() { return String::new(); }
+        let bytes = std::slice::from_raw_parts((*blob).GetBufferPointer() as *const u8, (*blob).GetBufferSize());
+        let s = String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string();
+        (*blob).Release();
+        s
+    }
+
+    impl Compiler for D3DCompiler {
+        fn compile (&self, entry: &Entry, source_path: &Path, source: &[u8]) -> Result<Vec<u8>, String> {
+            let options = options(entry, self.release)?;
+            let source_name = CString::new(source_path.to_string_lossy().into_owned()).unwrap();
+            let entrypoint = CString::new(entry.entrypoint.clone()).unwrap();
+            let target = CString::new(entry.target.clone()).unwrap();
+            let defines : Vec<(CString, CString)> = entry.defines.iter().map(|(name, value)| (CString::new(name.clone()).unwrap(), CString::new(value.clone()).unwrap())).collect();
+            let mut macros : Vec<D3D_SHADER_MACRO> = defines.iter().map(|(name, value)| D3D_SHADER_MACRO { Name: name.as_ptr(), Definition: value.as_ptr() }).collect();
+            macros.push(D3D_SHADER_MACRO { Name: null(), Definition: null() });
+
+            unsafe {
+                let mut code = null_mut();
+                let mut errors = null_mut();
+                let hresult = D3DCompile(
+                    source.as_ptr() as *const _,
+                    source.len(),
+                    source_name.as_ptr(),
+                    macros.as_ptr(),
+                    D3D_COMPILE_STANDARD_FILE_INCLUDE,
+                    entrypoint.as_ptr(),
+                    target.as_ptr(),
+                    options.flags1(),
+                    options.flags2(),
+                    &mut code,
+                    &mut errors
+                );
+                let messages = blob_string(errors);
+                if SUCCEEDED(hresult) {
+                    for line in messages.lines() { println!("cargo:warning={}", line); }
+                    let bytecode = std::slice::from_raw_parts((*code).GetBufferPointer() as *const u8, (*code).GetBufferSize()).to_vec();
+                    (*code).Release();
+                    Ok(bytecode)
+                } else {
+                    Err(format!("{}: D3DCompile failed with HRESULT=0x{:08X}\n{}", entry.name, hresult, messages))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! LZ4 block format compression:  a token byte (literal count << 4 | match length - 4, where 15 means more length
+//! bytes follow), the literals, then a 2 byte backwards offset and any extra match length bytes.  The last sequence
+//! is literals only.
+//!
+//! Fast enough to decompress at load time, and small enough not to need a dependency.
+
+const MIN_MATCH     : usize = 4;
+/// Matches can't start in the last 12 bytes...
+const MATCH_LIMIT   : usize = 12;
+/// ...or extend into the last 5, which are always literals.
+const LAST_LITERALS : usize = 5;
+const HASH_LOG      : u32   = 16;
+
+pub fn compress (input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    let mut table = vec![0usize; 1 << HASH_LOG]; // Position + 1 of the last 4 bytes with each hash, 0 if none
+    let match_limit = input.len().saturating_sub(MATCH_LIMIT);
+    let end_limit = input.len().saturating_sub(LAST_LITERALS);
+
+    let mut anchor = 0;
+    let mut i = 0;
+    while i < match_limit {
+        let sequence = u32_at(input, i);
+        let hash = (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize;
+        let candidate = table[hash];
+        table[hash] = i + 1;
+
+        if candidate != 0 && i - (candidate - 1) <= 0xFFFF && u32_at(input, candidate - 1) == sequence {
+            let start = candidate - 1;
+            let mut len = MIN_MATCH;
+            while i + len < end_limit && input[start + len] == input[i + len] { len += 1; }
+            write_sequence(&mut out, &input[anchor..i], Some((i - start, len)));
+            i += len;
+            anchor = i;
+        } else {
+            i += 1;
+        }
+    }
+    write_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+/// Decompress `input`, which must decompress to exactly `size` bytes.  `None` if it's corrupt.
+pub fn decompress (input: &[u8], size: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(size.min(input.len().saturating_mul(255))); // Don't trust `size` too far
+    let mut i = 0;
+    loop {
+        let token = *input.get(i)?;
+        i += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 { literals = literals.checked_add(read_length(input, &mut i)?)?; }
+        if out.len().checked_add(literals)? > size { return None; }
+        out.extend_from_slice(input.get(i..i.checked_add(literals)?)?);
+        i += literals;
+        if i == input.len() { break; }
+
+        let offset = u16::from_le_bytes([*input.get(i)?, *input.get(i + 1)?]) as usize;
+        i += 2;
+        let mut len = (token & 15) as usize;
+        if len == 15 { len = len.checked_add(read_length(input, &mut i)?)?; }
+        len = len.checked_add(MIN_MATCH)?;
+        if offset == 0 || offset > out.len() || out.len().checked_add(len)? > size { return None; }
+
+        // Byte by byte:  the match may overlap what it's copying, e.g. offset 1 repeats the last byte.
+        for _ in 0..len {
+            let byte = out[out.len() - offset];
+            out.push(byte);
+        }
+    }
+    if out.len() == size { Some(out) } else { None }
+}
+
+fn u32_at (input: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([input[i], input[i+1], input[i+2], input[i+3]])
+}
+
+fn write_sequence (out: &mut Vec<u8>, literals: &[u8], found: Option<(usize, usize)>) {
+    let match_len = found.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push((literals.len().min(15) << 4) as u8 | match_len.min(15) as u8);
+    if literals.len() >= 15 { write_length(out, literals.len() - 15); }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = found {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 { write_length(out, match_len - 15); }
+    }
+}
+
+fn write_length (out: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        out.push(255);
+        length -= 255;
+    }
+    out.push(length as u8);
+}
+
+fn read_length (input: &[u8], i: &mut usize) -> Option<usize> {
+    let mut length = 0usize;
+    loop {
+        let byte = *input.get(*i)?;
+        *i += 1;
+        length = length.checked_add(byte as usize)?;
+        if byte != 255 { return Some(length); }
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    /// xorshift, for incompressible input.
+    fn noise (len: usize) -> Vec<u8> {
+        let mut state = 0x2545F491u32;
+        (0..len).map(|_| { state ^= state << 13; state ^= state >> 17; state ^= state << 5; state as u8 }).collect()
+    }
+
+    fn round_trip (input: &[u8]) -> Vec<u8> {
+        let compressed = compress(input);
+        assert_eq!(decompress(&compressed[..], input.len()).as_deref(), Some(input), "{} bytes", input.len());
+        compressed
+    }
+
+    #[test] fn round_trips () {
+        for len in 0..40 { round_trip(&noise(len)[..]); round_trip(&vec![b'a'; len][..]); }
+        round_trip(&noise(100_000)[..]);
+        round_trip(&include_bytes!("../../tests/data/fxc/sdl_vs_9_1.dxbc")[..]);
+        round_trip(&b"the quick brown fox jumps over the lazy dog, the quick brown dog jumps over the lazy fox"[..]);
+
+        // Literal and match lengths of 15, 15 + 255, and more, which take extra length bytes
+        for &len in [14, 15, 16, 19, 20, 269, 270, 271, 274, 275, 1000].iter() {
+            let mut literals_then_match = noise(len);
+            literals_then_match.extend_from_slice(&vec![7u8; len][..]);
+            literals_then_match.extend_from_slice(&noise(len)[..]);
+            round_trip(&literals_then_match[..]);
+        }
+    }
+
+    #[test] fn compresses () {
+        assert!(round_trip(&vec![0u8; 65536][..]).len() < 300);
+        assert!(round_trip(&b"abcd".repeat(1000)[..]).len() < 50);
+        // Incompressible input grows by a token and length bytes, no more
+        assert!(round_trip(&noise(1000)[..]).len() <= 1000 + 1 + 4);
+        // The last 5 bytes are always literals
+        let compressed = compress(&vec![1u8; 100][..]);
+        assert_eq!(&compressed[compressed.len() - 5..], &[1u8; 5][..]);
+    }
+
+    #[test] fn reference_block () {
+        // Written out by hand from the block format:  "abc", a 9 byte match 3 back, then the literals "xyzzy"
+        let block = [0x35, b'a', b'b', b'c', 3, 0, 0x50, b'x', b'y', b'z', b'z', b'y'];
+        assert_eq!(decompress(&block[..], 17).as_deref(), Some(&b"abcabcabcabcxyzzy"[..]));
+        assert_eq!(decompress(&[0x00][..], 0).as_deref(), Some(&b""[..]));
+        assert_eq!(compress(&[]), [0x00]);
+    }
+
+    #[test] fn malformed () {
+        let input = b"abcd".repeat(100);
+        let compressed = compress(&input[..]);
+
+        assert_eq!(decompress(&[], 0), None);
+        assert_eq!(decompress(&compressed[..], input.len() - 1), None, "too big");
+        assert_eq!(decompress(&compressed[..], input.len() + 1), None, "too small");
+        for len in 0..compressed.len() { assert_eq!(decompress(&compressed[..len], input.len()), None, "truncated to {}", len); }
+
+        assert_eq!(decompress(&[0x10, b'a', 0, 0, 0x00][..], 5),  None, "offset 0");
+        assert_eq!(decompress(&[0x10, b'a', 2, 0, 0x00][..], 5),  None, "offset before the start");
+        assert_eq!(decompress(&[0xF0, 255, 255, 255][..], 1000), None, "literals past the end");
+        let mut endless = vec![0x1F, b'a', 1, 0];
+        endless.extend_from_slice(&[255u8; 64][..]);
+        assert_eq!(decompress(&endless[..], 100), None, "match length past the end");
+        assert_eq!(decompress(&[0x1F, b'a', 1, 0, 255, 255, 255, 255, 255, 255, 255, 255][..], usize::MAX), None);
+    }
+}
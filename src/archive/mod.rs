@@ -0,0 +1,226 @@
+//! A packed asset archive:  shaders and other resources in a single file, with an index, per-entry compression,
+//! content hashes, and aligned data for memory mapped access.
+//!
+//! Nothing in here touches `winapi`.  `Writer` builds archives (see the `assetpack` tool), `Archive` reads them from
+//! memory (a `Vec`, `include_bytes!`, a `MappedFile`, ...) and `StreamReader` reads entries on demand from any
+//! `Read + Seek`.
+//!
+//! All integers are little endian:
+//!
+//! ```text
+//! header  "APAK", u32 version, u64 index offset, u64 index size, u64 index hash
+//! data    each entry's stored bytes, starting at a multiple of its alignment
+//! index   u32 entry count, then per entry:
+//!             u16 name length, UTF-8 name, u8 compression, u64 offset, u64 stored size, u64 size, u64 hash
+//! ```
+//!
+//! Hashes are FNV-1a 64 - enough to catch truncated or corrupted files, not tampering.  `hash` is of the entry's
+//! decompressed contents, and is checked on every load.
+
+mod lz;
+mod reader;
+mod writer;
+pub use reader::*;
+pub use writer::*;
+
+use std::fmt;
+use std::io;
+
+pub const MAGIC : [u8; 4] = *b"APAK";
+
+/// Bumped on any layout change.  Readers reject versions they don't know rather than guessing.
+pub const VERSION : u32 = 1;
+
+const HEADER_SIZE : usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Compression {
+    None,
+    /// LZ4 block format.
+    Lz4,
+}
+
+impl Compression {
+    fn from_u8 (value: u8) -> Option<Compression> {
+        match value {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+
+    fn to_u8 (self) -> u8 {
+        match self {
+            Compression::None   => 0,
+            Compression::Lz4    => 1,
+        }
+    }
+}
+
+/// An index entry.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    /// `/` separated, e.g. `"shaders/vs.dxbc"`.
+    pub name:           String,
+    pub compression:    Compression,
+    /// Of the stored bytes, from the start of the archive.
+    pub offset:         u64,
+    pub stored_size:    u64,
+    /// Decompressed.
+    pub size:           u64,
+    pub hash:           u64,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// Doesn't start with `"APAK"`.
+    NotArchive,
+    /// Written by a newer (or older) `Writer` than this reader understands.
+    UnsupportedVersion(u32),
+    /// Something at `offset` runs past the end of the archive.
+    Truncated { offset: u64 },
+    /// The index doesn't match its hash, or describes entries that don't fit.
+    CorruptIndex,
+    /// An entry's data doesn't decompress, or doesn't match its hash.
+    CorruptEntry(String),
+    /// No entry by that name.
+    NotFound(String),
+    /// `Writer::add` was given a name twice.
+    DuplicateName(String),
+    /// Names are limited to 65535 bytes.
+    NameTooLong(String),
+    /// `Options::align` must be a power of two.
+    BadAlignment(u32),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NotArchive                   => write!(f, "not an asset archive"),
+            Error::UnsupportedVersion(version)  => write!(f, "unsupported asset archive version {} (expected {})", version, VERSION),
+            Error::Truncated { offset }         => write!(f, "asset archive truncated at offset {}", offset),
+            Error::CorruptIndex                 => write!(f, "asset archive index is corrupt"),
+            Error::CorruptEntry(ref name)       => write!(f, "asset {:?} is corrupt", name),
+            Error::NotFound(ref name)           => write!(f, "no asset named {:?}", name),
+            Error::DuplicateName(ref name)      => write!(f, "asset {:?} added twice", name),
+            Error::NameTooLong(ref name)        => write!(f, "asset name {:?}... is too long", name.chars().take(32).collect::<String>()),
+            Error::BadAlignment(align)          => write!(f, "alignment {} isn't a power of two", align),
+            Error::Io(ref e)                    => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from (e: io::Error) -> Self { Error::Io(e) }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// FNV-1a 64.
+pub fn hash (data: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for &byte in data.iter() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+struct Header {
+    index_offset:   u64,
+    index_size:     u64,
+    index_hash:     u64,
+}
+
+impl Header {
+    fn parse (bytes: &[u8]) -> Result<Header> {
+        if bytes.len() < HEADER_SIZE { return Err(Error::Truncated { offset: bytes.len() as u64 }); }
+        if bytes[0..4] != MAGIC { return Err(Error::NotArchive); }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != VERSION { return Err(Error::UnsupportedVersion(version)); }
+        Ok(Header {
+            index_offset:   u64_at(bytes, 8),
+            index_size:     u64_at(bytes, 16),
+            index_hash:     u64_at(bytes, 24),
+        })
+    }
+
+    fn write (&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC[..]);
+        bytes[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.index_size.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.index_hash.to_le_bytes());
+        bytes
+    }
+}
+
+fn u64_at (bytes: &[u8], offset: usize) -> u64 {
+    let mut le = [0u8; 8];
+    le.copy_from_slice(&bytes[offset..offset+8]);
+    u64::from_le_bytes(le)
+}
+
+/// Parse the index, checking it against the header's hash and that every entry lies between the header and index.
+fn parse_index (header: &Header, index: &[u8]) -> Result<Vec<Entry>> {
+    if hash(index) != header.index_hash { return Err(Error::CorruptIndex); }
+
+    let mut position = 0;
+    let mut take = |n: usize| -> Result<&[u8]> {
+        let taken = index.get(position..position + n).ok_or(Error::CorruptIndex)?;
+        position += n;
+        Ok(taken)
+    };
+
+    let count = take(4)?;
+    let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]);
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let name_len = take(2)?;
+        let name_len = u16::from_le_bytes([name_len[0], name_len[1]]) as usize;
+        let name = String::from_utf8(take(name_len)?.to_vec()).map_err(|_| Error::CorruptIndex)?;
+        let compression = Compression::from_u8(take(1)?[0]).ok_or(Error::CorruptIndex)?;
+        let fields = take(32)?;
+        let entry = Entry {
+            name,
+            compression,
+            offset:         u64_at(fields, 0),
+            stored_size:    u64_at(fields, 8),
+            size:           u64_at(fields, 16),
+            hash:           u64_at(fields, 24),
+        };
+        let end = entry.offset.checked_add(entry.stored_size).ok_or(Error::CorruptIndex)?;
+        if entry.offset < HEADER_SIZE as u64 || end > header.index_offset { return Err(Error::CorruptIndex); }
+        if entry.compression == Compression::None && entry.size != entry.stored_size { return Err(Error::CorruptIndex); }
+        entries.push(entry);
+    }
+    if position != index.len() { return Err(Error::CorruptIndex); }
+    Ok(entries)
+}
+
+fn write_index (entries: &[Entry]) -> Vec<u8> {
+    let mut index = Vec::new();
+    index.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries.iter() {
+        index.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        index.extend_from_slice(entry.name.as_bytes());
+        index.push(entry.compression.to_u8());
+        index.extend_from_slice(&entry.offset.to_le_bytes());
+        index.extend_from_slice(&entry.stored_size.to_le_bytes());
+        index.extend_from_slice(&entry.size.to_le_bytes());
+        index.extend_from_slice(&entry.hash.to_le_bytes());
+    }
+    index
+}
+
+/// Decompress (if needed) and hash check an entry's stored bytes.
+fn unpack (entry: &Entry, stored: &[u8]) -> Result<Vec<u8>> {
+    let contents = match entry.compression {
+        Compression::None   => stored.to_vec(),
+        Compression::Lz4    => lz::decompress(stored, entry.size as usize).ok_or_else(|| Error::CorruptEntry(entry.name.clone()))?,
+    };
+    if hash(&contents[..]) != entry.hash { return Err(Error::CorruptEntry(entry.name.clone())); }
+    Ok(contents)
+}
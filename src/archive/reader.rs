@@ -0,0 +1,123 @@
+use super::*;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// An archive in memory:  read into a `Vec`, `include_bytes!`ed, or a `MappedFile`.  Uncompressed entries are
+/// borrowed straight out of `D`, so `Blob::from_data(archive.load(name)?)` doesn't copy them.
+pub struct Archive<D: AsRef<[u8]>> {
+    data:       Arc<D>,
+    entries:    Vec<Entry>,
+}
+
+impl<D: AsRef<[u8]>> Clone for Archive<D> {
+    fn clone (&self) -> Self { Self { data: self.data.clone(), entries: self.entries.clone() } }
+}
+
+impl<D: AsRef<[u8]>> Archive<D> {
+    /// Parse the header and index.  Entries aren't hash checked until they're loaded, see `verify` to check them all.
+    pub fn parse (data: D) -> Result<Self> {
+        let bytes = data.as_ref();
+        let header = Header::parse(bytes)?;
+        let index = (header.index_offset as usize).checked_add(header.index_size as usize)
+            .and_then(|end| bytes.get(header.index_offset as usize .. end))
+            .ok_or(Error::Truncated { offset: bytes.len() as u64 })?;
+        let entries = parse_index(&header, index)?;
+        Ok(Self { data: Arc::new(data), entries })
+    }
+
+    pub fn entries (&self) -> &[Entry] { &self.entries[..] }
+
+    pub fn entry (&self, name: &str) -> Option<&Entry> { self.entries.iter().find(|e| e.name == name) }
+
+    /// `name`'s contents, decompressing and hash checking them.
+    pub fn load (&self, name: &str) -> Result<Contents<D>> {
+        let entry = self.entry(name).ok_or_else(|| Error::NotFound(name.to_string()))?;
+        let range = entry.offset as usize .. (entry.offset + entry.stored_size) as usize; // parse checked the bounds
+        match entry.compression {
+            Compression::None => {
+                if hash(&self.data.as_ref().as_ref()[range.clone()]) != entry.hash { return Err(Error::CorruptEntry(entry.name.clone())); }
+                Ok(Contents::Stored { data: self.data.clone(), range })
+            },
+            _ => Ok(Contents::Unpacked(unpack(entry, &self.data.as_ref().as_ref()[range])?)),
+        }
+    }
+
+    /// Load every entry, returning the first that's corrupt.
+    pub fn verify (&self) -> Result<()> {
+        for entry in self.entries.iter() { self.load(&entry.name)?; }
+        Ok(())
+    }
+}
+
+/// An entry's contents, from `Archive::load`.
+pub enum Contents<D: AsRef<[u8]>> {
+    /// Uncompressed, and still in the archive.
+    Stored { data: Arc<D>, range: Range<usize> },
+    /// Decompressed into memory of its own.
+    Unpacked(Vec<u8>),
+}
+
+impl<D: AsRef<[u8]>> AsRef<[u8]> for Contents<D> {
+    fn as_ref (&self) -> &[u8] {
+        match *self {
+            Contents::Stored { ref data, ref range }    => &data.as_ref().as_ref()[range.clone()],
+            Contents::Unpacked(ref bytes)               => &bytes[..],
+        }
+    }
+}
+
+/// An archive read on demand, e.g. from a `File`, for when mapping or reading all of it isn't wanted.  Only the index
+/// is kept in memory.
+pub struct StreamReader<R: Read + Seek> {
+    input:      R,
+    entries:    Vec<Entry>,
+}
+
+impl<R: Read + Seek> StreamReader<R> {
+    /// Read the header and index.
+    pub fn new (mut input: R) -> Result<Self> {
+        let mut header = [0u8; HEADER_SIZE];
+        input.seek(SeekFrom::Start(0))?;
+        read_exact(&mut input, 0, &mut header[..])?;
+        let header = Header::parse(&header[..])?;
+
+        // Check before allocating:  the header isn't covered by a hash.
+        let len = input.seek(SeekFrom::End(0))?;
+        if header.index_offset.checked_add(header.index_size).filter(|&end| end <= len).is_none() { return Err(Error::Truncated { offset: len }); }
+        let mut index = vec![0u8; header.index_size as usize];
+        input.seek(SeekFrom::Start(header.index_offset))?;
+        read_exact(&mut input, header.index_offset, &mut index[..])?;
+        let entries = parse_index(&header, &index[..])?;
+        Ok(Self { input, entries })
+    }
+
+    pub fn entries (&self) -> &[Entry] { &self.entries[..] }
+
+    pub fn entry (&self, name: &str) -> Option<&Entry> { self.entries.iter().find(|e| e.name == name) }
+
+    /// Read, decompress, and hash check `name`'s contents.
+    pub fn read (&mut self, name: &str) -> Result<Vec<u8>> {
+        let entry = self.entries.iter().find(|e| e.name == name).ok_or_else(|| Error::NotFound(name.to_string()))?;
+        let mut stored = vec![0u8; entry.stored_size as usize];
+        self.input.seek(SeekFrom::Start(entry.offset))?;
+        read_exact(&mut self.input, entry.offset, &mut stored[..])?;
+        match entry.compression {
+            Compression::None => {
+                if hash(&stored[..]) != entry.hash { return Err(Error::CorruptEntry(entry.name.clone())); }
+                Ok(stored)
+            },
+            _ => unpack(entry, &stored[..]),
+        }
+    }
+
+    pub fn into_inner (self) -> R { self.input }
+}
+
+/// `read_exact`, reporting running out of input as `Truncated`.
+fn read_exact<R: Read> (input: &mut R, offset: u64, buf: &mut [u8]) -> Result<()> {
+    input.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof    => Error::Truncated { offset },
+        _                               => Error::Io(e),
+    })
+}
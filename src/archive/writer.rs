@@ -0,0 +1,74 @@
+use super::*;
+use std::io::{Seek, SeekFrom, Write};
+
+/// How `Writer::add` stores an entry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Options {
+    /// Compress, if that makes it smaller.  Compressed entries can't be borrowed from a mapped archive.
+    pub compress:   bool,
+    /// Power of two the stored bytes' offset is a multiple of.
+    pub align:      u32,
+}
+
+impl Default for Options {
+    fn default () -> Self { Self { compress: true, align: 16 } }
+}
+
+/// Writes an archive entry by entry, then the index on `finish`.  Use a `Cursor<Vec<u8>>` to build one in memory.
+pub struct Writer<W: Write + Seek> {
+    output:     W,
+    offset:     u64,
+    entries:    Vec<Entry>,
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Start an archive at the beginning of `output`.  The header is filled in by `finish`.
+    pub fn new (mut output: W) -> Result<Self> {
+        output.seek(SeekFrom::Start(0))?;
+        output.write_all(&[0u8; HEADER_SIZE][..])?;
+        Ok(Self { output, offset: HEADER_SIZE as u64, entries: Vec::new() })
+    }
+
+    pub fn entries (&self) -> &[Entry] { &self.entries[..] }
+
+    pub fn add (&mut self, name: &str, data: &[u8], options: &Options) -> Result<()> {
+        if !options.align.is_power_of_two() { return Err(Error::BadAlignment(options.align)); }
+        if name.len() > u16::MAX as usize { return Err(Error::NameTooLong(name.to_string())); }
+        if self.entries.iter().any(|e| e.name == name) { return Err(Error::DuplicateName(name.to_string())); }
+
+        let compressed = if options.compress { Some(lz::compress(data)) } else { None };
+        let (compression, stored) = match compressed {
+            Some(ref compressed) if compressed.len() < data.len()   => (Compression::Lz4, &compressed[..]),
+            _                                                       => (Compression::None, data),
+        };
+
+        let align = options.align as u64;
+        let padding = (align - self.offset % align) % align;
+        self.output.write_all(&vec![0u8; padding as usize][..])?;
+        self.offset += padding;
+
+        self.output.write_all(stored)?;
+        self.entries.push(Entry {
+            name:           name.to_string(),
+            compression,
+            offset:         self.offset,
+            stored_size:    stored.len() as u64,
+            size:           data.len() as u64,
+            hash:           hash(data),
+        });
+        self.offset += stored.len() as u64;
+        Ok(())
+    }
+
+    /// Write the index and header, returning `output`.
+    pub fn finish (mut self) -> Result<W> {
+        let index = write_index(&self.entries[..]);
+        self.output.write_all(&index[..])?;
+        let header = Header { index_offset: self.offset, index_size: index.len() as u64, index_hash: hash(&index[..]) };
+        self.output.seek(SeekFrom::Start(0))?;
+        self.output.write_all(&header.write()[..])?;
+        self.output.seek(SeekFrom::End(0))?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
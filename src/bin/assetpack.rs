@@ -0,0 +1,123 @@
+//! `assetpack`:  builds `archive` files from `res/` and `build.rs` outputs, and lists or verifies existing ones.
+//!
+//! ```text
+//! assetpack -o target/assets.apak target/debug/build/rust_win32_d3d11-*/out/shaders res/textures
+//! assetpack --list target/assets.apak
+//! ```
+//!
+//! Exits with 0 on success, 1 if a listed archive is corrupt, and 2 for bad arguments or I/O errors.
+
+use rust_win32_d3d11::archive::{self, Archive, Options, Writer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+const USAGE : &str = "\
+usage: assetpack [options] -o <archive> <input>...
+       assetpack --list <archive>
+
+    -o <archive>        Write the archive to <archive>.
+    --align <n>         Align each entry to <n> bytes, a power of two.  Defaults to 16.
+    --store             Don't compress entries.
+    --list <archive>    Print <archive>'s entries, and check their hashes.
+    -h, --help          Print this message.
+
+Each <input> is a directory, whose files are named <directory name>/<relative path>, a file, named by its file name,
+or <name>=<file>.";
+
+fn main () {
+    let mut args = std::env::args().skip(1);
+    let mut output = None;
+    let mut options = Options::default();
+    let mut inputs = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = |arg: &str| args.next().unwrap_or_else(|| usage_error(&format!("{} expects a value", arg)));
+        match &arg[..] {
+            "-h" | "--help" => { println!("{}", USAGE); return; },
+            "-o"            => output = Some(PathBuf::from(value(&arg))),
+            "--align"       => {
+                let align = value(&arg);
+                options.align = align.parse().ok().filter(|a: &u32| a.is_power_of_two()).unwrap_or_else(|| usage_error(&format!("invalid value {:?} for --align", align)));
+            },
+            "--store"       => options.compress = false,
+            "--list"        => exit(list(Path::new(&value(&arg)))),
+            _ if arg.starts_with('-') => usage_error(&format!("unknown argument {:?}", arg)),
+            _               => inputs.push(arg),
+        }
+    }
+    let output = output.unwrap_or_else(|| usage_error("no -o <archive>"));
+    if inputs.is_empty() { usage_error("no inputs"); }
+    exit(pack(&output, &inputs[..], &options));
+}
+
+fn usage_error (message: &str) -> ! {
+    eprintln!("assetpack: {}\n\n{}", message, USAGE);
+    exit(2);
+}
+
+/// Gather `(name, path)` for every file `inputs` refer to.
+fn collect (inputs: &[String]) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    for input in inputs.iter() {
+        if let Some(eq) = input.find('=') {
+            files.push((input[..eq].to_string(), PathBuf::from(&input[eq+1..])));
+            continue;
+        }
+        let path = Path::new(input);
+        let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        if path.is_dir() {
+            collect_dir(path, &name, &mut files)?;
+        } else {
+            files.push((name, path.to_path_buf()));
+        }
+    }
+    Ok(files)
+}
+
+fn collect_dir (dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> std::io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name()); // Reproducible archives
+    for entry in entries.iter() {
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect_dir(&entry.path(), &name, files)?;
+        } else {
+            files.push((name, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+fn pack (output: &Path, inputs: &[String], options: &Options) -> i32 {
+    let files = match collect(inputs) {
+        Ok(files)   => files,
+        Err(e)      => { eprintln!("assetpack: {}", e); return 2; },
+    };
+
+    let result = fs::File::create(output).map_err(archive::Error::Io).and_then(|file| {
+        let mut writer = Writer::new(std::io::BufWriter::new(file))?;
+        for (name, path) in files.iter() {
+            let data = fs::read(path).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            writer.add(name, &data[..], options)?;
+        }
+        writer.finish().map(|_| ())
+    });
+    match result {
+        Ok(())  => { println!("assetpack: wrote {} entries to {}", files.len(), output.display()); 0 },
+        Err(e)  => { eprintln!("assetpack: {}: {}", output.display(), e); 2 },
+    }
+}
+
+fn list (path: &Path) -> i32 {
+    let archive = match fs::read(path).map_err(archive::Error::Io).and_then(Archive::parse) {
+        Ok(archive) => archive,
+        Err(e)      => { eprintln!("assetpack: {}: {}", path.display(), e); return 2; },
+    };
+    let mut status = 0;
+    for entry in archive.entries().iter() {
+        let ok = archive.load(&entry.name).is_ok();
+        if !ok { status = 1; }
+        println!("{:>10} {:>10} {:<4} {:016x} {}{}", entry.size, entry.stored_size, format!("{:?}", entry.compression), entry.hash, entry.name, if ok { "" } else { "  CORRUPT" });
+    }
+    status
+}
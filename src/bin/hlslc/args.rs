@@ -0,0 +1,249 @@
+//! `hlslc`'s command line.  Pure `std`, so it parses the same everywhere.
+
+use crate::diagnostics::MessageFormat;
+use rust_win32_d3d11::com::d3d::CompileOptions;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const USAGE : &str = "\
+usage: hlslc [options] <source.hlsl>
+
+    -T, --target <target>       Compiler target, e.g. vs_5_0.  Required.
+    -E, --entry <name>          Entrypoint.  Defaults to main (none for fx_* targets.)
+    -D <name>[=<value>]         Preprocessor define.  <value> defaults to 1.
+    -I <dir>                    Search <dir> for #includes, after the source's own directory.
+    -Fo, -o <file>              Write bytecode to <file>.
+    -Fh <file>                  Write bytecode as a C header.
+    --rust <file>               Write bytecode as a Rust const array.
+    -Vn <name>                  Variable name for -Fh / --rust.  Defaults to g_<entry> / <ENTRY>.
+    --message-format <format>   Print diagnostics as rustc (default) or msbuild.
+    /Zi /Od /O3 /WX ...         fxc compile switches, see d3d::CompileOptions.
+    -h, --help                  Print this message.
+
+With no -Fo / -Fh / --rust, bytecode is written next to the source, with a .dxbc extension.";
+
+#[derive(Clone, Debug)]
+pub struct Args {
+    pub source:         PathBuf,
+    pub target:         String,
+    /// `None` for effects (`fx_*`) targets, which have no entrypoint.
+    pub entrypoint:     Option<String>,
+    pub defines:        Vec<(String, String)>,
+    pub include_dirs:   Vec<PathBuf>,
+    pub options:        CompileOptions,
+    pub bytecode:       Option<PathBuf>,
+    pub header:         Option<PathBuf>,
+    pub rust:           Option<PathBuf>,
+    pub variable_name:  Option<String>,
+    pub message_format: MessageFormat,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// `-h` / `--help`:  not really an error, but stops parsing all the same.
+    Help,
+    MissingValue(String),
+    UnknownArgument(String),
+    BadValue { arg: String, value: String },
+    NoSource,
+    MultipleSources(String),
+    NoTarget,
+}
+
+impl fmt::Display for Error {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Help                             => write!(f, "{}", USAGE),
+            Error::MissingValue(ref arg)            => write!(f, "{} expects a value", arg),
+            Error::UnknownArgument(ref arg)         => write!(f, "unknown argument {:?}", arg),
+            Error::BadValue { ref arg, ref value }  => write!(f, "invalid value {:?} for {}", value, arg),
+            Error::NoSource                         => write!(f, "no source file"),
+            Error::MultipleSources(ref source)      => write!(f, "unexpected second source file {:?}", source),
+            Error::NoTarget                         => write!(f, "no --target"),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Args {
+    /// Parse the arguments after the executable name.
+    pub fn parse<I: IntoIterator<Item = String>> (args: I) -> Result<Args> {
+        let mut args = args.into_iter();
+        let mut source = None;
+        let mut target = None;
+        let mut entrypoint = None;
+        let mut parsed = Args {
+            source:         PathBuf::new(),
+            target:         String::new(),
+            entrypoint:     None,
+            defines:        Vec::new(),
+            include_dirs:   Vec::new(),
+            options:        CompileOptions::default(),
+            bytecode:       None,
+            header:         None,
+            rust:           None,
+            variable_name:  None,
+            message_format: MessageFormat::Rustc,
+        };
+
+        while let Some(arg) = args.next() {
+            // fxc style `-Emain` as well as `-E main`
+            let (flag, attached) = split_flag(&arg);
+            let mut value = || -> Result<String> {
+                match attached {
+                    Some(v) => Ok(v.to_string()),
+                    None    => args.next().ok_or_else(|| Error::MissingValue(flag.to_string())),
+                }
+            };
+            match flag {
+                "-h" | "--help" | "/?"      => return Err(Error::Help),
+                "-T" | "--target"           => target = Some(value()?),
+                "-E" | "--entry"            => entrypoint = Some(value()?),
+                "-D"                        => parsed.defines.push(parse_define(&value()?)?),
+                "-I"                        => parsed.include_dirs.push(PathBuf::from(value()?)),
+                "-Fo" | "-o"                => parsed.bytecode = Some(PathBuf::from(value()?)),
+                "-Fh"                       => parsed.header = Some(PathBuf::from(value()?)),
+                "--rust"                    => parsed.rust = Some(PathBuf::from(value()?)),
+                "-Vn"                       => {
+                    let name = value()?;
+                    if !is_identifier(&name) { return Err(Error::BadValue { arg: flag.to_string(), value: name }); }
+                    parsed.variable_name = Some(name);
+                },
+                "--message-format"          => {
+                    let format = value()?;
+                    parsed.message_format = MessageFormat::parse(&format).ok_or_else(|| Error::BadValue { arg: flag.to_string(), value: format })?;
+                },
+                _ if parsed.options.apply_switch(&arg) => {},
+                // `/O3` is a switch, `/src/ps.hlsl` an absolute path on Unix, even if it doesn't exist.
+                _ if arg.starts_with('-') || (arg.starts_with('/') && !arg[1..].contains('/') && !Path::new(&arg).exists()) => {
+                    return Err(Error::UnknownArgument(arg));
+                },
+                _ => match source {
+                    None    => source = Some(PathBuf::from(arg)),
+                    Some(_) => return Err(Error::MultipleSources(arg)),
+                },
+            }
+        }
+
+        parsed.source = source.ok_or(Error::NoSource)?;
+        parsed.target = target.ok_or(Error::NoTarget)?;
+        parsed.entrypoint = match entrypoint {
+            Some(entrypoint)                        => Some(entrypoint),
+            None if parsed.target.starts_with("fx_") => None,
+            None                                    => Some(String::from("main")),
+        };
+        Ok(parsed)
+    }
+
+    /// Where to write bytecode:  `-Fo`, or next to the source if no outputs were requested at all.
+    pub fn bytecode_path (&self) -> Option<PathBuf> {
+        match (&self.bytecode, &self.header, &self.rust) {
+            (Some(path), _, _)      => Some(path.clone()),
+            (None, None, None)      => Some(self.source.with_extension("dxbc")),
+            _                       => None,
+        }
+    }
+
+    /// The variable name for `-Fh`, fxc style:  `g_main`.
+    pub fn header_name (&self) -> String {
+        self.variable_name.clone().unwrap_or_else(|| format!("g_{}", self.entrypoint.as_ref().map_or("effect", |e| &e[..])))
+    }
+
+    /// The `const` name for `--rust`:  `MAIN`.
+    pub fn rust_name (&self) -> String {
+        self.variable_name.clone().unwrap_or_else(|| self.entrypoint.as_ref().map_or("EFFECT", |e| &e[..]).to_uppercase())
+    }
+}
+
+/// Splits `-Emain` into `("-E", Some("main"))`.  Flags that take values are matched longest first, so `-Fo` isn't
+/// mistaken for `-F` + `o`.
+fn split_flag (arg: &str) -> (&str, Option<&str>) {
+    if arg.starts_with("--") {
+        return match arg.find('=') {
+            Some(eq) => (&arg[..eq], Some(&arg[eq+1..])),
+            None     => (arg, None),
+        };
+    }
+    for flag in ["-Fo", "-Fh", "-Vn", "-T", "-E", "-D", "-I", "-o"].iter() {
+        if arg.starts_with(flag) && arg.len() > flag.len() { return (flag, Some(&arg[flag.len()..])); }
+    }
+    (arg, None)
+}
+
+fn parse_define (define: &str) -> Result<(String, String)> {
+    let mut parts = define.splitn(2, '=');
+    let name = parts.next().unwrap();
+    if !is_identifier(name) { return Err(Error::BadValue { arg: String::from("-D"), value: define.to_string() }); }
+    Ok((name.to_string(), parts.next().unwrap_or("1").to_string()))
+}
+
+fn is_identifier (name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn parse (args: &[&str]) -> Result<Args> { Args::parse(args.iter().map(|a| a.to_string())) }
+
+    #[test] fn full () {
+        let args = parse(&["--target", "ps_5_0", "-Efog", "-D", "FOG", "-DDENSITY=0.5", "-I", "inc", "/O3", "-WX", "-Fo", "out/ps.dxbc", "-Fhout/ps.h", "--rust=out/ps.rs", "-Vn", "g_fog", "--message-format", "msbuild", "ps.hlsl"]).unwrap();
+        assert_eq!(args.source,         PathBuf::from("ps.hlsl"));
+        assert_eq!(args.target,         "ps_5_0");
+        assert_eq!(args.entrypoint,     Some(String::from("fog")));
+        assert_eq!(args.defines,        vec![(String::from("FOG"), String::from("1")), (String::from("DENSITY"), String::from("0.5"))]);
+        assert_eq!(args.include_dirs,   vec![PathBuf::from("inc")]);
+        assert_eq!(args.options,        CompileOptions::parse("/O3 /WX").unwrap());
+        assert_eq!(args.bytecode_path(), Some(PathBuf::from("out/ps.dxbc")));
+        assert_eq!(args.header,         Some(PathBuf::from("out/ps.h")));
+        assert_eq!(args.rust,           Some(PathBuf::from("out/ps.rs")));
+        assert_eq!((args.header_name(), args.rust_name()), (String::from("g_fog"), String::from("g_fog")));
+        assert_eq!(args.message_format, MessageFormat::MsBuild);
+    }
+
+    #[test] fn defaults () {
+        let args = parse(&["-T", "vs_5_0", "shaders/vs.hlsl"]).unwrap();
+        assert_eq!(args.entrypoint,     Some(String::from("main")));
+        assert_eq!(args.options,        CompileOptions::default());
+        assert_eq!(args.message_format, MessageFormat::Rustc);
+        assert_eq!(args.bytecode_path(), Some(PathBuf::from("shaders/vs.dxbc")));
+        assert_eq!((args.header_name(), args.rust_name()), (String::from("g_main"), String::from("MAIN")));
+
+        let effect = parse(&["-T", "fx_5_0", "fx.fx"]).unwrap();
+        assert_eq!(effect.entrypoint, None);
+        assert_eq!((effect.header_name(), effect.rust_name()), (String::from("g_effect"), String::from("EFFECT")));
+
+        // Only -Fh:  no bytecode file
+        assert_eq!(parse(&["-T", "vs_5_0", "-Fh", "vs.h", "vs.hlsl"]).unwrap().bytecode_path(), None);
+    }
+
+    #[test] fn errors () {
+        let bad_value = |arg: &str, value: &str| Err(Error::BadValue { arg: arg.into(), value: value.into() });
+        assert_eq!(parse(&["-T", "vs_5_0", "--help"]).err(),             Some(Error::Help));
+        assert_eq!(parse(&["/?"]).err(),                                  Some(Error::Help));
+        assert_eq!(parse(&["-T", "vs_5_0"]).err(),                        Some(Error::NoSource));
+        assert_eq!(parse(&["vs.hlsl"]).err(),                             Some(Error::NoTarget));
+        assert_eq!(parse(&["vs.hlsl", "-T"]).err(),                       Some(Error::MissingValue(String::from("-T"))));
+        assert_eq!(parse(&["-T", "vs_5_0", "a.hlsl", "b.hlsl"]).err(),    Some(Error::MultipleSources(String::from("b.hlsl"))));
+        assert_eq!(parse(&["-T", "vs_5_0", "--frobnicate", "a"]).err(),   Some(Error::UnknownArgument(String::from("--frobnicate"))));
+        assert_eq!(parse(&["-T", "vs_5_0", "/Qstrip_debug", "a"]).err(),  Some(Error::UnknownArgument(String::from("/Qstrip_debug"))));
+        assert_eq!(parse(&["-T", "vs_5_0", "/missing/a.hlsl"]).map(|a| a.source), Ok(PathBuf::from("/missing/a.hlsl")));
+        assert_eq!(parse(&["-T", "vs_5_0", "-D", "1X", "a"]).map(|_| ()), bad_value("-D", "1X"));
+        assert_eq!(parse(&["-T", "vs_5_0", "-Vn", "g-x", "a"]).map(|_| ()), bad_value("-Vn", "g-x"));
+        assert_eq!(parse(&["-T", "vs_5_0", "--message-format=json", "a"]).map(|_| ()), bad_value("--message-format", "json"));
+    }
+
+    #[test] fn split_flags () {
+        assert_eq!(split_flag("-Fofoo.dxbc"),   ("-Fo", Some("foo.dxbc")));
+        assert_eq!(split_flag("-Fo"),           ("-Fo", None));
+        assert_eq!(split_flag("-Tps_5_0"),      ("-T", Some("ps_5_0")));
+        assert_eq!(split_flag("--target=x"),    ("--target", Some("x")));
+        assert_eq!(split_flag("--target"),      ("--target", None));
+        assert_eq!(split_flag("/O3"),           ("/O3", None));
+    }
+}
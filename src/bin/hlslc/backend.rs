@@ -0,0 +1,97 @@
+//! The compile itself:  `d3d::compile_raw` on Windows, a stub that always fails elsewhere.
+
+use crate::args::Args;
+
+pub struct Compiled {
+    pub bytecode:   Vec<u8>,
+    /// D3DCompile's warnings, in its own text format (see `diagnostics`.)
+    pub messages:   String,
+}
+
+pub struct Failed {
+    /// D3DCompile's errors, in its own text format (see `diagnostics`.)
+    pub messages:   String,
+}
+
+pub trait Backend {
+    fn compile (&self, args: &Args, source: &[u8]) -> Result<Compiled, Failed>;
+}
+
+/// Where `D3DCompiler_47.dll` isn't available:  parsing, diagnostics, and output formatting still work.
+#[cfg_attr(windows, allow(dead_code))]
+pub struct Unavailable;
+
+impl Backend for Unavailable {
+    fn compile (&self, _args: &Args, _source: &[u8]) -> Result<Compiled, Failed> {
+        Err(Failed { messages: String::from("error: D3DCompiler isn't available on this platform") })
+    }
+}
+
+#[cfg(windows)]
+pub use self::d3dcompiler::D3DCompiler;
+
+#[cfg(windows)]
+mod d3dcompiler {
+    use super::*;
+    use rust_win32_d3d11::com::d3d::{self, Blob, Include, IncludeObject, Target};
+    use rust_win32_d3d11::win32::*;
+    use std::ffi::{CStr, CString};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::ptr::null;
+
+    pub struct D3DCompiler;
+
+    /// `#include "..."` searches the source's directory, then `-I` directories.  `#include <...>` only searches `-I`.
+    struct IncludeDirs {
+        source_dir:     PathBuf,
+        include_dirs:   Vec<PathBuf>,
+    }
+
+    impl Include for IncludeDirs {
+        fn open (&self, include_type: D3D_INCLUDE_TYPE, file_name: &CStr, _parent: Option<&[u8]>) -> Result<Vec<u8>, HRESULT> {
+            let file_name = file_name.to_str().map_err(|_| E_INVALIDARG)?;
+            let local = if include_type == D3D_INCLUDE_LOCAL { Some(&self.source_dir) } else { None };
+            local.into_iter().chain(self.include_dirs.iter())
+                .find_map(|dir| fs::read(dir.join(file_name)).ok())
+                .ok_or(E_FAIL)
+        }
+    }
+
+    fn blob_text (blob: Option<Blob>) -> String {
+        blob.map_or(String::new(), |blob| blob.to_string_lossy())
+    }
+
+    impl Backend for D3DCompiler {
+        fn compile (&self, args: &Args, source: &[u8]) -> Result<Compiled, Failed> {
+            let target = Target::parse(&args.target).ok_or_else(|| Failed { messages: format!("error: unknown target {:?}", args.target) })?;
+            let entrypoint = args.entrypoint.as_ref().map(|e| CString::new(e.clone()).unwrap());
+            let defines : Vec<(CString, CString)> = args.defines.iter().map(|(name, value)| (CString::new(name.clone()).unwrap(), CString::new(value.clone()).unwrap())).collect();
+            let mut macros : Vec<D3D_SHADER_MACRO> = defines.iter().map(|(name, value)| D3D_SHADER_MACRO { Name: name.as_ptr(), Definition: value.as_ptr() }).collect();
+            macros.push(D3D_SHADER_MACRO { Name: null(), Definition: null() });
+            let include = IncludeObject::new(IncludeDirs {
+                source_dir:     args.source.parent().map_or(PathBuf::new(), |p| p.to_path_buf()),
+                include_dirs:   args.include_dirs.clone(),
+            });
+
+            let result = unsafe { d3d::compile_raw(
+                source,
+                Some(&args.source),
+                Some(&macros[..]),
+                Some(&include),
+                entrypoint.as_ref().map(|e| &e[..]),
+                target.to_cstr(),
+                args.options.flags1(),
+                args.options.flags2()
+            )};
+            match result {
+                Ok(ok)  => Ok(Compiled { bytecode: ok.shader.as_bytes().to_vec(), messages: blob_text(ok.warnings) }),
+                Err(e)  => {
+                    let mut messages = blob_text(e.errors);
+                    if messages.is_empty() { messages = format!("error: D3DCompile failed with HRESULT=0x{:08X}", e.hresult); }
+                    Err(Failed { messages })
+                },
+            }
+        }
+    }
+}
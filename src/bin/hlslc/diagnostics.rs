@@ -0,0 +1,185 @@
+//! Parses D3DCompile's error blob, and reprints it in a format editors and build tools pick up.
+//!
+//! D3DCompile reports one message per line, mostly with a location:
+//!
+//! ```text
+//! C:\src\ps.hlsl(12,5-9): error X3004: undeclared identifier 'foo'
+//! C:\src\ps.hlsl(3,1): warning X3206: implicit truncation of vector type
+//! error X3501: 'main': entrypoint not found
+//! ```
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MessageFormat {
+    /// `error[X3004]: message` followed by ` --> file:line:column`, like rustc.
+    Rustc,
+    /// `file(line,column): error X3004: message`, which Visual Studio's error list parses.
+    MsBuild,
+}
+
+impl MessageFormat {
+    pub fn parse (name: &str) -> Option<MessageFormat> {
+        match name {
+            "rustc"     => Some(MessageFormat::Rustc),
+            "msbuild"   => Some(MessageFormat::MsBuild),
+            _           => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Lines D3DCompile printed that weren't recognizable messages.
+    Note,
+}
+
+impl Severity {
+    fn as_str (self) -> &'static str {
+        match self {
+            Severity::Error     => "error",
+            Severity::Warning   => "warning",
+            Severity::Note      => "note",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub file:       String,
+    /// 1-based.
+    pub line:       u32,
+    /// 1-based.
+    pub column:     u32,
+    /// The last column of a `(line,start-end)` range, if any.
+    pub end_column: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub location:   Option<Location>,
+    pub severity:   Severity,
+    /// e.g. `X3004`
+    pub code:       Option<String>,
+    pub message:    String,
+}
+
+/// Parse every non-empty line of `text`, a D3DCompile error blob.
+pub fn parse (text: &str) -> Vec<Diagnostic> {
+    text.lines().map(|l| l.trim_end_matches('\0').trim()).filter(|l| !l.is_empty()).map(parse_line).collect()
+}
+
+fn parse_line (line: &str) -> Diagnostic {
+    let (location, rest) = match split_location(line) {
+        Some((location, rest))  => (Some(location), rest),
+        None                    => (None, line),
+    };
+
+    for &(prefix, severity) in [("error", Severity::Error), ("warning", Severity::Warning)].iter() {
+        if !rest.starts_with(prefix) { continue; }
+        let rest = rest[prefix.len()..].trim_start();
+        let (code, message) = match rest.find(": ") {
+            Some(colon) if colon > 0 && !rest[..colon].contains(' ') => (Some(rest[..colon].to_string()), &rest[colon+2..]),
+            _ => (None, rest.trim_start_matches(':').trim_start()),
+        };
+        return Diagnostic { location, severity, code, message: message.to_string() };
+    }
+
+    Diagnostic { location: None, severity: Severity::Note, code: None, message: line.to_string() }
+}
+
+/// Splits `file(line,col[-end]): rest` into its location and `rest`.
+fn split_location (line: &str) -> Option<(Location, &str)> {
+    let close = line.find("): ")?;
+    let open = line[..close].rfind('(')?;
+    let mut numbers = line[open+1..close].splitn(2, ',');
+    let line_no = numbers.next()?.parse().ok()?;
+    let (column, end_column) = match numbers.next() {
+        Some(columns) => {
+            let mut columns = columns.splitn(2, '-');
+            let column = columns.next()?.parse().ok()?;
+            let end_column = match columns.next() { Some(end) => Some(end.parse().ok()?), None => None };
+            (column, end_column)
+        },
+        None => (1, None),
+    };
+    let location = Location { file: line[..open].to_string(), line: line_no, column, end_column };
+    Some((location, &line[close+3..]))
+}
+
+/// Prints a `Diagnostic` in a `MessageFormat`.
+pub struct Formatted<'a> {
+    pub diagnostic: &'a Diagnostic,
+    pub format:     MessageFormat,
+}
+
+impl<'a> fmt::Display for Formatted<'a> {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = self.diagnostic;
+        match self.format {
+            MessageFormat::Rustc => {
+                write!(f, "{}", d.severity.as_str())?;
+                if let Some(ref code) = d.code { write!(f, "[{}]", code)?; }
+                write!(f, ": {}", d.message)?;
+                if let Some(ref l) = d.location { write!(f, "\n  --> {}:{}:{}", l.file, l.line, l.column)?; }
+                Ok(())
+            },
+            MessageFormat::MsBuild => {
+                match d.location {
+                    Some(ref l) => match l.end_column {
+                        Some(end)   => write!(f, "{}({},{},{},{}): ", l.file, l.line, l.column, l.line, end)?,
+                        None        => write!(f, "{}({},{}): ", l.file, l.line, l.column)?,
+                    },
+                    None => write!(f, "hlslc : ")?,
+                }
+                write!(f, "{}", d.severity.as_str())?;
+                if let Some(ref code) = d.code { write!(f, " {}", code)?; }
+                write!(f, ": {}", d.message)
+            },
+        }
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn location (file: &str, line: u32, column: u32, end_column: Option<u32>) -> Option<Location> {
+        Some(Location { file: file.into(), line, column, end_column })
+    }
+
+    #[test] fn parsing () {
+        let parsed = parse(concat!(
+            "C:\\src\\ps.hlsl(12,5-9): error X3004: undeclared identifier 'foo'\n",
+            "C:\\src\\ps.hlsl(3,1): warning X3206: implicit truncation of vector type\r\n",
+            "\n",
+            "error X3501: 'main': entrypoint not found\n",
+            "C:\\src (x86)\\ps.hlsl(7): warning: no code\n",
+            "compilation failed; no code produced\0",
+        ));
+        assert_eq!(parsed, vec![
+            Diagnostic { location: location("C:\\src\\ps.hlsl", 12, 5, Some(9)), severity: Severity::Error, code: Some("X3004".into()), message: "undeclared identifier 'foo'".into() },
+            Diagnostic { location: location("C:\\src\\ps.hlsl", 3, 1, None), severity: Severity::Warning, code: Some("X3206".into()), message: "implicit truncation of vector type".into() },
+            Diagnostic { location: None, severity: Severity::Error, code: Some("X3501".into()), message: "'main': entrypoint not found".into() },
+            Diagnostic { location: location("C:\\src (x86)\\ps.hlsl", 7, 1, None), severity: Severity::Warning, code: None, message: "no code".into() },
+            Diagnostic { location: None, severity: Severity::Note, code: None, message: "compilation failed; no code produced".into() },
+        ]);
+    }
+
+    #[test] fn formatting () {
+        let error = Diagnostic { location: location("ps.hlsl", 12, 5, Some(9)), severity: Severity::Error, code: Some("X3004".into()), message: "undeclared identifier 'foo'".into() };
+        let note  = Diagnostic { location: None, severity: Severity::Note, code: None, message: "compilation failed".into() };
+        let format = |diagnostic, format| Formatted { diagnostic, format }.to_string();
+        assert_eq!(format(&error, MessageFormat::Rustc),    "error[X3004]: undeclared identifier 'foo'\n  --> ps.hlsl:12:5");
+        assert_eq!(format(&error, MessageFormat::MsBuild),  "ps.hlsl(12,5,12,9): error X3004: undeclared identifier 'foo'");
+        assert_eq!(format(&note,  MessageFormat::Rustc),    "note: compilation failed");
+        assert_eq!(format(&note,  MessageFormat::MsBuild),  "hlslc : note: compilation failed");
+    }
+
+    #[test] fn formats () {
+        assert_eq!(MessageFormat::parse("rustc"),   Some(MessageFormat::Rustc));
+        assert_eq!(MessageFormat::parse("msbuild"), Some(MessageFormat::MsBuild));
+        assert_eq!(MessageFormat::parse("MSBuild"), None);
+    }
+}
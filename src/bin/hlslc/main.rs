@@ -0,0 +1,144 @@
+//! `hlslc`:  an `fxc` style command line for `d3d::compile`, for build scripts and editors.
+//!
+//! ```text
+//! hlslc --target ps_5_0 -E main -D FOG=1 -I res/include /O3 --message-format msbuild -Fo target/ps.dxbc res/ps.hlsl
+//! ```
+//!
+//! Exits with 0 on success, 1 if the shader failed to compile, and 2 for bad arguments or I/O errors.
+
+mod args;
+mod backend;
+mod diagnostics;
+mod output;
+
+use args::Args;
+use backend::Backend;
+use diagnostics::Formatted;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::exit;
+
+fn main () {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args)                => args,
+        Err(args::Error::Help)  => { println!("{}", args::USAGE); return; },
+        Err(e)                  => { eprintln!("hlslc: {}\n\n{}", e, args::USAGE); exit(2); },
+    };
+
+    #[cfg(windows)] let backend = backend::D3DCompiler;
+    #[cfg(not(windows))] let backend = backend::Unavailable;
+    exit(run(&args, &backend, &mut io::stderr()));
+}
+
+/// Compile, print diagnostics to `stderr` (warnings included, like `fxc`, leaving stdout to tools), and write outputs.
+/// Returns the exit code.
+fn run (args: &Args, backend: &dyn Backend, stderr: &mut dyn Write) -> i32 {
+    let source = match fs::read(&args.source) {
+        Ok(source) => source,
+        Err(e) => { let _ = writeln!(stderr, "hlslc: {}: {}", args.source.display(), e); return 2; },
+    };
+
+    let (bytecode, messages) = match backend.compile(args, &source[..]) {
+        Ok(compiled)    => (Some(compiled.bytecode), compiled.messages),
+        Err(failed)     => (None, failed.messages),
+    };
+    for diagnostic in diagnostics::parse(&messages).iter() {
+        let _ = writeln!(stderr, "{}", Formatted { diagnostic, format: args.message_format });
+    }
+    let bytecode = match bytecode { Some(b) => b, None => return 1 };
+
+    let source_name = args.source.display().to_string();
+    let options = args.options.to_string();
+    let origin = output::Origin { source: &source_name, target: &args.target, entrypoint: args.entrypoint.as_ref().map(|e| &e[..]), options: &options };
+    let outputs = [
+        (args.bytecode_path(),  bytecode.clone()),
+        (args.header.clone(),   output::header(&bytecode[..], &args.header_name(), &origin).into_bytes()),
+        (args.rust.clone(),     output::rust(&bytecode[..], &args.rust_name(), &origin).into_bytes()),
+    ];
+    for (path, contents) in outputs.iter() {
+        if let Some(path) = path {
+            if let Err(e) = write(path, contents) { let _ = writeln!(stderr, "hlslc: {}: {}", path.display(), e); return 2; }
+        }
+    }
+    0
+}
+
+fn write (path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() { fs::create_dir_all(dir)?; }
+    }
+    fs::write(path, contents)
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use backend::{Compiled, Failed};
+    use std::path::PathBuf;
+
+    /// Succeeds with fixed bytecode and a warning.
+    struct Canned;
+
+    impl Backend for Canned {
+        fn compile (&self, args: &Args, _source: &[u8]) -> Result<Compiled, Failed> {
+            Ok(Compiled { bytecode: b"DXBC".to_vec(), messages: format!("{}(1,1): warning X3206: implicit truncation", args.source.display()) })
+        }
+    }
+
+    /// A fresh directory containing `ps.hlsl`.
+    fn scratch (name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hlslc-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ps.hlsl"), "float4 main () : SV_TARGET { return 1; }").unwrap();
+        dir
+    }
+
+    fn args (dir: &Path, extra: &[&str]) -> Args {
+        let source = dir.join("ps.hlsl").display().to_string();
+        Args::parse(["-T", "ps_5_0"].iter().chain(extra.iter()).map(|a| a.to_string()).chain(Some(source))).unwrap()
+    }
+
+    fn run_to_string (args: &Args, backend: &dyn Backend) -> (i32, String) {
+        let mut stderr = Vec::new();
+        let code = run(args, backend, &mut stderr);
+        (code, String::from_utf8(stderr).unwrap())
+    }
+
+    #[test] fn unavailable () {
+        let dir = scratch("unavailable");
+        let (code, stderr) = run_to_string(&args(&dir, &[]), &backend::Unavailable);
+        assert_eq!((code, &stderr[..]), (1, "error: D3DCompiler isn't available on this platform\n"));
+        assert!(!dir.join("ps.dxbc").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test] fn missing_source () {
+        let dir = scratch("missing");
+        fs::remove_file(dir.join("ps.hlsl")).unwrap();
+        let (code, stderr) = run_to_string(&args(&dir, &[]), &backend::Unavailable);
+        assert_eq!(code, 2);
+        assert!(stderr.starts_with("hlslc: ") && stderr.contains("ps.hlsl"), "{}", stderr);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test] fn outputs () {
+        let dir = scratch("outputs");
+        let out = dir.join("out");
+        let (fo, fh, rs) = (out.join("ps.dxbc").display().to_string(), out.join("ps.h").display().to_string(), out.join("ps.rs").display().to_string());
+        let (code, stderr) = run_to_string(&args(&dir, &["--message-format", "msbuild", "-Fo", &fo, "-Fh", &fh, "--rust", &rs]), &Canned);
+        assert_eq!(code, 0);
+        assert_eq!(stderr, format!("{}(1,1): warning X3206: implicit truncation\n", dir.join("ps.hlsl").display())); // Warnings go to stderr too
+        assert_eq!(fs::read(&fo).unwrap(), b"DXBC");
+        assert!(fs::read_to_string(&fh).unwrap().contains("const BYTE g_main[] =\n{\n     68,  88,  66,  67,\n};\n"));
+        assert!(fs::read_to_string(&rs).unwrap().contains("pub const MAIN : [u8; 4] = [\n    0x44, 0x58, 0x42, 0x43,\n];\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test] fn default_output () {
+        let dir = scratch("default");
+        assert_eq!(run_to_string(&args(&dir, &[]), &Canned).0, 0);
+        assert_eq!(fs::read(dir.join("ps.dxbc")).unwrap(), b"DXBC");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
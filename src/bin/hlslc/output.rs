@@ -0,0 +1,73 @@
+//! Bytecode as source code, for embedding without a build step.
+
+use std::fmt::Write;
+
+const BYTES_PER_LINE : usize = 16;
+
+/// What the bytecode was compiled from, for the generated file's comment.
+pub struct Origin<'a> {
+    pub source:     &'a str,
+    pub target:     &'a str,
+    pub entrypoint: Option<&'a str>,
+    pub options:    &'a str,
+}
+
+impl<'a> Origin<'a> {
+    fn describe (&self) -> String {
+        format!("Generated by hlslc from {}, target {}, entry {}, options {:?}", self.source, self.target, self.entrypoint.unwrap_or("(none)"), self.options)
+    }
+}
+
+/// A C/C++ header declaring `const BYTE name[]`, as `fxc /Fh` writes.
+pub fn header (bytecode: &[u8], name: &str, origin: &Origin) -> String {
+    let mut out = String::new();
+    writeln!(out, "// {}", origin.describe()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "const BYTE {}[] =", name).unwrap();
+    writeln!(out, "{{").unwrap();
+    write_bytes(&mut out, bytecode, |b| format!("{:3}", b));
+    writeln!(out, "}};").unwrap();
+    out
+}
+
+/// A Rust module item `pub const NAME : [u8; N] = [...];`, to `include!`.
+pub fn rust (bytecode: &[u8], name: &str, origin: &Origin) -> String {
+    let mut out = String::new();
+    writeln!(out, "// {}", origin.describe()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub const {} : [u8; {}] = [", name, bytecode.len()).unwrap();
+    write_bytes(&mut out, bytecode, |b| format!("0x{:02X}", b));
+    writeln!(out, "];").unwrap();
+    out
+}
+
+fn write_bytes (out: &mut String, bytecode: &[u8], byte: impl Fn(u8) -> String) {
+    for line in bytecode.chunks(BYTES_PER_LINE) {
+        let line = line.iter().map(|&b| byte(b)).collect::<Vec<_>>().join(", ");
+        writeln!(out, "    {},", line).unwrap();
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    const ORIGIN : Origin = Origin { source: "ps.hlsl", target: "ps_5_0", entrypoint: Some("main"), options: "/O3" };
+    const COMMENT : &str = "// Generated by hlslc from ps.hlsl, target ps_5_0, entry main, options \"/O3\"\n\n";
+
+    #[test] fn c_header () {
+        let bytecode = (0..18).collect::<Vec<u8>>();
+        assert_eq!(header(&bytecode, "g_main", &ORIGIN), format!("{}{}", COMMENT, concat!(
+            "const BYTE g_main[] =\n",
+            "{\n",
+            "      0,   1,   2,   3,   4,   5,   6,   7,   8,   9,  10,  11,  12,  13,  14,  15,\n",
+            "     16,  17,\n",
+            "};\n",
+        )));
+    }
+
+    #[test] fn rust_const () {
+        assert_eq!(rust(&[0x44, 0x58, 0xBC], "MAIN", &ORIGIN), format!("{}{}", COMMENT, "pub const MAIN : [u8; 3] = [\n    0x44, 0x58, 0xBC,\n];\n"));
+        let effect = Origin { entrypoint: None, ..ORIGIN };
+        assert!(rust(&[], "EFFECT", &effect).starts_with("// Generated by hlslc from ps.hlsl, target ps_5_0, entry (none), options \"/O3\"\n\npub const EFFECT : [u8; 0] = [\n];\n"));
+    }
+}
@@ -0,0 +1,131 @@
+//! The handful of COM ABI types `com_wrapper!` and `implement` are built on.
+//!
+//! On Windows these are `winapi`'s own.  Everywhere else they're layout compatible stand-ins, so wrappers and Rust
+//! implemented objects can be built and tested against mock interfaces without a D3D runtime.
+
+/// A minimal `winapi::RIDL!`:  a `#[repr(C)]` interface and vtable, methods forwarding through the vtable, `Deref`
+/// to the base interface, and `Interface` if a `#[uuid(...)]` is given.  Also used to declare mock interfaces.
+#[allow(unused_macros)] // Only used by tests on Windows
+macro_rules! com_interface {
+    (
+        $(#[uuid($l:expr, $w1:expr, $w2:expr, $b1:expr, $b2:expr, $b3:expr, $b4:expr, $b5:expr, $b6:expr, $b7:expr, $b8:expr)])?
+        interface $interface:ident ($vtbl:ident) $(: $base:ident ($base_vtbl:ident))? {
+            $(fn $method:ident ($($param:ident : $ty:ty),* $(,)?) -> $ret:ty;)*
+        }
+    ) => {
+        #[repr(C)]
+        pub struct $vtbl {
+            $(pub parent: $base_vtbl,)?
+            $(pub $method: unsafe extern "system" fn (This: *mut $interface, $($param: $ty),*) -> $ret,)*
+        }
+
+        #[repr(C)]
+        pub struct $interface {
+            pub lpVtbl: *const $vtbl,
+        }
+
+        impl $interface {
+            $(#[allow(dead_code)] pub unsafe fn $method (&self, $($param: $ty),*) -> $ret {
+                ((*self.lpVtbl).$method)(self as *const _ as *mut _, $($param),*)
+            })*
+        }
+
+        $(impl std::ops::Deref for $interface {
+            type Target = $base;
+            fn deref (&self) -> &$base { unsafe { &*(self as *const $interface as *const $base) } }
+        })?
+
+        $(impl crate::com::abi::Interface for $interface {
+            fn uuidof () -> crate::com::abi::GUID {
+                crate::com::abi::GUID { Data1: $l, Data2: $w1, Data3: $w2, Data4: [$b1, $b2, $b3, $b4, $b5, $b6, $b7, $b8] }
+            }
+        })?
+    };
+}
+
+#[cfg(windows)] pub use winapi::Interface;
+#[cfg(windows)] pub use winapi::ctypes::c_void;
+#[cfg(windows)] pub use winapi::shared::basetsd::SIZE_T;
+#[cfg(windows)] pub use winapi::shared::guiddef::{GUID, REFIID, IsEqualGUID};
+#[cfg(windows)] pub use winapi::shared::minwindef::{LPCVOID, LPVOID, UINT, ULONG};
+#[cfg(windows)] pub use winapi::shared::winerror::{HRESULT, E_FAIL, E_INVALIDARG, E_NOINTERFACE, E_POINTER, S_OK, SUCCEEDED, FAILED};
+#[cfg(windows)] pub use winapi::um::d3dcommon::{D3D_INCLUDE_TYPE, D3D_INCLUDE_LOCAL, D3D_INCLUDE_SYSTEM};
+#[cfg(windows)] pub use winapi::um::d3dcommon::{ID3D10Blob, ID3D10BlobVtbl, ID3DBlob, ID3DInclude, ID3DIncludeVtbl, IID_ID3D10Blob};
+#[cfg(windows)] pub use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+#[cfg(windows)] pub use winapi::um::winnt::LPCSTR;
+
+#[cfg(not(windows))] pub use neutral::*;
+
+#[cfg(not(windows))]
+#[allow(non_camel_case_types, non_upper_case_globals)]
+mod neutral {
+    pub use std::ffi::c_void;
+
+    pub type HRESULT    = i32;
+    pub type UINT       = u32;
+    pub type ULONG      = u32;
+    pub type SIZE_T     = usize;
+    pub type LPVOID     = *mut c_void;
+    pub type LPCVOID    = *const c_void;
+    pub type LPCSTR     = *const i8;
+    pub type REFIID     = *const GUID;
+
+    pub const S_OK          : HRESULT = 0;
+    pub const E_NOINTERFACE : HRESULT = 0x8000_4002_u32 as HRESULT;
+    pub const E_POINTER     : HRESULT = 0x8000_4003_u32 as HRESULT;
+    pub const E_FAIL        : HRESULT = 0x8000_4005_u32 as HRESULT;
+    pub const E_INVALIDARG  : HRESULT = 0x8007_0057_u32 as HRESULT;
+
+    pub fn SUCCEEDED (hr: HRESULT) -> bool { hr >= 0 }
+    pub fn FAILED (hr: HRESULT) -> bool { hr < 0 }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug)]
+    pub struct GUID {
+        pub Data1: u32,
+        pub Data2: u16,
+        pub Data3: u16,
+        pub Data4: [u8; 8],
+    }
+
+    pub fn IsEqualGUID (a: &GUID, b: &GUID) -> bool {
+        a.Data1 == b.Data1 && a.Data2 == b.Data2 && a.Data3 == b.Data3 && a.Data4 == b.Data4
+    }
+
+    pub trait Interface {
+        fn uuidof () -> GUID;
+    }
+
+    com_interface! {
+        #[uuid(0x00000000, 0x0000, 0x0000, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46)]
+        interface IUnknown (IUnknownVtbl) {
+            fn QueryInterface (riid: REFIID, ppvObject: *mut *mut c_void) -> HRESULT;
+            fn AddRef () -> ULONG;
+            fn Release () -> ULONG;
+        }
+    }
+
+    pub const IID_ID3D10Blob : GUID = GUID { Data1: 0x8ba5fb08, Data2: 0x5195, Data3: 0x40e2, Data4: [0xac, 0x58, 0x0d, 0x98, 0x9c, 0x3a, 0x01, 0x02] };
+
+    com_interface! {
+        #[uuid(0x8ba5fb08, 0x5195, 0x40e2, 0xac, 0x58, 0x0d, 0x98, 0x9c, 0x3a, 0x01, 0x02)]
+        interface ID3D10Blob (ID3D10BlobVtbl) : IUnknown (IUnknownVtbl) {
+            fn GetBufferPointer () -> LPVOID;
+            fn GetBufferSize () -> SIZE_T;
+        }
+    }
+
+    pub type ID3DBlob = ID3D10Blob;
+
+    pub type D3D_INCLUDE_TYPE = u32;
+    pub const D3D_INCLUDE_LOCAL  : D3D_INCLUDE_TYPE = 0;
+    pub const D3D_INCLUDE_SYSTEM : D3D_INCLUDE_TYPE = 1;
+
+    // Not an `IUnknown`, and has no IID.
+    com_interface! {
+        interface ID3DInclude (ID3DIncludeVtbl) {
+            fn Open (IncludeType: D3D_INCLUDE_TYPE, pFileName: LPCSTR, pParentData: LPCVOID, ppData: *mut LPCVOID, pBytes: *mut UINT) -> HRESULT;
+            fn Close (pData: LPCVOID) -> HRESULT;
+        }
+    }
+}
@@ -1,14 +1,46 @@
 use crate::win32::*;
-use crate::com::Wrapper;
+use crate::com::{Object, Wrapper};
+use crate::com::d3d::BlobData;
 use std::os::windows::ffi::OsStrExt;
 use std::path::Path;
 use std::ptr::null_mut;
 
 com_wrapper! {
+    // Immutable once created, but D3D doesn't document `ID3DBlob`'s refcounting as thread safe, so neither `Send` nor
+    // `Sync`.  See `BlobMut` for filling in a new one.
     pub struct Blob(*mut ID3DBlob);
 }
 
 impl Blob {
+    /// MSDN: [D3DCreateBlob](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dcreateblob)
+    ///
+    /// A D3D allocated copy of `bytes`.  See `Blob::from_data` to wrap existing memory without copying.
+    pub fn from_bytes (bytes: &[u8]) -> Result<Blob,HRESULT> {
+        let mut blob = Blob::with_capacity(bytes.len())?;
+        blob.as_bytes_mut().copy_from_slice(bytes);
+        Ok(blob.into_blob())
+    }
+
+    /// MSDN: [D3DCreateBlob](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dcreateblob)
+    ///
+    /// `size` zeroed bytes to fill in, before `BlobMut::into_blob` freezes them.
+    pub fn with_capacity (size: usize) -> Result<BlobMut,HRESULT> {
+        let mut blob = null_mut();
+        let result = unsafe { D3DCreateBlob(size as SIZE_T, &mut blob) };
+        if SUCCEEDED(result) {
+            let mut blob = BlobMut(unsafe { Blob::own(blob) }.unwrap());
+            for b in blob.as_bytes_mut().iter_mut() { *b = 0; }
+            Ok(blob)
+        } else {
+            Err(result)
+        }
+    }
+
+    /// Expose `data` as an `ID3DBlob` without copying it, e.g. a memory mapped file or `include_bytes!`.
+    pub fn from_data<D: AsRef<[u8]> + Send + Sync + 'static> (data: D) -> Blob {
+        Object::new(BlobData(data))
+    }
+
     pub fn from_file<P: AsRef<Path>> (path: &P) -> Result<Blob,HRESULT> {
         let mut path : Vec<u16> = path.as_ref().as_os_str().encode_wide().collect();
         path.push(0u16);
@@ -46,4 +78,56 @@ impl Blob {
             )
         }
     }
+
+    /// Text blobs (errors, disassembly, preprocessed source) as a `String`, without the trailing NUL.
+    pub fn to_string_lossy (&self) -> String {
+        String::from_utf8_lossy(self.as_bytes()).trim_end_matches('\0').to_string()
+    }
+
+    /// Whether this DXBC container's checksum is correct, e.g. after editing it.  See `dxbc::sign` to fix it.
+    pub fn verify_checksum (&self) -> Result<bool, crate::dxbc::Error> {
+        crate::dxbc::verify_checksum(self.as_bytes())
+    }
+}
+
+impl From<Vec<u8>> for Blob {
+    fn from (bytes: Vec<u8>) -> Self { Blob::from_data(bytes) }
+}
+
+impl From<Box<[u8]>> for Blob {
+    fn from (bytes: Box<[u8]>) -> Self { Blob::from_data(bytes) }
+}
+
+impl From<&'static [u8]> for Blob {
+    fn from (bytes: &'static [u8]) -> Self { Blob::from_data(bytes) }
+}
+
+impl From<&Blob> for Vec<u8> {
+    fn from (blob: &Blob) -> Self { blob.as_bytes().to_vec() }
+}
+
+impl From<Blob> for Vec<u8> {
+    fn from (blob: Blob) -> Self { blob.as_bytes().to_vec() }
+}
+
+impl From<Blob> for Box<[u8]> {
+    fn from (blob: Blob) -> Self { blob.as_bytes().into() }
+}
+
+/// A `Blob` nothing else references yet, so it's still safe to write to.  Not `Clone`, for the same reason.
+pub struct BlobMut(Blob);
+
+impl BlobMut {
+    pub fn as_bytes (&self) -> &[u8] { self.0.as_bytes() }
+
+    pub fn as_bytes_mut (&mut self) -> &mut [u8] {
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                (*self.0.as_ptr()).GetBufferPointer() as *mut _,
+                (*self.0.as_ptr()).GetBufferSize()
+            )
+        }
+    }
+
+    pub fn into_blob (self) -> Blob { self.0 }
 }
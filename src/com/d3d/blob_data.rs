@@ -0,0 +1,90 @@
+use crate::com::{Class, Object};
+use crate::com::abi::*;
+
+/// An `ID3DBlob` implemented in Rust, backed by any `D`.  Use `Blob::from_data` to create one.
+///
+/// `GetBufferPointer` hands out a mutable pointer as the interface requires, but the bytes are only borrowed from
+/// `D`:  nothing may write through it.  (D3D itself never writes to blobs it's given.)
+pub struct BlobData<D: AsRef<[u8]> + Send + Sync + 'static>(pub D);
+
+unsafe impl<D: AsRef<[u8]> + Send + Sync + 'static> Class for BlobData<D> {
+    type Interface  = ID3DBlob;
+    type Vtbl       = ID3D10BlobVtbl;
+    const VTBL : &'static ID3D10BlobVtbl = &ID3D10BlobVtbl {
+        parent:             Object::<Self>::IUNKNOWN,
+        GetBufferPointer:   Self::get_buffer_pointer,
+        GetBufferSize:      Self::get_buffer_size,
+    };
+    const INTERFACES : &'static [GUID] = &[IID_ID3D10Blob];
+}
+
+impl<D: AsRef<[u8]> + Send + Sync + 'static> BlobData<D> {
+    unsafe extern "system" fn get_buffer_pointer (this: *mut ID3DBlob) -> LPVOID {
+        Object::<Self>::from_interface(this).0.as_ref().as_ptr() as LPVOID
+    }
+
+    unsafe extern "system" fn get_buffer_size (this: *mut ID3DBlob) -> SIZE_T {
+        Object::<Self>::from_interface(this).0.as_ref().len() as SIZE_T
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com::Wrapper;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Only one wrapper per interface:  on Windows, `Blob` already wraps `ID3DBlob`.
+    #[cfg(windows)] use crate::com::d3d::Blob as TestBlob;
+    #[cfg(not(windows))] com_wrapper! {
+        pub struct TestBlob(*mut ID3DBlob);
+    }
+
+    /// Bytes that set their flag when dropped.
+    struct Bytes(Vec<u8>, Arc<AtomicBool>);
+
+    impl AsRef<[u8]> for Bytes {
+        fn as_ref (&self) -> &[u8] { &self.0 }
+    }
+
+    impl Drop for Bytes {
+        fn drop (&mut self) { self.1.store(true, Ordering::SeqCst); }
+    }
+
+    #[test]
+    fn buffer () {
+        static DATA : &[u8] = b"DXBC";
+        let blob : TestBlob = Object::new(BlobData(DATA));
+        let blob = blob.as_ref();
+        assert_eq!(unsafe { blob.GetBufferPointer() } as usize, DATA.as_ptr() as usize);
+        assert_eq!(unsafe { blob.GetBufferSize() }, 4);
+
+        let blob : TestBlob = Object::new(BlobData(Vec::new()));
+        assert_eq!(unsafe { blob.as_ref().GetBufferSize() }, 0);
+    }
+
+    #[test]
+    fn release () {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let blob : TestBlob = Object::new(BlobData(Bytes(vec![1, 2, 3], dropped.clone())));
+        let blob2 = blob.clone();
+        drop(blob);
+        assert!(!dropped.load(Ordering::SeqCst));
+        assert_eq!(unsafe { std::slice::from_raw_parts(blob2.as_ref().GetBufferPointer() as *const u8, blob2.as_ref().GetBufferSize()) }, &[1, 2, 3]);
+
+        let iblob = blob2.as_ptr();
+        assert_eq!(unsafe { (*iblob).AddRef() }, 2);
+        assert_eq!(unsafe { (*iblob).Release() }, 1);
+        drop(blob2);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn query_interface () {
+        let blob : TestBlob = Object::new(BlobData(&b"DXBC"[..]));
+        let as_blob = blob.cast::<TestBlob>().unwrap();
+        assert_eq!(as_blob.as_ptr() as usize, blob.as_ptr() as usize);
+        assert_eq!(unsafe { as_blob.as_ref().GetBufferSize() }, 4);
+    }
+}
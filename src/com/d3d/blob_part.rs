@@ -0,0 +1,81 @@
+use crate::win32::*;
+use crate::com::Wrapper;
+use crate::com::d3d::Blob;
+use crate::dxbc::BlobPart;
+use std::ffi::CStr;
+use std::ptr::{null, null_mut};
+
+fn check (hresult: HRESULT) -> Result<(), HRESULT> {
+    if SUCCEEDED(hresult) { Ok(()) } else { Err(hresult) }
+}
+
+fn d3d_blob_part (part: BlobPart) -> D3D_BLOB_PART {
+    match part {
+        BlobPart::InputSignature            => D3D_BLOB_INPUT_SIGNATURE_BLOB,
+        BlobPart::OutputSignature           => D3D_BLOB_OUTPUT_SIGNATURE_BLOB,
+        BlobPart::InputAndOutputSignature   => D3D_BLOB_INPUT_AND_OUTPUT_SIGNATURE_BLOB,
+        BlobPart::PatchConstantSignature    => D3D_BLOB_PATCH_CONSTANT_SIGNATURE_BLOB,
+        BlobPart::AllSignature              => D3D_BLOB_ALL_SIGNATURE_BLOB,
+        BlobPart::DebugInfo                 => D3D_BLOB_DEBUG_INFO,
+        BlobPart::LegacyShader              => D3D_BLOB_LEGACY_SHADER,
+        BlobPart::XnaPrepassShader          => D3D_BLOB_XNA_PREPASS_SHADER,
+        BlobPart::XnaShader                 => D3D_BLOB_XNA_SHADER,
+        BlobPart::Pdb                       => D3D_BLOB_PDB,
+        BlobPart::PrivateData               => D3D_BLOB_PRIVATE_DATA,
+        BlobPart::RootSignature             => D3D_BLOB_ROOT_SIGNATURE,
+    }
+}
+
+/// MSDN: [D3DStripShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dstripshader)
+///
+/// Remove the chunks `D3DCOMPILER_STRIP_*` `flags` select from `bytecode`, e.g. debug info and reflection data for
+/// shipping.  `dxbc::strip` is the pure Rust equivalent.
+pub fn strip_shader (bytecode: &[u8], flags: UINT) -> Result<Blob, HRESULT> {
+    let mut blob = null_mut();
+    check(unsafe { D3DStripShader(bytecode.as_ptr() as LPCVOID, bytecode.len() as SIZE_T, flags, &mut blob) })?;
+    Ok(unsafe { Blob::own(blob) }.unwrap())
+}
+
+/// MSDN: [D3DGetBlobPart](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dgetblobpart)
+///
+/// Extract `part` from `bytecode`.  `dxbc::get_part` is the pure Rust equivalent.
+pub fn get_blob_part (bytecode: &[u8], part: BlobPart) -> Result<Blob, HRESULT> {
+    let mut blob = null_mut();
+    check(unsafe { D3DGetBlobPart(bytecode.as_ptr() as LPCVOID, bytecode.len() as SIZE_T, d3d_blob_part(part), 0, &mut blob) })?;
+    Ok(unsafe { Blob::own(blob) }.unwrap())
+}
+
+/// MSDN: [D3DSetBlobPart](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dsetblobpart)
+///
+/// `bytecode` with `part` replaced by `data`.  Only `PrivateData` and `RootSignature` are supported.  `dxbc::set_part`
+/// is the pure Rust equivalent.
+pub fn set_blob_part (bytecode: &[u8], part: BlobPart, data: &[u8]) -> Result<Blob, HRESULT> {
+    let mut blob = null_mut();
+    check(unsafe { D3DSetBlobPart(
+        bytecode.as_ptr() as LPCVOID,
+        bytecode.len() as SIZE_T,
+        d3d_blob_part(part),
+        0,
+        data.as_ptr() as LPCVOID,
+        data.len() as SIZE_T,
+        &mut blob
+    )})?;
+    Ok(unsafe { Blob::own(blob) }.unwrap())
+}
+
+/// MSDN: [D3DDisassemble](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3ddisassemble)
+///
+/// `bytecode` as assembly text.  `flags` are `D3D_DISASM_*`, `comments` is printed at the top.  `dxbc::disassemble`
+/// is the pure Rust equivalent, without the flags or comments.
+pub fn disassemble (bytecode: &[u8], flags: UINT, comments: Option<&CStr>) -> Result<String, HRESULT> {
+    let mut blob = null_mut();
+    check(unsafe { D3DDisassemble(
+        bytecode.as_ptr() as LPCVOID,
+        bytecode.len() as SIZE_T,
+        flags,
+        comments.map_or(null(), |c| c.as_ptr()),
+        &mut blob
+    )})?;
+    let blob = unsafe { Blob::own(blob) }.unwrap();
+    Ok(blob.to_string_lossy())
+}
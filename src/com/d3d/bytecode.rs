@@ -0,0 +1,97 @@
+use crate::com::d3d::{ShaderStage, Target};
+use crate::dxbc::{self, Container, ShaderVersion};
+use std::fmt;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BytecodeError {
+    /// Not DXBC bytecode `dxbc` can read the version of.  D3D9 (`vs_3_0` etc.) bytecode always ends up here.
+    Dxbc(dxbc::Error),
+    /// Bytecode for `found`, where `expected` was wanted.
+    WrongStage { expected: ShaderStage, found: ShaderStage },
+    /// A DXBC version no `Target` produces.
+    UnknownVersion(ShaderVersion),
+}
+
+impl fmt::Display for BytecodeError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BytecodeError::Dxbc(ref e)                      => write!(f, "invalid bytecode: {:?}", e),
+            BytecodeError::WrongStage { expected, found }   => write!(f, "expected {:?} shader bytecode, found {:?}", expected, found),
+            BytecodeError::UnknownVersion(version)          => write!(f, "unknown shader version {:?}", version),
+        }
+    }
+}
+
+impl From<dxbc::Error> for BytecodeError {
+    fn from (e: dxbc::Error) -> Self { BytecodeError::Dxbc(e) }
+}
+
+/// The `Target` `bytecode` was compiled for, read from its DXBC container.
+pub fn bytecode_target (bytecode: &[u8]) -> Result<Target, BytecodeError> {
+    let container = Container::parse(bytecode)?;
+    let version = ShaderVersion::from_container(&container)?;
+    let legacy_version = dxbc::legacy_shader_version(&container)?;
+    Target::from_version(version, legacy_version).ok_or(BytecodeError::UnknownVersion(version))
+}
+
+/// Compiled bytecode for a single shader stage, checked to be DXBC for that stage.  Only the matching
+/// `Device::create_*_shader` accepts it.
+pub trait Bytecode : Sized {
+    const STAGE : ShaderStage;
+
+    /// Check that `bytes` are DXBC bytecode for `STAGE`.
+    fn from_bytes (bytes: Vec<u8>) -> Result<Self, BytecodeError>;
+
+    /// Check that `bytes` are DXBC bytecode for `STAGE`, copying them.
+    fn from_slice (bytes: &[u8]) -> Result<Self, BytecodeError> { Self::from_bytes(bytes.to_vec()) }
+
+    /// The target this bytecode was compiled for.
+    fn target (&self) -> Target;
+
+    fn as_bytes (&self) -> &[u8];
+    fn into_bytes (self) -> Vec<u8>;
+}
+
+macro_rules! bytecode {
+    ($(pub struct $name:ident : $stage:ident;)+) => {$(
+        #[derive(Clone, PartialEq, Eq, Debug)]
+        pub struct $name {
+            bytes:  Vec<u8>,
+            target: Target,
+        }
+
+        impl Bytecode for $name {
+            const STAGE : ShaderStage = ShaderStage::$stage;
+
+            fn from_bytes (bytes: Vec<u8>) -> Result<Self, BytecodeError> {
+                let target = bytecode_target(&bytes[..])?;
+                if target.stage() != Self::STAGE { return Err(BytecodeError::WrongStage { expected: Self::STAGE, found: target.stage() }); }
+                Ok(Self { bytes, target })
+            }
+
+            fn target (&self) -> Target { self.target }
+            fn as_bytes (&self) -> &[u8] { &self.bytes[..] }
+            fn into_bytes (self) -> Vec<u8> { self.bytes }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref (&self) -> &[u8] { &self.bytes[..] }
+        }
+    )+};
+}
+
+bytecode! {
+    pub struct VertexBytecode   : Vertex;
+    pub struct HullBytecode     : Hull;
+    pub struct DomainBytecode   : Domain;
+    pub struct GeometryBytecode : Geometry;
+    pub struct PixelBytecode    : Pixel;
+    pub struct ComputeBytecode  : Compute;
+}
+
+/// Bytecode `Device::create_geometry_shader_with_stream_output` can stream out from:  whichever stage runs last before
+/// the rasterizer.
+pub trait StreamOutputBytecode : Bytecode {}
+impl StreamOutputBytecode for VertexBytecode {}
+impl StreamOutputBytecode for DomainBytecode {}
+impl StreamOutputBytecode for GeometryBytecode {}
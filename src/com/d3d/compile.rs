@@ -1,7 +1,8 @@
 use crate::win32::*;
 use crate::com::Wrapper;
-use crate::com::d3d::Blob;
-use std::ffi::CStr;
+use crate::com::d3d::{Blob, Bytecode, CompileOptions, Target};
+use crate::permutation::{Permutation, PermutationSet, Variant};
+use std::ffi::{CStr, CString};
 use std::path::Path;
 use std::ptr::{null, null_mut};
 
@@ -15,8 +16,9 @@ pub struct MagicConstantInclude(*mut ID3DInclude);
 pub const COMPILE_STANDARD_FILE_INCLUDE : MagicConstantInclude = MagicConstantInclude(D3D_COMPILE_STANDARD_FILE_INCLUDE);
 
 /// `unsafe` - `into_compile_include` is assumed to return a *valid* pointer!
+#[allow(clippy::wrong_self_convention)] // Borrows, since the include must outlive the compile
 pub unsafe trait IntoSafeCompileInclude                     { fn into_compile_include(&self) -> *mut ID3DInclude; }
-unsafe impl IntoSafeCompileInclude for ID3DInclude          { fn into_compile_include(&self) -> *mut ID3DInclude { &*self as *const _ as *mut _ } }
+unsafe impl IntoSafeCompileInclude for ID3DInclude          { fn into_compile_include(&self) -> *mut ID3DInclude { self as *const _ as *mut _ } }
 unsafe impl IntoSafeCompileInclude for MagicConstantInclude { fn into_compile_include(&self) -> *mut ID3DInclude { self.0 } }
 
 #[derive(Clone)]
@@ -25,6 +27,13 @@ pub struct ShaderAndWarnings {
     pub warnings: Option<Blob>,
 }
 
+/// The result of `compile`:  bytecode for the shader stage `B` is for.
+#[derive(Clone)]
+pub struct CompiledShader<B: Bytecode> {
+    pub shader:   B,
+    pub warnings: Option<Blob>,
+}
+
 #[derive(Clone)]
 pub struct ResultAndErrors {
     pub hresult: HRESULT,
@@ -37,6 +46,19 @@ impl std::fmt::Debug for ResultAndErrors {
     }
 }
 
+/// A `ResultAndErrors` with the errors copied out of their `Blob`, so it can be handed between threads.
+#[derive(Clone, Debug)]
+pub struct CompileFailure {
+    pub hresult: HRESULT,
+    pub errors:  String,
+}
+
+impl From<ResultAndErrors> for CompileFailure {
+    fn from (failure: ResultAndErrors) -> Self {
+        Self { hresult: failure.hresult, errors: failure.errors.map_or(String::new(), |e| e.to_string_lossy()) }
+    }
+}
+
 /// # [D3DCompile](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dcompile)
 /// 
 /// `unsafe` - `defines` contains raw pointers that must be dereferenced.
@@ -46,10 +68,15 @@ impl std::fmt::Debug for ResultAndErrors {
 /// * `source_data` - ASCII shader source code.
 /// * `source_name` - Used for error messages.
 /// * `defines` - If present, must be terminated with a "NULL" `D3D_SHADER_MACRO` (e.g. one with `Name`/`Definition` being `null()`)
-/// * `include` - If present, allows `#include` statements.  Consider using `Some(d3d::COMPILE_STANDARD_FILE_INCLUDE)`.
+/// * `include` - If present, allows `#include` statements.  Consider using `Some(d3d::COMPILE_STANDARD_FILE_INCLUDE)`,
+///   or `Some(&d3d::IncludeObject::new(...))` to resolve them from Rust.
 /// * `entrypoint` - Should be `None` for `fx_*` profiles, must be a valid function name (`Some("main")`?) otherwise.
 /// * `target` - A valid [compiler target](https://docs.microsoft.com/en-us/windows/desktop/direct3dhlsl/specifying-compiler-targets)
-pub unsafe fn compile<I: IntoSafeCompileInclude> (
+/// 
+/// Prefer `compile`, unless compiling effects or D3D9 shaders.  `fx_4_*` and `fx_5_0` output can be loaded with
+/// `d3d11::Effect::from_bytecode`.
+#[allow(clippy::too_many_arguments)] // One per D3DCompile parameter
+pub unsafe fn compile_raw<I: IntoSafeCompileInclude> (
     source_data:            &[u8],
     source_name:            Option<&Path>,
     defines:                Option<&[D3D_SHADER_MACRO]>,
@@ -66,11 +93,8 @@ pub unsafe fn compile<I: IntoSafeCompileInclude> (
         expect_eq!(null(), last_define.Definition);
     }
 
-    let mut source_name_buf = Vec::new();
-    let source_name = source_name.map_or(null(), |sn| {
-        source_name_buf = Vec::from(sn.as_os_str().to_string_lossy().as_bytes());
-        source_name_buf.as_ptr() as *const _
-    });
+    let source_name = source_name.map(source_name_cstring);
+    let source_name = source_name.as_ref().map_or(null(), |sn| sn.as_ptr());
 
     let mut shader = null_mut();
     let mut errors = null_mut();
@@ -79,7 +103,7 @@ pub unsafe fn compile<I: IntoSafeCompileInclude> (
         source_data.len() as SIZE_T,
         source_name,
         defines.map_or(null_mut(), |d| d.as_ptr()),
-        include.map_or(null_mut(), |i| i.into_compile_include()),
+        include.as_ref().map_or(null_mut(), |i| i.into_compile_include()),
         entrypoint.map_or(null_mut(), |e| e.as_ptr()),
         target.as_ptr(),
         flags1,
@@ -88,8 +112,127 @@ pub unsafe fn compile<I: IntoSafeCompileInclude> (
         &mut errors
     );
     if SUCCEEDED(hresult) {
-        Ok(ShaderAndWarnings { shader: Blob::own(shader).unwrap(), warnings: Blob::own(errors).map_or(None, |e| Some(e)) })
+        Ok(ShaderAndWarnings { shader: Blob::own(shader).unwrap(), warnings: Blob::own(errors) })
     } else {
-        Err(ResultAndErrors { hresult, errors: Blob::own(errors).map_or(None, |e| Some(e)) })
+        Err(ResultAndErrors { hresult, errors: Blob::own(errors) })
     }
 }
+
+/// `source_name` as the NUL terminated string `D3DCompile` / `D3DPreprocess` expect, which they copy into diagnostics.
+fn source_name_cstring (source_name: &Path) -> CString {
+    CString::new(source_name.as_os_str().to_string_lossy().into_owned()).expect("paths can't contain NUL")
+}
+
+/// The result of `preprocess`.
+#[derive(Clone)]
+pub struct TextAndWarnings {
+    pub text:     String,
+    pub warnings: Option<Blob>,
+}
+
+/// # [D3DPreprocess](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dpreprocess)
+/// 
+/// Run only the preprocessor over `source_data`, e.g. to see what macro-heavy includes expand to.  Arguments are as
+/// for `compile_raw`.
+/// 
+/// `unsafe` - `defines` contains raw pointers that must be dereferenced.
+pub unsafe fn preprocess<I: IntoSafeCompileInclude> (
+    source_data:            &[u8],
+    source_name:            Option<&Path>,
+    defines:                Option<&[D3D_SHADER_MACRO]>,
+    include:                Option<I>,
+) -> std::result::Result<TextAndWarnings, ResultAndErrors> {
+    if let Some(defines) = defines {
+        expect_ne!(defines.len(), 0);
+        let last_define = defines[defines.len()-1];
+        expect_eq!(null(), last_define.Name);
+        expect_eq!(null(), last_define.Definition);
+    }
+
+    let source_name = source_name.map(source_name_cstring);
+    let source_name = source_name.as_ref().map_or(null(), |sn| sn.as_ptr());
+
+    let mut text = null_mut();
+    let mut errors = null_mut();
+    let hresult = D3DPreprocess(
+        source_data.as_ptr() as LPCVOID,
+        source_data.len() as SIZE_T,
+        source_name,
+        defines.map_or(null(), |d| d.as_ptr()),
+        include.as_ref().map_or(null_mut(), |i| i.into_compile_include()),
+        &mut text,
+        &mut errors
+    );
+    if SUCCEEDED(hresult) {
+        Ok(TextAndWarnings { text: Blob::own(text).unwrap().to_string_lossy(), warnings: Blob::own(errors) })
+    } else {
+        Err(ResultAndErrors { hresult, errors: Blob::own(errors) })
+    }
+}
+
+/// # [D3DCompile](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dcompile)
+/// 
+/// `compile_raw` a single shader stage, producing bytecode only the matching `Device::create_*_shader` accepts:
+/// 
+/// ```ignore
+/// let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+/// let include = Some(d3d::COMPILE_STANDARD_FILE_INCLUDE);
+/// let options = d3d::CompileOptions::release();
+/// let vs : d3d::CompiledShader<d3d::VertexBytecode> = d3d::compile(source, None, None, include, main, d3d::Target::vs_5_0, &options)?;
+/// ```
+/// 
+/// Fails with `E_INVALIDARG` if `target` isn't a D3D10+ target for `B`'s stage.
+pub unsafe fn compile<B: Bytecode, I: IntoSafeCompileInclude> (
+    source_data:            &[u8],
+    source_name:            Option<&Path>,
+    defines:                Option<&[D3D_SHADER_MACRO]>,
+    include:                Option<I>,
+    entrypoint:             &CStr,
+    target:                 Target,
+    options:                &CompileOptions,
+) -> std::result::Result<CompiledShader<B>, ResultAndErrors> {
+    if target.stage() != B::STAGE || target.min_feature_level().is_none() {
+        return Err(ResultAndErrors { hresult: E_INVALIDARG, errors: None });
+    }
+
+    let ShaderAndWarnings { shader, warnings } = compile_raw(source_data, source_name, defines, include, Some(entrypoint), target.to_cstr(), options.flags1(), options.flags2())?;
+    match B::from_slice(shader.as_bytes()) {
+        Ok(shader)  => Ok(CompiledShader { shader, warnings }),
+        Err(_)      => Err(ResultAndErrors { hresult: E_FAIL, errors: None }), // D3DCompile produced something unexpected?
+    }
+}
+
+/// `compile` every variant of `permutation` on `threads` threads, with each variant's keywords as defines.  `#include`s
+/// resolve through `COMPILE_STANDARD_FILE_INCLUDE`, relative to `source_name`.
+pub fn compile_permutations<B: Bytecode + Send + 'static> (
+    source_data:            &[u8],
+    source_name:            Option<&Path>,
+    permutation:            &Permutation,
+    entrypoint:             &CStr,
+    target:                 Target,
+    options:                &CompileOptions,
+    threads:                usize,
+) -> std::result::Result<PermutationSet<B>, (Variant, CompileFailure)> {
+    let source_data = source_data.to_vec();
+    let source_name = source_name.map(|p| p.to_path_buf());
+    let entrypoint = entrypoint.to_owned();
+    let options = *options;
+
+    permutation.compile_all(threads, move |variant| {
+        // `Permutation::new` only accepts identifiers and values without NULs.
+        let cstring = |s: &String| CString::new(s.clone()).expect("compile_permutations: NUL in a validated keyword");
+        let defines = variant.defines().iter().map(|(name, value)| (cstring(name), cstring(value))).collect::<Vec<_>>();
+        let mut macros = defines.iter().map(|(name, value)| D3D_SHADER_MACRO { Name: name.as_ptr(), Definition: value.as_ptr() }).collect::<Vec<_>>();
+        macros.push(D3D_SHADER_MACRO { Name: null(), Definition: null() });
+        let compiled = unsafe { compile::<B, _>(
+            &source_data[..],
+            source_name.as_deref(),
+            Some(&macros[..]),
+            Some(COMPILE_STANDARD_FILE_INCLUDE),
+            &entrypoint,
+            target,
+            &options
+        )}.map_err(CompileFailure::from)?;
+        Ok(compiled.shader)
+    })
+}
@@ -0,0 +1,401 @@
+use std::fmt;
+
+// MSDN: [D3DCOMPILE Constants](https://docs.microsoft.com/en-us/windows/desktop/direct3dhlsl/d3dcompile-constants)
+// Spelled out here rather than taken from winapi, so options can be built, compared, and serialized anywhere.
+const DEBUG                             : u32 = 1 << 0;
+const SKIP_VALIDATION                   : u32 = 1 << 1;
+const SKIP_OPTIMIZATION                 : u32 = 1 << 2;
+const PACK_MATRIX_ROW_MAJOR             : u32 = 1 << 3;
+const PACK_MATRIX_COLUMN_MAJOR          : u32 = 1 << 4;
+const PARTIAL_PRECISION                 : u32 = 1 << 5;
+const AVOID_FLOW_CONTROL                : u32 = 1 << 9;
+const PREFER_FLOW_CONTROL               : u32 = 1 << 10;
+const ENABLE_STRICTNESS                 : u32 = 1 << 11;
+const ENABLE_BACKWARDS_COMPATIBILITY    : u32 = 1 << 12;
+const IEEE_STRICTNESS                   : u32 = 1 << 13;
+const OPTIMIZATION_LEVEL0               : u32 = 1 << 14;
+const OPTIMIZATION_LEVEL1               : u32 = 0;
+const OPTIMIZATION_LEVEL2               : u32 = (1 << 14) | (1 << 15);
+const OPTIMIZATION_LEVEL3               : u32 = 1 << 15;
+const WARNINGS_ARE_ERRORS               : u32 = 1 << 18;
+const RESOURCES_MAY_ALIAS               : u32 = 1 << 19;
+const ALL_RESOURCES_BOUND               : u32 = 1 << 21;
+
+const EFFECT_CHILD_EFFECT               : u32 = 1 << 0;
+const EFFECT_ALLOW_SLOW_OPS             : u32 = 1 << 1;
+
+const OPTIMIZATION_MASK                 : u32 = (1 << 14) | (1 << 15);
+
+/// `D3DCOMPILE_SKIP_OPTIMIZATION` / `D3DCOMPILE_OPTIMIZATION_LEVEL*`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Optimization {
+    Skip,
+    Level0,
+    /// The compiler's default.
+    Level1,
+    Level2,
+    Level3,
+}
+
+/// `D3DCOMPILE_PACK_MATRIX_*`:  how matrices in constant buffers are laid out, unless declared otherwise.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MatrixPacking {
+    /// Column major.
+    Default,
+    RowMajor,
+    ColumnMajor,
+}
+
+/// `D3DCOMPILE_*_FLOW_CONTROL`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FlowControl {
+    Default,
+    Avoid,
+    Prefer,
+}
+
+/// Typed `flags1` / `flags2` for `d3d::compile`.  Start from `default()` or a preset, then adjust:
+///
+/// ```ignore
+/// let options = CompileOptions::release().matrix_packing(MatrixPacking::RowMajor).warnings_are_errors(true);
+/// ```
+///
+/// `Display` / `parse` round trip through `fxc`-style switches (e.g. `"/O3 /Zpr /WX"`), always in the same order, for
+/// use in shader cache keys.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CompileOptions {
+    pub debug:                  bool,
+    pub skip_validation:        bool,
+    pub optimization:           Optimization,
+    pub matrix_packing:         MatrixPacking,
+    pub partial_precision:      bool,
+    pub flow_control:           FlowControl,
+    pub strictness:             bool,
+    pub backwards_compatibility: bool,
+    pub ieee_strictness:        bool,
+    pub warnings_are_errors:    bool,
+    pub resources_may_alias:    bool,
+    pub all_resources_bound:    bool,
+    /// `D3DCOMPILE_EFFECT_CHILD_EFFECT` (`fx_*` targets only.)
+    pub child_effect:           bool,
+    /// `D3DCOMPILE_EFFECT_ALLOW_SLOW_OPS` (`fx_*` targets only.)
+    pub allow_slow_ops:         bool,
+}
+
+/// Raw flags that `CompileOptions` doesn't know, or a combination it can't represent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnknownFlags {
+    pub flags1: u32,
+    pub flags2: u32,
+}
+
+impl Default for CompileOptions {
+    /// What `D3DCompile` does with flags of `0, 0`.
+    fn default () -> Self {
+        Self {
+            debug:                      false,
+            skip_validation:            false,
+            optimization:               Optimization::Level1,
+            matrix_packing:             MatrixPacking::Default,
+            partial_precision:          false,
+            flow_control:               FlowControl::Default,
+            strictness:                 false,
+            backwards_compatibility:    false,
+            ieee_strictness:            false,
+            warnings_are_errors:        false,
+            resources_may_alias:        false,
+            all_resources_bound:        false,
+            child_effect:               false,
+            allow_slow_ops:             false,
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Debug info, no optimization:  for stepping through shaders in a graphics debugger.
+    pub fn debug () -> Self { Self::default().debug_info(true).optimization(Optimization::Skip) }
+
+    /// Fully optimized, with strict HLSL.
+    pub fn release () -> Self { Self::default().optimization(Optimization::Level3).strictness(true) }
+
+    /// `release`, with warnings promoted to errors so nothing questionable ships.
+    pub fn shipping () -> Self { Self::release().warnings_are_errors(true) }
+
+    pub fn debug_info               (mut self, value: bool) -> Self { self.debug = value; self }
+    pub fn skip_validation          (mut self, value: bool) -> Self { self.skip_validation = value; self }
+    pub fn optimization             (mut self, value: Optimization) -> Self { self.optimization = value; self }
+    pub fn matrix_packing           (mut self, value: MatrixPacking) -> Self { self.matrix_packing = value; self }
+    pub fn partial_precision        (mut self, value: bool) -> Self { self.partial_precision = value; self }
+    pub fn flow_control             (mut self, value: FlowControl) -> Self { self.flow_control = value; self }
+    pub fn strictness               (mut self, value: bool) -> Self { self.strictness = value; self }
+    pub fn backwards_compatibility  (mut self, value: bool) -> Self { self.backwards_compatibility = value; self }
+    pub fn ieee_strictness          (mut self, value: bool) -> Self { self.ieee_strictness = value; self }
+    pub fn warnings_are_errors      (mut self, value: bool) -> Self { self.warnings_are_errors = value; self }
+    pub fn resources_may_alias      (mut self, value: bool) -> Self { self.resources_may_alias = value; self }
+    pub fn all_resources_bound      (mut self, value: bool) -> Self { self.all_resources_bound = value; self }
+    pub fn child_effect             (mut self, value: bool) -> Self { self.child_effect = value; self }
+    pub fn allow_slow_ops           (mut self, value: bool) -> Self { self.allow_slow_ops = value; self }
+
+    /// `D3DCOMPILE_*` flags, for `D3DCompile`'s `Flags1`.
+    pub fn flags1 (&self) -> u32 {
+        let mut flags = 0;
+        if self.debug                   { flags |= DEBUG; }
+        if self.skip_validation         { flags |= SKIP_VALIDATION; }
+        flags |= match self.optimization {
+            Optimization::Skip      => SKIP_OPTIMIZATION,
+            Optimization::Level0    => OPTIMIZATION_LEVEL0,
+            Optimization::Level1    => OPTIMIZATION_LEVEL1,
+            Optimization::Level2    => OPTIMIZATION_LEVEL2,
+            Optimization::Level3    => OPTIMIZATION_LEVEL3,
+        };
+        flags |= match self.matrix_packing {
+            MatrixPacking::Default      => 0,
+            MatrixPacking::RowMajor     => PACK_MATRIX_ROW_MAJOR,
+            MatrixPacking::ColumnMajor  => PACK_MATRIX_COLUMN_MAJOR,
+        };
+        if self.partial_precision       { flags |= PARTIAL_PRECISION; }
+        flags |= match self.flow_control {
+            FlowControl::Default    => 0,
+            FlowControl::Avoid      => AVOID_FLOW_CONTROL,
+            FlowControl::Prefer     => PREFER_FLOW_CONTROL,
+        };
+        if self.strictness              { flags |= ENABLE_STRICTNESS; }
+        if self.backwards_compatibility { flags |= ENABLE_BACKWARDS_COMPATIBILITY; }
+        if self.ieee_strictness         { flags |= IEEE_STRICTNESS; }
+        if self.warnings_are_errors     { flags |= WARNINGS_ARE_ERRORS; }
+        if self.resources_may_alias     { flags |= RESOURCES_MAY_ALIAS; }
+        if self.all_resources_bound     { flags |= ALL_RESOURCES_BOUND; }
+        flags
+    }
+
+    /// `D3DCOMPILE_EFFECT_*` flags, for `D3DCompile`'s `Flags2`.
+    pub fn flags2 (&self) -> u32 {
+        let mut flags = 0;
+        if self.child_effect            { flags |= EFFECT_CHILD_EFFECT; }
+        if self.allow_slow_ops          { flags |= EFFECT_ALLOW_SLOW_OPS; }
+        flags
+    }
+
+    /// The inverse of `flags1` / `flags2`.  Fails on flags this doesn't know (`FORCE_*_SOFTWARE_NO_OPT`, `NO_PRESHADER`,
+    /// `DEBUG_NAME_FOR_*`, ...) and contradictory combinations like row *and* column major packing.
+    pub fn from_raw (flags1: u32, flags2: u32) -> Result<Self, UnknownFlags> {
+        let err = UnknownFlags { flags1, flags2 };
+        let has = |flag: u32| flags1 & flag != 0;
+
+        let optimization = match (has(SKIP_OPTIMIZATION), flags1 & OPTIMIZATION_MASK) {
+            (true,  OPTIMIZATION_LEVEL1)    => Optimization::Skip, // SKIP_OPTIMIZATION + LEVEL0 etc. is ambiguous
+            (true,  _)                      => return Err(err),
+            (false, OPTIMIZATION_LEVEL0)    => Optimization::Level0,
+            (false, OPTIMIZATION_LEVEL1)    => Optimization::Level1,
+            (false, OPTIMIZATION_LEVEL2)    => Optimization::Level2,
+            (false, _)                      => Optimization::Level3,
+        };
+        let matrix_packing = match (has(PACK_MATRIX_ROW_MAJOR), has(PACK_MATRIX_COLUMN_MAJOR)) {
+            (false, false)  => MatrixPacking::Default,
+            (true,  false)  => MatrixPacking::RowMajor,
+            (false, true )  => MatrixPacking::ColumnMajor,
+            (true,  true )  => return Err(err),
+        };
+        let flow_control = match (has(AVOID_FLOW_CONTROL), has(PREFER_FLOW_CONTROL)) {
+            (false, false)  => FlowControl::Default,
+            (true,  false)  => FlowControl::Avoid,
+            (false, true )  => FlowControl::Prefer,
+            (true,  true )  => return Err(err),
+        };
+
+        let options = Self {
+            debug:                      has(DEBUG),
+            skip_validation:            has(SKIP_VALIDATION),
+            optimization,
+            matrix_packing,
+            partial_precision:          has(PARTIAL_PRECISION),
+            flow_control,
+            strictness:                 has(ENABLE_STRICTNESS),
+            backwards_compatibility:    has(ENABLE_BACKWARDS_COMPATIBILITY),
+            ieee_strictness:            has(IEEE_STRICTNESS),
+            warnings_are_errors:        has(WARNINGS_ARE_ERRORS),
+            resources_may_alias:        has(RESOURCES_MAY_ALIAS),
+            all_resources_bound:        has(ALL_RESOURCES_BOUND),
+            child_effect:               flags2 & EFFECT_CHILD_EFFECT != 0,
+            allow_slow_ops:             flags2 & EFFECT_ALLOW_SLOW_OPS != 0,
+        };
+        // Anything left over is a flag we don't model.
+        if options.flags1() != flags1 || options.flags2() != flags2 { return Err(err); }
+        Ok(options)
+    }
+
+    /// The `fxc` switches for these options, in a fixed order.  `Display` joins them with spaces.
+    pub fn switches (&self) -> Vec<&'static str> {
+        let mut switches = Vec::new();
+        if self.debug                   { switches.push("/Zi"); }
+        if self.skip_validation         { switches.push("/Vd"); }
+        switches.push(match self.optimization {
+            Optimization::Skip      => "/Od",
+            Optimization::Level0    => "/O0",
+            Optimization::Level1    => "/O1",
+            Optimization::Level2    => "/O2",
+            Optimization::Level3    => "/O3",
+        });
+        match self.matrix_packing {
+            MatrixPacking::Default      => {},
+            MatrixPacking::RowMajor     => switches.push("/Zpr"),
+            MatrixPacking::ColumnMajor  => switches.push("/Zpc"),
+        }
+        if self.partial_precision       { switches.push("/Gpp"); }
+        match self.flow_control {
+            FlowControl::Default    => {},
+            FlowControl::Avoid      => switches.push("/Gfa"),
+            FlowControl::Prefer     => switches.push("/Gfp"),
+        }
+        if self.strictness              { switches.push("/Ges"); }
+        if self.backwards_compatibility { switches.push("/Gec"); }
+        if self.ieee_strictness         { switches.push("/Gis"); }
+        if self.warnings_are_errors     { switches.push("/WX"); }
+        if self.resources_may_alias     { switches.push("/res_may_alias"); }
+        if self.all_resources_bound     { switches.push("/all_resources_bound"); }
+        if self.child_effect            { switches.push("/Gch"); }
+        if self.allow_slow_ops          { switches.push("/allow_slow_ops"); }
+        switches
+    }
+
+    /// Apply a single `fxc` switch (`/` or `-` prefixed) to `self`.  Returns `false` if it isn't one `switches` emits.
+    pub fn apply_switch (&mut self, switch: &str) -> bool {
+        let name = if switch.starts_with('/') || switch.starts_with('-') { &switch[1..] } else { return false; };
+        match name {
+            "Zi"                    => self.debug = true,
+            "Vd"                    => self.skip_validation = true,
+            "Od"                    => self.optimization = Optimization::Skip,
+            "O0"                    => self.optimization = Optimization::Level0,
+            "O1"                    => self.optimization = Optimization::Level1,
+            "O2"                    => self.optimization = Optimization::Level2,
+            "O3"                    => self.optimization = Optimization::Level3,
+            "Zpr"                   => self.matrix_packing = MatrixPacking::RowMajor,
+            "Zpc"                   => self.matrix_packing = MatrixPacking::ColumnMajor,
+            "Gpp"                   => self.partial_precision = true,
+            "Gfa"                   => self.flow_control = FlowControl::Avoid,
+            "Gfp"                   => self.flow_control = FlowControl::Prefer,
+            "Ges"                   => self.strictness = true,
+            "Gec"                   => self.backwards_compatibility = true,
+            "Gis"                   => self.ieee_strictness = true,
+            "WX"                    => self.warnings_are_errors = true,
+            "res_may_alias"         => self.resources_may_alias = true,
+            "all_resources_bound"   => self.all_resources_bound = true,
+            "Gch"                   => self.child_effect = true,
+            "allow_slow_ops"        => self.allow_slow_ops = true,
+            _                       => return false,
+        }
+        true
+    }
+
+    /// Parse whitespace separated `fxc` switches (such as `Display` produces) on top of `default()`.  Returns the first
+    /// unrecognized switch on failure.
+    pub fn parse (switches: &str) -> Result<Self, String> {
+        let mut options = Self::default();
+        for switch in switches.split_whitespace() {
+            if !options.apply_switch(switch) { return Err(switch.to_string()); }
+        }
+        Ok(options)
+    }
+}
+
+impl fmt::Display for CompileOptions {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.switches().join(" "))
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    /// Every combination of options:  11 bools times every optimization, packing and flow control.
+    fn all () -> Vec<CompileOptions> {
+        let optimizations   = [Optimization::Skip, Optimization::Level0, Optimization::Level1, Optimization::Level2, Optimization::Level3];
+        let packings        = [MatrixPacking::Default, MatrixPacking::RowMajor, MatrixPacking::ColumnMajor];
+        let flow_controls   = [FlowControl::Default, FlowControl::Avoid, FlowControl::Prefer];
+        let mut all = Vec::new();
+        for bits in 0..(1u32 << 11) {
+            let bit = |n: u32| bits & (1 << n) != 0;
+            for &optimization in optimizations.iter() {
+                for &matrix_packing in packings.iter() {
+                    for &flow_control in flow_controls.iter() {
+                        all.push(CompileOptions {
+                            debug: bit(0), skip_validation: bit(1), optimization, matrix_packing, partial_precision: bit(2),
+                            flow_control, strictness: bit(3), backwards_compatibility: bit(4), ieee_strictness: bit(5),
+                            warnings_are_errors: bit(6), resources_may_alias: bit(7), all_resources_bound: bit(8),
+                            child_effect: bit(9), allow_slow_ops: bit(10),
+                        });
+                    }
+                }
+            }
+        }
+        all
+    }
+
+    #[test] fn raw_round_trip () {
+        for options in all() {
+            assert_eq!(CompileOptions::from_raw(options.flags1(), options.flags2()), Ok(options));
+        }
+    }
+
+    #[test] fn switches_round_trip () {
+        for options in all() {
+            assert_eq!(CompileOptions::parse(&options.to_string()), Ok(options));
+        }
+    }
+
+    #[test] fn default_is_zero () {
+        assert_eq!((CompileOptions::default().flags1(), CompileOptions::default().flags2()), (0, 0));
+        assert_eq!(CompileOptions::from_raw(0, 0), Ok(CompileOptions::default()));
+    }
+
+    #[test] fn optimization_levels () {
+        // OPTIMIZATION_LEVEL1 is 0, so SKIP_OPTIMIZATION alone is unambiguous...
+        assert_eq!(CompileOptions::from_raw(SKIP_OPTIMIZATION, 0).map(|o| o.optimization), Ok(Optimization::Skip));
+        assert_eq!(CompileOptions::from_raw(SKIP_OPTIMIZATION | OPTIMIZATION_LEVEL1, 0).map(|o| o.optimization), Ok(Optimization::Skip));
+        // ...but combined with any other level, it isn't.
+        for &level in [OPTIMIZATION_LEVEL0, OPTIMIZATION_LEVEL2, OPTIMIZATION_LEVEL3].iter() {
+            let flags1 = SKIP_OPTIMIZATION | level;
+            assert_eq!(CompileOptions::from_raw(flags1, 0), Err(UnknownFlags { flags1, flags2: 0 }));
+        }
+        assert_eq!(CompileOptions::from_raw(OPTIMIZATION_LEVEL2, 0).map(|o| o.optimization), Ok(Optimization::Level2));
+        assert_eq!(CompileOptions::from_raw(OPTIMIZATION_LEVEL3, 0).map(|o| o.optimization), Ok(Optimization::Level3));
+    }
+
+    #[test] fn unknown_flags () {
+        let unknown = |flags1, flags2| CompileOptions::from_raw(flags1, flags2) == Err(UnknownFlags { flags1, flags2 });
+        assert!(unknown(PACK_MATRIX_ROW_MAJOR | PACK_MATRIX_COLUMN_MAJOR, 0));
+        assert!(unknown(AVOID_FLOW_CONTROL | PREFER_FLOW_CONTROL, 0));
+        assert!(unknown(1 << 6, 0));    // FORCE_VS_SOFTWARE_NO_OPT
+        assert!(unknown(1 << 8, 0));    // NO_PRESHADER
+        assert!(unknown(1 << 22, 0));   // DEBUG_NAME_FOR_SOURCE
+        assert!(unknown(0, 1 << 2));
+    }
+
+    #[test] fn presets () {
+        assert_eq!(CompileOptions::debug().to_string(),     "/Zi /Od");
+        assert_eq!(CompileOptions::release().to_string(),   "/O3 /Ges");
+        assert_eq!(CompileOptions::shipping().to_string(),  "/O3 /Ges /WX");
+        assert_eq!(CompileOptions::default().to_string(),   "/O1");
+        assert_eq!(CompileOptions::debug().flags1(),        DEBUG | SKIP_OPTIMIZATION);
+        assert_eq!(CompileOptions::release().flags1(),      OPTIMIZATION_LEVEL3 | ENABLE_STRICTNESS);
+        assert_eq!(CompileOptions::shipping().flags1(),     OPTIMIZATION_LEVEL3 | ENABLE_STRICTNESS | WARNINGS_ARE_ERRORS);
+    }
+
+    #[test] fn display_is_stable () {
+        // Shader cache keys depend on this exact text:  don't reorder or rename switches.
+        let everything = CompileOptions::default()
+            .debug_info(true).skip_validation(true).optimization(Optimization::Level2).matrix_packing(MatrixPacking::RowMajor)
+            .partial_precision(true).flow_control(FlowControl::Prefer).strictness(true).backwards_compatibility(true)
+            .ieee_strictness(true).warnings_are_errors(true).resources_may_alias(true).all_resources_bound(true)
+            .child_effect(true).allow_slow_ops(true);
+        assert_eq!(everything.to_string(), "/Zi /Vd /O2 /Zpr /Gpp /Gfp /Ges /Gec /Gis /WX /res_may_alias /all_resources_bound /Gch /allow_slow_ops");
+    }
+
+    #[test] fn parse () {
+        assert_eq!(CompileOptions::parse(""), Ok(CompileOptions::default()));
+        assert_eq!(CompileOptions::parse("-O3  -Ges\t/WX"), Ok(CompileOptions::shipping()));
+        assert_eq!(CompileOptions::parse("/O3 /Od"), Ok(CompileOptions::default().optimization(Optimization::Skip))); // Last wins
+        assert_eq!(CompileOptions::parse("/O3 /Qstrip_debug"), Err(String::from("/Qstrip_debug")));
+        assert_eq!(CompileOptions::parse("O3"), Err(String::from("O3")));
+    }
+}
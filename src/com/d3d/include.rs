@@ -0,0 +1,140 @@
+use crate::com::abi::*;
+use std::cell::RefCell;
+use std::ffi::CStr;
+
+/// Resolves `#include`s for `d3d::compile` from Rust.
+pub trait Include {
+    /// Load the contents of `file_name`.
+    ///
+    /// * `include_type` - `D3D_INCLUDE_LOCAL` for `#include "..."`, `D3D_INCLUDE_SYSTEM` for `#include <...>`.
+    /// * `parent` - The contents of the file doing the including, or `None` for the top level source.
+    fn open (&self, include_type: D3D_INCLUDE_TYPE, file_name: &CStr, parent: Option<&[u8]>) -> Result<Vec<u8>, HRESULT>;
+}
+
+/// MSDN: [ID3DInclude](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcommon/nn-d3dcommon-id3dinclude)
+///
+/// An `Include` behind an `ID3DInclude` vtable.  `ID3DInclude` isn't an `IUnknown`, so there's no refcount:  the
+/// `IncludeObject` simply has to outlive the `d3d::compile` call it's passed to, which borrowing it ensures.
+#[repr(C)]
+pub struct IncludeObject<I: Include> {
+    vtbl:       &'static ID3DIncludeVtbl,
+    include:    I,
+    // Files handed out by `Open` and not yet `Close`d, so they can be freed, or passed back in as `parent`.
+    files:      RefCell<Vec<Box<[u8]>>>,
+}
+
+impl<I: Include> IncludeObject<I> {
+    const VTBL : &'static ID3DIncludeVtbl = &ID3DIncludeVtbl { Open: Self::open, Close: Self::close };
+
+    pub fn new (include: I) -> Self { Self { vtbl: Self::VTBL, include, files: RefCell::new(Vec::new()) } }
+
+    unsafe extern "system" fn open (this: *mut ID3DInclude, include_type: D3D_INCLUDE_TYPE, file_name: LPCSTR, parent: LPCVOID, data: *mut LPCVOID, bytes: *mut UINT) -> HRESULT {
+        if file_name.is_null() || data.is_null() || bytes.is_null() { return E_POINTER; }
+        let this = &*(this as *const Self);
+        let file = {
+            let files = this.files.borrow();
+            let parent = files.iter().find(|file| file.as_ptr() as LPCVOID == parent).map(|file| &file[..]);
+            this.include.open(include_type, CStr::from_ptr(file_name), parent)
+        };
+        match file {
+            Ok(file) => {
+                let file = file.into_boxed_slice();
+                *data  = file.as_ptr() as LPCVOID;
+                *bytes = file.len() as UINT;
+                this.files.borrow_mut().push(file);
+                S_OK
+            },
+            Err(hresult) => hresult,
+        }
+    }
+
+    unsafe extern "system" fn close (this: *mut ID3DInclude, data: LPCVOID) -> HRESULT {
+        let this = &*(this as *const Self);
+        let mut files = this.files.borrow_mut();
+        match files.iter().position(|file| file.as_ptr() as *const c_void == data) {
+            Some(index) => { files.swap_remove(index); S_OK },
+            None        => E_INVALIDARG,
+        }
+    }
+}
+
+#[cfg(windows)]
+unsafe impl<I: Include> crate::com::d3d::IntoSafeCompileInclude for &IncludeObject<I> {
+    fn into_compile_include (&self) -> *mut ID3DInclude { *self as *const IncludeObject<I> as *mut ID3DInclude }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::{null, null_mut};
+
+    type Opened = (D3D_INCLUDE_TYPE, String, Option<Vec<u8>>);
+
+    /// Serves `a.hlsl` and `b.hlsl`, recording every request.
+    #[derive(Default)]
+    struct Files {
+        opened: RefCell<Vec<Opened>>,
+    }
+
+    impl Include for Files {
+        fn open (&self, include_type: D3D_INCLUDE_TYPE, file_name: &CStr, parent: Option<&[u8]>) -> Result<Vec<u8>, HRESULT> {
+            let name = file_name.to_str().unwrap().to_string();
+            self.opened.borrow_mut().push((include_type, name.clone(), parent.map(|p| p.to_vec())));
+            match &name[..] {
+                "a.hlsl" => Ok(b"#include <b.hlsl>".to_vec()),
+                "b.hlsl" => Ok(b"float4 b;".to_vec()),
+                _        => Err(E_FAIL),
+            }
+        }
+    }
+
+    fn open (include: &ID3DInclude, include_type: D3D_INCLUDE_TYPE, name: &[u8], parent: LPCVOID) -> Result<&'static [u8], HRESULT> {
+        let mut data = null();
+        let mut bytes = 0;
+        let hresult = unsafe { include.Open(include_type, name.as_ptr() as LPCSTR, parent, &mut data, &mut bytes) };
+        if hresult != S_OK { return Err(hresult); }
+        Ok(unsafe { std::slice::from_raw_parts(data as *const u8, bytes as usize) })
+    }
+
+    fn as_include<I: Include> (object: &IncludeObject<I>) -> &ID3DInclude {
+        unsafe { &*(object as *const IncludeObject<I> as *const ID3DInclude) }
+    }
+
+    #[test]
+    fn open_close () {
+        let object = IncludeObject::new(Files::default());
+        let include = as_include(&object);
+
+        let a = open(include, D3D_INCLUDE_LOCAL, b"a.hlsl\0", null()).unwrap();
+        assert_eq!(a, b"#include <b.hlsl>");
+        let b = open(include, D3D_INCLUDE_SYSTEM, b"b.hlsl\0", a.as_ptr() as LPCVOID).unwrap();
+        assert_eq!(b, b"float4 b;");
+        assert_eq!(object.files.borrow().len(), 2);
+
+        assert_eq!(unsafe { include.Close(b.as_ptr() as LPCVOID) }, S_OK);
+        assert_eq!(unsafe { include.Close(a.as_ptr() as LPCVOID) }, S_OK);
+        assert_eq!(object.files.borrow().len(), 0);
+
+        let opened = object.include.opened.borrow();
+        assert_eq!(opened[0], (D3D_INCLUDE_LOCAL, "a.hlsl".to_string(), None));
+        assert_eq!(opened[1], (D3D_INCLUDE_SYSTEM, "b.hlsl".to_string(), Some(b"#include <b.hlsl>".to_vec())));
+    }
+
+    #[test]
+    fn errors () {
+        let object = IncludeObject::new(Files::default());
+        let include = as_include(&object);
+
+        assert_eq!(open(include, D3D_INCLUDE_LOCAL, b"missing.hlsl\0", null()), Err(E_FAIL));
+        assert_eq!(object.files.borrow().len(), 0);
+
+        let a = open(include, D3D_INCLUDE_LOCAL, b"a.hlsl\0", null()).unwrap();
+        assert_eq!(unsafe { include.Close(a.as_ptr() as LPCVOID) }, S_OK);
+        assert_eq!(unsafe { include.Close(a.as_ptr() as LPCVOID) }, E_INVALIDARG);
+
+        let mut data = null();
+        let mut bytes = 0;
+        assert_eq!(unsafe { include.Open(D3D_INCLUDE_LOCAL, null(), null(), &mut data, &mut bytes) }, E_POINTER);
+        assert_eq!(unsafe { include.Open(D3D_INCLUDE_LOCAL, b"a.hlsl\0".as_ptr() as LPCSTR, null(), null_mut(), &mut bytes) }, E_POINTER);
+    }
+}
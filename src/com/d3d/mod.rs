@@ -1,7 +1,19 @@
-mod blob;
+#[cfg(windows)] mod blob;
+mod blob_data;
+mod bytecode;
 mod target;
-pub use blob::*;
+#[cfg(windows)] pub use blob::*;
+pub use blob_data::*;
+pub use bytecode::*;
 pub use target::*;
 
-mod compile;
-pub use compile::*;
+#[cfg(windows)] mod blob_part;
+#[cfg(windows)] mod compile;
+mod compile_options;
+mod include;
+#[cfg(windows)] mod reflect;
+#[cfg(windows)] pub use blob_part::*;
+#[cfg(windows)] pub use compile::*;
+pub use compile_options::*;
+pub use include::*;
+#[cfg(windows)] pub use reflect::*;
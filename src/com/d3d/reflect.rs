@@ -0,0 +1,130 @@
+use crate::win32::*;
+use crate::com::Wrapper;
+use crate::dxbc::{self, BoundResource, ConstantBuffer, Member, ShaderReflection, SignatureElement, Type, Variable};
+use std::ffi::CStr;
+use std::mem;
+use std::ptr::null_mut;
+
+com_wrapper! {
+    pub struct ShaderReflector(*mut ID3D11ShaderReflection);
+}
+
+unsafe fn string (s: *const i8) -> String {
+    if s.is_null() { String::new() } else { CStr::from_ptr(s).to_string_lossy().into_owned() }
+}
+
+fn check (hresult: HRESULT) -> Result<(), HRESULT> {
+    if SUCCEEDED(hresult) { Ok(()) } else { Err(hresult) }
+}
+
+/// MSDN: [D3DReflect](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcompiler/nf-d3dcompiler-d3dreflect)
+///
+/// Reflect `bytecode` through `D3DCompiler`.  `dxbc::reflect` is the pure Rust equivalent, and should agree.
+pub fn reflect (bytecode: &[u8]) -> Result<ShaderReflection, HRESULT> {
+    let mut reflector = null_mut();
+    check(unsafe { D3DReflect(bytecode.as_ptr() as LPCVOID, bytecode.len() as SIZE_T, &ID3D11ShaderReflection::uuidof(), &mut reflector) })?;
+    let reflector = unsafe { ShaderReflector::own(reflector as *mut _) }.unwrap();
+    let r = reflector.as_ref();
+
+    unsafe {
+        let mut desc : D3D11_SHADER_DESC = mem::zeroed();
+        check(r.GetDesc(&mut desc))?;
+
+        let mut constant_buffers = Vec::new();
+        for index in 0..desc.ConstantBuffers {
+            let cb = &*r.GetConstantBufferByIndex(index);
+            let mut cb_desc : D3D11_SHADER_BUFFER_DESC = mem::zeroed();
+            check(cb.GetDesc(&mut cb_desc))?;
+            let mut variables = Vec::new();
+            for v in 0..cb_desc.Variables {
+                let variable = &*cb.GetVariableByIndex(v);
+                let mut v_desc : D3D11_SHADER_VARIABLE_DESC = mem::zeroed();
+                check(variable.GetDesc(&mut v_desc))?;
+                variables.push(Variable {
+                    name:   string(v_desc.Name),
+                    offset: v_desc.StartOffset,
+                    size:   v_desc.Size,
+                    flags:  v_desc.uFlags,
+                    ty:     reflect_type(&*variable.GetType())?,
+                });
+            }
+            constant_buffers.push(ConstantBuffer { name: string(cb_desc.Name), kind: cb_desc.Type, size: cb_desc.Size, flags: cb_desc.uFlags, variables });
+        }
+
+        let mut bound_resources = Vec::new();
+        for index in 0..desc.BoundResources {
+            let mut b : D3D11_SHADER_INPUT_BIND_DESC = mem::zeroed();
+            check(r.GetResourceBindingDesc(index, &mut b))?;
+            bound_resources.push(BoundResource {
+                name:           string(b.Name),
+                kind:           b.Type,
+                return_type:    b.ReturnType,
+                dimension:      b.Dimension,
+                samples:        b.NumSamples,
+                bind_point:     b.BindPoint,
+                bind_count:     b.BindCount,
+                flags:          b.uFlags,
+            });
+        }
+
+        let parameter = |p: D3D11_SIGNATURE_PARAMETER_DESC| SignatureElement {
+            semantic_name:      string(p.SemanticName),
+            semantic_index:     p.SemanticIndex,
+            system_value:       p.SystemValueType,
+            component_type:     p.ComponentType,
+            register:           p.Register,
+            mask:               p.Mask,
+            read_write_mask:    p.ReadWriteMask,
+            stream:             p.Stream,
+            min_precision:      p.MinPrecision,
+        };
+        let mut input_signature = Vec::new();
+        for index in 0..desc.InputParameters {
+            let mut p = mem::zeroed();
+            check(r.GetInputParameterDesc(index, &mut p))?;
+            input_signature.push(parameter(p));
+        }
+        let mut output_signature = Vec::new();
+        for index in 0..desc.OutputParameters {
+            let mut p = mem::zeroed();
+            check(r.GetOutputParameterDesc(index, &mut p))?;
+            output_signature.push(parameter(p));
+        }
+
+        let (mut x, mut y, mut z) = (0, 0, 0);
+        let thread_group_size = if r.GetThreadGroupSize(&mut x, &mut y, &mut z) == 0 { None } else { Some([x, y, z]) };
+
+        Ok(ShaderReflection {
+            version:    dxbc::ShaderVersion::from_token(desc.Version).ok_or(E_INVALIDARG)?,
+            creator:    string(desc.Creator),
+            constant_buffers,
+            bound_resources,
+            input_signature,
+            output_signature,
+            thread_group_size,
+        })
+    }
+}
+
+/// `ID3D11ShaderReflectionType`s (like constant buffers and variables) aren't refcounted, they live as long as the
+/// `ShaderReflector` they came from.
+unsafe fn reflect_type (ty: &ID3D11ShaderReflectionType) -> Result<Type, HRESULT> {
+    let mut desc : D3D11_SHADER_TYPE_DESC = mem::zeroed();
+    check(ty.GetDesc(&mut desc))?;
+    let mut members = Vec::new();
+    for index in 0..desc.Members {
+        let member = &*ty.GetMemberTypeByIndex(index);
+        let mut member_desc : D3D11_SHADER_TYPE_DESC = mem::zeroed();
+        check(member.GetDesc(&mut member_desc))?;
+        members.push(Member { name: string(ty.GetMemberTypeName(index)), offset: member_desc.Offset, ty: reflect_type(member)? });
+    }
+    Ok(Type {
+        class:      desc.Class,
+        kind:       desc.Type,
+        rows:       desc.Rows,
+        columns:    desc.Columns,
+        elements:   desc.Elements,
+        members,
+        name:       if desc.Name.is_null() { None } else { Some(string(desc.Name)) },
+    })
+}
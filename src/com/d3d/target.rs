@@ -1,6 +1,43 @@
+use crate::com::FeatureLevel;
+use crate::dxbc::{ProgramType, ShaderVersion};
 use std::ffi::CStr;
 
-#[derive(Clone, Copy, Debug)]
+/// Which kind of shader a `Target` compiles.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ShaderStage {
+    Vertex,
+    Hull,
+    Domain,
+    Geometry,
+    Pixel,
+    Compute,
+    /// `tx_1_0` - D3DX9 texture fill functions.
+    Texture,
+    /// `fx_*` - Effects, containing shaders for several stages.
+    Effect,
+}
+
+impl ShaderStage {
+    /// The stage DXBC bytecode of `program` is for.
+    pub fn from_program (program: ProgramType) -> Self {
+        match program {
+            ProgramType::Vertex     => ShaderStage::Vertex,
+            ProgramType::Hull       => ShaderStage::Hull,
+            ProgramType::Domain     => ShaderStage::Domain,
+            ProgramType::Geometry   => ShaderStage::Geometry,
+            ProgramType::Pixel      => ShaderStage::Pixel,
+            ProgramType::Compute    => ShaderStage::Compute,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ShaderModel {
+    pub major: u8,
+    pub minor: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[allow(dead_code, non_camel_case_types)]
 pub enum Target {
     // FL 11.0 / 11.1
@@ -52,6 +89,119 @@ pub enum Target {
 }
 
 impl Target {
+    pub const ALL : &'static [Target] = &[
+        Target::cs_5_0, Target::ds_5_0, Target::gs_5_0, Target::hs_5_0, Target::ps_5_0, Target::vs_5_0,
+        Target::cs_4_1, Target::gs_4_1, Target::ps_4_1, Target::vs_4_1,
+        Target::cs_4_0, Target::gs_4_0, Target::ps_4_0, Target::vs_4_0,
+        Target::ps_4_0_level_9_1, Target::ps_4_0_level_9_3, Target::vs_4_0_level_9_1, Target::vs_4_0_level_9_3,
+        Target::ps_3_0, Target::ps_3_sw, Target::vs_3_0, Target::vs_3_sw,
+        Target::ps_2_0, Target::ps_2_a, Target::ps_2_b, Target::ps_2_sw, Target::vs_2_0, Target::vs_2_a, Target::vs_2_sw,
+        Target::tx_1_0, Target::vs_1_1,
+        Target::fx_2_0, Target::fx_4_0, Target::fx_4_1, Target::fx_5_0,
+    ];
+
+    /// Look up a target by name, e.g. `"ps_5_0"`.
+    pub fn parse (name: &str) -> Option<Target> {
+        Target::ALL.iter().cloned().find(|t| t.as_str() == name)
+    }
+
+    pub fn as_str (self) -> &'static str {
+        self.to_cstr().to_str().unwrap()
+    }
+
+    pub fn stage (self) -> ShaderStage {
+        self.info().0
+    }
+
+    /// `_level_9_*` targets are shader model 4.0, limited to what feature level 9.x hardware can run.
+    pub fn shader_model (self) -> ShaderModel {
+        let (major, minor) = self.info().1;
+        ShaderModel { major, minor }
+    }
+
+    /// The lowest feature level a D3D11 device can create this target's shaders on, or `None` if D3D11 can't create
+    /// them at all (D3D9 targets, and effects - which the effects runtime creates shaders from instead.)  On 10.x
+    /// devices, `cs_4_x` shaders also need `ComputeShaders_Plus_RawAndStructuredBuffers_Via_Shader_4_x`.
+    pub fn min_feature_level (self) -> Option<FeatureLevel> {
+        self.info().2
+    }
+
+    /// The D3D10+ target that produces bytecode with `version`.  `legacy_version` is the D3D9 version token of the
+    /// shader in the `Aon9` chunk `_level_9_*` targets add (see `dxbc::legacy_shader_version`):  `_level_9_1` targets
+    /// embed `vs_2_0` / `ps_2_0` shaders, `_level_9_3` targets `vs_2_x` / `ps_2_x`.
+    pub fn from_version (version: ShaderVersion, legacy_version: Option<u32>) -> Option<Target> {
+        let stage = ShaderStage::from_program(version.program);
+        let model = (version.major, version.minor);
+        let level_9 = match legacy_version.map(|token| token & 0xFFFF) {
+            None            => None,
+            Some(0x0200)    => Some(FeatureLevel::_9_1),
+            Some(0x0201)    => Some(FeatureLevel::_9_3),
+            Some(_)         => return None,
+        };
+        Target::ALL.iter().cloned().find(|t| {
+            let (t_stage, t_model, t_feature_level) = t.info();
+            let t_level_9 = t_feature_level.filter(|&fl| fl < FeatureLevel::_10_0);
+            t_stage == stage && t_model == model && t_feature_level.is_some() && t_level_9 == level_9
+        })
+    }
+
+    fn info (self) -> (ShaderStage, (u8, u8), Option<FeatureLevel>) {
+        use self::ShaderStage::*;
+        let fl_9_1  = Some(FeatureLevel::_9_1);
+        let fl_9_3  = Some(FeatureLevel::_9_3);
+        let fl_10_0 = Some(FeatureLevel::_10_0);
+        let fl_10_1 = Some(FeatureLevel::_10_1);
+        let fl_11_0 = Some(FeatureLevel::_11_0);
+        match self {
+            // FL 11.0 / 11.1
+            Target::cs_5_0              => (Compute,  (5, 0), fl_11_0),
+            Target::ds_5_0              => (Domain,   (5, 0), fl_11_0),
+            Target::gs_5_0              => (Geometry, (5, 0), fl_11_0),
+            Target::hs_5_0              => (Hull,     (5, 0), fl_11_0),
+            Target::ps_5_0              => (Pixel,    (5, 0), fl_11_0),
+            Target::vs_5_0              => (Vertex,   (5, 0), fl_11_0),
+
+            // FL 10.1
+            Target::cs_4_1              => (Compute,  (4, 1), fl_10_1),
+            Target::gs_4_1              => (Geometry, (4, 1), fl_10_1),
+            Target::ps_4_1              => (Pixel,    (4, 1), fl_10_1),
+            Target::vs_4_1              => (Vertex,   (4, 1), fl_10_1),
+
+            // FL 10.0
+            Target::cs_4_0              => (Compute,  (4, 0), fl_10_0),
+            Target::gs_4_0              => (Geometry, (4, 0), fl_10_0),
+            Target::ps_4_0              => (Pixel,    (4, 0), fl_10_0),
+            Target::vs_4_0              => (Vertex,   (4, 0), fl_10_0),
+
+            // FL 9.x
+            Target::ps_4_0_level_9_1    => (Pixel,    (4, 0), fl_9_1),
+            Target::ps_4_0_level_9_3    => (Pixel,    (4, 0), fl_9_3),
+            Target::vs_4_0_level_9_1    => (Vertex,   (4, 0), fl_9_1),
+            Target::vs_4_0_level_9_3    => (Vertex,   (4, 0), fl_9_3),
+
+            // D3D9
+            Target::ps_3_0              => (Pixel,    (3, 0), None),
+            Target::ps_3_sw             => (Pixel,    (3, 0), None),
+            Target::vs_3_0              => (Vertex,   (3, 0), None),
+            Target::vs_3_sw             => (Vertex,   (3, 0), None),
+            Target::ps_2_0              => (Pixel,    (2, 0), None),
+            Target::ps_2_a              => (Pixel,    (2, 0), None),
+            Target::ps_2_b              => (Pixel,    (2, 0), None),
+            Target::ps_2_sw             => (Pixel,    (2, 0), None),
+            Target::vs_2_0              => (Vertex,   (2, 0), None),
+            Target::vs_2_a              => (Vertex,   (2, 0), None),
+            Target::vs_2_sw             => (Vertex,   (2, 0), None),
+            Target::tx_1_0              => (Texture,  (1, 0), None),
+            Target::vs_1_1              => (Vertex,   (1, 1), None),
+
+            // Effects
+            Target::fx_2_0              => (Effect,   (2, 0), None),
+            Target::fx_4_0              => (Effect,   (4, 0), None),
+            Target::fx_4_1              => (Effect,   (4, 1), None),
+            Target::fx_5_0              => (Effect,   (5, 0), None),
+        }
+    }
+
     pub fn to_cstr (self) -> &'static CStr {
         CStr::from_bytes_with_nul(match self {
             // FL 11.0 / 11.1
@@ -103,3 +253,59 @@ impl Target {
         }).unwrap()
     }
 }
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn version (program: ProgramType, major: u8, minor: u8) -> ShaderVersion { ShaderVersion { program, major, minor } }
+
+    #[test] fn names () {
+        for &target in Target::ALL.iter() {
+            assert_eq!(Target::parse(target.as_str()), Some(target));
+        }
+        assert_eq!(Target::parse("ps_4_0_level_9_1"), Some(Target::ps_4_0_level_9_1));
+        assert_eq!(Target::parse("ps_6_0"), None);
+        assert_eq!(Target::parse(""), None);
+    }
+
+    #[test] fn from_version () {
+        assert_eq!(Target::from_version(version(ProgramType::Compute,  5, 0), None), Some(Target::cs_5_0));
+        assert_eq!(Target::from_version(version(ProgramType::Geometry, 4, 1), None), Some(Target::gs_4_1));
+        assert_eq!(Target::from_version(version(ProgramType::Vertex,   4, 0), None), Some(Target::vs_4_0));
+        assert_eq!(Target::from_version(version(ProgramType::Hull,     4, 0), None), None);
+        assert_eq!(Target::from_version(version(ProgramType::Pixel,    3, 0), None), None); // D3D9 isn't DXBC
+        assert_eq!(Target::from_version(version(ProgramType::Pixel,    6, 0), None), None);
+    }
+
+    #[test] fn from_version_level_9 () {
+        let ps = version(ProgramType::Pixel, 4, 0);
+        let vs = version(ProgramType::Vertex, 4, 0);
+        assert_eq!(Target::from_version(ps, Some(0xFFFF_0200)), Some(Target::ps_4_0_level_9_1));
+        assert_eq!(Target::from_version(ps, Some(0xFFFF_0201)), Some(Target::ps_4_0_level_9_3));
+        assert_eq!(Target::from_version(vs, Some(0xFFFE_0200)), Some(Target::vs_4_0_level_9_1));
+        assert_eq!(Target::from_version(vs, Some(0xFFFE_0201)), Some(Target::vs_4_0_level_9_3));
+        assert_eq!(Target::from_version(ps, Some(0xFFFF_0300)), None);
+        assert_eq!(Target::from_version(version(ProgramType::Pixel, 4, 1), Some(0xFFFF_0200)), None);
+    }
+
+    #[test] fn every_d3d11_target_round_trips () {
+        for &target in Target::ALL.iter().filter(|t| t.min_feature_level().is_some()) {
+            let model = target.shader_model();
+            let program = match target.stage() {
+                ShaderStage::Vertex     => ProgramType::Vertex,
+                ShaderStage::Hull       => ProgramType::Hull,
+                ShaderStage::Domain     => ProgramType::Domain,
+                ShaderStage::Geometry   => ProgramType::Geometry,
+                ShaderStage::Pixel      => ProgramType::Pixel,
+                ShaderStage::Compute    => ProgramType::Compute,
+                other                   => panic!("{:?} has a feature level", other),
+            };
+            let legacy_version = match target.min_feature_level() {
+                Some(FeatureLevel::_9_1)    => Some(0x0200),
+                Some(FeatureLevel::_9_3)    => Some(0x0201),
+                _                           => None,
+            };
+            assert_eq!(Target::from_version(version(program, model.major, model.minor), legacy_version), Some(target));
+        }
+    }
+}
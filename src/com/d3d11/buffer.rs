@@ -0,0 +1,72 @@
+use crate::com::d3d11::*;
+use crate::compute::{BufferKind, BufferView, UavCounter};
+
+/// MSDN: [ID3D11Buffer](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nn-d3d11-id3d11buffer)
+impl Buffer {
+    /// MSDN: [ID3D11Buffer::GetDesc](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11buffer-getdesc)
+    pub fn get_desc (&self) -> D3D11_BUFFER_DESC {
+        let mut desc = unsafe { std::mem::zeroed() };
+        unsafe { self.as_ref().GetDesc(&mut desc) };
+        desc
+    }
+}
+
+/// A `D3D11_USAGE_DEFAULT` buffer of `kind`, for binding as `bind_flags` (typically `D3D11_BIND_SHADER_RESOURCE` and/or
+/// `D3D11_BIND_UNORDERED_ACCESS`.)
+pub fn compute_buffer_desc (kind: BufferKind, byte_width: UINT, bind_flags: UINT) -> D3D11_BUFFER_DESC {
+    let (misc_flags, stride) = match kind {
+        BufferKind::Structured { stride }   => (D3D11_RESOURCE_MISC_BUFFER_STRUCTURED, stride),
+        BufferKind::Raw                     => (D3D11_RESOURCE_MISC_BUFFER_ALLOW_RAW_VIEWS, 0),
+    };
+    D3D11_BUFFER_DESC {
+        ByteWidth:              byte_width,
+        Usage:                  D3D11_USAGE_DEFAULT,
+        BindFlags:              bind_flags,
+        CPUAccessFlags:         0,
+        MiscFlags:              misc_flags,
+        StructureByteStride:    stride,
+    }
+}
+
+/// Structured buffers must be viewed with `DXGI_FORMAT_UNKNOWN`, raw buffers with `DXGI_FORMAT_R32_TYPELESS`.
+fn view_format (kind: BufferKind) -> DXGI_FORMAT {
+    match kind {
+        BufferKind::Structured { .. }   => DXGI_FORMAT_UNKNOWN,
+        BufferKind::Raw                 => DXGI_FORMAT_R32_TYPELESS,
+    }
+}
+
+/// A view of a buffer of `kind` for `StructuredBuffer` / `ByteAddressBuffer`.  Check `view` with `BufferView::validate`
+/// first.
+pub fn buffer_srv_desc (kind: BufferKind, view: BufferView) -> D3D11_SHADER_RESOURCE_VIEW_DESC {
+    let mut desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+        Format:         view_format(kind),
+        ViewDimension:  D3D11_SRV_DIMENSION_BUFFEREX,
+        u:              unsafe { std::mem::zeroed() },
+    };
+    let buffer_ex = unsafe { desc.u.BufferEx_mut() };
+    buffer_ex.FirstElement  = view.first_element;
+    buffer_ex.NumElements   = view.num_elements;
+    buffer_ex.Flags         = if kind == BufferKind::Raw { D3D11_BUFFEREX_SRV_FLAG_RAW } else { 0 };
+    desc
+}
+
+/// A view of a buffer of `kind` for `RWStructuredBuffer` / `RWByteAddressBuffer` / `AppendStructuredBuffer` etc.  Check
+/// `view` with `BufferView::validate_uav` first.
+pub fn buffer_uav_desc (kind: BufferKind, view: BufferView, counter: UavCounter) -> D3D11_UNORDERED_ACCESS_VIEW_DESC {
+    let mut desc = D3D11_UNORDERED_ACCESS_VIEW_DESC {
+        Format:         view_format(kind),
+        ViewDimension:  D3D11_UAV_DIMENSION_BUFFER,
+        u:              unsafe { std::mem::zeroed() },
+    };
+    let buffer = unsafe { desc.u.Buffer_mut() };
+    buffer.FirstElement = view.first_element;
+    buffer.NumElements  = view.num_elements;
+    buffer.Flags        = match (kind, counter) {
+        (BufferKind::Raw, _)        => D3D11_BUFFER_UAV_FLAG_RAW,
+        (_, UavCounter::None)       => 0,
+        (_, UavCounter::Append)     => D3D11_BUFFER_UAV_FLAG_APPEND,
+        (_, UavCounter::Counter)    => D3D11_BUFFER_UAV_FLAG_COUNTER,
+    };
+    desc
+}
@@ -0,0 +1,31 @@
+use crate::com::*;
+use crate::com::d3d11::*;
+use std::ops::Deref;
+use std::ptr::{null_mut};
+
+/// MSDN: [Immediate and Deferred Rendering](https://docs.microsoft.com/en-us/windows/desktop/direct3d11/overviews-direct3d-11-render-multi-thread-render)
+/// 
+/// A `DeviceContext` created by `Device::create_deferred_context`.  Records commands into a `CommandList` instead of
+/// executing them, so unlike the immediate context it's useful to hand to another thread.
+pub struct DeferredContext(pub(crate) DeviceContext);
+
+impl Deref for DeferredContext {
+    type Target = DeviceContext;
+    fn deref (&self) -> &DeviceContext { &self.0 }
+}
+
+impl DeferredContext {
+    /// MSDN: [ID3D11DeviceContext::FinishCommandList](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-finishcommandlist)
+    /// 
+    /// Wrap up everything recorded so far, for `DeviceContext::execute_command_list` on the immediate context.  Unless
+    /// `restore_deferred_context_state` is set, this context's state is reset to defaults afterwards.
+    pub fn finish_command_list (&self, restore_deferred_context_state: bool) -> Result<CommandList, HRESULT> {
+        let mut command_list = null_mut();
+        let result = unsafe { self.0.as_ref().FinishCommandList(if restore_deferred_context_state { TRUE } else { FALSE }, &mut command_list) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { CommandList::own(command_list) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+}
@@ -1,13 +1,13 @@
+use crate::com::d3d::{Bytecode, StreamOutputBytecode, VertexBytecode, HullBytecode, DomainBytecode, GeometryBytecode, PixelBytecode, ComputeBytecode};
 use crate::com::d3d11::*;
-use crate::com::Wrapper;
+use crate::com::{Inherits, Wrapper};
+use crate::compute::{BufferKind, BufferView, UavCounter};
+use crate::dxbc::{Container, ShaderVersion};
+use crate::pipeline;
+use std::mem::{size_of, size_of_val};
 use std::ptr::{null, null_mut};
 
-com_wrapper!{ pub struct Device(*mut ID3D11Device); }
-
-pub trait IntoResource { fn into_resource(self) -> *mut ID3D11Resource; }
-impl IntoResource for *mut ID3D11Resource  { fn into_resource(self) -> *mut ID3D11Resource { self } }
-impl IntoResource for *mut ID3D11Texture2D { fn into_resource(self) -> *mut ID3D11Resource { self as *mut _ } }
-impl IntoResource for &Texture2D           { fn into_resource(self) -> *mut ID3D11Resource { &**self.as_ref() as *const _ as *mut _ } } // XXX
+com_wrapper!{ #[threading(Send, Sync)] pub struct Device(*mut ID3D11Device); }
 
 /// MSDN: [ID3D11Device](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nn-d3d11-id3d11device)
 impl Device {
@@ -19,8 +19,8 @@ impl Device {
     pub unsafe fn create_buffer (&self, desc: &D3D11_BUFFER_DESC, initial_data: Option<&D3D11_SUBRESOURCE_DATA>) -> Result<Buffer, HRESULT> {
         let mut buffer = null_mut();
         let result = self.as_ref().CreateBuffer(
-            &*desc,
-            initial_data.map_or(null(), |id| &*id),
+            desc,
+            initial_data.map_or(null(), |id| id),
             &mut buffer
         );
         if SUCCEEDED(result) {
@@ -30,12 +30,63 @@ impl Device {
         }
     }
 
+    /// Create a `D3D11_USAGE_DEFAULT` `StructuredBuffer<T>` holding `initial_data`, for binding as `bind_flags`.
+    pub fn create_structured_buffer<T: Copy> (&self, initial_data: &[T], bind_flags: UINT) -> Result<Buffer, HRESULT> {
+        self.create_compute_buffer(BufferKind::Structured { stride: size_of::<T>() as UINT }, initial_data, bind_flags)
+    }
+
+    /// Create a `D3D11_USAGE_DEFAULT` `ByteAddressBuffer` holding `initial_data`, for binding as `bind_flags`.
+    pub fn create_raw_buffer (&self, initial_data: &[u32], bind_flags: UINT) -> Result<Buffer, HRESULT> {
+        self.create_compute_buffer(BufferKind::Raw, initial_data, bind_flags)
+    }
+
+    /// Create a buffer of `[UINT; 3]` thread group counts for `DeviceContext::dispatch_indirect`.  `bind_flags` may be 0,
+    /// or `D3D11_BIND_UNORDERED_ACCESS` to have a compute shader write the counts.
+    pub fn create_dispatch_indirect_buffer (&self, initial_data: &[[UINT; 3]], bind_flags: UINT) -> Result<Buffer, HRESULT> {
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth:              size_of_val(initial_data) as UINT,
+            Usage:                  D3D11_USAGE_DEFAULT,
+            BindFlags:              bind_flags,
+            CPUAccessFlags:         0,
+            MiscFlags:              D3D11_RESOURCE_MISC_DRAWINDIRECT_ARGS,
+            StructureByteStride:    0,
+        };
+        let initial_data = D3D11_SUBRESOURCE_DATA { pSysMem: initial_data.as_ptr() as *const _, SysMemPitch: 0, SysMemSlicePitch: 0 };
+        unsafe { self.create_buffer(&desc, Some(&initial_data)) }
+    }
+
+    fn create_compute_buffer<T: Copy> (&self, kind: BufferKind, initial_data: &[T], bind_flags: UINT) -> Result<Buffer, HRESULT> {
+        let byte_width = size_of_val(initial_data);
+        if byte_width > UINT::MAX as usize { return Err(E_INVALIDARG); }
+        kind.validate(byte_width as UINT).map_err(|_| E_INVALIDARG)?;
+        let desc = compute_buffer_desc(kind, byte_width as UINT, bind_flags);
+        let initial_data = D3D11_SUBRESOURCE_DATA { pSysMem: initial_data.as_ptr() as *const _, SysMemPitch: 0, SysMemSlicePitch: 0 };
+        unsafe { self.create_buffer(&desc, Some(&initial_data)) }
+    }
+
+    /// MSDN: [ID3D11Device::CreateTexture2D](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createtexture2d)
+    /// 
+    /// `unsafe`:  `initial_data`, if present, must point to `desc.MipLevels * desc.ArraySize` valid `D3D11_SUBRESOURCE_DATA`s.
+    pub unsafe fn create_texture_2d (&self, desc: &D3D11_TEXTURE2D_DESC, initial_data: Option<&D3D11_SUBRESOURCE_DATA>) -> Result<Texture2D, HRESULT> {
+        let mut texture = null_mut();
+        let result = self.as_ref().CreateTexture2D(
+            desc,
+            initial_data.map_or(null(), |id| id),
+            &mut texture
+        );
+        if SUCCEEDED(result) {
+            Ok(Texture2D::own(texture).unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
     /// MSDN: [ID3D11Device::CreateRenderTargetView](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createrendertargetview)
-    pub fn create_render_target_view<D: IntoResource> (&self, resource: D, desc: Option<&D3D11_RENDER_TARGET_VIEW_DESC>) -> Result<RenderTargetView, HRESULT> {
+    pub fn create_render_target_view<R: Inherits<Resource>> (&self, resource: &R, desc: Option<&D3D11_RENDER_TARGET_VIEW_DESC>) -> Result<RenderTargetView, HRESULT> {
         let mut rtv = null_mut();
         let result = unsafe { self.as_ref().CreateRenderTargetView(
-            resource.into_resource(),
-            desc.map_or(null(), |d| &*d),
+            resource.upcast::<Resource>().as_ptr(),
+            desc.map_or(null(), |d| d),
             &mut rtv
         )};
         if SUCCEEDED(result) {
@@ -45,8 +96,128 @@ impl Device {
         }
     }
 
+    /// MSDN: [ID3D11Device::CreateDepthStencilView](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createdepthstencilview)
+    /// 
+    /// `desc` is required if `resource` was created with a typeless format.
+    pub fn create_depth_stencil_view<R: Inherits<Resource>> (&self, resource: &R, desc: Option<&D3D11_DEPTH_STENCIL_VIEW_DESC>) -> Result<DepthStencilView, HRESULT> {
+        let mut dsv = null_mut();
+        let result = unsafe { self.as_ref().CreateDepthStencilView(
+            resource.upcast::<Resource>().as_ptr(),
+            desc.map_or(null(), |d| d),
+            &mut dsv
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { DepthStencilView::own(dsv) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateShaderResourceView](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createshaderresourceview)
+    /// 
+    /// `desc` is required if `resource` was created with a typeless format.
+    pub fn create_shader_resource_view<R: Inherits<Resource>> (&self, resource: &R, desc: Option<&D3D11_SHADER_RESOURCE_VIEW_DESC>) -> Result<ShaderResourceView, HRESULT> {
+        let mut srv = null_mut();
+        let result = unsafe { self.as_ref().CreateShaderResourceView(
+            resource.upcast::<Resource>().as_ptr(),
+            desc.map_or(null(), |d| d),
+            &mut srv
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { ShaderResourceView::own(srv) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateUnorderedAccessView](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createunorderedaccessview)
+    /// 
+    /// `desc` is required for structured and raw buffers, see `create_buffer_uav`.
+    pub fn create_unordered_access_view<R: Inherits<Resource>> (&self, resource: &R, desc: Option<&D3D11_UNORDERED_ACCESS_VIEW_DESC>) -> Result<UnorderedAccessView, HRESULT> {
+        let mut uav = null_mut();
+        let result = unsafe { self.as_ref().CreateUnorderedAccessView(
+            resource.upcast::<Resource>().as_ptr(),
+            desc.map_or(null(), |d| d),
+            &mut uav
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { UnorderedAccessView::own(uav) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// View `view` of a structured or raw `buffer`.  `E_INVALIDARG` if `view` doesn't fit, see `BufferView::validate`.
+    pub fn create_buffer_srv (&self, buffer: &Buffer, kind: BufferKind, view: BufferView) -> Result<ShaderResourceView, HRESULT> {
+        view.validate(kind, buffer.get_desc().ByteWidth).map_err(|_| E_INVALIDARG)?;
+        self.create_shader_resource_view(buffer, Some(&buffer_srv_desc(kind, view)))
+    }
+
+    /// View `view` of a structured or raw `buffer` for writing.  `E_INVALIDARG` if `view` doesn't fit, see
+    /// `BufferView::validate_uav`.
+    pub fn create_buffer_uav (&self, buffer: &Buffer, kind: BufferKind, view: BufferView, counter: UavCounter) -> Result<UnorderedAccessView, HRESULT> {
+        view.validate_uav(kind, buffer.get_desc().ByteWidth, counter).map_err(|_| E_INVALIDARG)?;
+        self.create_unordered_access_view(buffer, Some(&buffer_uav_desc(kind, view, counter)))
+    }
+
+    /// MSDN: [ID3D11Device::CreateDepthStencilState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createdepthstencilstate)
+    pub fn create_depth_stencil_state (&self, desc: &D3D11_DEPTH_STENCIL_DESC) -> Result<DepthStencilState, HRESULT> {
+        let mut state = null_mut();
+        let result = unsafe { self.as_ref().CreateDepthStencilState(desc, &mut state) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { DepthStencilState::own(state) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateBlendState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createblendstate)
+    pub fn create_blend_state (&self, desc: &D3D11_BLEND_DESC) -> Result<BlendState, HRESULT> {
+        let mut state = null_mut();
+        let result = unsafe { self.as_ref().CreateBlendState(desc, &mut state) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { BlendState::own(state) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateRasterizerState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createrasterizerstate)
+    pub fn create_rasterizer_state (&self, desc: &D3D11_RASTERIZER_DESC) -> Result<RasterizerState, HRESULT> {
+        let mut state = null_mut();
+        let result = unsafe { self.as_ref().CreateRasterizerState(desc, &mut state) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { RasterizerState::own(state) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateDeferredContext](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createdeferredcontext)
+    pub fn create_deferred_context (&self) -> Result<DeferredContext, HRESULT> {
+        let mut context = null_mut();
+        let result = unsafe { self.as_ref().CreateDeferredContext(0, &mut context) };
+        if SUCCEEDED(result) {
+            Ok(DeferredContext(unsafe { DeviceContext::own(context) }.unwrap()))
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::GetImmediateContext](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-getimmediatecontext)
+    ///
+    /// `unsafe`:  `Device` is `Sync`, so any thread can get another owner of the immediate context this way.  The
+    /// caller must make sure it's never used while another thread is using the immediate context.
+    pub unsafe fn get_immediate_context (&self) -> DeviceContext {
+        let mut context = null_mut();
+        unsafe { self.as_ref().GetImmediateContext(&mut context) };
+        unsafe { DeviceContext::own(context) }.unwrap()
+    }
+
     /// MSDN: [ID3D11Device::CreateVertexShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createvertexshader)
-    pub fn create_vertex_shader (&self, bytecode: &[u8], class_linkage: Option<&ClassLinkage>) -> Result<VertexShader, HRESULT> {
+    pub fn create_vertex_shader (&self, bytecode: &VertexBytecode, class_linkage: Option<&ClassLinkage>) -> Result<VertexShader, HRESULT> {
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
         let mut vs = null_mut();
         let result = unsafe { self.as_ref().CreateVertexShader(
             bytecode.as_ptr() as *const _,
@@ -62,7 +233,9 @@ impl Device {
     }
 
     /// MSDN: [ID3D11Device::CreatePixelShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createpixelshader)
-    pub fn create_pixel_shader (&self, bytecode: &[u8], class_linkage: Option<&ClassLinkage>) -> Result<PixelShader, HRESULT> {
+    pub fn create_pixel_shader (&self, bytecode: &PixelBytecode, class_linkage: Option<&ClassLinkage>) -> Result<PixelShader, HRESULT> {
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
         let mut ps = null_mut();
         let result = unsafe { self.as_ref().CreatePixelShader(
             bytecode.as_ptr() as *const _,
@@ -77,6 +250,123 @@ impl Device {
         }
     }
 
+    /// MSDN: [ID3D11Device::CreateGeometryShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-creategeometryshader)
+    pub fn create_geometry_shader (&self, bytecode: &GeometryBytecode, class_linkage: Option<&ClassLinkage>) -> Result<GeometryShader, HRESULT> {
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
+        let mut gs = null_mut();
+        let result = unsafe { self.as_ref().CreateGeometryShader(
+            bytecode.as_ptr() as *const _,
+            bytecode.len() as SIZE_T,
+            class_linkage.map_or(null_mut(), |cl| cl.as_ptr()),
+            &mut gs
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { GeometryShader::own(gs) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateGeometryShaderWithStreamOutput](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-creategeometryshaderwithstreamoutput)
+    /// 
+    /// `bytecode` may also be for a vertex or domain shader, to stream out its output without a geometry shader of its
+    /// own.
+    /// `rasterized_stream` is `None` to rasterize nothing (`D3D11_SO_NO_RASTERIZED_STREAM`.)  Declarations that fail
+    /// `pipeline::validate_stream_output` are rejected with `E_INVALIDARG` - including any use of streams other than 0
+    /// by anything but `gs_5_0` bytecode.
+    pub fn create_geometry_shader_with_stream_output<'a, B: StreamOutputBytecode> (
+        &self,
+        bytecode:           &B,
+        so_declaration:     &[SoDeclarationEntry<'a>],
+        buffer_strides:     &[UINT],
+        rasterized_stream:  Option<UINT>,
+        class_linkage:      Option<&ClassLinkage>
+    ) -> Result<GeometryShader, HRESULT> {
+        let layout : Vec<_> = so_declaration.iter().map(|e| e.layout()).collect();
+        let version = Container::parse(bytecode.as_bytes()).and_then(|c| ShaderVersion::from_container(&c)).map_err(|_| E_INVALIDARG)?;
+        pipeline::validate_stream_output(version, &layout[..], buffer_strides, rasterized_stream).map_err(|_| E_INVALIDARG)?;
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
+
+        let so_declaration = so_declaration.into_so_declaration();
+        let mut gs = null_mut();
+        let result = unsafe { self.as_ref().CreateGeometryShaderWithStreamOutput(
+            bytecode.as_ptr() as *const _,
+            bytecode.len() as SIZE_T,
+            so_declaration.as_ptr(),
+            so_declaration.len() as UINT,
+            buffer_strides.as_ptr(),
+            buffer_strides.len() as UINT,
+            rasterized_stream.unwrap_or(D3D11_SO_NO_RASTERIZED_STREAM),
+            class_linkage.map_or(null_mut(), |cl| cl.as_ptr()),
+            &mut gs
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { GeometryShader::own(gs) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateHullShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createhullshader)
+    pub fn create_hull_shader (&self, bytecode: &HullBytecode, class_linkage: Option<&ClassLinkage>) -> Result<HullShader, HRESULT> {
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
+        let mut hs = null_mut();
+        let result = unsafe { self.as_ref().CreateHullShader(
+            bytecode.as_ptr() as *const _,
+            bytecode.len() as SIZE_T,
+            class_linkage.map_or(null_mut(), |cl| cl.as_ptr()),
+            &mut hs
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { HullShader::own(hs) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateDomainShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createdomainshader)
+    pub fn create_domain_shader (&self, bytecode: &DomainBytecode, class_linkage: Option<&ClassLinkage>) -> Result<DomainShader, HRESULT> {
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
+        let mut ds = null_mut();
+        let result = unsafe { self.as_ref().CreateDomainShader(
+            bytecode.as_ptr() as *const _,
+            bytecode.len() as SIZE_T,
+            class_linkage.map_or(null_mut(), |cl| cl.as_ptr()),
+            &mut ds
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { DomainShader::own(ds) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [ID3D11Device::CreateComputeShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createcomputeshader)
+    /// 
+    /// `cs_5_0` requires feature level 11.0.  `cs_4_x` also runs on 10.x hardware that reports
+    /// `ComputeShaders_Plus_RawAndStructuredBuffers_Via_Shader_4_x`.  `compute::validate_shader` checks `[numthreads]`
+    /// against the shader model's limits.
+    pub fn create_compute_shader (&self, bytecode: &ComputeBytecode, class_linkage: Option<&ClassLinkage>) -> Result<ComputeShader, HRESULT> {
+        self.check_feature_level(bytecode)?;
+        let bytecode = bytecode.as_bytes();
+        let mut cs = null_mut();
+        let result = unsafe { self.as_ref().CreateComputeShader(
+            bytecode.as_ptr() as *const _,
+            bytecode.len() as SIZE_T,
+            class_linkage.map_or(null_mut(), |cl| cl.as_ptr()),
+            &mut cs
+        )};
+        if SUCCEEDED(result) {
+            Ok(unsafe { ComputeShader::own(cs) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
     /// MSDN: [ID3D11Device::CreateInputLayout](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-createinputlayout)
     pub fn create_input_layout<I: IntoInputElements> (&self, input_element_descs: I, shader_bytecode_with_input_signature: &[u8]) -> Result<InputLayout, HRESULT> {
         let input_element_descs = input_element_descs.into_input_elements();
@@ -94,4 +384,24 @@ impl Device {
             Err(result)
         }
     }
+
+    /// MSDN: [ID3D11Device::GetFeatureLevel](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-getfeaturelevel)
+    pub fn get_feature_level (&self) -> FeatureLevel {
+        FeatureLevel(unsafe { self.as_ref().GetFeatureLevel() })
+    }
+
+    /// `E_INVALIDARG` if `bytecode` was compiled for a target above this device's feature level.
+    fn check_feature_level<B: Bytecode> (&self, bytecode: &B) -> Result<(), HRESULT> {
+        match bytecode.target().min_feature_level() {
+            Some(min) if self.get_feature_level() >= min    => Ok(()),
+            _                                               => Err(E_INVALIDARG),
+        }
+    }
+
+    /// MSDN: [ID3D11Device::GetDeviceRemovedReason](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11device-getdeviceremovedreason)
+    /// 
+    /// Returns `S_OK` if the device hasn't been removed, or the `DXGI_ERROR_*` code explaining why it was.
+    pub fn get_device_removed_reason (&self) -> HRESULT {
+        unsafe { self.as_ref().GetDeviceRemovedReason() }
+    }
 }
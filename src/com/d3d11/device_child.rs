@@ -0,0 +1,22 @@
+use crate::com::*;
+use crate::com::d3d11::*;
+use std::ptr::{null_mut};
+
+/// MSDN: [ID3D11DeviceChild](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nn-d3d11-id3d11devicechild)
+impl DeviceChild {
+    /// MSDN: [ID3D11DeviceChild::GetDevice](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicechild-getdevice)
+    pub fn get_device (&self) -> Device {
+        let mut device = null_mut();
+        unsafe { self.as_ref().GetDevice(&mut device) };
+        unsafe { Device::own(device) }.unwrap()
+    }
+
+    /// MSDN: [ID3D11DeviceChild::SetPrivateDataInterface](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicechild-setprivatedatainterface)
+    /// 
+    /// Attach `data` (e.g. an `Object::new(Payload(...))`) to this object under `guid`, or detach it if `None`.
+    #[allow(dead_code)]
+    pub fn set_private_data_interface (&self, guid: &GUID, data: Option<&Unknown>) -> Result<(), HRESULT> {
+        let result = unsafe { self.as_ref().SetPrivateDataInterface(guid, data.map_or(null_mut(), |d| d.as_ptr())) };
+        if SUCCEEDED(result) { Ok(()) } else { Err(result) }
+    }
+}
@@ -1,8 +1,11 @@
 use crate::com::*;
 use crate::com::d3d11::*;
-use std::ptr::{null_mut};
+use crate::compute;
+use std::mem::size_of;
+use std::ptr::{self, null, null_mut};
 
-com_wrapper!{ pub struct DeviceContext(*mut ID3D11DeviceContext); }
+// Deferred or immediate, a context may only be used by one thread at a time:  `Send`, but neither `Sync` nor `Clone`.
+com_wrapper!{ #[threading(Send)] pub struct DeviceContext(*mut ID3D11DeviceContext) : DeviceChild; }
 
 /// MSDN: [ID3D11DeviceContext](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nn-d3d11-id3d11devicecontext)
 impl DeviceContext {
@@ -18,6 +21,34 @@ impl DeviceContext {
         }
     }
 
+    /// MSDN: [ID3D11DeviceContext::OMSetDepthStencilState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-omsetdepthstencilstate)
+    /// 
+    /// `None` restores the default state (depth test `LESS`, depth write on, stencil off.)
+    pub fn om_set_depth_stencil_state (&self, depth_stencil_state: Option<&DepthStencilState>, stencil_ref: UINT) {
+        unsafe { self.as_ref().OMSetDepthStencilState(
+            depth_stencil_state.map_or(null_mut(), |dss| dss.as_ptr()),
+            stencil_ref
+        )};
+    }
+
+    /// MSDN: [ID3D11DeviceContext::OMSetBlendState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-omsetblendstate)
+    /// 
+    /// `None` restores the default state (blending off, all channels written.)
+    pub fn om_set_blend_state (&self, blend_state: Option<&BlendState>, blend_factor: &[FLOAT; 4], sample_mask: UINT) {
+        unsafe { self.as_ref().OMSetBlendState(
+            blend_state.map_or(null_mut(), |bs| bs.as_ptr()),
+            blend_factor,
+            sample_mask
+        )};
+    }
+
+    /// MSDN: [ID3D11DeviceContext::RSSetState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-rssetstate)
+    /// 
+    /// `None` restores the default state (solid, back faces culled, depth clip on.)
+    pub fn rs_set_state (&self, rasterizer_state: Option<&RasterizerState>) {
+        unsafe { self.as_ref().RSSetState(rasterizer_state.map_or(null_mut(), |rs| rs.as_ptr())) };
+    }
+
     /// MSDN: [ID3D11DeviceContext::RSSetViewports](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-rssetviewports)
     pub fn rs_set_viewports (&self, viewports: &[D3D11_VIEWPORT]) {
         unsafe { self.as_ref().RSSetViewports(
@@ -34,6 +65,28 @@ impl DeviceContext {
         )};
     }
 
+    /// MSDN: [ID3D11DeviceContext::ClearDepthStencilView](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-cleardepthstencilview)
+    /// 
+    /// `clear_flags` is a combination of `D3D11_CLEAR_DEPTH` and `D3D11_CLEAR_STENCIL`.
+    pub fn clear_depth_stencil_view (&self, depth_stencil_view: &DepthStencilView, clear_flags: UINT, depth: FLOAT, stencil: UINT8) {
+        unsafe { self.as_ref().ClearDepthStencilView(
+            depth_stencil_view.as_ptr(),
+            clear_flags,
+            depth,
+            stencil
+        )};
+    }
+
+    /// MSDN: [ID3D11DeviceContext::ExecuteCommandList](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-executecommandlist)
+    /// 
+    /// Replay `command_list`.  Prefer `FrameRecorder`, which only ever executes its own deferred contexts' command lists
+    /// on the immediate context of the same device.
+    /// 
+    /// `unsafe`:  `command_list` must have been recorded by a `DeferredContext` of this context's device.
+    pub unsafe fn execute_command_list (&self, command_list: &CommandList, restore_context_state: bool) {
+        self.as_ref().ExecuteCommandList(command_list.as_ptr(), if restore_context_state { TRUE } else { FALSE });
+    }
+
     /// MSDN: [ID3D11DeviceContext::IASetInputLayout](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-iasetinputlayout)
     pub fn ia_set_input_layout (&self, input_layout: &InputLayout) {
         unsafe { self.as_ref().IASetInputLayout(input_layout.as_ptr()) };
@@ -78,6 +131,158 @@ impl DeviceContext {
         };
     }
 
+    /// MSDN: [ID3D11DeviceContext::GSSetShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-gssetshader)
+    /// 
+    /// `None` unbinds the stage.
+    pub fn gs_set_shader (&self, geometry_shader: Option<&GeometryShader>, class_instances: &[Option<&ID3D11ClassInstance>]) {
+        unsafe {
+            let class_instances = class_instances.as_native_slice();
+            self.as_ref().GSSetShader(geometry_shader.map_or(null_mut(), |s| s.as_ptr()), class_instances.as_ptr(), class_instances.len() as UINT)
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::HSSetShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-hssetshader)
+    /// 
+    /// `None` unbinds the stage.
+    pub fn hs_set_shader (&self, hull_shader: Option<&HullShader>, class_instances: &[Option<&ID3D11ClassInstance>]) {
+        unsafe {
+            let class_instances = class_instances.as_native_slice();
+            self.as_ref().HSSetShader(hull_shader.map_or(null_mut(), |s| s.as_ptr()), class_instances.as_ptr(), class_instances.len() as UINT)
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::DSSetShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-dssetshader)
+    /// 
+    /// `None` unbinds the stage.
+    pub fn ds_set_shader (&self, domain_shader: Option<&DomainShader>, class_instances: &[Option<&ID3D11ClassInstance>]) {
+        unsafe {
+            let class_instances = class_instances.as_native_slice();
+            self.as_ref().DSSetShader(domain_shader.map_or(null_mut(), |s| s.as_ptr()), class_instances.as_ptr(), class_instances.len() as UINT)
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::SOSetTargets](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-sosettargets)
+    /// 
+    /// `!0` offsets append to wherever the previous stream output to that buffer left off.
+    pub fn so_set_targets (&self, targets: &[Option<&ID3D11Buffer>], offsets: &[UINT]) {
+        expect_eq!(targets.len(), offsets.len());
+        let n = targets.len().min(offsets.len());
+
+        unsafe {
+            let targets = targets.as_native_slice();
+            self.as_ref().SOSetTargets(n as UINT, targets.as_ptr(), offsets.as_ptr())
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::CSSetShader](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-cssetshader)
+    pub fn cs_set_shader (&self, compute_shader: &ComputeShader, class_instances: &[Option<&ID3D11ClassInstance>]) {
+        unsafe {
+            let class_instances = class_instances.as_native_slice();
+            self.as_ref().CSSetShader(compute_shader.as_ptr(), class_instances.as_ptr(), class_instances.len() as UINT)
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::CSSetConstantBuffers](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-cssetconstantbuffers)
+    pub fn cs_set_constant_buffers (&self, start_slot: UINT, constant_buffers: &[Option<&ID3D11Buffer>]) {
+        unsafe {
+            let constant_buffers = constant_buffers.as_native_slice();
+            self.as_ref().CSSetConstantBuffers(start_slot, constant_buffers.len() as UINT, constant_buffers.as_ptr())
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::CSSetShaderResources](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-cssetshaderresources)
+    /// 
+    /// A resource can't be bound as both an SRV and a UAV.  Bind `None` to unbind one before binding the other.
+    pub fn cs_set_shader_resources (&self, start_slot: UINT, shader_resource_views: &[Option<&ID3D11ShaderResourceView>]) {
+        unsafe {
+            let shader_resource_views = shader_resource_views.as_native_slice();
+            self.as_ref().CSSetShaderResources(start_slot, shader_resource_views.len() as UINT, shader_resource_views.as_ptr())
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::CSSetUnorderedAccessViews](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-cssetunorderedaccessviews)
+    /// 
+    /// `initial_counts`, if present, sets the hidden counters of append / counter UAVs.  `!0` keeps the current count.
+    pub fn cs_set_unordered_access_views (&self, start_slot: UINT, unordered_access_views: &[Option<&ID3D11UnorderedAccessView>], initial_counts: Option<&[UINT]>) {
+        if let Some(initial_counts) = initial_counts { expect_eq!(unordered_access_views.len(), initial_counts.len()); }
+        let n = initial_counts.map_or(unordered_access_views.len(), |ic| ic.len().min(unordered_access_views.len()));
+
+        unsafe {
+            let unordered_access_views = unordered_access_views.as_native_slice();
+            self.as_ref().CSSetUnorderedAccessViews(start_slot, n as UINT, unordered_access_views.as_ptr(), initial_counts.map_or(null(), |ic| ic.as_ptr()))
+        };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::Dispatch](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-dispatch)
+    /// 
+    /// Counts are of thread groups, not threads - see `compute::thread_groups_for`.  `cs_4_x` shaders are further limited
+    /// to `thread_group_count_z == 1`.  Counts are checked against the limits of the device's feature level, so that's
+    /// only enforced below 11.0:  a `cs_4_x` shader dispatched on newer hardware must keep to it itself.
+    pub fn dispatch (&self, thread_group_count_x: UINT, thread_group_count_y: UINT, thread_group_count_z: UINT) {
+        let thread_groups = [thread_group_count_x, thread_group_count_y, thread_group_count_z];
+        let limits = compute::Limits::for_feature_level(self.upcast::<DeviceChild>().get_device().get_feature_level());
+        expect_eq!(limits.map(|limits| compute::validate_dispatch(thread_groups, &limits)), Some(Ok(())));
+        unsafe { self.as_ref().Dispatch(thread_group_count_x, thread_group_count_y, thread_group_count_z) };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::DispatchIndirect](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-dispatchindirect)
+    /// 
+    /// Dispatch the `[UINT; 3]` thread group counts at `aligned_byte_offset` into `args`, which must have been created with
+    /// `D3D11_RESOURCE_MISC_DRAWINDIRECT_ARGS` (e.g. by `Device::create_dispatch_indirect_buffer`.)
+    pub fn dispatch_indirect (&self, args: &Buffer, aligned_byte_offset: UINT) {
+        let desc = args.get_desc();
+        expect!(desc.MiscFlags & D3D11_RESOURCE_MISC_DRAWINDIRECT_ARGS != 0);
+        expect_eq!(compute::validate_indirect_args(aligned_byte_offset, desc.ByteWidth), Ok(()));
+        unsafe { self.as_ref().DispatchIndirect(args.as_ptr(), aligned_byte_offset) };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::CopyResource](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-copyresource)
+    pub fn copy_resource<D: Inherits<Resource>, S: Inherits<Resource>> (&self, dst: &D, src: &S) {
+        unsafe { self.as_ref().CopyResource(dst.upcast::<Resource>().as_ptr(), src.upcast::<Resource>().as_ptr()) };
+    }
+
+    /// Copy `buffer` back to the CPU through a temporary staging buffer.  Blocks until the GPU has caught up, so this is
+    /// for tools, tests, and debugging rather than every frame.
+    /// 
+    /// `unsafe`:  Every `T` sized chunk of `buffer` must be a valid `T`.
+    pub unsafe fn read_buffer<T: Copy> (&self, buffer: &Buffer) -> Result<Vec<T>, HRESULT> {
+        let desc = buffer.get_desc();
+        if size_of::<T>() == 0 || !(desc.ByteWidth as usize).is_multiple_of(size_of::<T>()) { return Err(E_INVALIDARG); }
+
+        let staging_desc = D3D11_BUFFER_DESC {
+            ByteWidth:              desc.ByteWidth,
+            Usage:                  D3D11_USAGE_STAGING,
+            BindFlags:              0,
+            CPUAccessFlags:         D3D11_CPU_ACCESS_READ,
+            MiscFlags:              0,
+            StructureByteStride:    0,
+        };
+        let staging = self.upcast::<DeviceChild>().get_device().create_buffer(&staging_desc, None)?;
+        self.copy_resource(&staging, buffer);
+
+        let mut mapped : D3D11_MAPPED_SUBRESOURCE = std::mem::zeroed();
+        let result = self.as_ref().Map(staging.upcast::<Resource>().as_ptr(), 0, D3D11_MAP_READ, 0, &mut mapped);
+        if !SUCCEEDED(result) { return Err(result); }
+
+        let len = desc.ByteWidth as usize / size_of::<T>();
+        let mut data = Vec::with_capacity(len);
+        ptr::copy_nonoverlapping(mapped.pData as *const T, data.as_mut_ptr(), len);
+        data.set_len(len);
+
+        self.as_ref().Unmap(staging.upcast::<Resource>().as_ptr(), 0);
+        Ok(data)
+    }
+
+    /// MSDN: [ID3D11DeviceContext::ClearState](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-clearstate)
+    pub fn clear_state (&self) {
+        unsafe { self.as_ref().ClearState() };
+    }
+
+    /// MSDN: [ID3D11DeviceContext::Flush](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-flush)
+    pub fn flush (&self) {
+        unsafe { self.as_ref().Flush() };
+    }
+
     /// MSDN: [ID3D11DeviceContext::Draw](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-id3d11devicecontext-draw)
     pub fn draw (&self, vertex_count: UINT, start_vertex_location: UINT) {
         unsafe { self.as_ref().Draw(vertex_count, start_vertex_location) };
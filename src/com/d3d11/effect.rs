@@ -0,0 +1,336 @@
+//! A minimal Effects runtime:  creates the shaders and state objects each pass of a `dxbc::effect::Effect` needs up
+//! front, and sets them on a `DeviceContext` in `EffectPass::apply`.
+//!
+//! Constant buffers, resources and samplers are still the caller's to bind.  Passes that need what this can't create
+//! (expressions, variable indices, stream output, interfaces, render target assignments) fail `Effect::new` with
+//! `EffectError::Unsupported`.
+
+use crate::com::d3d::{Bytecode, BytecodeError, VertexBytecode, HullBytecode, DomainBytecode, GeometryBytecode, PixelBytecode, ComputeBytecode};
+use crate::com::d3d11::*;
+use crate::dxbc::{self, effect as fx};
+use crate::dxbc::effect::{Assignment, AssignmentValue, Constant, ObjectInitializer, State};
+use std::fmt;
+use std::ptr::{null, null_mut};
+
+#[derive(Debug)]
+pub enum EffectError {
+    /// Not a compiled effect `dxbc::effect` can read.
+    Parse(dxbc::Error),
+    /// A pass's shader isn't valid bytecode for its stage.
+    Bytecode(BytecodeError),
+    /// Creating a shader or state object failed.
+    Create(HRESULT),
+    /// A pass refers to a variable the effect doesn't have, or that isn't the right kind of object.
+    UnknownVariable(String),
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for EffectError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EffectError::Parse(ref e)               => write!(f, "invalid effect: {:?}", e),
+            EffectError::Bytecode(ref e)            => write!(f, "{}", e),
+            EffectError::Create(hresult)            => write!(f, "creating a shader or state failed with HRESULT={:X}", hresult),
+            EffectError::UnknownVariable(ref name)  => write!(f, "no usable variable named {:?}", name),
+            EffectError::Unsupported(what)          => write!(f, "unsupported: {}", what),
+        }
+    }
+}
+
+impl From<dxbc::Error> for EffectError {
+    fn from (e: dxbc::Error) -> Self { EffectError::Parse(e) }
+}
+
+/// An effect's techniques, ready to apply.  Groups are flattened away.
+pub struct Effect {
+    techniques: Vec<EffectTechnique>,
+}
+
+pub struct EffectTechnique {
+    pub name:   String,
+    passes:     Vec<EffectPass>,
+}
+
+/// What a pass sets.  `None` leaves that state alone, `Some(None)` unbinds it / restores its default.
+pub struct EffectPass {
+    pub name:           String,
+    vertex_shader:      Option<Option<VertexShader>>,
+    hull_shader:        Option<Option<HullShader>>,
+    domain_shader:      Option<Option<DomainShader>>,
+    geometry_shader:    Option<Option<GeometryShader>>,
+    pixel_shader:       Option<Option<PixelShader>>,
+    compute_shader:     Option<Option<ComputeShader>>,
+    rasterizer_state:   Option<Option<RasterizerState>>,
+    depth_stencil:      Option<Option<DepthStencilState>>,
+    stencil_ref:        UINT,
+    blend_state:        Option<Option<BlendState>>,
+    blend_factor:       [FLOAT; 4],
+    sample_mask:        UINT,
+}
+
+impl Effect {
+    /// Parse `bytecode`, as compiled for an `fx_*` target, and create its passes' shaders and states.
+    pub fn from_bytecode (device: &Device, bytecode: &[u8]) -> Result<Effect, EffectError> {
+        Self::new(device, &fx::Effect::parse(bytecode)?)
+    }
+
+    pub fn new (device: &Device, effect: &fx::Effect) -> Result<Effect, EffectError> {
+        let techniques = effect.techniques().map(|technique| Ok(EffectTechnique {
+            name:   technique.name.clone(),
+            passes: technique.passes.iter().map(|pass| EffectPass::new(device, effect, pass)).collect::<Result<Vec<_>, EffectError>>()?,
+        })).collect::<Result<Vec<_>, EffectError>>()?;
+        Ok(Effect { techniques })
+    }
+
+    pub fn techniques (&self) -> &[EffectTechnique] { &self.techniques[..] }
+
+    pub fn technique (&self, name: &str) -> Option<&EffectTechnique> { self.techniques.iter().find(|t| t.name == name) }
+}
+
+impl EffectTechnique {
+    pub fn passes (&self) -> &[EffectPass] { &self.passes[..] }
+
+    pub fn pass (&self, name: &str) -> Option<&EffectPass> { self.passes.iter().find(|p| p.name == name) }
+}
+
+impl EffectPass {
+    fn new (device: &Device, effect: &fx::Effect, pass: &fx::Pass) -> Result<EffectPass, EffectError> {
+        let mut p = EffectPass {
+            name:               pass.name.clone(),
+            vertex_shader:      None,
+            hull_shader:        None,
+            domain_shader:      None,
+            geometry_shader:    None,
+            pixel_shader:       None,
+            compute_shader:     None,
+            rasterizer_state:   None,
+            depth_stencil:      None,
+            stencil_ref:        0,
+            blend_state:        None,
+            blend_factor:       [1.0; 4],
+            sample_mask:        !0,
+        };
+
+        for a in pass.assignments.iter() {
+            match a.state {
+                State::VertexShader     => p.vertex_shader      = Some(create_shader(shader(effect, a)?, |b: &VertexBytecode   | device.create_vertex_shader(b, None))?),
+                State::HullShader       => p.hull_shader        = Some(create_shader(shader(effect, a)?, |b: &HullBytecode     | device.create_hull_shader(b, None))?),
+                State::DomainShader     => p.domain_shader      = Some(create_shader(shader(effect, a)?, |b: &DomainBytecode   | device.create_domain_shader(b, None))?),
+                State::GeometryShader   => p.geometry_shader    = Some(create_shader(shader(effect, a)?, |b: &GeometryBytecode | device.create_geometry_shader(b, None))?),
+                State::PixelShader      => p.pixel_shader       = Some(create_shader(shader(effect, a)?, |b: &PixelBytecode    | device.create_pixel_shader(b, None))?),
+                State::ComputeShader    => p.compute_shader     = Some(create_shader(shader(effect, a)?, |b: &ComputeBytecode  | device.create_compute_shader(b, None))?),
+                State::RasterizerState  => p.rasterizer_state   = Some(match state_block(effect, a)? {
+                    Some(block) => Some(device.create_rasterizer_state(&rasterizer_desc(block)?).map_err(EffectError::Create)?),
+                    None        => None,
+                }),
+                State::DepthStencilState => p.depth_stencil     = Some(match state_block(effect, a)? {
+                    Some(block) => Some(device.create_depth_stencil_state(&depth_stencil_desc(block)?).map_err(EffectError::Create)?),
+                    None        => None,
+                }),
+                State::BlendState       => p.blend_state        = Some(match state_block(effect, a)? {
+                    Some(block) => Some(device.create_blend_state(&blend_desc(block)?).map_err(EffectError::Create)?),
+                    None        => None,
+                }),
+                State::StencilRef       => p.stencil_ref        = constant(a)?.as_u32(),
+                State::BlendFactor      => p.blend_factor       = color(a)?,
+                State::SampleMask       => p.sample_mask        = constant(a)?.as_u32(),
+                State::RenderTargetView | State::DepthStencilView | State::GenerateMips => return Err(EffectError::Unsupported("render target, depth stencil view, and GenerateMips assignments")),
+                _                       => return Err(EffectError::Unsupported("state block assignments in a pass")),
+            }
+        }
+        Ok(p)
+    }
+
+    /// Set the shaders and states this pass assigns, leaving everything else as it was.  `StencilRef` and
+    /// `BlendFactor` / `SampleMask` are only set along with the depth stencil and blend states they belong to.
+    pub fn apply (&self, context: &DeviceContext) {
+        // vs/ps/cs_set_shader always bind a shader, so `NULL` assignments unbind those stages directly.
+        let raw : &ID3D11DeviceContext = context.as_ref();
+        match self.vertex_shader {
+            Some(Some(ref s))   => context.vs_set_shader(s, &[]),
+            Some(None)          => unsafe { raw.VSSetShader(null_mut(), null(), 0) },
+            None                => {},
+        }
+        if let Some(ref s) = self.hull_shader       { context.hs_set_shader(s.as_ref(), &[]); }
+        if let Some(ref s) = self.domain_shader     { context.ds_set_shader(s.as_ref(), &[]); }
+        if let Some(ref s) = self.geometry_shader   { context.gs_set_shader(s.as_ref(), &[]); }
+        match self.pixel_shader {
+            Some(Some(ref s))   => context.ps_set_shader(s, &[]),
+            Some(None)          => unsafe { raw.PSSetShader(null_mut(), null(), 0) },
+            None                => {},
+        }
+        match self.compute_shader {
+            Some(Some(ref s))   => context.cs_set_shader(s, &[]),
+            Some(None)          => unsafe { raw.CSSetShader(null_mut(), null(), 0) },
+            None                => {},
+        }
+        if let Some(ref s) = self.rasterizer_state  { context.rs_set_state(s.as_ref()); }
+        if let Some(ref s) = self.depth_stencil     { context.om_set_depth_stencil_state(s.as_ref(), self.stencil_ref); }
+        if let Some(ref s) = self.blend_state       { context.om_set_blend_state(s.as_ref(), &self.blend_factor, self.sample_mask); }
+    }
+}
+
+/// The object variable element `value` names, or `None` if it's a `NULL` constant.
+fn object<'e> (effect: &'e fx::Effect, value: &AssignmentValue) -> Result<Option<(&'e fx::ObjectVariable, usize)>, EffectError> {
+    let (name, index) = match *value {
+        AssignmentValue::Constants(_)                   => return Ok(None),
+        AssignmentValue::Variable(ref name)             => (name, 0),
+        AssignmentValue::ConstIndex { ref array, index } => (array, index as usize),
+        _                                               => return Err(EffectError::Unsupported("indexing by variables or expressions")),
+    };
+    effect.object(name).map(|o| Some((o, index))).ok_or_else(|| EffectError::UnknownVariable(name.clone()))
+}
+
+/// The shader `a` assigns, or `None` to unbind the stage.
+fn shader<'e> (effect: &'e fx::Effect, a: &'e Assignment) -> Result<Option<&'e fx::Shader>, EffectError> {
+    if let AssignmentValue::Shader(ref shader) = a.value { return Ok(Some(shader)); }
+    match object(effect, &a.value)? {
+        None => Ok(None),
+        Some((variable, index)) => match variable.initializer {
+            ObjectInitializer::Shaders(ref shaders) => shaders.get(index).map(Some).ok_or_else(|| EffectError::UnknownVariable(variable.name.clone())),
+            _                                       => Err(EffectError::UnknownVariable(variable.name.clone())),
+        },
+    }
+}
+
+/// The state block `a` assigns, or `None` to restore the default state.
+fn state_block<'e> (effect: &'e fx::Effect, a: &Assignment) -> Result<Option<&'e [Assignment]>, EffectError> {
+    match object(effect, &a.value)? {
+        None => Ok(None),
+        Some((variable, index)) => match variable.initializer {
+            ObjectInitializer::States(ref blocks)   => blocks.get(index).map(|b| Some(&b[..])).ok_or_else(|| EffectError::UnknownVariable(variable.name.clone())),
+            _                                       => Err(EffectError::UnknownVariable(variable.name.clone())),
+        },
+    }
+}
+
+fn create_shader<B: Bytecode, S> (shader: Option<&fx::Shader>, create: impl Fn(&B) -> Result<S, HRESULT>) -> Result<Option<S>, EffectError> {
+    let shader = match shader { Some(shader) => shader, None => return Ok(None) };
+    if !shader.so_declarations.is_empty() { return Err(EffectError::Unsupported("stream output")); }
+    if !shader.interface_bindings.is_empty() { return Err(EffectError::Unsupported("interface bindings")); }
+    let bytecode = match shader.bytecode { Some(ref bytecode) => bytecode, None => return Ok(None) };
+    let bytecode = B::from_slice(&bytecode[..]).map_err(EffectError::Bytecode)?;
+    create(&bytecode).map(Some).map_err(EffectError::Create)
+}
+
+fn constants (a: &Assignment) -> Result<&[Constant], EffectError> {
+    match a.value {
+        AssignmentValue::Constants(ref c) if !c.is_empty()  => Ok(&c[..]),
+        _                                                   => Err(EffectError::Unsupported("state values other than constants")),
+    }
+}
+
+fn constant (a: &Assignment) -> Result<Constant, EffectError> { constants(a).map(|c| c[0]) }
+
+fn color (a: &Assignment) -> Result<[FLOAT; 4], EffectError> {
+    let c = constants(a)?;
+    if c.len() < 4 { return Err(EffectError::Unsupported("colors with fewer than 4 components")); }
+    Ok([c[0].as_f32(), c[1].as_f32(), c[2].as_f32(), c[3].as_f32()])
+}
+
+fn boolean (a: &Assignment) -> Result<BOOL, EffectError> { constant(a).map(|c| c.as_bool() as BOOL) }
+
+fn rasterizer_desc (block: &[Assignment]) -> Result<D3D11_RASTERIZER_DESC, EffectError> {
+    let mut desc = D3D11_RASTERIZER_DESC {
+        FillMode:               D3D11_FILL_SOLID,
+        CullMode:               D3D11_CULL_BACK,
+        FrontCounterClockwise:  FALSE,
+        DepthBias:              0,
+        DepthBiasClamp:         0.0,
+        SlopeScaledDepthBias:   0.0,
+        DepthClipEnable:        TRUE,
+        ScissorEnable:          FALSE,
+        MultisampleEnable:      FALSE,
+        AntialiasedLineEnable:  FALSE,
+    };
+    for a in block.iter() {
+        match a.state {
+            State::FillMode                 => desc.FillMode                = constant(a)?.as_u32(),
+            State::CullMode                 => desc.CullMode                = constant(a)?.as_u32(),
+            State::FrontCounterClockwise    => desc.FrontCounterClockwise   = boolean(a)?,
+            State::DepthBias                => desc.DepthBias               = constant(a)?.as_i32(),
+            State::DepthBiasClamp           => desc.DepthBiasClamp          = constant(a)?.as_f32(),
+            State::SlopeScaledDepthBias     => desc.SlopeScaledDepthBias    = constant(a)?.as_f32(),
+            State::DepthClipEnable          => desc.DepthClipEnable         = boolean(a)?,
+            State::ScissorEnable            => desc.ScissorEnable           = boolean(a)?,
+            State::MultisampleEnable        => desc.MultisampleEnable       = boolean(a)?,
+            State::AntialiasedLineEnable    => desc.AntialiasedLineEnable   = boolean(a)?,
+            _                               => return Err(EffectError::Unsupported("non-rasterizer state in a RasterizerState")),
+        }
+    }
+    Ok(desc)
+}
+
+fn depth_stencil_desc (block: &[Assignment]) -> Result<D3D11_DEPTH_STENCIL_DESC, EffectError> {
+    let face = D3D11_DEPTH_STENCILOP_DESC {
+        StencilFailOp:      D3D11_STENCIL_OP_KEEP,
+        StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
+        StencilPassOp:      D3D11_STENCIL_OP_KEEP,
+        StencilFunc:        D3D11_COMPARISON_ALWAYS,
+    };
+    let mut desc = D3D11_DEPTH_STENCIL_DESC {
+        DepthEnable:        TRUE,
+        DepthWriteMask:     D3D11_DEPTH_WRITE_MASK_ALL,
+        DepthFunc:          D3D11_COMPARISON_LESS,
+        StencilEnable:      FALSE,
+        StencilReadMask:    D3D11_DEFAULT_STENCIL_READ_MASK as UINT8,
+        StencilWriteMask:   D3D11_DEFAULT_STENCIL_WRITE_MASK as UINT8,
+        FrontFace:          face,
+        BackFace:           face,
+    };
+    for a in block.iter() {
+        match a.state {
+            State::DepthEnable                  => desc.DepthEnable                     = boolean(a)?,
+            State::DepthWriteMask               => desc.DepthWriteMask                  = constant(a)?.as_u32(),
+            State::DepthFunc                    => desc.DepthFunc                       = constant(a)?.as_u32(),
+            State::StencilEnable                => desc.StencilEnable                   = boolean(a)?,
+            State::StencilReadMask              => desc.StencilReadMask                 = constant(a)?.as_u32() as UINT8,
+            State::StencilWriteMask             => desc.StencilWriteMask                = constant(a)?.as_u32() as UINT8,
+            State::FrontFaceStencilFail         => desc.FrontFace.StencilFailOp         = constant(a)?.as_u32(),
+            State::FrontFaceStencilDepthFail    => desc.FrontFace.StencilDepthFailOp    = constant(a)?.as_u32(),
+            State::FrontFaceStencilPass         => desc.FrontFace.StencilPassOp         = constant(a)?.as_u32(),
+            State::FrontFaceStencilFunc         => desc.FrontFace.StencilFunc           = constant(a)?.as_u32(),
+            State::BackFaceStencilFail          => desc.BackFace.StencilFailOp          = constant(a)?.as_u32(),
+            State::BackFaceStencilDepthFail     => desc.BackFace.StencilDepthFailOp     = constant(a)?.as_u32(),
+            State::BackFaceStencilPass          => desc.BackFace.StencilPassOp          = constant(a)?.as_u32(),
+            State::BackFaceStencilFunc          => desc.BackFace.StencilFunc            = constant(a)?.as_u32(),
+            _                                   => return Err(EffectError::Unsupported("non-depth stencil state in a DepthStencilState")),
+        }
+    }
+    Ok(desc)
+}
+
+fn blend_desc (block: &[Assignment]) -> Result<D3D11_BLEND_DESC, EffectError> {
+    let target = D3D11_RENDER_TARGET_BLEND_DESC {
+        BlendEnable:            FALSE,
+        SrcBlend:               D3D11_BLEND_ONE,
+        DestBlend:              D3D11_BLEND_ZERO,
+        BlendOp:                D3D11_BLEND_OP_ADD,
+        SrcBlendAlpha:          D3D11_BLEND_ONE,
+        DestBlendAlpha:         D3D11_BLEND_ZERO,
+        BlendOpAlpha:           D3D11_BLEND_OP_ADD,
+        RenderTargetWriteMask:  D3D11_COLOR_WRITE_ENABLE_ALL as UINT8,
+    };
+    let mut desc = D3D11_BLEND_DESC {
+        AlphaToCoverageEnable:  FALSE,
+        IndependentBlendEnable: FALSE,
+        RenderTarget:           [target; 8],
+    };
+    for a in block.iter() {
+        if a.state == State::AlphaToCoverageEnable { desc.AlphaToCoverageEnable = boolean(a)?; continue; }
+        if a.index > 0 { desc.IndependentBlendEnable = TRUE; }
+        let target = desc.RenderTarget.get_mut(a.index as usize).ok_or(EffectError::Unsupported("blend state render target index past 7"))?;
+        match a.state {
+            State::BlendEnable              => target.BlendEnable           = boolean(a)?,
+            State::SrcBlend                 => target.SrcBlend              = constant(a)?.as_u32(),
+            State::DestBlend                => target.DestBlend             = constant(a)?.as_u32(),
+            State::BlendOp                  => target.BlendOp               = constant(a)?.as_u32(),
+            State::SrcBlendAlpha            => target.SrcBlendAlpha         = constant(a)?.as_u32(),
+            State::DestBlendAlpha           => target.DestBlendAlpha        = constant(a)?.as_u32(),
+            State::BlendOpAlpha             => target.BlendOpAlpha          = constant(a)?.as_u32(),
+            State::RenderTargetWriteMask    => target.RenderTargetWriteMask = constant(a)?.as_u32() as UINT8,
+            _                               => return Err(EffectError::Unsupported("non-blend state in a BlendState")),
+        }
+    }
+    Ok(desc)
+}
@@ -1,6 +1,7 @@
 use crate::win32::*;
 
 pub trait IntoInputElements {
+    #[allow(clippy::wrong_self_convention)] // A cheap view of `self`, but named for what it's converted into
     fn into_input_elements (&self) -> &[D3D11_INPUT_ELEMENT_DESC];
 }
 
@@ -11,6 +12,12 @@ pub struct InputElementDesc<'a>(pub(crate) D3D11_INPUT_ELEMENT_DESC, pub(crate)
 
 unsafe impl<'a> Sync for InputElementDesc<'a> {}
 
+impl InputElementDesc<'static> {
+    /// For `input_layout!`.  `unsafe`:  `desc.SemanticName` must point to a `'static`, NUL terminated string.
+    #[doc(hidden)]
+    pub const unsafe fn from_raw (desc: D3D11_INPUT_ELEMENT_DESC) -> Self { Self(desc, std::marker::PhantomData) }
+}
+
 impl IntoInputElements for &[D3D11_INPUT_ELEMENT_DESC] {
     fn into_input_elements (&self) -> &[D3D11_INPUT_ELEMENT_DESC] {
         self
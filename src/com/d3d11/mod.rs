@@ -1,29 +1,95 @@
 use crate::win32::*;
 
+// MSDN: [Introduction to a Device in Direct3D 11](https://docs.microsoft.com/en-us/windows/desktop/direct3d11/overviews-direct3d-11-devices-intro)
+// The device and everything it creates are free-threaded.  Device contexts are not, but may be handed between threads.
 com_wrapper! {
-    pub struct Buffer(*mut ID3D11Buffer);
-    pub struct Texture2D(*mut ID3D11Texture2D);
-    pub struct PixelShader(*mut ID3D11PixelShader);
-    pub struct VertexShader(*mut ID3D11VertexShader);
-    pub struct InputLayout(*mut ID3D11InputLayout);
-    pub struct ClassInstance(*mut ID3D11ClassInstance);
-    pub struct ClassLinkage(*mut ID3D11ClassLinkage);
-    pub struct RenderTargetView(*mut ID3D11RenderTargetView);
-    pub struct DepthStencilView(*mut ID3D11DepthStencilView);
+    pub struct DeviceChild(*mut ID3D11DeviceChild);
+    pub struct Resource(*mut ID3D11Resource) : DeviceChild;
+    pub struct View(*mut ID3D11View) : DeviceChild;
+    #[threading(Send, Sync)] pub struct Buffer(*mut ID3D11Buffer) : Resource : DeviceChild;
+    #[threading(Send, Sync)] pub struct Texture2D(*mut ID3D11Texture2D) : Resource : DeviceChild;
+    #[threading(Send, Sync)] pub struct PixelShader(*mut ID3D11PixelShader) : DeviceChild;
+    #[threading(Send, Sync)] pub struct VertexShader(*mut ID3D11VertexShader) : DeviceChild;
+    #[threading(Send, Sync)] pub struct ComputeShader(*mut ID3D11ComputeShader) : DeviceChild;
+    #[threading(Send, Sync)] pub struct GeometryShader(*mut ID3D11GeometryShader) : DeviceChild;
+    #[threading(Send, Sync)] pub struct HullShader(*mut ID3D11HullShader) : DeviceChild;
+    #[threading(Send, Sync)] pub struct DomainShader(*mut ID3D11DomainShader) : DeviceChild;
+    #[threading(Send, Sync)] pub struct InputLayout(*mut ID3D11InputLayout) : DeviceChild;
+    pub struct ClassInstance(*mut ID3D11ClassInstance) : DeviceChild;
+    pub struct ClassLinkage(*mut ID3D11ClassLinkage) : DeviceChild;
+    #[threading(Send, Sync)] pub struct RenderTargetView(*mut ID3D11RenderTargetView) : View : DeviceChild;
+    #[threading(Send, Sync)] pub struct DepthStencilView(*mut ID3D11DepthStencilView) : View : DeviceChild;
+    #[threading(Send, Sync)] pub struct ShaderResourceView(*mut ID3D11ShaderResourceView) : View : DeviceChild;
+    #[threading(Send, Sync)] pub struct UnorderedAccessView(*mut ID3D11UnorderedAccessView) : View : DeviceChild;
+    #[threading(Send, Sync)] pub struct DepthStencilState(*mut ID3D11DepthStencilState) : DeviceChild;
+    #[threading(Send, Sync)] pub struct BlendState(*mut ID3D11BlendState) : DeviceChild;
+    #[threading(Send, Sync)] pub struct RasterizerState(*mut ID3D11RasterizerState) : DeviceChild;
+    #[threading(Send, Sync)] pub struct CommandList(*mut ID3D11CommandList) : DeviceChild;
 }
 
 mod driver_type;
-mod feature_level;
 mod input_element_desc;
 mod primitive_topology;
+mod so_declaration_entry;
 
+mod buffer;
 mod device;
+mod device_child;
 mod device_context;
+mod deferred_context;
+mod effect;
 
 pub use driver_type::*;
-pub use feature_level::*;
+pub use crate::com::FeatureLevel;
 pub use input_element_desc::*;
 pub use primitive_topology::*;
+pub use so_declaration_entry::*;
 
+pub use buffer::*;
 pub use device::*;
 pub use device_context::*;
+pub use deferred_context::*;
+pub use effect::*;
+
+/// Fails to compile if the `#[threading(...)]` markers above regress.  The markers that must stay *off* are checked
+/// by the doctests below.  `DeviceContext` may be handed between threads, but not shared - not even through a clone:
+///
+/// ```compile_fail
+/// fn sync<T: Sync> () {}
+/// sync::<rust_win32_d3d11::com::d3d11::DeviceContext>();
+/// ```
+///
+/// ```compile_fail
+/// fn clone<T: Clone> () {}
+/// clone::<rust_win32_d3d11::com::d3d11::DeviceContext>();
+/// ```
+///
+/// Interfaces D3D doesn't document as free-threaded are neither `Send` nor `Sync`:
+///
+/// ```compile_fail
+/// fn send<T: Send> () {}
+/// send::<rust_win32_d3d11::com::d3d11::ClassLinkage>();
+/// ```
+///
+/// ```compile_fail
+/// fn send<T: Send> () {}
+/// send::<rust_win32_d3d11::com::d3d11::DeviceChild>();
+/// ```
+///
+/// ```compile_fail
+/// fn send<T: Send> () {}
+/// send::<rust_win32_d3d11::com::d3d::Blob>();
+/// ```
+#[allow(dead_code)]
+fn assert_threading () {
+    fn send_sync<T: Send + Sync> () {}
+    fn send<T: Send> () {}
+    send_sync::<Device>();
+    send_sync::<Buffer>();
+    send_sync::<Texture2D>();
+    send_sync::<VertexShader>();
+    send_sync::<PixelShader>();
+    send_sync::<ComputeShader>();
+    send_sync::<UnorderedAccessView>();
+    send::<DeviceContext>();
+}
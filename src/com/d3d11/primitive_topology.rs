@@ -1,3 +1,4 @@
+use crate::pipeline::Primitive;
 use crate::win32::*;
 
 #[repr(u32)]
@@ -51,4 +52,17 @@ pub enum PrimitiveTopology {
 
 impl PrimitiveTopology {
     pub fn raw(self) -> D3D_PRIMITIVE_TOPOLOGY { self as D3D_PRIMITIVE_TOPOLOGY }
+
+    /// What this topology feeds the pipeline, for `pipeline::validate`.  `None` if `Undefined`.
+    pub fn primitive(self) -> Option<Primitive> {
+        match self {
+            PrimitiveTopology::Undefined                                                => None,
+            PrimitiveTopology::PointList                                                => Some(Primitive::Points),
+            PrimitiveTopology::LineList        | PrimitiveTopology::LineStrip           => Some(Primitive::Lines),
+            PrimitiveTopology::TriangleList    | PrimitiveTopology::TriangleStrip       => Some(Primitive::Triangles),
+            PrimitiveTopology::LineListAdj     | PrimitiveTopology::LineStripAdj        => Some(Primitive::LinesAdj),
+            PrimitiveTopology::TriangleListAdj | PrimitiveTopology::TriangleStripAdj    => Some(Primitive::TrianglesAdj),
+            patches                                                                     => Some(Primitive::Patches(patches.raw() - D3D_PRIMITIVE_TOPOLOGY_1_CONTROL_POINT_PATCHLIST + 1)),
+        }
+    }
 }
@@ -0,0 +1,90 @@
+use crate::pipeline::StreamOutputEntry;
+use crate::win32::*;
+use std::ffi::CStr;
+use std::ptr::null;
+
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+/// MSDN: [D3D11_SO_DECLARATION_ENTRY](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/ns-d3d11-d3d11_so_declaration_entry)
+///
+/// Writes all of `semantic`'s components to stream 0, output slot 0, unless changed with the builder methods:
+///
+/// ```ignore
+/// let name = |s: &'static [u8]| CStr::from_bytes_with_nul(s).unwrap();
+/// let entries = [
+///     SoDeclarationEntry::semantic(name(b"SV_POSITION\0"), 0),
+///     SoDeclarationEntry::semantic(name(b"TEXCOORD\0"), 0).components(0, 2),
+///     SoDeclarationEntry::gap(2),
+///     SoDeclarationEntry::semantic(name(b"COLOR\0"), 0).output_slot(1),
+/// ];
+/// ```
+pub struct SoDeclarationEntry<'a>(pub(crate) D3D11_SO_DECLARATION_ENTRY, pub(crate) std::marker::PhantomData<&'a CStr>);
+
+unsafe impl<'a> Sync for SoDeclarationEntry<'a> {}
+
+impl<'a> SoDeclarationEntry<'a> {
+    /// Write the `xyzw` components of `semantic_name` + `semantic_index` (e.g. `TEXCOORD1`.)
+    pub fn semantic (semantic_name: &'a CStr, semantic_index: UINT) -> Self {
+        Self(D3D11_SO_DECLARATION_ENTRY {
+            Stream:         0,
+            SemanticName:   semantic_name.as_ptr(),
+            SemanticIndex:  semantic_index,
+            StartComponent: 0,
+            ComponentCount: 4,
+            OutputSlot:     0,
+        }, std::marker::PhantomData)
+    }
+
+    /// Skip `component_count` components of the output slot, leaving them unwritten.
+    pub fn gap (component_count: BYTE) -> Self {
+        Self(D3D11_SO_DECLARATION_ENTRY {
+            Stream:         0,
+            SemanticName:   null(),
+            SemanticIndex:  0,
+            StartComponent: 0,
+            ComponentCount: component_count,
+            OutputSlot:     0,
+        }, std::marker::PhantomData)
+    }
+
+    /// Which of the geometry shader's streams (0 ..= 3) to read from.
+    pub fn stream (mut self, stream: UINT) -> Self { self.0.Stream = stream; self }
+
+    /// Write only components `start_component .. start_component + component_count`, e.g. `(0, 3)` for `xyz`.
+    pub fn components (mut self, start_component: BYTE, component_count: BYTE) -> Self {
+        self.0.StartComponent = start_component;
+        self.0.ComponentCount = component_count;
+        self
+    }
+
+    /// Which of the stream output buffers (0 ..= 3) to write to.
+    pub fn output_slot (mut self, output_slot: BYTE) -> Self { self.0.OutputSlot = output_slot; self }
+
+    /// This entry, for `pipeline::validate_stream_output`.
+    pub fn layout (&self) -> StreamOutputEntry {
+        StreamOutputEntry {
+            stream:             self.0.Stream,
+            gap:                self.0.SemanticName.is_null(),
+            start_component:    self.0.StartComponent,
+            component_count:    self.0.ComponentCount,
+            output_slot:        self.0.OutputSlot,
+        }
+    }
+}
+
+pub trait IntoSoDeclaration {
+    #[allow(clippy::wrong_self_convention)] // Named after `IntoInputElements`, which it mirrors
+    fn into_so_declaration (&self) -> &[D3D11_SO_DECLARATION_ENTRY];
+}
+
+impl IntoSoDeclaration for &[D3D11_SO_DECLARATION_ENTRY] {
+    fn into_so_declaration (&self) -> &[D3D11_SO_DECLARATION_ENTRY] {
+        self
+    }
+}
+
+impl<'a> IntoSoDeclaration for &[SoDeclarationEntry<'a>] {
+    fn into_so_declaration (&self) -> &[D3D11_SO_DECLARATION_ENTRY] {
+        unsafe { std::slice::from_raw_parts(self.as_ptr() as *const _, self.len()) }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::com::*;
+use crate::com::d3d11::*;
+use crate::win32::*;
+use std::ptr::{null, null_mut};
+
+pub struct DeviceAndContext {
+    pub feature_level:  FeatureLevel,
+    pub device:         Device,
+    pub device_context: DeviceContext,
+}
+
+impl DeviceAndContext {
+    /// MSDN: [D3D11CreateDevice](https://docs.microsoft.com/en-us/windows/desktop/api/d3d11/nf-d3d11-d3d11createdevice)
+    /// 
+    /// Create a device without a swap chain.  Swap chains can be created later with `dxgi::Factory::create_swap_chain`.
+    /// 
+    /// `unsafe`:  Possible undefined behavior if `software` is `Some(invalid_ptr)`
+    pub unsafe fn create(
+        adapter:            Option<&IDXGIAdapter>,
+        driver_type:        DriverType,
+        software:           Option<HMODULE>,
+        flags:              UINT, // D3D11_CREATE_DEVICE_FLAG
+        feature_levels:     Option<&[FeatureLevel]>,
+    ) -> Result<Self, HRESULT> {
+        let mut device          = null_mut();
+        let mut device_context  = null_mut();
+        let mut feature_level   = FeatureLevel::_9_1;
+        let hresult = D3D11CreateDevice(
+            adapter.map_or(null_mut(), |a| a as *const _ as *mut _),
+            driver_type.raw(),
+            software.unwrap_or(null_mut()),
+            flags,
+            feature_levels.map_or(null(), |s| s.as_ptr() as *const _),
+            feature_levels.map_or(0,      |s| s.len() as u32),
+            D3D11_SDK_VERSION,
+            &mut device,
+            &mut feature_level.0,
+            &mut device_context
+        );
+        if SUCCEEDED(hresult) {
+            Ok(Self{
+                feature_level,
+                device:         Device::own(device).unwrap(),
+                device_context: DeviceContext::own(device_context).unwrap(),
+            })
+        } else {
+            Err(hresult)
+        }
+    }
+}
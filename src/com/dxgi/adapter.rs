@@ -0,0 +1,24 @@
+use crate::com::*;
+use crate::com::dxgi::Object;
+use crate::win32::*;
+use std::ptr::{null_mut};
+
+com_wrapper! {
+    pub struct Adapter(*mut IDXGIAdapter) : Object;
+    // `dxgi::Device` would clash with `d3d11::Device` wherever both modules are glob imported.
+    pub struct DxgiDevice(*mut IDXGIDevice) : Object;
+}
+
+/// MSDN: [IDXGIDevice](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nn-dxgi-idxgidevice)
+impl DxgiDevice {
+    /// MSDN: [IDXGIDevice::GetAdapter](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgidevice-getadapter)
+    pub fn get_adapter (&self) -> Result<Adapter, HRESULT> {
+        let mut adapter = null_mut();
+        let result = unsafe { self.as_ref().GetAdapter(&mut adapter) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { Adapter::own(adapter) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+}
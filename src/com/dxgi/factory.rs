@@ -0,0 +1,47 @@
+use crate::com::*;
+use crate::com::d3d11::Device;
+use crate::com::dxgi::*;
+use crate::com::dxgi::Object;
+use crate::win32::*;
+use std::ptr::{null_mut};
+
+com_wrapper! {
+    pub struct Factory(*mut IDXGIFactory) : Object;
+}
+
+/// Not (yet?) exposed by `winapi`.
+pub const DXGI_MWA_NO_ALT_ENTER : UINT = 1 << 1;
+
+/// MSDN: [IDXGIFactory](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nn-dxgi-idxgifactory)
+impl Factory {
+    /// Get the factory that created `device`'s adapter.  Swap chains for `device` must be created by this factory.
+    pub fn from_device (device: &Device) -> Result<Factory, HRESULT> {
+        let adapter = device.cast::<DxgiDevice>()?.get_adapter()?;
+        adapter.upcast::<Object>().get_parent::<Factory>()
+    }
+
+    /// MSDN: [IDXGIFactory::CreateSwapChain](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgifactory-createswapchain)
+    /// 
+    /// `unsafe`:  Possible undefined behavior if `desc.OutputWindow` is an invalid handle.
+    pub unsafe fn create_swap_chain (&self, device: &Device, desc: &DXGI_SWAP_CHAIN_DESC) -> Result<SwapChain, HRESULT> {
+        let mut desc = *desc;
+        let mut swap_chain = null_mut();
+        let result = self.as_ref().CreateSwapChain(device.as_ptr() as *mut IUnknown, &mut desc, &mut swap_chain);
+        if SUCCEEDED(result) {
+            Ok(SwapChain::own(swap_chain).unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [IDXGIFactory::MakeWindowAssociation](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgifactory-makewindowassociation)
+    /// 
+    /// `flags` is a combination of `DXGI_MWA_*`.
+    /// 
+    /// `unsafe`:  `window` must be a valid handle.
+    pub unsafe fn make_window_association (&self, window: HWND, flags: UINT) -> Result<(), HRESULT> {
+        let result = self.as_ref().MakeWindowAssociation(window, flags);
+        if SUCCEEDED(result) { Ok(()) }
+        else { Err(result) }
+    }
+}
@@ -1,3 +1,11 @@
+mod adapter;
+mod factory;
+mod object;
+mod output;
 mod swap_chain;
 
+pub use adapter::*;
+pub use factory::*;
+pub use object::*;
+pub use output::*;
 pub use swap_chain::*;
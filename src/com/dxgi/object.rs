@@ -0,0 +1,25 @@
+use crate::com::*;
+use crate::win32::*;
+use std::ptr::{null_mut};
+
+com_wrapper! {
+    pub struct Object(*mut IDXGIObject);
+    pub struct DeviceSubObject(*mut IDXGIDeviceSubObject) : Object;
+}
+
+/// MSDN: [IDXGIObject](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nn-dxgi-idxgiobject)
+impl Object {
+    /// MSDN: [IDXGIObject::GetParent](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiobject-getparent)
+    /// 
+    /// The parent of an adapter is the `Factory` that enumerated it.
+    pub fn get_parent<T: Wrapper> (&self) -> Result<T, HRESULT> {
+        let mut parent = null_mut();
+        let result = unsafe { self.as_ref().GetParent(&T::uuidof(), &mut parent) };
+        if SUCCEEDED(result) {
+            expect_ne!(parent, null_mut());
+            Ok(unsafe { T::own(parent as *mut _) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+}
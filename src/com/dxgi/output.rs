@@ -0,0 +1,36 @@
+use crate::com::dxgi::Object;
+use crate::win32::*;
+use std::ptr::{null_mut};
+
+com_wrapper! {
+    pub struct Output(*mut IDXGIOutput) : Object;
+}
+
+/// MSDN: [IDXGIOutput](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nn-dxgi-idxgioutput)
+impl Output {
+    /// MSDN: [IDXGIOutput::GetDesc](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgioutput-getdesc)
+    pub fn get_desc (&self) -> Result<DXGI_OUTPUT_DESC, HRESULT> {
+        let mut desc = unsafe { std::mem::zeroed() };
+        let result = unsafe { self.as_ref().GetDesc(&mut desc) };
+        if SUCCEEDED(result) { Ok(desc) }
+        else { Err(result) }
+    }
+
+    /// MSDN: [IDXGIOutput::GetDisplayModeList](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgioutput-getdisplaymodelist)
+    /// 
+    /// `flags` is a combination of `DXGI_ENUM_MODES_*`.
+    pub fn get_display_mode_list (&self, format: DXGI_FORMAT, flags: UINT) -> Result<Vec<DXGI_MODE_DESC>, HRESULT> {
+        loop {
+            let mut n = 0;
+            let result = unsafe { self.as_ref().GetDisplayModeList(format, flags, &mut n, null_mut()) };
+            if !SUCCEEDED(result) { return Err(result); }
+
+            let mut modes = vec![unsafe { std::mem::zeroed::<DXGI_MODE_DESC>() }; n as usize];
+            let result = unsafe { self.as_ref().GetDisplayModeList(format, flags, &mut n, modes.as_mut_ptr()) };
+            if result == DXGI_ERROR_MORE_DATA { continue; } // Modes changed between calls
+            if !SUCCEEDED(result) { return Err(result); }
+            modes.truncate(n as usize);
+            return Ok(modes);
+        }
+    }
+}
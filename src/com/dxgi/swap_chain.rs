@@ -1,17 +1,29 @@
 use crate::com::*;
+use crate::com::dxgi::*;
+use crate::com::dxgi::Object;
 use crate::win32::*;
 use std::ptr::{null_mut};
 
 com_wrapper! {
-    pub struct SwapChain(*mut IDXGISwapChain);
+    pub struct SwapChain(*mut IDXGISwapChain) : DeviceSubObject : Object;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PresentStatus {
+    Presented,
+    /// `DXGI_STATUS_OCCLUDED`:  The window isn't visible, so nothing was presented.  Keep rendering at your own risk.
+    Occluded,
 }
 
 /// MSDN: [IDXGISwapChain](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nn-dxgi-idxgiswapchain)
 impl SwapChain {
     /// MSDN: [IDXGISwapChain::Present](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-present)
-    pub fn present (&self, sync_interval: UINT, flags: UINT) -> Result<(),HRESULT> {
+    /// 
+    /// With `flags = DXGI_PRESENT_TEST`, nothing is presented, but the returned status still reflects occlusion.
+    pub fn present (&self, sync_interval: UINT, flags: UINT) -> Result<PresentStatus,HRESULT> {
         let result = unsafe { self.as_ref().Present(sync_interval, flags) };
-        if SUCCEEDED(result) { Ok(()) }
+        if result == DXGI_STATUS_OCCLUDED { Ok(PresentStatus::Occluded) }
+        else if SUCCEEDED(result) { Ok(PresentStatus::Presented) }
         else { Err(result) }
     }
 
@@ -26,4 +38,62 @@ impl SwapChain {
             Err(result)
         }
     }
+
+    /// MSDN: [IDXGISwapChain::GetDesc](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-getdesc)
+    pub fn get_desc (&self) -> Result<DXGI_SWAP_CHAIN_DESC, HRESULT> {
+        let mut desc = unsafe { std::mem::zeroed() };
+        let result = unsafe { self.as_ref().GetDesc(&mut desc) };
+        if SUCCEEDED(result) { Ok(desc) }
+        else { Err(result) }
+    }
+
+    /// MSDN: [IDXGISwapChain::GetContainingOutput](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-getcontainingoutput)
+    pub fn get_containing_output (&self) -> Result<Output, HRESULT> {
+        let mut output = null_mut();
+        let result = unsafe { self.as_ref().GetContainingOutput(&mut output) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { Output::own(output) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// MSDN: [IDXGISwapChain::SetFullscreenState](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-setfullscreenstate)
+    /// 
+    /// `target` of `None` lets DXGI pick the output containing most of the window.
+    pub fn set_fullscreen_state (&self, fullscreen: bool, target: Option<&Output>) -> Result<(), HRESULT> {
+        let result = unsafe { self.as_ref().SetFullscreenState(
+            if fullscreen { TRUE } else { FALSE },
+            target.map_or(null_mut(), |t| t.as_ptr())
+        )};
+        if SUCCEEDED(result) { Ok(()) }
+        else { Err(result) }
+    }
+
+    /// MSDN: [IDXGISwapChain::GetFullscreenState](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-getfullscreenstate)
+    pub fn get_fullscreen_state (&self) -> Result<bool, HRESULT> {
+        let mut fullscreen = FALSE;
+        let result = unsafe { self.as_ref().GetFullscreenState(&mut fullscreen, null_mut()) };
+        if SUCCEEDED(result) { Ok(fullscreen != FALSE) }
+        else { Err(result) }
+    }
+
+    /// MSDN: [IDXGISwapChain::ResizeBuffers](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-resizebuffers)
+    /// 
+    /// All outstanding references to the back buffers (including views of them) must be released first.  Pass `0`
+    /// for `buffer_count`, `width`, `height` or `DXGI_FORMAT_UNKNOWN` for `format` to keep / infer the current values.
+    pub fn resize_buffers (&self, buffer_count: UINT, width: UINT, height: UINT, format: DXGI_FORMAT, flags: UINT) -> Result<(), HRESULT> {
+        let result = unsafe { self.as_ref().ResizeBuffers(buffer_count, width, height, format, flags) };
+        if SUCCEEDED(result) { Ok(()) }
+        else { Err(result) }
+    }
+
+    /// MSDN: [IDXGISwapChain::ResizeTarget](https://docs.microsoft.com/en-us/windows/desktop/api/dxgi/nf-dxgi-idxgiswapchain-resizetarget)
+    /// 
+    /// Resizes the window when windowed, or changes the display mode when fullscreen.
+    pub fn resize_target (&self, new_target_parameters: &DXGI_MODE_DESC) -> Result<(), HRESULT> {
+        let result = unsafe { self.as_ref().ResizeTarget(new_target_parameters) };
+        if SUCCEEDED(result) { Ok(()) }
+        else { Err(result) }
+    }
 }
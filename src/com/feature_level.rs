@@ -1,10 +1,20 @@
-use crate::win32::*;
 use std::fmt;
 
+// Spelled out here rather than taken from winapi, so feature level checks build and can be tested everywhere.
+const D3D_FEATURE_LEVEL_9_1     : u32 = 0x9100;
+const D3D_FEATURE_LEVEL_9_2     : u32 = 0x9200;
+const D3D_FEATURE_LEVEL_9_3     : u32 = 0x9300;
+const D3D_FEATURE_LEVEL_10_0    : u32 = 0xa000;
+const D3D_FEATURE_LEVEL_10_1    : u32 = 0xa100;
+const D3D_FEATURE_LEVEL_11_0    : u32 = 0xb000;
+const D3D_FEATURE_LEVEL_11_1    : u32 = 0xb100;
+const D3D_FEATURE_LEVEL_12_0    : u32 = 0xc000;
+const D3D_FEATURE_LEVEL_12_1    : u32 = 0xc100;
+
 #[repr(transparent)]
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 /// MSDN: [D3D_FEATURE_LEVEL](https://docs.microsoft.com/en-us/windows/desktop/api/d3dcommon/ne-d3dcommon-d3d_feature_level)
-pub struct FeatureLevel(pub(crate) D3D_FEATURE_LEVEL);
+pub struct FeatureLevel(pub(crate) u32); // D3D_FEATURE_LEVEL
 
 impl FeatureLevel {
     pub const _9_1  : FeatureLevel = FeatureLevel(D3D_FEATURE_LEVEL_9_1);
@@ -0,0 +1,204 @@
+//! Implementing COM interfaces in Rust, so our own objects can be handed to D3D.
+//!
+//! Implement `Class` for a Rust type, then `Object::new` boxes it behind a vtable with an atomic refcount.  The
+//! `IUnknown` part of the vtable is provided by `Object::<C>::IUNKNOWN`, the rest is up to the implementor:
+//!
+//! ```ignore
+//! struct Tag(u32);
+//!
+//! unsafe impl Class for Tag {
+//!     type Interface  = IUnknown;
+//!     type Vtbl       = IUnknownVtbl;
+//!     const VTBL : &'static IUnknownVtbl = &Object::<Tag>::IUNKNOWN;
+//!     const INTERFACES : &'static [GUID] = &[];
+//! }
+//!
+//! let tag : Unknown = Object::new(Tag(42));
+//! ```
+
+use crate::com::*;
+use crate::com::abi::*;
+use std::ptr::null_mut;
+use std::sync::atomic::{self, AtomicU32, Ordering};
+
+com_wrapper! {
+    pub struct Unknown(*mut IUnknown);
+}
+
+/// A Rust type implementing a COM interface.
+///
+/// `unsafe`:  `VTBL` must be a valid vtable for `Interface` that begins with `Object::<Self>::IUNKNOWN`, and whose
+/// methods expect `This` to point to an `Object<Self>`.
+pub unsafe trait Class : Sized + Send + Sync + 'static {
+    /// The interface `Object::new` returns, e.g. `IUnknown`.
+    type Interface;
+    type Vtbl : 'static;
+    const VTBL : &'static Self::Vtbl;
+    /// IIDs `QueryInterface` succeeds for, besides `IUnknown`'s.  Since there's only one vtable, these must all be
+    /// `Interface` or one of its bases.
+    const INTERFACES : &'static [GUID];
+}
+
+/// A heap allocated, reference counted `C`, laid out so a pointer to it is a valid `C::Interface` pointer.
+#[repr(C)]
+pub struct Object<C: Class> {
+    vtbl:   &'static C::Vtbl,
+    refs:   AtomicU32,
+    value:  C,
+}
+
+impl<C: Class> Object<C> {
+    pub const IUNKNOWN : IUnknownVtbl = IUnknownVtbl {
+        QueryInterface: Self::query_interface,
+        AddRef:         Self::add_ref,
+        Release:        Self::release,
+    };
+
+    /// Move `value` to the heap with a refcount of 1.  It's dropped when the last reference is released.
+    #[allow(clippy::new_ret_no_self)] // Returns the new object, just already wrapped
+    pub fn new<W: Wrapper<Target = C::Interface>> (value: C) -> W {
+        let object = Box::into_raw(Box::new(Self { vtbl: C::VTBL, refs: AtomicU32::new(1), value }));
+        unsafe { W::own(object as *mut C::Interface) }.unwrap()
+    }
+
+    /// Get the `C` behind an interface pointer, e.g. `This` inside one of `C::VTBL`'s methods.
+    ///
+    /// `unsafe`:  `this` must point to a live `Object<C>`, which must outlive `'a`.
+    pub unsafe fn from_interface<'a, I> (this: *mut I) -> &'a C {
+        &(*(this as *const Self)).value
+    }
+
+    unsafe extern "system" fn query_interface (this: *mut IUnknown, riid: REFIID, object: *mut *mut c_void) -> HRESULT {
+        if riid.is_null() || object.is_null() { return E_POINTER; }
+        let riid = &*riid;
+        if IsEqualGUID(riid, &IUnknown::uuidof()) || C::INTERFACES.iter().any(|iid| IsEqualGUID(iid, riid)) {
+            Self::add_ref(this);
+            *object = this as *mut c_void;
+            S_OK
+        } else {
+            *object = null_mut();
+            E_NOINTERFACE
+        }
+    }
+
+    unsafe extern "system" fn add_ref (this: *mut IUnknown) -> ULONG {
+        // Relaxed is enough:  a new reference can only be made from an existing one.
+        (*(this as *const Self)).refs.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    unsafe extern "system" fn release (this: *mut IUnknown) -> ULONG {
+        let refs = (*(this as *const Self)).refs.fetch_sub(1, Ordering::Release) - 1;
+        if refs == 0 {
+            // Make sure every other thread's use of the object happens before we drop it.
+            atomic::fence(Ordering::Acquire);
+            drop(Box::from_raw(this as *mut Self));
+        }
+        refs
+    }
+}
+
+/// Any `T`, exposed to COM as a plain `IUnknown`.  Handy for `SetPrivateDataInterface`.
+#[allow(dead_code)]
+pub struct Payload<T: Send + Sync + 'static>(pub T);
+
+unsafe impl<T: Send + Sync + 'static> Class for Payload<T> {
+    type Interface  = IUnknown;
+    type Vtbl       = IUnknownVtbl;
+    const VTBL : &'static IUnknownVtbl = &Object::<Self>::IUNKNOWN;
+    const INTERFACES : &'static [GUID] = &[];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    com_interface! {
+        #[uuid(0x2d0c5e71, 0x8f43, 0x4a0b, 0xb6, 0x2e, 0x51, 0x7a, 0x90, 0x3c, 0xd4, 0x01)]
+        interface IMockTag (IMockTagVtbl) : IUnknown (IUnknownVtbl) {
+            fn Tag () -> u32;
+        }
+    }
+
+    com_wrapper! {
+        pub struct MockTag(*mut IMockTag);
+    }
+
+    struct Tag(u32);
+
+    unsafe impl Class for Tag {
+        type Interface  = IMockTag;
+        type Vtbl       = IMockTagVtbl;
+        const VTBL : &'static IMockTagVtbl = &IMockTagVtbl { parent: Object::<Self>::IUNKNOWN, Tag: Self::tag };
+        const INTERFACES : &'static [GUID] = &[IID_IMOCKTAG];
+    }
+
+    const IID_IMOCKTAG : GUID = GUID { Data1: 0x2d0c5e71, Data2: 0x8f43, Data3: 0x4a0b, Data4: [0xb6, 0x2e, 0x51, 0x7a, 0x90, 0x3c, 0xd4, 0x01] };
+
+    impl Tag {
+        unsafe extern "system" fn tag (this: *mut IMockTag) -> u32 { Object::<Self>::from_interface(this).0 }
+    }
+
+    /// Sets its flag when dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop (&mut self) { self.0.store(true, Ordering::SeqCst); }
+    }
+
+    fn query (unknown: &IUnknown, iid: &GUID) -> (HRESULT, *mut c_void) {
+        let mut object = std::ptr::dangling_mut::<c_void>(); // Not null, so failures must clear it
+        let hresult = unsafe { unknown.QueryInterface(iid, &mut object) };
+        (hresult, object)
+    }
+
+    #[test]
+    fn add_ref_release () {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let unknown : Unknown = Object::new(Payload(DropFlag(dropped.clone())));
+        let iunknown = unknown.as_unknown();
+        assert_eq!(unsafe { iunknown.AddRef() }, 2);
+        assert_eq!(unsafe { iunknown.AddRef() }, 3);
+        assert_eq!(unsafe { iunknown.Release() }, 2);
+        assert_eq!(unsafe { iunknown.Release() }, 1);
+        assert!(!dropped.load(Ordering::SeqCst));
+        drop(unknown);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn query_interface () {
+        let unknown : Unknown = Object::new(Payload(()));
+        let iunknown = unknown.as_unknown();
+
+        let (hresult, object) = query(iunknown, &IUnknown::uuidof());
+        assert_eq!(hresult, S_OK);
+        assert_eq!(object as usize, unknown.as_ptr() as usize);
+        assert_eq!(unsafe { iunknown.Release() }, 1);
+
+        let (hresult, object) = query(iunknown, &IID_IMOCKTAG);
+        assert_eq!(hresult, E_NOINTERFACE);
+        assert!(object.is_null());
+        assert_eq!(unsafe { iunknown.QueryInterface(&IID_IMOCKTAG, null_mut()) }, E_POINTER);
+        assert_eq!(unsafe { iunknown.QueryInterface(std::ptr::null(), &mut null_mut()) }, E_POINTER);
+    }
+
+    #[test]
+    fn custom_interface () {
+        let tag : MockTag = Object::new(Tag(42));
+        assert_eq!(unsafe { tag.as_ref().Tag() }, 42);
+
+        let (hresult, object) = query(tag.as_unknown(), &IID_IMOCKTAG);
+        assert_eq!(hresult, S_OK);
+        let tag2 = unsafe { MockTag::own(object as *mut IMockTag) }.unwrap();
+        assert_eq!(unsafe { tag2.as_ref().Tag() }, 42);
+
+        let unknown = tag.cast::<Unknown>().unwrap();
+        assert_eq!(unknown.as_ptr() as usize, tag.as_ptr() as usize);
+        drop(tag);
+        drop(tag2);
+        assert_eq!(unsafe { unknown.as_unknown().AddRef() }, 2);
+        assert_eq!(unsafe { unknown.as_unknown().Release() }, 1);
+    }
+}
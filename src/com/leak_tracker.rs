@@ -0,0 +1,122 @@
+//! Bookkeeping of live `com_wrapper!` references, to find leaks before D3D's live object report does.
+//!
+//! Opt-in:  build with `--features leak-tracking`.  Otherwise `track` / `untrack` compile down to nothing, and the
+//! reporting API doesn't exist.
+//!
+//! Every `own` and `clone` records the type and a backtrace, every `drop` forgets one record for the same object.
+//! Which record a `drop` forgets can't be known - the wrappers are a bare pointer with no room for an ID - so the most
+//! recent is assumed.  Counts are always exact, creation sites are a best guess when clones are dropped out of order.
+
+#[cfg(not(feature = "leak-tracking"))] #[inline(always)] pub fn track   (_type_name: &'static str, _com: usize) {}
+#[cfg(not(feature = "leak-tracking"))] #[inline(always)] pub fn untrack (_type_name: &'static str, _com: usize) {}
+
+#[cfg(feature = "leak-tracking")] pub use tracking::*;
+
+#[cfg(feature = "leak-tracking")]
+#[allow(dead_code)] // Reporting is up to the caller
+mod tracking {
+    use backtrace::Backtrace;
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    struct Record {
+        sequence:   u64,
+        backtrace:  Backtrace,
+    }
+
+    #[derive(Default)]
+    struct Tracker {
+        next_sequence:  u64,
+        live:           HashMap<(&'static str, usize), Vec<Record>>,
+    }
+
+    fn with_tracker<R> (f: impl FnOnce(&mut Tracker) -> R) -> R {
+        static TRACKER : OnceLock<Mutex<Tracker>> = OnceLock::new();
+        let mut tracker = TRACKER.get_or_init(Default::default).lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut tracker)
+    }
+
+    /// Record a new reference to `com`.  Called by `com_wrapper!`'s `own` and `clone`.
+    pub fn track (type_name: &'static str, com: usize) {
+        let backtrace = Backtrace::new_unresolved();
+        with_tracker(|tracker| {
+            let sequence = tracker.next_sequence;
+            tracker.next_sequence += 1;
+            tracker.live.entry((type_name, com)).or_default().push(Record { sequence, backtrace });
+        });
+    }
+
+    /// Forget a reference to `com`.  Called by `com_wrapper!`'s `drop`.
+    ///
+    /// Dropping a reference that was never tracked is logged rather than treated as fatal:  `drop` may be running
+    /// during a panic, and a bookkeeping mistake shouldn't turn that into an abort.
+    pub fn untrack (type_name: &'static str, com: usize) {
+        let tracked = with_tracker(|tracker| {
+            let records = match tracker.live.get_mut(&(type_name, com)) {
+                Some(records) => records,
+                None => return false,
+            };
+            records.pop();
+            if records.is_empty() { tracker.live.remove(&(type_name, com)); }
+            true
+        });
+        if !tracked {
+            crate::debug::output(&format!("leak_tracker::untrack: dropped an untracked {} (0x{:x})\n", type_name, com));
+        }
+    }
+
+    /// A point in time to look for leaks since.
+    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    pub struct Checkpoint(u64);
+
+    /// References created after now will be considered by `live_since(checkpoint())`.
+    pub fn checkpoint () -> Checkpoint {
+        with_tracker(|tracker| Checkpoint(tracker.next_sequence))
+    }
+
+    /// Live references of one type, created from one place.
+    #[derive(Clone, Debug)]
+    pub struct Live {
+        pub type_name:  &'static str,
+        pub site:       String,
+        pub count:      usize,
+    }
+
+    /// Every reference created since `checkpoint` that's still alive, grouped by type and creation site.
+    pub fn live_since (checkpoint: Checkpoint) -> Vec<Live> {
+        // Resolving symbols is slow, so do it outside the lock.
+        let mut backtraces : Vec<(&'static str, Backtrace)> = with_tracker(|tracker| {
+            tracker.live.iter()
+                .flat_map(|((type_name, _), records)| records.iter().map(move |r| (type_name, r)))
+                .filter(|(_, record)| record.sequence >= checkpoint.0)
+                .map(|(type_name, record)| (*type_name, record.backtrace.clone()))
+                .collect()
+        });
+
+        let mut grouped = HashMap::<(&'static str, String), usize>::new();
+        for (type_name, backtrace) in backtraces.iter_mut() {
+            backtrace.resolve();
+            *grouped.entry((*type_name, format!("{:?}", backtrace))).or_default() += 1;
+        }
+
+        let mut live : Vec<Live> = grouped.into_iter().map(|((type_name, site), count)| Live { type_name, site, count }).collect();
+        live.sort_by(|a, b| (a.type_name, &a.site).cmp(&(b.type_name, &b.site)));
+        live
+    }
+
+    /// Every reference that's still alive, grouped by type and creation site.
+    pub fn live () -> Vec<Live> { live_since(Checkpoint(0)) }
+
+    /// Report every reference created since `checkpoint` that's still alive, and fail if there are any.
+    pub fn expect_none_live_since (checkpoint: Checkpoint) {
+        let live = live_since(checkpoint);
+        if live.is_empty() { return; }
+
+        let mut report = String::from("Leaked COM references:\n");
+        for l in live.iter() {
+            report.push_str(&format!("{} x{} created at:\n{}\n", l.type_name, l.count, l.site));
+        }
+        crate::debug::output(&report);
+        expect_eq!(live.len(), 0);
+    }
+}
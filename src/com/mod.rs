@@ -1,3 +1,23 @@
+use crate::com::abi::{GUID, HRESULT, IUnknown, SUCCEEDED};
+
+/// A `com_wrapper!` owning one reference to a COM object.
+///
+/// Neither `Send` nor `Sync` unless `com_wrapper!` opts the type in with `#[threading(...)]`:
+///
+/// ```
+/// fn wrapper<T: rust_win32_d3d11::com::Wrapper> () {}
+/// wrapper::<rust_win32_d3d11::com::Unknown>();
+/// ```
+///
+/// ```compile_fail
+/// fn send<T: Send> () {}
+/// send::<rust_win32_d3d11::com::Unknown>();
+/// ```
+///
+/// ```compile_fail
+/// fn sync<T: Sync> () {}
+/// sync::<rust_win32_d3d11::com::Unknown>();
+/// ```
 pub trait Wrapper : Sized {
     type Target;
 
@@ -5,12 +25,50 @@ pub trait Wrapper : Sized {
     unsafe fn own (com: *mut Self::Target) -> Option<Self>;
 
     /// Get the UUID of the native COM interface type.
-    fn uuidof () -> winapi::shared::guiddef::GUID;
+    fn uuidof () -> GUID;
 
     /// Get a raw pointer to the native COM object type.
     fn as_ptr (&self) -> *mut Self::Target;
+
+    /// Every COM interface starts with `IUnknown`'s vtable.
+    fn as_unknown (&self) -> &IUnknown {
+        unsafe { &*(self.as_ptr() as *mut IUnknown) }
+    }
+
+    /// MSDN: [IUnknown::QueryInterface](https://docs.microsoft.com/en-us/windows/desktop/api/unknwn/nf-unknwn-iunknown-queryinterface(refiid_void))
+    /// 
+    /// Get another interface to the same object, or `Err(E_NOINTERFACE)` if it doesn't implement `U`.  Prefer `upcast`
+    /// when `Self: Inherits<U>`, which can't fail and doesn't touch the refcount.
+    fn cast<U: Wrapper> (&self) -> Result<U, HRESULT> {
+        let mut com = std::ptr::null_mut();
+        let result = unsafe { self.as_unknown().QueryInterface(&U::uuidof(), &mut com) };
+        if SUCCEEDED(result) {
+            Ok(unsafe { U::own(com as *mut _) }.unwrap())
+        } else {
+            Err(result)
+        }
+    }
+
+    /// Borrow `self` as one of the interfaces it inherits from.
+    fn upcast<B: Wrapper> (&self) -> &B where Self: Inherits<B> {
+        unsafe { &*(self as *const Self as *const B) }
+    }
+
+    /// Convert `self` into one of the interfaces it inherits from.
+    fn into_upcast<B: Wrapper + Clone> (self) -> B where Self: Inherits<B> {
+        // Rather than transferring the reference, AddRef + Release so `leak_tracker` sees a matching own/drop pair.
+        self.upcast::<B>().clone()
+    }
 }
 
+/// `Self`'s interface derives from `Base`'s, so a `Self` can be reinterpreted as a `Base` without a `QueryInterface`.
+/// 
+/// `unsafe`:  Both must be `#[repr(transparent)]` wrappers around interface pointers, and `Self::Target`'s vtable must
+/// begin with `Base::Target`'s.  `com_wrapper!` takes care of this given an accurate inheritance list.
+pub unsafe trait Inherits<Base: Wrapper> : Wrapper {}
+
+unsafe impl<T: Wrapper> Inherits<T> for T {}
+
 pub trait AsNativeSlice<Target> {
     fn as_native_slice (&self) -> &[Target];
 }
@@ -26,35 +84,64 @@ impl<T> crate::com::AsNativeSlice<*mut T> for [&T] {
 }
 
 // XXX: This has a lot of overlap with https://github.com/retep998/wio-rs/blob/master/src/com.rs
+//
+// Interfaces list their bases, nearest first, after a colon:
+//
+//     pub struct Texture2D(*mut ID3D11Texture2D) : Resource : DeviceChild;
+//
+// Wrappers are neither `Send` nor `Sync` unless the interface is documented as safe to use that way, in which case
+// it's opted in with `#[threading(...)]`:
+//
+//     #[threading(Send, Sync)] pub struct Device(*mut ID3D11Device);
+//     #[threading(Send)]       pub struct DeviceContext(*mut ID3D11DeviceContext) : DeviceChild;
+//
+// `Send` alone means the object may be handed to another thread, but only ever used by one at a time.  Those wrappers
+// don't implement `Clone`:  a clone is another owner of the same object, which could be sent to a second thread.
 macro_rules! com_wrapper {
-    ($(pub struct $wrapper:ident(*mut $target:ident);)+) => {$(
+    (@clone $wrapper:ident Send) => {};
+    (@clone $wrapper:ident $($marker:ident),*) => {
+        impl Clone for $wrapper {
+            fn clone (&self) -> Self {
+                unsafe { self.as_ref().AddRef() };
+                crate::com::leak_tracker::track(stringify!($wrapper), self.0 as usize);
+                Self(self.0)
+            }
+        }
+    };
+    ($($(#[threading($($marker:ident),+)])? pub struct $wrapper:ident(*mut $target:ident) $(: $base:ident)*;)+) => {$(
+        #[repr(transparent)]
         pub struct $wrapper(*mut $target);
 
+        $(unsafe impl crate::com::Inherits<$base> for $wrapper {})*
+        $($(unsafe impl $marker for $wrapper {})+)?
+
         impl AsRef<$target> for $wrapper {
             fn as_ref (&self) -> &$target { unsafe { &*self.0 } }
         }
 
-        impl Clone for $wrapper {
-            fn clone (&self) -> Self { unsafe { self.as_ref().AddRef() }; Self(self.0) }
-        }
+        com_wrapper!(@clone $wrapper $($($marker),+)?);
 
         impl Drop for $wrapper {
-            fn drop (&mut self) { unsafe { self.as_ref().Release() }; }
+            fn drop (&mut self) {
+                crate::com::leak_tracker::untrack(stringify!($wrapper), self.0 as usize);
+                unsafe { self.as_ref().Release() };
+            }
         }
 
         impl crate::com::Wrapper for $wrapper {
             type Target = $target;
 
             unsafe fn own (com_object: *mut $target) -> Option<$wrapper> {
-                if com_object == std::ptr::null_mut() {
+                if com_object.is_null() {
                     None
                 } else {
+                    crate::com::leak_tracker::track(stringify!($wrapper), com_object as usize);
                     Some(Self(com_object))
                 }
             }
 
-            fn uuidof () -> winapi::shared::guiddef::GUID {
-                $target::uuidof()
+            fn uuidof () -> crate::com::abi::GUID {
+                <$target as crate::com::abi::Interface>::uuidof()
             }
 
             fn as_ptr (&self) -> *mut Self::Target {
@@ -71,9 +158,161 @@ macro_rules! com_wrapper {
     )+};
 }
 
+#[macro_use] pub mod abi;
 pub mod d3d;
-pub mod d3d11;
-pub mod dxgi;
+#[cfg(windows)] pub mod d3d11;
+#[cfg(windows)] pub mod dxgi;
+
+#[cfg(windows)] mod device_and_context;
+mod feature_level;
+mod implement;
+pub mod leak_tracker;
+#[cfg(windows)] mod device_and_swap_chain;
+#[cfg(windows)] pub use device_and_context::*;
+pub use feature_level::*;
+pub use implement::*;
+#[cfg(windows)] pub use device_and_swap_chain::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com::abi::*;
+    use std::mem;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    com_interface! {
+        #[uuid(0x6b3f1a20, 0x4c1e, 0x4d55, 0x9a, 0x61, 0x0e, 0x52, 0x37, 0xc4, 0x11, 0x01)]
+        interface IMockBase (IMockBaseVtbl) : IUnknown (IUnknownVtbl) {
+            fn Base () -> u32;
+        }
+    }
+
+    com_interface! {
+        #[uuid(0x6b3f1a20, 0x4c1e, 0x4d55, 0x9a, 0x61, 0x0e, 0x52, 0x37, 0xc4, 0x11, 0x02)]
+        interface IMockDerived (IMockDerivedVtbl) : IMockBase (IMockBaseVtbl) {
+            fn Derived () -> u32;
+        }
+    }
 
-mod device_and_swap_chain;
-pub use device_and_swap_chain::*;
+    com_interface! {
+        #[uuid(0x6b3f1a20, 0x4c1e, 0x4d55, 0x9a, 0x61, 0x0e, 0x52, 0x37, 0xc4, 0x11, 0x03)]
+        interface IMockOther (IMockOtherVtbl) : IUnknown (IUnknownVtbl) {}
+    }
+
+    com_wrapper! {
+        pub struct MockBase(*mut IMockBase);
+        pub struct MockDerived(*mut IMockDerived) : MockBase;
+        pub struct MockOther(*mut IMockOther);
+    }
+
+    /// Implements `IMockDerived` (and so `IMockBase` and `IUnknown`) but not `IMockOther`.  Never freed, so the
+    /// refcount can be inspected after the last `Release`.
+    #[repr(C)]
+    struct Mock {
+        vtbl:   &'static IMockDerivedVtbl,
+        refs:   AtomicU32,
+    }
+
+    static VTBL : IMockDerivedVtbl = IMockDerivedVtbl {
+        parent: IMockBaseVtbl {
+            parent: IUnknownVtbl { QueryInterface: Mock::query_interface, AddRef: Mock::add_ref, Release: Mock::release },
+            Base:   Mock::base,
+        },
+        Derived: Mock::derived,
+    };
+
+    impl Mock {
+        fn new () -> Box<Mock> { Box::new(Mock { vtbl: &VTBL, refs: AtomicU32::new(1) }) }
+        fn refs (&self) -> u32 { self.refs.load(Ordering::SeqCst) }
+        fn as_derived (&self) -> *mut IMockDerived { self as *const Mock as *mut IMockDerived }
+
+        unsafe extern "system" fn query_interface (this: *mut IUnknown, riid: REFIID, object: *mut *mut c_void) -> HRESULT {
+            let riid = &*riid;
+            if [IUnknown::uuidof(), IMockBase::uuidof(), IMockDerived::uuidof()].iter().any(|iid| IsEqualGUID(iid, riid)) {
+                Mock::add_ref(this);
+                *object = this as *mut c_void;
+                S_OK
+            } else {
+                *object = std::ptr::null_mut();
+                E_NOINTERFACE
+            }
+        }
+
+        unsafe extern "system" fn add_ref (this: *mut IUnknown) -> ULONG { (*(this as *const Mock)).refs.fetch_add(1, Ordering::SeqCst) + 1 }
+        unsafe extern "system" fn release (this: *mut IUnknown) -> ULONG { (*(this as *const Mock)).refs.fetch_sub(1, Ordering::SeqCst) - 1 }
+        unsafe extern "system" fn base (_this: *mut IMockBase) -> u32 { 1 }
+        unsafe extern "system" fn derived (_this: *mut IMockDerived) -> u32 { 2 }
+    }
+
+    #[test]
+    fn own_and_drop () {
+        let mock = Mock::new();
+        assert!(unsafe { MockDerived::own(std::ptr::null_mut()) }.is_none());
+        let derived = unsafe { MockDerived::own(mock.as_derived()) }.unwrap();
+        assert_eq!(mock.refs(), 1);
+        let clone = derived.clone();
+        assert_eq!(mock.refs(), 2);
+        drop(clone);
+        drop(derived);
+        assert_eq!(mock.refs(), 0);
+    }
+
+    #[test]
+    fn cast_succeeds () {
+        let mock = Mock::new();
+        let derived = unsafe { MockDerived::own(mock.as_derived()) }.unwrap();
+        let base = derived.cast::<MockBase>().unwrap();
+        assert_eq!(mock.refs(), 2);
+        assert_eq!(base.as_ptr() as usize, derived.as_ptr() as usize);
+        assert_eq!(unsafe { base.as_ref().Base() }, 1);
+        drop(base);
+        assert_eq!(mock.refs(), 1);
+        drop(derived);
+        assert_eq!(mock.refs(), 0);
+    }
+
+    #[test]
+    fn cast_fails () {
+        let mock = Mock::new();
+        let derived = unsafe { MockDerived::own(mock.as_derived()) }.unwrap();
+        assert_eq!(derived.cast::<MockOther>().err(), Some(E_NOINTERFACE));
+        assert_eq!(mock.refs(), 1);
+        drop(derived);
+        assert_eq!(mock.refs(), 0);
+    }
+
+    #[test]
+    fn upcast () {
+        let mock = Mock::new();
+        let derived = unsafe { MockDerived::own(mock.as_derived()) }.unwrap();
+        let base : &MockBase = derived.upcast();
+        assert_eq!(mock.refs(), 1);
+        assert_eq!(base.as_ptr() as usize, derived.as_ptr() as usize);
+        assert_eq!(unsafe { base.as_ref().Base() }, 1);
+        assert_eq!(unsafe { derived.as_ref().Derived() }, 2);
+
+        let base : MockBase = derived.into_upcast();
+        assert_eq!(mock.refs(), 1);
+        drop(base);
+        assert_eq!(mock.refs(), 0);
+    }
+
+    #[test]
+    fn transparent_layout () {
+        assert_eq!(mem::size_of::<MockDerived>(),  mem::size_of::<*mut IMockDerived>());
+        assert_eq!(mem::align_of::<MockDerived>(), mem::align_of::<*mut IMockDerived>());
+
+        let mock = Mock::new();
+        let derived = unsafe { MockDerived::own(mock.as_derived()) }.unwrap();
+        let wrappers = [derived.clone(), derived.clone()];
+        let natives : &[*mut IMockDerived] = unsafe { std::slice::from_raw_parts(wrappers.as_ptr() as *const _, wrappers.len()) };
+        assert_eq!(natives, &[mock.as_derived(), mock.as_derived()]);
+
+        let refs = [Some(derived.as_ref()), None];
+        let natives : &[*mut IMockDerived] = refs.as_native_slice();
+        assert_eq!(natives, &[mock.as_derived(), std::ptr::null_mut()]);
+        drop(wrappers);
+        drop(derived);
+        assert_eq!(mock.refs(), 0);
+    }
+}
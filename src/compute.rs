@@ -0,0 +1,335 @@
+//! Compute shader limits, and the rules structured and raw buffer views must follow.
+//!
+//! Everything here is plain arithmetic on sizes and counts, checked before handing descriptions to D3D (which would
+//! otherwise fail with a bare `E_INVALIDARG`, or a debug layer message if you're lucky.)  `com::d3d11` turns the
+//! checked descriptions into `D3D11_*_DESC`s.
+
+use crate::com::FeatureLevel;
+use crate::dxbc::{self, Container, ProgramType, ShaderVersion};
+use std::fmt;
+
+/// `D3D11_CS_DISPATCH_MAX_THREAD_GROUPS_PER_DIMENSION`
+pub const MAX_THREAD_GROUPS_PER_DIMENSION : u32 = 65535;
+
+/// `D3D11_REQ_MULTI_ELEMENT_STRUCTURE_SIZE_IN_BYTES`
+pub const MAX_STRUCTURE_STRIDE : u32 = 2048;
+
+/// Size of the arguments `dispatch_indirect` reads:  three `UINT` thread group counts.
+pub const DISPATCH_INDIRECT_ARGS_SIZE : u32 = 12;
+
+/// How large `[numthreads(x, y, z)]` and `dispatch(x, y, z)` may be.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Limits {
+    pub max_thread_group:           [u32; 3],
+    pub max_threads_per_group:      u32,
+    pub max_thread_groups:          [u32; 3],
+}
+
+impl Limits {
+    /// `cs_4_0` / `cs_4_1`, on feature level 10.x hardware that supports them.
+    pub const CS_4_X : Limits = Limits {
+        max_thread_group:       [768, 768, 1],
+        max_threads_per_group:  768,
+        max_thread_groups:      [MAX_THREAD_GROUPS_PER_DIMENSION, MAX_THREAD_GROUPS_PER_DIMENSION, 1],
+    };
+
+    /// `cs_5_0`
+    pub const CS_5_0 : Limits = Limits {
+        max_thread_group:       [1024, 1024, 64],
+        max_threads_per_group:  1024,
+        max_thread_groups:      [MAX_THREAD_GROUPS_PER_DIMENSION; 3],
+    };
+
+    /// The limits for a compute shader of `version`, or `None` if it isn't one.
+    pub fn for_version (version: ShaderVersion) -> Option<Limits> {
+        match (version.program, version.major) {
+            (ProgramType::Compute, 4) => Some(Limits::CS_4_X),
+            (ProgramType::Compute, 5) => Some(Limits::CS_5_0),
+            _                         => None,
+        }
+    }
+
+    /// The limits of the newest compute shader model a device of `feature_level` can run, or `None` below 10.0.
+    /// Feature level 10.x hardware only runs `cs_4_x`, if it runs compute shaders at all.
+    pub fn for_feature_level (feature_level: FeatureLevel) -> Option<Limits> {
+        if feature_level >= FeatureLevel::_11_0 {
+            Some(Limits::CS_5_0)
+        } else if feature_level >= FeatureLevel::_10_0 {
+            Some(Limits::CS_4_X)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// `[numthreads]` has a zero component.
+    Empty,
+    /// Component `axis` of `[numthreads]` is `size`, more than `max`.
+    ThreadGroupTooLarge { axis: usize, size: u32, max: u32 },
+    /// `[numthreads]` multiplies out to `threads`, more than `max`.
+    TooManyThreads { threads: u32, max: u32 },
+    /// Component `axis` of a dispatch is `count` thread groups, more than `max`.
+    TooManyThreadGroups { axis: usize, count: u32, max: u32 },
+    /// A structured buffer's stride isn't a multiple of 4 in `4 ..= MAX_STRUCTURE_STRIDE`.
+    BadStride(u32),
+    /// A buffer's size isn't a whole number of elements (`stride` bytes each.)
+    BadByteWidth { byte_width: u32, stride: u32 },
+    /// A view covers elements `first .. first + count`, but the buffer only has `elements`.
+    OutOfBounds { first: u32, count: u32, elements: u32 },
+    /// An offset into a buffer isn't 4 byte aligned, or leaves too little room after it.
+    BadOffset { offset: u32, byte_width: u32 },
+    /// Append / consume and hidden counters are only available on structured buffer UAVs.
+    CounterOnRawBuffer,
+    /// Not a compute shader, or not one `dxbc` can read.
+    NotComputeShader,
+    Dxbc(dxbc::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Empty                                        => write!(f, "zero sized thread group"),
+            Error::ThreadGroupTooLarge { axis, size, max }      => write!(f, "numthreads[{}] is {}, limit is {}", axis, size, max),
+            Error::TooManyThreads { threads, max }              => write!(f, "{} threads per group, limit is {}", threads, max),
+            Error::TooManyThreadGroups { axis, count, max }     => write!(f, "dispatch[{}] is {} groups, limit is {}", axis, count, max),
+            Error::BadStride(stride)                            => write!(f, "structure stride {} isn't a multiple of 4 in 4..={}", stride, MAX_STRUCTURE_STRIDE),
+            Error::BadByteWidth { byte_width, stride }          => write!(f, "{} bytes isn't a whole number of {} byte elements", byte_width, stride),
+            Error::OutOfBounds { first, count, elements }       => write!(f, "view of elements {}..{} exceeds {} elements", first, first as u64 + count as u64, elements),
+            Error::BadOffset { offset, byte_width }             => write!(f, "offset {} is misaligned or out of bounds of a {} byte buffer", offset, byte_width),
+            Error::CounterOnRawBuffer                           => write!(f, "append/counter UAVs require a structured buffer"),
+            Error::NotComputeShader                             => write!(f, "not a compute shader"),
+            Error::Dxbc(ref e)                                  => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl From<dxbc::Error> for Error {
+    fn from (e: dxbc::Error) -> Self { Error::Dxbc(e) }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Check `[numthreads(x, y, z)]` against `limits`.
+pub fn validate_thread_group (size: [u32; 3], limits: &Limits) -> Result<()> {
+    if size.contains(&0) { return Err(Error::Empty); }
+    for (axis, (&size, &max)) in size.iter().zip(limits.max_thread_group.iter()).enumerate() {
+        if size > max { return Err(Error::ThreadGroupTooLarge { axis, size, max }); }
+    }
+    let threads = size[0] * size[1] * size[2]; // Can't overflow after the per-axis checks
+    if threads > limits.max_threads_per_group { return Err(Error::TooManyThreads { threads, max: limits.max_threads_per_group }); }
+    Ok(())
+}
+
+/// Check the thread group counts of `dispatch(x, y, z)` against `limits`.  Unlike `[numthreads]`, D3D accepts zero
+/// sized dispatches (as no-ops), so this does too.
+pub fn validate_dispatch (thread_groups: [u32; 3], limits: &Limits) -> Result<()> {
+    for (axis, (&count, &max)) in thread_groups.iter().zip(limits.max_thread_groups.iter()).enumerate() {
+        if count > max { return Err(Error::TooManyThreadGroups { axis, count, max }); }
+    }
+    Ok(())
+}
+
+/// Read compute shader `bytecode`'s `[numthreads]` and check it against the limits of its shader model.
+pub fn validate_shader (bytecode: &[u8]) -> Result<[u32; 3]> {
+    let container = Container::parse(bytecode)?;
+    let limits = Limits::for_version(ShaderVersion::from_container(&container)?).ok_or(Error::NotComputeShader)?;
+    let size = dxbc::thread_group_size(&container)?.ok_or(Error::NotComputeShader)?;
+    validate_thread_group(size, &limits)?;
+    Ok(size)
+}
+
+/// How many thread groups of `thread_group` size it takes to cover `threads`, rounding up.  Panics if `thread_group`
+/// has a zero component.
+pub fn thread_groups_for (threads: [u32; 3], thread_group: [u32; 3]) -> [u32; 3] {
+    [threads[0].div_ceil(thread_group[0]), threads[1].div_ceil(thread_group[1]), threads[2].div_ceil(thread_group[2])]
+}
+
+/// How a buffer's bytes are viewed by shaders.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BufferKind {
+    /// `StructuredBuffer<T>` / `RWStructuredBuffer<T>`, where `stride == sizeof(T)`.
+    Structured { stride: u32 },
+    /// `ByteAddressBuffer` / `RWByteAddressBuffer`, addressed as 4 byte `R32_TYPELESS` elements.
+    Raw,
+}
+
+impl BufferKind {
+    /// Size of one view element.
+    pub fn stride (&self) -> u32 {
+        match *self {
+            BufferKind::Structured { stride }   => stride,
+            BufferKind::Raw                     => 4,
+        }
+    }
+
+    /// Check that a buffer of this kind can be `byte_width` bytes, returning how many elements that is.
+    pub fn validate (&self, byte_width: u32) -> Result<u32> {
+        let stride = self.stride();
+        if stride == 0 || !stride.is_multiple_of(4) || stride > MAX_STRUCTURE_STRIDE { return Err(Error::BadStride(stride)); }
+        if byte_width == 0 || !byte_width.is_multiple_of(stride) { return Err(Error::BadByteWidth { byte_width, stride }); }
+        Ok(byte_width / stride)
+    }
+}
+
+/// Hidden counters a structured buffer UAV can carry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UavCounter {
+    None,
+    /// `AppendStructuredBuffer<T>` / `ConsumeStructuredBuffer<T>`
+    Append,
+    /// `RWStructuredBuffer<T>::IncrementCounter` / `DecrementCounter`
+    Counter,
+}
+
+/// The elements of a buffer an SRV or UAV covers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BufferView {
+    pub first_element:  u32,
+    pub num_elements:   u32,
+}
+
+impl BufferView {
+    /// Every element of a `byte_width` byte buffer of `kind`.
+    pub fn whole (kind: BufferKind, byte_width: u32) -> Result<Self> {
+        Ok(Self { first_element: 0, num_elements: kind.validate(byte_width)? })
+    }
+
+    /// Check this view fits a `byte_width` byte buffer of `kind`.
+    pub fn validate (&self, kind: BufferKind, byte_width: u32) -> Result<()> {
+        let elements = kind.validate(byte_width)?;
+        let (first, count) = (self.first_element, self.num_elements);
+        if count == 0 || first as u64 + count as u64 > elements as u64 { return Err(Error::OutOfBounds { first, count, elements }); }
+        Ok(())
+    }
+
+    /// Check this view fits a `byte_width` byte buffer of `kind`, as a UAV with `counter`.
+    pub fn validate_uav (&self, kind: BufferKind, byte_width: u32, counter: UavCounter) -> Result<()> {
+        if kind == BufferKind::Raw && counter != UavCounter::None { return Err(Error::CounterOnRawBuffer); }
+        self.validate(kind, byte_width)
+    }
+}
+
+/// Check that `dispatch_indirect` can read its arguments at `offset` into a `byte_width` byte buffer.
+pub fn validate_indirect_args (offset: u32, byte_width: u32) -> Result<()> {
+    let fits = offset as u64 + DISPATCH_INDIRECT_ARGS_SIZE as u64 <= byte_width as u64;
+    if !offset.is_multiple_of(4) || !fits { return Err(Error::BadOffset { offset, byte_width }); }
+    Ok(())
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    const STRUCTURED_16 : BufferKind = BufferKind::Structured { stride: 16 };
+
+    #[test] fn thread_group_cs_4_x () {
+        let limits = Limits::CS_4_X;
+        assert_eq!(validate_thread_group([768, 1, 1], &limits), Ok(()));
+        assert_eq!(validate_thread_group([1, 768, 1], &limits), Ok(()));
+        assert_eq!(validate_thread_group([32, 24, 1], &limits), Ok(()));
+        assert_eq!(validate_thread_group([769, 1, 1], &limits), Err(Error::ThreadGroupTooLarge { axis: 0, size: 769, max: 768 }));
+        assert_eq!(validate_thread_group([1, 1, 2],   &limits), Err(Error::ThreadGroupTooLarge { axis: 2, size: 2, max: 1 }));
+        assert_eq!(validate_thread_group([32, 25, 1], &limits), Err(Error::TooManyThreads { threads: 800, max: 768 }));
+        assert_eq!(validate_thread_group([0, 1, 1],   &limits), Err(Error::Empty));
+    }
+
+    #[test] fn thread_group_cs_5_0 () {
+        let limits = Limits::CS_5_0;
+        assert_eq!(validate_thread_group([1024, 1, 1], &limits), Ok(()));
+        assert_eq!(validate_thread_group([16, 1, 64],  &limits), Ok(()));
+        assert_eq!(validate_thread_group([1, 1, 65],   &limits), Err(Error::ThreadGroupTooLarge { axis: 2, size: 65, max: 64 }));
+        assert_eq!(validate_thread_group([1024, 1024, 64], &limits), Err(Error::TooManyThreads { threads: 1024 * 1024 * 64, max: 1024 }));
+        assert_eq!(validate_thread_group([1, 1, 0],    &limits), Err(Error::Empty));
+    }
+
+    #[test] fn dispatch () {
+        let max = MAX_THREAD_GROUPS_PER_DIMENSION;
+        assert_eq!(validate_dispatch([0, 0, 0],         &Limits::CS_5_0), Ok(()));
+        assert_eq!(validate_dispatch([max, max, max],   &Limits::CS_5_0), Ok(()));
+        assert_eq!(validate_dispatch([max, max + 1, 1], &Limits::CS_5_0), Err(Error::TooManyThreadGroups { axis: 1, count: max + 1, max }));
+        assert_eq!(validate_dispatch([max, max, 1],     &Limits::CS_4_X), Ok(()));
+        assert_eq!(validate_dispatch([1, 1, 2],         &Limits::CS_4_X), Err(Error::TooManyThreadGroups { axis: 2, count: 2, max: 1 }));
+    }
+
+    #[test] fn feature_level_limits () {
+        assert_eq!(Limits::for_feature_level(FeatureLevel::_9_3),   None);
+        assert_eq!(Limits::for_feature_level(FeatureLevel::_10_0),  Some(Limits::CS_4_X));
+        assert_eq!(Limits::for_feature_level(FeatureLevel::_10_1),  Some(Limits::CS_4_X));
+        assert_eq!(Limits::for_feature_level(FeatureLevel::_11_0),  Some(Limits::CS_5_0));
+        assert_eq!(Limits::for_feature_level(FeatureLevel::_12_1),  Some(Limits::CS_5_0));
+    }
+
+    #[test] fn thread_groups () {
+        assert_eq!(thread_groups_for([1920, 1080, 1], [8, 8, 1]), [240, 135, 1]);
+        assert_eq!(thread_groups_for([1921, 1081, 0], [8, 8, 1]), [241, 136, 0]);
+        assert_eq!(thread_groups_for([u32::MAX, 1, 1], [2, 1, 1]), [u32::MAX / 2 + 1, 1, 1]);
+    }
+
+    #[test] fn buffer_kind () {
+        assert_eq!(BufferKind::Raw.validate(4),             Ok(1));
+        assert_eq!(BufferKind::Raw.validate(6),             Err(Error::BadByteWidth { byte_width: 6, stride: 4 }));
+        assert_eq!(BufferKind::Raw.validate(0),             Err(Error::BadByteWidth { byte_width: 0, stride: 4 }));
+        assert_eq!(STRUCTURED_16.validate(64),              Ok(4));
+        assert_eq!(STRUCTURED_16.validate(72),              Err(Error::BadByteWidth { byte_width: 72, stride: 16 }));
+        for &stride in [4, 12, MAX_STRUCTURE_STRIDE].iter() {
+            assert_eq!(BufferKind::Structured { stride }.validate(stride * 3), Ok(3), "stride {}", stride);
+        }
+        for &stride in [0, 1, 2, 6, 13, MAX_STRUCTURE_STRIDE + 4].iter() {
+            assert_eq!(BufferKind::Structured { stride }.validate(4096), Err(Error::BadStride(stride)), "stride {}", stride);
+        }
+    }
+
+    #[test] fn views () {
+        assert_eq!(BufferView::whole(STRUCTURED_16, 64), Ok(BufferView { first_element: 0, num_elements: 4 }));
+        assert_eq!(BufferView::whole(STRUCTURED_16, 60), Err(Error::BadByteWidth { byte_width: 60, stride: 16 }));
+
+        let view = |first_element, num_elements| BufferView { first_element, num_elements };
+        assert_eq!(view(0, 4).validate(STRUCTURED_16, 64),  Ok(()));
+        assert_eq!(view(3, 1).validate(STRUCTURED_16, 64),  Ok(()));
+        assert_eq!(view(3, 2).validate(STRUCTURED_16, 64),  Err(Error::OutOfBounds { first: 3, count: 2, elements: 4 }));
+        assert_eq!(view(4, 0).validate(STRUCTURED_16, 64),  Err(Error::OutOfBounds { first: 4, count: 0, elements: 4 }));
+        assert_eq!(view(0, 0).validate(STRUCTURED_16, 64),  Err(Error::OutOfBounds { first: 0, count: 0, elements: 4 }));
+        assert_eq!(view(u32::MAX, 2).validate(STRUCTURED_16, 64), Err(Error::OutOfBounds { first: u32::MAX, count: 2, elements: 4 })); // No overflow
+    }
+
+    #[test] fn uav_views () {
+        let view = BufferView { first_element: 1, num_elements: 3 };
+        for &counter in [UavCounter::None, UavCounter::Append, UavCounter::Counter].iter() {
+            assert_eq!(view.validate_uav(STRUCTURED_16, 64, counter), Ok(()));
+            assert_eq!(view.validate_uav(STRUCTURED_16, 48, counter), Err(Error::OutOfBounds { first: 1, count: 3, elements: 3 }));
+        }
+        assert_eq!(view.validate_uav(BufferKind::Raw, 16, UavCounter::None),     Ok(()));
+        assert_eq!(view.validate_uav(BufferKind::Raw, 12, UavCounter::None),     Err(Error::OutOfBounds { first: 1, count: 3, elements: 3 }));
+        assert_eq!(view.validate_uav(BufferKind::Raw, 16, UavCounter::Append),   Err(Error::CounterOnRawBuffer));
+        assert_eq!(view.validate_uav(BufferKind::Raw, 16, UavCounter::Counter),  Err(Error::CounterOnRawBuffer));
+    }
+
+    #[test] fn indirect_args () {
+        assert_eq!(validate_indirect_args(0, 12),   Ok(()));
+        assert_eq!(validate_indirect_args(4, 16),   Ok(()));
+        assert_eq!(validate_indirect_args(8, 16),   Err(Error::BadOffset { offset: 8, byte_width: 16 }));
+        assert_eq!(validate_indirect_args(2, 64),   Err(Error::BadOffset { offset: 2, byte_width: 64 }));
+        assert_eq!(validate_indirect_args(0, 8),    Err(Error::BadOffset { offset: 0, byte_width: 8 }));
+        assert_eq!(validate_indirect_args(u32::MAX - 3, u32::MAX), Err(Error::BadOffset { offset: u32::MAX - 3, byte_width: u32::MAX })); // No overflow
+    }
+
+    /// Hand assembled `dcl_thread_group numthreads; ret` for `version` (`0x0005_00XY` is `cs_X_Y`.)
+    fn compute_shader (version: u32, numthreads: [u32; 3]) -> Vec<u8> {
+        let shex = [version, 7, 0x0400_009B, numthreads[0], numthreads[1], numthreads[2], 0x0100_003E];
+        let shex = shex.iter().flat_map(|dword| dword.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+        dxbc::write_container(1, &[dxbc::Chunk { fourcc: dxbc::FourCC::SHEX, data: &shex[..] }])
+    }
+
+    #[test] fn shader () {
+        assert_eq!(validate_shader(&compute_shader(0x0005_0040, [768, 1, 1])),   Ok([768, 1, 1]));
+        assert_eq!(validate_shader(&compute_shader(0x0005_0040, [8, 8, 2])),     Err(Error::ThreadGroupTooLarge { axis: 2, size: 2, max: 1 }));
+        assert_eq!(validate_shader(&compute_shader(0x0005_0050, [8, 8, 2])),     Ok([8, 8, 2]));
+        assert_eq!(validate_shader(&compute_shader(0x0005_0050, [64, 32, 1])),   Err(Error::TooManyThreads { threads: 2048, max: 1024 }));
+        assert_eq!(validate_shader(&compute_shader(0x0001_0050, [8, 8, 1])),     Err(Error::NotComputeShader)); // vs_5_0
+
+        let vs = include_bytes!("../tests/data/fxc/sdl_vs_9_1.dxbc");
+        assert_eq!(validate_shader(vs), Err(Error::NotComputeShader));
+        assert_eq!(validate_shader(b"not a shader").map_err(|e| matches!(e, Error::Dxbc(_))), Err(true));
+    }
+}
@@ -0,0 +1,321 @@
+//! Rust structs laid out with HLSL's constant buffer packing rules, and matching `cbuffer` declarations.
+//!
+//! HLSL packs constant buffers into 16 byte registers:
+//!
+//! * A variable never straddles a register boundary - a `float3` after a `float2` starts a new register.
+//! * Arrays, matrices, and structs always start a new register, and every array element does too.
+//! * Whatever follows an array can pack into its last element's register, but whatever follows a struct can't.
+//!
+//! Rust's `#[repr(C)]` knows none of this, so structs declared with `constant_buffer!` (in macros.rs) aren't uploaded
+//! as-is.  Instead, `to_bytes` writes each field at the offset `Layout::pack` computes - once per type, the first time
+//! it's needed.  Matrices are declared `row_major` so their Rust rows are HLSL rows.
+//!
+//! `constant_buffer!` stands in for a `#[derive(ConstantBuffer)]`, which would need a proc-macro crate of its own.  So
+//! the layout is computed at runtime rather than at compile time, and fields must be listed with types the macro can
+//! see - no `#[derive]` on existing structs.
+//!
+//! ```ignore
+//! constant_buffer! {
+//!     pub struct Transform {
+//!         pub world:  Float4x4,
+//!         pub tint:   Float3,
+//!         pub time:   f32,
+//!     }
+//! }
+//!
+//! let hlsl = Transform::hlsl_cbuffer(Some(0)); // "cbuffer Transform : register(b0) { ... };"
+//! let data = transform.to_bytes();             // Transform::layout().size bytes
+//! ```
+
+use crate::com::abi::{D3D_INCLUDE_TYPE, E_FAIL, HRESULT};
+use crate::com::d3d::Include;
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+/// Size of a constant buffer register.
+pub const REGISTER : usize = 16;
+
+fn round_up (n: usize) -> usize { n.div_ceil(REGISTER) * REGISTER }
+
+/// A type that can appear in a constant buffer.
+pub trait HlslType {
+    /// The HLSL type, e.g. `"float3"` or `"row_major float4x4"`.  For arrays, the element type.
+    fn hlsl_type () -> String;
+    /// What follows the variable name in a declaration, e.g. `"[4]"` for arrays.
+    fn hlsl_suffix () -> String { String::new() }
+    /// Bytes occupied, not counting padding after the last component (e.g. 12 for `float3`.)
+    fn size () -> usize;
+    /// Arrays, matrices, and structs must start on a register boundary.
+    fn starts_register () -> bool;
+    /// Whatever follows must start on a new register.  Only true for structs.
+    fn ends_register () -> bool { false }
+    /// Write `self` to the start of `out`, which is at least `size()` bytes.
+    fn write (&self, out: &mut [u8]);
+}
+
+fn write_u32s (values: &[u32], out: &mut [u8]) {
+    for (i, value) in values.iter().enumerate() {
+        out[4*i..4*i+4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+macro_rules! hlsl_scalar {
+    ($($rust:ty => $hlsl:expr, $to_bits:expr;)+) => {$(
+        impl HlslType for $rust {
+            fn hlsl_type () -> String { String::from($hlsl) }
+            fn size () -> usize { 4 }
+            fn starts_register () -> bool { false }
+            fn write (&self, out: &mut [u8]) { write_u32s(&[$to_bits(*self)], out) }
+        }
+    )+};
+}
+
+hlsl_scalar! {
+    f32 => "float", |v: f32| v.to_bits();
+    i32 => "int",   |v: i32| v as u32;
+    u32 => "uint",  |v: u32| v;
+}
+
+macro_rules! hlsl_vector {
+    ($($name:ident([$scalar:ty; $n:expr]) => $hlsl:expr;)+) => {$(
+        #[repr(C)]
+        #[derive(Clone, Copy, Default, PartialEq, Debug)]
+        pub struct $name(pub [$scalar; $n]);
+
+        impl HlslType for $name {
+            fn hlsl_type () -> String { String::from($hlsl) }
+            fn size () -> usize { 4 * $n }
+            fn starts_register () -> bool { false }
+            fn write (&self, out: &mut [u8]) {
+                for (i, v) in self.0.iter().enumerate() { v.write(&mut out[4*i..]); }
+            }
+        }
+    )+};
+}
+
+hlsl_vector! {
+    Float2([f32; 2]) => "float2";
+    Float3([f32; 3]) => "float3";
+    Float4([f32; 4]) => "float4";
+    Int2  ([i32; 2]) => "int2";
+    Int3  ([i32; 3]) => "int3";
+    Int4  ([i32; 4]) => "int4";
+    Uint2 ([u32; 2]) => "uint2";
+    Uint3 ([u32; 3]) => "uint3";
+    Uint4 ([u32; 4]) => "uint4";
+}
+
+macro_rules! hlsl_matrix {
+    ($($name:ident[$rows:expr][$columns:expr] => $hlsl:expr;)+) => {$(
+        /// Stored and declared `row_major`:  `self.0[row][column]`.
+        #[repr(C)]
+        #[derive(Clone, Copy, Default, PartialEq, Debug)]
+        pub struct $name(pub [[f32; $columns]; $rows]);
+
+        impl HlslType for $name {
+            fn hlsl_type () -> String { String::from(concat!("row_major ", $hlsl)) }
+            fn size () -> usize { REGISTER * ($rows - 1) + 4 * $columns }
+            fn starts_register () -> bool { true }
+            fn write (&self, out: &mut [u8]) {
+                for (r, row) in self.0.iter().enumerate() {
+                    for (c, v) in row.iter().enumerate() { v.write(&mut out[REGISTER*r + 4*c..]); }
+                }
+            }
+        }
+    )+};
+}
+
+hlsl_matrix! {
+    Float2x2[2][2] => "float2x2";
+    Float3x3[3][3] => "float3x3";
+    Float3x4[3][4] => "float3x4";
+    Float4x3[4][3] => "float4x3";
+    Float4x4[4][4] => "float4x4";
+}
+
+// Listed out, the way std implemented traits for arrays before const generics, rather than
+// `impl<T, const N: usize>`:  HLSL has no zero-length arrays (and `size` would underflow for them.)  1 through 16 covers
+// small fixed arrays like light lists, powers of two up to 256 cover bigger tables.  Add sizes here as they're needed.
+macro_rules! hlsl_array {
+    ($($n:expr),+) => {$(
+        impl<T: HlslType> HlslType for [T; $n] {
+            fn hlsl_type () -> String { T::hlsl_type() }
+            fn hlsl_suffix () -> String { format!("[{}]{}", $n, T::hlsl_suffix()) }
+            fn size () -> usize { round_up(T::size()) * ($n - 1) + T::size() }
+            fn starts_register () -> bool { true }
+            /// An array of structs ends with a struct, so nothing can pack after it either.
+            fn ends_register () -> bool { T::ends_register() }
+            fn write (&self, out: &mut [u8]) {
+                let stride = round_up(T::size());
+                for (i, element) in self.iter().enumerate() { element.write(&mut out[stride*i..]); }
+            }
+        }
+    )+};
+}
+
+hlsl_array!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 32, 64, 128, 256);
+
+/// Everything `Layout::pack` needs to know about a field.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldDesc {
+    pub name:               &'static str,
+    pub hlsl_type:          String,
+    pub hlsl_suffix:        String,
+    pub size:               usize,
+    pub starts_register:    bool,
+    pub ends_register:      bool,
+}
+
+impl FieldDesc {
+    pub fn of<T: HlslType> (name: &'static str) -> Self {
+        Self { name, hlsl_type: T::hlsl_type(), hlsl_suffix: T::hlsl_suffix(), size: T::size(), starts_register: T::starts_register(), ends_register: T::ends_register() }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldLayout {
+    pub name:   &'static str,
+    pub offset: usize,
+    pub size:   usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Layout {
+    pub fields: Vec<FieldLayout>,
+    /// Total size, rounded up to a whole number of registers as `D3D11_BUFFER_DESC::ByteWidth` requires.
+    pub size:   usize,
+}
+
+impl Layout {
+    /// Place `fields`, in order, per HLSL's packing rules.
+    pub fn pack (fields: &[FieldDesc]) -> Self {
+        let mut offset = 0;
+        let mut force_new_register = false;
+        let fields = fields.iter().map(|field| {
+            let straddles = offset % REGISTER + field.size > REGISTER;
+            if force_new_register || field.starts_register || straddles { offset = round_up(offset); }
+            let layout = FieldLayout { name: field.name, offset, size: field.size };
+            offset += field.size;
+            force_new_register = field.ends_register;
+            layout
+        }).collect();
+        Self { fields, size: round_up(offset) }
+    }
+
+    /// Where the last field ends, before rounding up to a whole register.
+    pub fn end (&self) -> usize {
+        self.fields.last().map_or(0, |f| f.offset + f.size)
+    }
+
+    pub fn offset_of (&self, name: &str) -> Option<usize> {
+        self.fields.iter().find(|f| f.name == name).map(|f| f.offset)
+    }
+}
+
+/// Implemented by `constant_buffer!` structs.
+pub trait ConstantBuffer : HlslType + Sized {
+    fn name () -> &'static str;
+    fn fields () -> Vec<FieldDesc>;
+
+    /// `Layout::pack(&Self::fields())`, computed once.  `constant_buffer!` keeps it in a `static`.
+    fn layout () -> &'static Layout;
+
+    /// Pack `self` into `layout().size` bytes, ready for `UpdateSubresource` or `Map`.
+    fn to_bytes (&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; Self::layout().size];
+        self.write(&mut bytes[..]);
+        bytes
+    }
+
+    fn hlsl_members () -> String {
+        Self::fields().iter().map(|f| format!("    {} {}{};\n", f.hlsl_type, f.name, f.hlsl_suffix)).collect()
+    }
+
+    /// `struct Name { ... };`, for use as a member of other constant buffers.
+    fn hlsl_struct () -> String {
+        format!("struct {}\n{{\n{}}};\n", Self::name(), Self::hlsl_members())
+    }
+
+    /// `cbuffer Name : register(bN) { ... };`
+    fn hlsl_cbuffer (register: Option<u32>) -> String {
+        let register = register.map_or(String::new(), |r| format!(" : register(b{})", r));
+        format!("cbuffer {}{}\n{{\n{}}};\n", Self::name(), register, Self::hlsl_members())
+    }
+}
+
+/// Serves generated HLSL, like `ConstantBuffer::hlsl_cbuffer`, to `#include "name"` through `d3d::IncludeObject`.
+#[derive(Default)]
+pub struct GeneratedIncludes {
+    files: HashMap<String, String>,
+}
+
+impl GeneratedIncludes {
+    pub fn new () -> Self { Self::default() }
+
+    pub fn insert (&mut self, name: impl Into<String>, hlsl: impl Into<String>) { self.files.insert(name.into(), hlsl.into()); }
+}
+
+impl Include for GeneratedIncludes {
+    fn open (&self, _include_type: D3D_INCLUDE_TYPE, file_name: &CStr, _parent: Option<&[u8]>) -> Result<Vec<u8>, HRESULT> {
+        let file_name = file_name.to_string_lossy();
+        self.files.get(&file_name[..]).map(|hlsl| hlsl.as_bytes().to_vec()).ok_or(E_FAIL)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    constant_buffer! { struct Light { color: Float3 } }
+    constant_buffer! { struct AfterStruct { ambient: f32, light: Light, after: f32 } }
+    constant_buffer! { struct AfterStructArray { lights: [Light; 2], after: f32 } }
+    constant_buffer! { struct AfterScalarArray { values: [f32; 3], after: f32 } }
+
+    fn offsets<C: ConstantBuffer> () -> (Vec<usize>, usize) {
+        let layout = C::layout();
+        (layout.fields.iter().map(|f| f.offset).collect(), layout.size)
+    }
+
+    /// MSDN's "Packing Rules for Constant Variables", a case or two per rule.  `tests/constant_buffer.rs` checks the
+    /// same rules against `fxc` output.
+    #[test] fn documented_packing () {
+        let pack = |fields: &[FieldDesc]| {
+            let layout = Layout::pack(fields);
+            (layout.fields.iter().map(|f| f.offset).collect::<Vec<_>>(), layout.size)
+        };
+        assert_eq!(pack(&[FieldDesc::of::<Float4>("a"), FieldDesc::of::<Float2>("b"), FieldDesc::of::<Float2>("c")]), (vec![0, 16, 24], 32));
+        assert_eq!(pack(&[FieldDesc::of::<Float2>("a"), FieldDesc::of::<Float4>("b"), FieldDesc::of::<Float2>("c")]), (vec![0, 16, 32], 48));
+        assert_eq!(pack(&[FieldDesc::of::<f32>("a"), FieldDesc::of::<Float3>("b"), FieldDesc::of::<Float2>("c")]), (vec![0, 4, 16], 32));
+        assert_eq!(pack(&[FieldDesc::of::<Float2>("a"), FieldDesc::of::<Float3>("b")]), (vec![0, 16], 32));
+        assert_eq!(pack(&[FieldDesc::of::<[f32; 2]>("a"), FieldDesc::of::<Float2>("b")]), (vec![0, 20], 32));
+        assert_eq!(pack(&[FieldDesc::of::<Float3x3>("a"), FieldDesc::of::<f32>("b")]), (vec![0, 44], 48));
+        assert_eq!(pack(&[FieldDesc::of::<f32>("a"), FieldDesc::of::<Float4x4>("b"), FieldDesc::of::<Float3>("c")]), (vec![0, 16, 80], 96));
+    }
+
+    #[test] fn structs_end_registers () {
+        assert_eq!(offsets::<AfterStruct>(),        (vec![0, 16, 32], 48));
+        assert_eq!(offsets::<AfterStructArray>(),   (vec![0, 32], 48), "arrays of structs end registers too");
+        assert_eq!(offsets::<AfterScalarArray>(),   (vec![0, 36], 48), "other arrays don't");
+    }
+
+    #[test] fn layout_computed_once () {
+        assert!(std::ptr::eq(AfterStruct::layout(), AfterStruct::layout()));
+        assert_eq!(AfterStruct::layout().fields.len(), 3, "one static per type");
+        assert_eq!(Light::layout().fields.len(), 1);
+    }
+
+    #[test] fn to_bytes () {
+        let cb = AfterStruct { ambient: 0.5, light: Light { color: Float3([1.0, 2.0, 3.0]) }, after: 4.0 };
+        let bytes = cb.to_bytes();
+        let f32_at = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset+1], bytes[offset+2], bytes[offset+3]]);
+        assert_eq!(bytes.len(), 48);
+        assert_eq!([f32_at(0), f32_at(16), f32_at(20), f32_at(24), f32_at(32)], [0.5, 1.0, 2.0, 3.0, 4.0]);
+        assert!(bytes[4..16].iter().chain(bytes[28..32].iter()).chain(bytes[36..].iter()).all(|&b| b == 0), "padding");
+    }
+
+    #[test] fn hlsl () {
+        assert_eq!(AfterStructArray::hlsl_cbuffer(Some(2)), "cbuffer AfterStructArray : register(b2)\n{\n    Light lights[2];\n    float after;\n};\n");
+        assert_eq!(Light::hlsl_struct(), "struct Light\n{\n    float3 color;\n};\n");
+        assert_eq!(<[Float4x4; 4]>::hlsl_type(), "row_major float4x4");
+        assert_eq!(<[[f32; 2]; 3]>::hlsl_suffix(), "[3][2]");
+    }
+}
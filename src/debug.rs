@@ -11,6 +11,17 @@ pub fn break_if_debugger() {
     }
 }
 
+/// `OutputDebugStringA` on Windows, where it shows up in the debugger's output window.  stderr everywhere else.
+pub fn output(message: &str) {
+    #[cfg(windows)]
+    unsafe {
+        let message = format!("{}\0", message);
+        crate::win32::OutputDebugStringA(message.as_ptr() as *const _);
+    }
+    #[cfg(not(windows))]
+    eprint!("{}", message);
+}
+
 pub fn init() {
     std::panic::set_hook(Box::new(|panic|{
         println!("{:?}", panic);
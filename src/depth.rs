@@ -0,0 +1,174 @@
+//! Depth buffers:  which formats to use (`DepthPolicy`), and a depth buffer sized to match a swap chain (`DepthBuffer`).
+
+use crate::com::FeatureLevel;
+#[cfg(windows)] use crate::com::d3d11::*;
+#[cfg(windows)] use crate::win32::*;
+
+// Spelled out here rather than taken from winapi, so format selection builds and can be tested everywhere.
+#[allow(non_camel_case_types)] type DXGI_FORMAT = u32;
+const DXGI_FORMAT_R32G8X24_TYPELESS         : DXGI_FORMAT = 19;
+const DXGI_FORMAT_D32_FLOAT_S8X24_UINT      : DXGI_FORMAT = 20;
+const DXGI_FORMAT_R32_FLOAT_X8X24_TYPELESS  : DXGI_FORMAT = 21;
+const DXGI_FORMAT_R32_TYPELESS              : DXGI_FORMAT = 39;
+const DXGI_FORMAT_D32_FLOAT                 : DXGI_FORMAT = 40;
+const DXGI_FORMAT_R32_FLOAT                 : DXGI_FORMAT = 41;
+const DXGI_FORMAT_R24G8_TYPELESS            : DXGI_FORMAT = 44;
+const DXGI_FORMAT_D24_UNORM_S8_UINT         : DXGI_FORMAT = 45;
+const DXGI_FORMAT_R24_UNORM_X8_TYPELESS     : DXGI_FORMAT = 46;
+const D3D11_BIND_SHADER_RESOURCE            : u32 = 0x8;
+const D3D11_BIND_DEPTH_STENCIL              : u32 = 0x40;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DepthPolicy {
+    /// Require a stencil buffer.
+    pub stencil:            bool,
+    /// Prefer 32-bit floating point depth over 24-bit normalized depth.  Costs 32 bits of padding if `stencil` is set.
+    /// Ignored below feature level 10.0, which only guarantees `D24_UNORM_S8_UINT` and `D16_UNORM` depth buffers.
+    pub float:              bool,
+    /// Create the depth buffer with a typeless format so it can also be bound as a `ShaderResourceView`.
+    pub shader_readable:    bool,
+}
+
+/// The formats to create a depth buffer's texture and views with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DepthFormats {
+    /// `DXGI_FORMAT`
+    pub texture:    u32,
+    /// `DXGI_FORMAT`
+    pub dsv:        u32,
+    /// `DXGI_FORMAT`, or `None` if the depth buffer won't be shader readable.
+    pub srv:        Option<u32>,
+}
+
+impl DepthFormats {
+    /// `D3D11_BIND_FLAG`s to create the depth buffer's texture with.
+    pub fn bind_flags (&self) -> u32 {
+        D3D11_BIND_DEPTH_STENCIL | if self.srv.is_some() { D3D11_BIND_SHADER_RESOURCE } else { 0 }
+    }
+}
+
+impl Default for DepthPolicy {
+    fn default () -> Self { Self { stencil: true, float: false, shader_readable: false } }
+}
+
+impl DepthPolicy {
+    /// Pick formats satisfying this policy on `feature_level`.  Feature levels 9.x can't sample depth buffers or
+    /// create 32-bit float ones, so `shader_readable` and `float` are ignored there.
+    pub fn select (&self, feature_level: FeatureLevel) -> DepthFormats {
+        let level_10 = feature_level >= FeatureLevel::_10_0;
+        let (typeless, dsv, srv) = match (self.float && level_10, self.stencil) {
+            (false, _    ) => (DXGI_FORMAT_R24G8_TYPELESS,     DXGI_FORMAT_D24_UNORM_S8_UINT,    DXGI_FORMAT_R24_UNORM_X8_TYPELESS),
+            (true,  false) => (DXGI_FORMAT_R32_TYPELESS,       DXGI_FORMAT_D32_FLOAT,            DXGI_FORMAT_R32_FLOAT),
+            (true,  true ) => (DXGI_FORMAT_R32G8X24_TYPELESS,  DXGI_FORMAT_D32_FLOAT_S8X24_UINT, DXGI_FORMAT_R32_FLOAT_X8X24_TYPELESS),
+        };
+
+        if self.shader_readable && level_10 {
+            DepthFormats { texture: typeless, dsv, srv: Some(srv) }
+        } else {
+            DepthFormats { texture: dsv, dsv, srv: None }
+        }
+    }
+}
+
+/// A depth buffer, and views of it.
+#[cfg(windows)]
+pub struct DepthBuffer {
+    pub formats:    DepthFormats,
+    pub texture:    Texture2D,
+    pub dsv:        DepthStencilView,
+    pub srv:        Option<ShaderResourceView>,
+}
+
+#[cfg(windows)]
+impl DepthBuffer {
+    /// `sample_desc` must match that of the render targets the depth buffer will be used with.
+    pub fn create (device: &Device, formats: DepthFormats, width: UINT, height: UINT, sample_desc: DXGI_SAMPLE_DESC) -> Result<Self, HRESULT> {
+        let multisampled = sample_desc.Count > 1;
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width:          width,
+            Height:         height,
+            MipLevels:      1,
+            ArraySize:      1,
+            Format:         formats.texture,
+            SampleDesc:     sample_desc,
+            Usage:          D3D11_USAGE_DEFAULT,
+            BindFlags:      formats.bind_flags(),
+            CPUAccessFlags: 0,
+            MiscFlags:      0,
+        };
+        let texture = unsafe { device.create_texture_2d(&desc, None) }?;
+
+        let mut dsv_desc = D3D11_DEPTH_STENCIL_VIEW_DESC {
+            Format:         formats.dsv,
+            ViewDimension:  if multisampled { D3D11_DSV_DIMENSION_TEXTURE2DMS } else { D3D11_DSV_DIMENSION_TEXTURE2D },
+            Flags:          0,
+            u:              unsafe { std::mem::zeroed() },
+        };
+        if !multisampled { unsafe { dsv_desc.u.Texture2D_mut().MipSlice = 0 }; }
+        let dsv = device.create_depth_stencil_view(&texture, Some(&dsv_desc))?;
+
+        let srv = match formats.srv {
+            None => None,
+            Some(format) => {
+                let mut srv_desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+                    Format:         format,
+                    ViewDimension:  if multisampled { D3D11_SRV_DIMENSION_TEXTURE2DMS } else { D3D11_SRV_DIMENSION_TEXTURE2D },
+                    u:              unsafe { std::mem::zeroed() },
+                };
+                if !multisampled {
+                    let tex2d = unsafe { srv_desc.u.Texture2D_mut() };
+                    tex2d.MostDetailedMip   = 0;
+                    tex2d.MipLevels         = 1;
+                }
+                Some(device.create_shader_resource_view(&texture, Some(&srv_desc))?)
+            },
+        };
+
+        Ok(Self { formats, texture, dsv, srv })
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    const LEVELS : [FeatureLevel; 6] = [FeatureLevel::_9_1, FeatureLevel::_9_2, FeatureLevel::_9_3, FeatureLevel::_10_0, FeatureLevel::_10_1, FeatureLevel::_11_0];
+
+    fn formats (texture: DXGI_FORMAT, dsv: DXGI_FORMAT, srv: Option<DXGI_FORMAT>) -> DepthFormats { DepthFormats { texture, dsv, srv } }
+
+    #[test] fn select () {
+        let d24s8       = formats(DXGI_FORMAT_D24_UNORM_S8_UINT,    DXGI_FORMAT_D24_UNORM_S8_UINT,    None);
+        let d24s8_srv   = formats(DXGI_FORMAT_R24G8_TYPELESS,       DXGI_FORMAT_D24_UNORM_S8_UINT,    Some(DXGI_FORMAT_R24_UNORM_X8_TYPELESS));
+        let d32         = formats(DXGI_FORMAT_D32_FLOAT,            DXGI_FORMAT_D32_FLOAT,            None);
+        let d32_srv     = formats(DXGI_FORMAT_R32_TYPELESS,         DXGI_FORMAT_D32_FLOAT,            Some(DXGI_FORMAT_R32_FLOAT));
+        let d32s8       = formats(DXGI_FORMAT_D32_FLOAT_S8X24_UINT, DXGI_FORMAT_D32_FLOAT_S8X24_UINT, None);
+        let d32s8_srv   = formats(DXGI_FORMAT_R32G8X24_TYPELESS,    DXGI_FORMAT_D32_FLOAT_S8X24_UINT, Some(DXGI_FORMAT_R32_FLOAT_X8X24_TYPELESS));
+
+        for &level in LEVELS.iter() {
+            let level_9 = level < FeatureLevel::_10_0;
+            for &stencil in [false, true].iter() {
+                for &float in [false, true].iter() {
+                    for &shader_readable in [false, true].iter() {
+                        let policy = DepthPolicy { stencil, float, shader_readable };
+                        let expected = match (level_9, float, stencil, shader_readable) {
+                            (true,  _,     _,     _    ) => d24s8,
+                            (false, false, _,     false) => d24s8,
+                            (false, false, _,     true ) => d24s8_srv,
+                            (false, true,  false, false) => d32,
+                            (false, true,  false, true ) => d32_srv,
+                            (false, true,  true,  false) => d32s8,
+                            (false, true,  true,  true ) => d32s8_srv,
+                        };
+                        assert_eq!(policy.select(level), expected, "{:?} on {:?}", policy, level);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test] fn bind_flags () {
+        assert_eq!(DepthPolicy::default().select(FeatureLevel::_11_0).bind_flags(), D3D11_BIND_DEPTH_STENCIL);
+        let readable = DepthPolicy { shader_readable: true, ..DepthPolicy::default() };
+        assert_eq!(readable.select(FeatureLevel::_11_0).bind_flags(), D3D11_BIND_DEPTH_STENCIL | D3D11_BIND_SHADER_RESOURCE);
+        assert_eq!(readable.select(FeatureLevel::_9_3 ).bind_flags(), D3D11_BIND_DEPTH_STENCIL);
+    }
+}
@@ -0,0 +1,95 @@
+//! The container checksum in the DXBC header, which D3D checks before creating a shader from it.
+//!
+//! It's MD5 over everything after the checksum field, except for how the final block is padded:  the bit count goes
+//! at the start of the block (or a block of its own), and `(bits >> 2) | 1` takes the place of MD5's high length word.
+
+use super::*;
+
+/// Offset and size of the checksum in the header.
+const CHECKSUM_RANGE : std::ops::Range<usize> = 4..20;
+
+/// Compute the checksum `bytes` should have.  Only the container is hashed, not any trailing bytes past its size.
+pub fn compute_checksum (bytes: &[u8]) -> Result<[u8; 16]> {
+    let container = Container::parse(bytes)?;
+    Ok(hash(&container.bytes()[CHECKSUM_RANGE.end..]))
+}
+
+/// Whether the checksum in `bytes`'s header is correct.
+pub fn verify_checksum (bytes: &[u8]) -> Result<bool> {
+    let container = Container::parse(bytes)?;
+    Ok(container.checksum() == hash(&container.bytes()[CHECKSUM_RANGE.end..]))
+}
+
+/// Recompute and store `bytes`'s checksum, after editing it.
+pub fn sign (bytes: &mut [u8]) -> Result<()> {
+    let checksum = compute_checksum(bytes)?;
+    bytes[CHECKSUM_RANGE].copy_from_slice(&checksum[..]);
+    Ok(())
+}
+
+fn hash (data: &[u8]) -> [u8; 16] {
+    let mut state = [0x67452301u32, 0xefcdab89, 0x98badcfe, 0x10325476];
+    let bits = (data.len() as u32).wrapping_mul(8);
+    let full = data.len() & !63;
+    for block in data[..full].chunks(64) { transform(&mut state, block); }
+
+    let last = &data[full..];
+    let mut block = [0u8; 64];
+    if last.len() >= 56 {
+        block[..last.len()].copy_from_slice(last);
+        block[last.len()] = 0x80;
+        transform(&mut state, &block[..]);
+
+        block = [0u8; 64];
+        block[0..4].copy_from_slice(&bits.to_le_bytes());
+        block[60..64].copy_from_slice(&((bits >> 2) | 1).to_le_bytes());
+        transform(&mut state, &block[..]);
+    } else {
+        block[0..4].copy_from_slice(&bits.to_le_bytes());
+        block[4..4+last.len()].copy_from_slice(last);
+        block[4+last.len()] = 0x80;
+        block[60..64].copy_from_slice(&((bits >> 2) | 1).to_le_bytes());
+        transform(&mut state, &block[..]);
+    }
+
+    let mut checksum = [0u8; 16];
+    for (i, word) in state.iter().enumerate() { checksum[4*i..4*i+4].copy_from_slice(&word.to_le_bytes()); }
+    checksum
+}
+
+/// The standard MD5 block transform.
+fn transform (state: &mut [u32; 4], block: &[u8]) {
+    const SHIFTS : [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    const SINES : [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+        0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+        0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+        0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+        0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+        0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+        0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+    ];
+
+    let mut m = [0u32; 16];
+    for (i, word) in m.iter_mut().enumerate() { *word = u32::from_le_bytes([block[4*i], block[4*i+1], block[4*i+2], block[4*i+3]]); }
+
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d),   i),
+            1 => ((d & b) | (!d & c),   (5 * i + 1) % 16),
+            2 => (b ^ c ^ d,            (3 * i + 5) % 16),
+            _ => (c ^ (b | !d),         (7 * i) % 16),
+        };
+        let rotated = a.wrapping_add(f).wrapping_add(SINES[i]).wrapping_add(m[g]).rotate_left(SHIFTS[(i / 16) * 4 + i % 4]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+}
@@ -0,0 +1,532 @@
+//! Pure Rust disassembly of `SHDR` / `SHEX` chunks into `fxc` style assembly, for reading shaders without
+//! `D3DDisassemble`.
+//!
+//! `Program` splits a chunk into `Instruction`s; `disassemble` prints a whole container:
+//!
+//! ```text
+//! vs_5_0
+//! dcl_globalFlags refactoringAllowed
+//! dcl_input v0.xyzw
+//! dcl_output_siv o0.xyzw, position
+//! mov o0.xyzw, v0.xyzw
+//! ret
+//! ```
+//!
+//! Everything `D3DCompile` emits for shader models 4.0 - 5.0 decodes.  Interface declarations (`dcl_function_body`,
+//! `dcl_function_table`, and `dcl_interface`) are formatted from the token layout:  `fxc` never emitted them for the
+//! checked in listings, so only the Windows tests compare them against `D3DDisassemble`.
+
+use super::*;
+use std::fmt::{self, Write};
+
+pub const OPCODE_CUSTOMDATA         : u32 = 53;
+pub const OPCODE_DCL_THREAD_GROUP   : u32 = 155;
+
+/// A shader program:  the dwords of a `SHDR` / `SHEX` chunk.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub version:    ShaderVersion,
+    dwords:         Vec<u32>,
+}
+
+/// A single instruction or declaration, including its opcode token.
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction<'a> {
+    /// Dword offset from the start of the program chunk.
+    pub offset:     usize,
+    pub opcode:     u32,
+    pub tokens:     &'a [u32],
+}
+
+impl Program {
+    pub fn parse (chunk: &[u8]) -> Result<Program> {
+        let version = ShaderVersion::from_token(u32_at(chunk, 0)?).ok_or(Error::Unsupported("unknown program type"))?;
+        let length = u32_at(chunk, 4)? as usize;
+        let dwords = (0..length).map(|i| u32_at(chunk, 4 * i)).collect::<Result<Vec<_>>>()?;
+        Ok(Program { version, dwords })
+    }
+
+    /// The program in `container`'s `SHEX` / `SHDR` chunk.
+    pub fn from_container (container: &Container) -> Result<Program> {
+        let shader = container.chunk_any(&[FourCC::SHEX, FourCC::SHDR]).ok_or(Error::MissingChunk(FourCC::SHDR))?;
+        Program::parse(shader.data)
+    }
+
+    /// Split the program into instructions, after the version and length tokens.
+    pub fn instructions (&self) -> Result<Vec<Instruction<'_>>> {
+        let mut instructions = Vec::new();
+        let mut dword = 2;
+        while dword < self.dwords.len() {
+            let token = self.dwords[dword];
+            let opcode = token & 0x7FF;
+            let length = if opcode == OPCODE_CUSTOMDATA {
+                *self.dwords.get(dword + 1).ok_or(Error::Truncated { offset: 4 * (dword + 1) })? as usize
+            } else {
+                ((token >> 24) & 0x7F) as usize
+            };
+            if length == 0 { return Err(Error::Unsupported("zero length instruction")); }
+            let tokens = self.dwords.get(dword..dword + length).ok_or(Error::Truncated { offset: 4 * dword })?;
+            instructions.push(Instruction { offset: dword, opcode, tokens });
+            dword += length;
+        }
+        Ok(instructions)
+    }
+}
+
+/// Disassemble the program in the container `bytecode`.
+pub fn disassemble (bytecode: &[u8]) -> Result<String> {
+    let container = Container::parse(bytecode)?;
+    let program = Program::from_container(&container)?;
+    let mut out = String::new();
+    writeln!(out, "{}", version_name(program.version)).unwrap();
+    let mut indent = 0usize;
+    for instruction in program.instructions()? {
+        let text = instruction.to_string();
+        if ends_block(instruction.opcode) { indent = indent.saturating_sub(1); }
+        writeln!(out, "{:width$}{}", "", text, width = 2 * indent).unwrap();
+        if starts_block(instruction.opcode) { indent += 1; }
+    }
+    Ok(out)
+}
+
+fn version_name (version: ShaderVersion) -> String {
+    let prefix = match version.program {
+        ProgramType::Pixel      => "ps",
+        ProgramType::Vertex     => "vs",
+        ProgramType::Geometry   => "gs",
+        ProgramType::Hull       => "hs",
+        ProgramType::Domain     => "ds",
+        ProgramType::Compute    => "cs",
+    };
+    format!("{}_{}_{}", prefix, version.major, version.minor)
+}
+
+// if, else, loop, switch
+fn starts_block (opcode: u32) -> bool { opcode == 31 || opcode == 18 || opcode == 48 || opcode == 76 }
+// else, endif, endloop, endswitch
+fn ends_block (opcode: u32) -> bool { opcode == 18 || opcode == 21 || opcode == 22 || opcode == 23 }
+
+/// Instruction names by opcode.  Declarations are named here, but formatted by `Instruction::fmt_declaration`.
+const OPCODE_NAMES : [&str; 218] = [
+    "add", "and", "break", "breakc", "call", "callc", "case", "continue", "continuec", "cut",
+    "default", "deriv_rtx", "deriv_rty", "discard", "div", "dp2", "dp3", "dp4", "else", "emit",
+    "emit_then_cut", "endif", "endloop", "endswitch", "eq", "exp", "frc", "ftoi", "ftou", "ge",
+    "iadd", "if", "ieq", "ige", "ilt", "imad", "imax", "imin", "imul", "ine",
+    "ineg", "ishl", "ishr", "itof", "label", "ld", "ld_ms", "log", "loop", "lt",
+    "mad", "min", "max", "customdata", "mov", "movc", "mul", "ne", "nop", "not",
+    "or", "resinfo", "ret", "retc", "round_ne", "round_ni", "round_pi", "round_z", "rsq", "sample",
+    "sample_c", "sample_c_lz", "sample_l", "sample_d", "sample_b", "sqrt", "switch", "sincos", "udiv", "ult",
+    "uge", "umul", "umad", "umax", "umin", "ushr", "utof", "xor", "dcl_resource", "dcl_constantbuffer",
+    "dcl_sampler", "dcl_indexrange", "dcl_outputtopology", "dcl_inputprimitive", "dcl_maxout", "dcl_input", "dcl_input_sgv", "dcl_input_siv", "dcl_input_ps", "dcl_input_ps_sgv",
+    "dcl_input_ps_siv", "dcl_output", "dcl_output_sgv", "dcl_output_siv", "dcl_temps", "dcl_indexableTemp", "dcl_globalFlags", "reserved0", "lod", "gather4",
+    "samplepos", "sampleinfo", "reserved1", "hs_decls", "hs_control_point_phase", "hs_fork_phase", "hs_join_phase", "emit_stream", "cut_stream", "emit_then_cut_stream",
+    "fcall", "bufinfo", "deriv_rtx_coarse", "deriv_rtx_fine", "deriv_rty_coarse", "deriv_rty_fine", "gather4_c", "gather4_po", "gather4_po_c", "rcp",
+    "f32tof16", "f16tof32", "uaddc", "usubb", "countbits", "firstbit_hi", "firstbit_lo", "firstbit_shi", "ubfe", "ibfe",
+    "bfi", "bfrev", "swapc", "dcl_stream", "dcl_function_body", "dcl_function_table", "dcl_interface", "dcl_input_control_point_count", "dcl_output_control_point_count", "dcl_tessellator_domain",
+    "dcl_tessellator_partitioning", "dcl_tessellator_output_primitive", "dcl_hs_max_tessfactor", "dcl_hs_fork_phase_instance_count", "dcl_hs_join_phase_instance_count", "dcl_thread_group", "dcl_uav_typed", "dcl_uav_raw", "dcl_uav_structured", "dcl_tgsm_raw",
+    "dcl_tgsm_structured", "dcl_resource_raw", "dcl_resource_structured", "ld_uav_typed", "store_uav_typed", "ld_raw", "store_raw", "ld_structured", "store_structured", "atomic_and",
+    "atomic_or", "atomic_xor", "atomic_cmp_store", "atomic_iadd", "atomic_imax", "atomic_imin", "atomic_umax", "atomic_umin", "imm_atomic_alloc", "imm_atomic_consume",
+    "imm_atomic_iadd", "imm_atomic_and", "imm_atomic_or", "imm_atomic_xor", "imm_atomic_exch", "imm_atomic_cmp_exch", "imm_atomic_imax", "imm_atomic_imin", "imm_atomic_umax", "imm_atomic_umin",
+    "sync", "dadd", "dmax", "dmin", "dmul", "deq", "dge", "dlt", "dne", "dmov",
+    "dmovc", "dtof", "ftod", "eval_snapped", "eval_sample_index", "eval_centroid", "dcl_gsinstances", "abort", "debug_break", "reserved2",
+    "ddiv", "dfma", "drcp", "msad", "dtoi", "dtou", "itod", "utod",
+];
+
+// Instructions with a `_z` / `_nz` test:  breakc, callc, continuec, discard, if, retc
+const CONDITIONAL : [u32; 6] = [3, 5, 8, 13, 31, 63];
+
+// Float instructions, whose `l(...)` operands `fxc` always prints as floats, and comma-space separated:  add, deriv_rt*, div, dp*, eq, exp, frc,
+// ftoi, ftou, ge, log, lt, mad, min, max, mul, ne, round_*, rsq, sqrt, sincos, deriv_rt*_coarse / fine, rcp, f32tof16
+const FLOAT : [u32; 33] = [
+    0, 11, 12, 14, 15, 16, 17, 24, 25, 26, 27, 28, 29, 47, 49, 50, 51, 52, 56, 57, 64, 65, 66, 67, 68, 75, 77,
+    122, 123, 124, 125, 129, 130,
+];
+
+const RESOURCE_DIMENSIONS : [&str; 13] = [
+    "unknown", "buffer", "texture1d", "texture2d", "texture2dms", "texture3d", "texturecube", "texture1darray",
+    "texture2darray", "texture2dmsarray", "texturecubearray", "raw_buffer", "structured_buffer",
+];
+
+const RETURN_TYPES : [&str; 10] = ["unknown", "unorm", "snorm", "sint", "uint", "float", "mixed", "double", "continued", "unused"];
+
+const SYSTEM_VALUES : [&str; 23] = [
+    "undefined", "position", "clip_distance", "cull_distance", "rendertarget_array_index", "viewport_array_index",
+    "vertex_id", "primitive_id", "instance_id", "is_front_face", "sampleIndex",
+    "finalQuadUeq0EdgeTessFactor", "finalQuadVeq0EdgeTessFactor", "finalQuadUeq1EdgeTessFactor",
+    "finalQuadVeq1EdgeTessFactor", "finalQuadUInsideTessFactor", "finalQuadVInsideTessFactor",
+    "finalTriUeq0EdgeTessFactor", "finalTriVeq0EdgeTessFactor", "finalTriWeq0EdgeTessFactor",
+    "finalTriInsideTessFactor", "finalLineDetailTessFactor", "finalLineDensityTessFactor",
+];
+
+const INTERPOLATION_MODES : [&str; 8] = [
+    "undefined", "constant", "linear", "linear centroid", "linear noperspective", "linear noperspective centroid",
+    "linear sample", "linear noperspective sample",
+];
+
+const GLOBAL_FLAGS : [&str; 8] = [
+    "refactoringAllowed", "enableDoublePrecisionFloatOps", "forceEarlyDepthStencil", "enableRawAndStructuredBuffers",
+    "skipOptimization", "enableMinimumPrecision", "enable11_1DoubleExtensions", "enable11_1ShaderExtensions",
+];
+
+fn lookup (table: &[&'static str], index: u32) -> String {
+    table.get(index as usize).map_or_else(|| format!("unknown({})", index), |s| s.to_string())
+}
+
+/// Which components a register operand reads or writes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Components {
+    /// Scalar registers like `oDepth`, or operands without components like `s0`.
+    None,
+    Mask(u8),
+    Swizzle([u8; 4]),
+    Select(u8),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Index {
+    immediate:  u64,
+    relative:   Option<Box<Operand>>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Operand {
+    ty:             u32,
+    components:     Components,
+    indices:        Vec<Index>,
+    /// `l(...)` / `d(...)` values.
+    immediates:     Vec<u32>,
+    /// 0 none, 1 `-`, 2 `|abs|`, 3 `-|abs|`
+    modifier:       u32,
+    /// Print `immediates` as floats, even if they look like integers, the way `fxc` does for float instructions.
+    float:          bool,
+}
+
+/// Reads tokens from an instruction, reporting overruns as byte offsets into the program chunk.
+struct Tokens<'a> {
+    instruction:    &'a Instruction<'a>,
+    next:           usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn remaining (&self) -> bool { self.next < self.instruction.tokens.len() }
+
+    fn token (&mut self) -> Result<u32> {
+        let token = *self.instruction.tokens.get(self.next).ok_or(Error::Truncated { offset: 4 * (self.instruction.offset + self.next) })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn operand (&mut self) -> Result<Operand> {
+        let token = self.token()?;
+        let num_components = token & 3;
+        let ty = (token >> 12) & 0xFF;
+        let components = match num_components {
+            2 => match (token >> 2) & 3 {
+                0 => Components::Mask(((token >> 4) & 0xF) as u8),
+                1 => Components::Swizzle([((token >> 4) & 3) as u8, ((token >> 6) & 3) as u8, ((token >> 8) & 3) as u8, ((token >> 10) & 3) as u8]),
+                _ => Components::Select(((token >> 4) & 3) as u8),
+            },
+            _ => Components::None,
+        };
+
+        let mut modifier = 0;
+        let mut extended = token & 0x8000_0000 != 0;
+        while extended {
+            let ext = self.token()?;
+            if ext & 0x3F == 1 { modifier = (ext >> 6) & 0xFF; }
+            extended = ext & 0x8000_0000 != 0;
+        }
+
+        let mut immediates = Vec::new();
+        if ty == 4 || ty == 5 {
+            let count = match num_components { 1 => 1, 2 => 4, _ => 0 } * if ty == 5 { 2 } else { 1 };
+            for _ in 0..count { immediates.push(self.token()?); }
+        }
+
+        let dimension = (token >> 20) & 3;
+        let mut indices = Vec::new();
+        for i in 0..dimension {
+            let representation = (token >> (22 + 3 * i)) & 7;
+            let immediate = match representation {
+                0 | 3   => self.token()? as u64,
+                1 | 4   => { let hi = self.token()? as u64; (hi << 32) | self.token()? as u64 },
+                _       => 0,
+            };
+            let relative = if representation >= 2 { Some(Box::new(self.operand()?)) } else { None };
+            indices.push(Index { immediate, relative });
+        }
+
+        Ok(Operand { ty, components, indices, immediates, modifier, float: false })
+    }
+}
+
+/// `fxc` prints 32-bit immediates as floats, unless they look like integers:  a zero exponent (0 and small ints) or
+/// an all-ones exponent (-1 and other small negative ints.)
+fn immediate32 (value: u32) -> String {
+    let exponent = (value >> 23) & 0xFF;
+    if exponent == 0 || exponent == 0xFF {
+        format!("{}", value as i32)
+    } else {
+        format!("{:.6}", f32::from_bits(value))
+    }
+}
+
+fn register_prefix (ty: u32) -> Option<&'static str> {
+    Some(match ty {
+        0 => "r", 1 => "v", 2 => "o", 3 => "x", 6 => "s", 7 => "t", 8 => "cb", 9 => "icb", 10 => "l",
+        11 => "vPrim", 12 => "oDepth", 13 => "null", 14 => "rasterizer", 15 => "oMask", 16 => "m",
+        17 => "fb", 18 => "ft", 19 => "fp", 20 => "fi", 21 => "fo",
+        22 => "vOutputControlPointID", 23 => "vForkInstanceID", 24 => "vJoinInstanceID", 25 => "vicp", 26 => "vocp",
+        27 => "vpc", 28 => "vDomain", 29 => "this", 30 => "u", 31 => "g",
+        32 => "vThreadID", 33 => "vThreadGroupID", 34 => "vThreadIDInGroup", 35 => "vCoverage",
+        36 => "vThreadIDInGroupFlattened", 37 => "vGSInstanceID", 38 => "oDepthGE", 39 => "oDepthLE",
+        40 => "vCycleCounter", 41 => "oStencilRef", 42 => "vInnerCoverage",
+        _ => return None,
+    })
+}
+
+impl fmt::Display for Index {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.relative {
+            Some(ref relative) if self.immediate != 0   => write!(f, "{} + {}", relative, self.immediate),
+            Some(ref relative)                          => write!(f, "{} + 0", relative),
+            None                                        => write!(f, "{}", self.immediate),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.modifier { 1 => write!(f, "-")?, 2 => write!(f, "|")?, 3 => write!(f, "-|")?, _ => {} }
+
+        match self.ty {
+            4 => {
+                if self.float {
+                    let values = self.immediates.iter().map(|&v| format!("{:.6}", f32::from_bits(v))).collect::<Vec<_>>();
+                    write!(f, "l({})", values.join(", "))?;
+                } else {
+                    let values = self.immediates.iter().map(|&v| immediate32(v)).collect::<Vec<_>>();
+                    write!(f, "l({})", values.join(","))?;
+                }
+            },
+            5 => {
+                let values = self.immediates.chunks(2).map(|d| format!("{:.6}", f64::from_bits((d[1] as u64) << 32 | d[0] as u64))).collect::<Vec<_>>();
+                write!(f, "d({})", values.join(","))?;
+            },
+            ty => {
+                let prefix = register_prefix(ty).map_or_else(|| format!("unknown{}_", ty), |p| p.to_string());
+                // 2D inputs (`v[vertex][register]`) and `icb[offset]` bracket every index, everything else puts the
+                // first index directly after the prefix (`cb0[4]`.)
+                let bracket_first = ty == 9 || (self.indices.len() == 2 && (ty == 1 || ty == 25 || ty == 26));
+                write!(f, "{}", prefix)?;
+                for (i, index) in self.indices.iter().enumerate() {
+                    if i == 0 && !bracket_first && index.relative.is_none() { write!(f, "{}", index.immediate)?; }
+                    else { write!(f, "[{}]", index)?; }
+                }
+            },
+        }
+
+        const XYZW : [char; 4] = ['x', 'y', 'z', 'w'];
+        match self.components {
+            Components::None        => {},
+            Components::Mask(mask)  => if mask != 0 {
+                write!(f, ".")?;
+                for (c, name) in XYZW.iter().enumerate() { if mask & (1 << c) != 0 { write!(f, "{}", name)?; } }
+            },
+            Components::Swizzle(s)  => write!(f, ".{}{}{}{}", XYZW[s[0] as usize], XYZW[s[1] as usize], XYZW[s[2] as usize], XYZW[s[3] as usize])?,
+            Components::Select(c)   => write!(f, ".{}", XYZW[c as usize])?,
+        }
+
+        match self.modifier { 2 | 3 => write!(f, "|"), _ => Ok(()) }
+    }
+}
+
+impl<'a> Instruction<'a> {
+    fn name (&self) -> String { lookup(&OPCODE_NAMES[..], self.opcode) }
+
+    fn fmt_declaration (&self, f: &mut fmt::Formatter, tokens: &mut Tokens) -> Result<()> {
+        let token = self.tokens[0];
+        let bits = |shift: u32, count: u32| (token >> shift) & ((1 << count) - 1);
+        let name = self.name();
+        let w = |f: &mut fmt::Formatter, s: String| f.write_str(&s).map_err(|_| Error::Unsupported("formatting"));
+        match self.opcode {
+            88 => { // dcl_resource
+                let dimension = bits(11, 5);
+                let samples = if dimension == 4 || dimension == 9 { format!("({})", bits(16, 7)) } else { String::new() };
+                let operand = tokens.operand()?;
+                let return_type = tokens.token()?;
+                w(f, format!("dcl_resource_{}{} {} {}", lookup(&RESOURCE_DIMENSIONS[..], dimension), samples, return_types(return_type), operand))
+            },
+            89 => { // dcl_constantbuffer
+                let operand = tokens.operand()?;
+                let register = operand.indices.first().map_or(0, |i| i.immediate);
+                let size = operand.indices.get(1).map_or(0, |i| i.immediate);
+                let access = if bits(11, 1) == 0 { "immediateIndexed" } else { "dynamicIndexed" };
+                w(f, format!("{} CB{}[{}], {}", name, register, size, access))
+            },
+            90 => { // dcl_sampler
+                let mode = ["mode_default", "mode_comparison", "mode_mono"].get(bits(11, 4) as usize).cloned().unwrap_or("mode_unknown");
+                w(f, format!("{} {}, {}", name, tokens.operand()?, mode))
+            },
+            91 => w(f, format!("{} {} {}", name, tokens.operand()?, tokens.token()?)),
+            92 => {
+                let topology = ["undefined", "pointlist", "linelist", "linestrip", "trianglelist", "trianglestrip"].get(bits(11, 7) as usize).cloned().unwrap_or("adjacency");
+                w(f, format!("{} {}", name, topology))
+            },
+            93 | 147 | 148 => {
+                let primitive = bits(11, 6);
+                let value = match (self.opcode, primitive) {
+                    (93, 1) => String::from("point"),
+                    (93, 2) => String::from("line"),
+                    (93, 3) => String::from("triangle"),
+                    (93, 6) => String::from("lineadj"),
+                    (93, 7) => String::from("triangleadj"),
+                    (93, n) if n >= 8 => format!("patch{}", n - 7),
+                    (_, n)  => format!("{}", n),
+                };
+                w(f, format!("{} {}", name, value))
+            },
+            95 | 101 => w(f, format!("{} {}", name, tokens.operand()?)),
+            96 | 97 | 102 | 103 => {
+                let operand = tokens.operand()?;
+                w(f, format!("{} {}, {}", name, operand, lookup(&SYSTEM_VALUES[..], tokens.token()?)))
+            },
+            98..=100 => {
+                let mode = lookup(&INTERPOLATION_MODES[..], bits(11, 4));
+                let operand = tokens.operand()?;
+                if self.opcode == 98 { w(f, format!("{} {} {}", name, mode, operand)) }
+                else { w(f, format!("{} {} {}, {}", name, mode, operand, lookup(&SYSTEM_VALUES[..], tokens.token()?))) }
+            },
+            104 | 94 | 206 | 153 | 154 => w(f, format!("{} {}", name, tokens.token()?)),
+            105 => w(f, format!("{} x{}[{}], {}", name, tokens.token()?, tokens.token()?, tokens.token()?)),
+            106 => {
+                let flags = GLOBAL_FLAGS.iter().enumerate().filter(|&(i, _)| token & (1 << (11 + i)) != 0).map(|(_, &f)| f).collect::<Vec<_>>();
+                w(f, format!("{} {}", name, flags.join(" | ")))
+            },
+            143 => w(f, format!("{} {}", name, tokens.operand()?)),
+            149 => w(f, format!("{} {}", name, ["domain_undefined", "domain_isoline", "domain_tri", "domain_quad"].get(bits(11, 2) as usize).cloned().unwrap_or("domain_unknown"))),
+            150 => w(f, format!("{} {}", name, ["partitioning_undefined", "partitioning_integer", "partitioning_pow2", "partitioning_fractional_odd", "partitioning_fractional_even"].get(bits(11, 3) as usize).cloned().unwrap_or("partitioning_unknown"))),
+            151 => w(f, format!("{} {}", name, ["output_undefined", "output_point", "output_line", "output_triangle_cw", "output_triangle_ccw"].get(bits(11, 3) as usize).cloned().unwrap_or("output_unknown"))),
+            152 => w(f, format!("{} l({})", name, immediate32(tokens.token()?))),
+            155 => w(f, format!("{} {}, {}, {}", name, tokens.token()?, tokens.token()?, tokens.token()?)),
+            156 => {
+                let dimension = lookup(&RESOURCE_DIMENSIONS[..], bits(11, 5));
+                let glc = if bits(16, 1) != 0 { "_glc" } else { "" };
+                let operand = tokens.operand()?;
+                w(f, format!("dcl_uav_typed_{}{} {} {}", dimension, glc, return_types(tokens.token()?), operand))
+            },
+            157 | 158 => {
+                let glc = if bits(16, 1) != 0 { "_glc" } else { "" };
+                let opc = if bits(23, 1) != 0 { "_opc" } else { "" };
+                let operand = tokens.operand()?;
+                if self.opcode == 157 { w(f, format!("{}{}{} {}", name, glc, opc, operand)) }
+                else { w(f, format!("{}{}{} {}, {}", name, glc, opc, operand, tokens.token()?)) }
+            },
+            159 | 162 => { let operand = tokens.operand()?; w(f, format!("{} {}, {}", name, operand, tokens.token()?)) },
+            160 => { let operand = tokens.operand()?; w(f, format!("{} {}, {}, {}", name, operand, tokens.token()?, tokens.token()?)) },
+            161 => w(f, format!("{} {}", name, tokens.operand()?)),
+            144 => w(f, format!("{} fb{}", name, tokens.token()?)),
+            145 => {
+                let table = tokens.token()?;
+                let bodies = (0..tokens.token()?).map(|_| tokens.token().map(|body| format!("fb{}", body))).collect::<Result<Vec<_>>>()?;
+                w(f, format!("{} ft{} = {{{}}}", name, table, bodies.join(", ")))
+            },
+            146 => {
+                let dynamic = if bits(11, 1) != 0 { "_dynamicindexed" } else { "" };
+                let interface = tokens.token()?;
+                let table_length = tokens.token()?;
+                let lengths = tokens.token()?;
+                let tables = (0..(lengths & 0xFFFF)).map(|_| tokens.token().map(|table| format!("ft{}", table))).collect::<Result<Vec<_>>>()?;
+                w(f, format!("{}{} fp{}[{}][{}] = {{{}}};", name, dynamic, interface, lengths >> 16, table_length, tables.join(", ")))
+            },
+            _ => {
+                // Interface declarations:  raw tokens
+                let raw = self.tokens[1..].iter().map(|t| format!("0x{:08x}", t)).collect::<Vec<_>>();
+                tokens.next = self.tokens.len();
+                w(f, format!("{} {}", name, raw.join(", ")))
+            },
+        }
+    }
+
+    fn fmt_customdata (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let class = self.tokens[0] >> 11;
+        let data = &self.tokens[2.min(self.tokens.len())..];
+        match class {
+            3 => {
+                writeln!(f, "dcl_immediateConstantBuffer {{")?;
+                let rows = data.chunks(4).map(|row| format!("{{ {} }}", row.iter().map(|&v| immediate32(v)).collect::<Vec<_>>().join(", "))).collect::<Vec<_>>();
+                write!(f, "{}\n}}", rows.join(",\n"))
+            },
+            0 => write!(f, "// comment: {} dwords", data.len()),
+            1 => write!(f, "// debug info: {} dwords", data.len()),
+            _ => write!(f, "// customdata class {}: {} dwords", class, data.len()),
+        }
+    }
+
+    fn fmt_instruction (&self, f: &mut fmt::Formatter, tokens: &mut Tokens) -> Result<()> {
+        let token = self.tokens[0];
+        let mut name = self.name();
+
+        // Extended opcode tokens:  sample offsets, resource dimension, and return type.
+        let mut extended = token & 0x8000_0000 != 0;
+        let mut suffix = String::new();
+        while extended {
+            let ext = tokens.token()?;
+            match ext & 0x3F {
+                1 => {
+                    let offset = |shift: u32| (((ext >> shift) & 0xF) as i32) << 28 >> 28;
+                    name.push_str(&format!("_aoffimmi({},{},{})", offset(9), offset(13), offset(17)));
+                },
+                2 => {
+                    let dimension = lookup(&RESOURCE_DIMENSIONS[..], (ext >> 6) & 0x1F);
+                    let stride = (ext >> 11) & 0xFFF;
+                    if stride != 0 { name.push_str(&format!("_indexable({}, stride={})", dimension, stride)); }
+                    else { name.push_str(&format!("_indexable({})", dimension)); }
+                },
+                3 => suffix = return_types(ext >> 6),
+                _ => {},
+            }
+            extended = ext & 0x8000_0000 != 0;
+        }
+        name.push_str(&suffix);
+
+        match self.opcode {
+            61 => name.push_str(["_float", "_rcpFloat", "_uint"].get(((token >> 11) & 3) as usize).cloned().unwrap_or("")),
+            111 if (token >> 11) & 1 != 0 => name.push_str("_uint"),
+            190 => for &(bit, flag) in [(8, "_uglobal"), (4, "_ugroup"), (2, "_g"), (1, "_t")].iter() {
+                if (token >> 11) & bit != 0 { name.push_str(flag); }
+            },
+            _ => {},
+        }
+        if CONDITIONAL.contains(&self.opcode) { name.push_str(if token & (1 << 18) != 0 { "_nz" } else { "_z" }); }
+        if token & (1 << 13) != 0 && self.opcode != 190 { name.push_str("_sat"); }
+
+        let float = FLOAT.contains(&self.opcode);
+        let mut operands = Vec::new();
+        while tokens.remaining() { operands.push(Operand { float, ..tokens.operand()? }.to_string()); }
+        let text = if operands.is_empty() { name } else { format!("{} {}", name, operands.join(", ")) };
+        f.write_str(&text).map_err(|_| Error::Unsupported("formatting"))
+    }
+}
+
+fn return_types (token: u32) -> String {
+    let types = (0..4).map(|c| lookup(&RETURN_TYPES[..], (token >> (4 * c)) & 0xF)).collect::<Vec<_>>();
+    format!("({})", types.join(","))
+}
+
+fn is_declaration (opcode: u32) -> bool {
+    (88..=106).contains(&opcode) || (143..=162).contains(&opcode) || opcode == 206
+}
+
+impl<'a> fmt::Display for Instruction<'a> {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.opcode == OPCODE_CUSTOMDATA { return self.fmt_customdata(f); }
+        let mut tokens = Tokens { instruction: self, next: 1 };
+        let result = if is_declaration(self.opcode) { self.fmt_declaration(f, &mut tokens) } else { self.fmt_instruction(f, &mut tokens) };
+        match result {
+            Ok(())  => Ok(()),
+            // Don't lose the rest of the listing to one odd instruction
+            Err(e)  => write!(f, "// {} (opcode {}): {:?}", self.name(), self.opcode, e),
+        }
+    }
+}
@@ -0,0 +1,836 @@
+//! Compiled effects (`fx_4_0`, `fx_4_1`, `fx_5_0`):  the Effects framework's techniques, passes, state blocks, and
+//! variables, from the `FX10` chunk `D3DCompile` wraps them in.
+//!
+//! The chunk is a header of counts, an "unstructured" blob of strings, types, default values and shader bytecode,
+//! then a "structured" stream of constant buffers, variables, groups, techniques and passes, which refer into the
+//! blob by offset.  Layouts follow Effects11's `EffectBinaryFormat.h`.  `d3d11::Effect` creates the shaders and
+//! states a pass needs from a parsed `Effect`.
+//!
+//! Effect pools (`fx_4_*` shared variables) aren't supported.  Expressions are returned as FXLVM code, unevaluated.
+
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EffectVersion {
+    Fx4_0,
+    Fx4_1,
+    Fx5_0,
+}
+
+impl EffectVersion {
+    /// Decode the tag that starts an `FX10` chunk.
+    pub fn from_tag (tag: u32) -> Option<Self> {
+        match tag {
+            0xFEFF1001 => Some(EffectVersion::Fx4_0),
+            0xFEFF1011 => Some(EffectVersion::Fx4_1),
+            0xFEFF2001 => Some(EffectVersion::Fx5_0),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Effect {
+    pub version:            EffectVersion,
+    pub constant_buffers:   Vec<ConstantBuffer>,
+    pub objects:            Vec<ObjectVariable>,
+    /// `fx_5_0` only.
+    pub interfaces:         Vec<InterfaceVariable>,
+    /// Techniques outside of any `fxgroup` (all of them, before `fx_5_0`) are in a group without a name.
+    pub groups:             Vec<Group>,
+}
+
+impl Effect {
+    /// Parse a compiled effect, as returned by `D3DCompile` for an `fx_*` target.
+    pub fn parse (bytecode: &[u8]) -> Result<Self> {
+        let container = Container::parse(bytecode)?;
+        let chunk = container.chunk(FourCC::FX10).ok_or(Error::MissingChunk(FourCC::FX10))?;
+        Self::parse_chunk(chunk.data)
+    }
+
+    /// Parse the contents of an `FX10` chunk.
+    pub fn parse_chunk (data: &[u8]) -> Result<Self> {
+        let version = EffectVersion::from_tag(u32_at(data, 0)?).ok_or(Error::Unsupported("not an effect, or an unknown effect version"))?;
+        let header = |index: usize| u32_at(data, 4 * index);
+
+        // SBinaryHeader:  tag, { buffers, numeric variables, object variables } for the effect then its pool,
+        // techniques, unstructured size, and more counts this doesn't need.  fx_5_0 appends groups, UAVs, and
+        // interface variables.
+        let buffer_count    = header(1)?;
+        let object_count    = header(3)?;
+        if header(4)? != 0 || header(5)? != 0 || header(6)? != 0 { return Err(Error::Unsupported("effect pools")); }
+        let technique_count = header(7)?;
+        let blob_size       = header(8)? as usize;
+        let (header_size, group_count, interface_count) = match version {
+            EffectVersion::Fx5_0    => (24 * 4, header(19)?, header(21)?),
+            _                       => (19 * 4, 0, 0),
+        };
+        let blob_end = header_size + blob_size;
+        if blob_end > data.len() { return Err(Error::Truncated { offset: data.len() }); }
+
+        let mut parser = Parser { data, blob_start: header_size, blob_end, position: blob_end };
+        let constant_buffers = (0..buffer_count).map(|_| parser.constant_buffer()).collect::<Result<Vec<_>>>()?;
+        let objects = (0..object_count).map(|_| parser.object_variable()).collect::<Result<Vec<_>>>()?;
+        let interfaces = (0..interface_count).map(|_| parser.interface_variable()).collect::<Result<Vec<_>>>()?;
+        let groups = match version {
+            EffectVersion::Fx5_0    => (0..group_count).map(|_| parser.group()).collect::<Result<Vec<_>>>()?,
+            _                       => vec![Group {
+                name:           None,
+                annotations:    Vec::new(),
+                techniques:     (0..technique_count).map(|_| parser.technique()).collect::<Result<Vec<_>>>()?,
+            }],
+        };
+        Ok(Self { version, constant_buffers, objects, interfaces, groups })
+    }
+
+    /// Every technique, in every group.
+    pub fn techniques (&self) -> impl Iterator<Item = &Technique> {
+        self.groups.iter().flat_map(|g| g.techniques.iter())
+    }
+
+    pub fn technique (&self, name: &str) -> Option<&Technique> {
+        self.techniques().find(|t| t.name == name)
+    }
+
+    pub fn group (&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name.as_deref() == Some(name))
+    }
+
+    pub fn object (&self, name: &str) -> Option<&ObjectVariable> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+
+    pub fn variable (&self, name: &str) -> Option<&NumericVariable> {
+        self.constant_buffers.iter().flat_map(|cb| cb.variables.iter()).find(|v| v.name == name)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConstantBuffer {
+    pub name:                   String,
+    pub size:                   u32,
+    pub tbuffer:                bool,
+    /// From `register(bN)`.
+    pub explicit_bind_point:    Option<u32>,
+    pub annotations:            Vec<Annotation>,
+    pub variables:              Vec<NumericVariable>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct NumericVariable {
+    pub name:           String,
+    pub semantic:       Option<String>,
+    pub ty:             Type,
+    /// In bytes, from the start of the constant buffer.
+    pub offset:         u32,
+    /// Packed (`ty.packed_size` bytes), not laid out as in the constant buffer.
+    pub default_value:  Option<Vec<u8>>,
+    pub flags:          u32,
+    pub annotations:    Vec<Annotation>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ObjectVariable {
+    pub name:                   String,
+    pub semantic:               Option<String>,
+    pub ty:                     Type,
+    /// From `register(...)`.
+    pub explicit_bind_point:    Option<u32>,
+    pub initializer:            ObjectInitializer,
+    pub annotations:            Vec<Annotation>,
+}
+
+/// An object variable's value, one per array element.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ObjectInitializer {
+    /// Textures, buffers, views, and the like, which the application binds.
+    None,
+    Strings(Vec<String>),
+    /// Blend, depth stencil, rasterizer and sampler states, as the assignments in their `{ ... }` blocks.
+    States(Vec<Vec<Assignment>>),
+    Shaders(Vec<Shader>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InterfaceVariable {
+    pub name:           String,
+    pub ty:             Type,
+    /// The class instances it's initialized to, one per element.  Empty if uninitialized.
+    pub initializers:   Vec<InterfaceInitializer>,
+    pub flags:          u32,
+    pub annotations:    Vec<Annotation>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InterfaceInitializer {
+    pub instance:   String,
+    pub index:      u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Annotation {
+    pub name:   String,
+    pub ty:     Type,
+    pub value:  AnnotationValue,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AnnotationValue {
+    /// Packed, `ty.packed_size` bytes.
+    Numeric(Vec<u8>),
+    Strings(Vec<String>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Group {
+    pub name:           Option<String>,
+    pub annotations:    Vec<Annotation>,
+    pub techniques:     Vec<Technique>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Technique {
+    pub name:           String,
+    pub annotations:    Vec<Annotation>,
+    pub passes:         Vec<Pass>,
+}
+
+impl Technique {
+    pub fn pass (&self, name: &str) -> Option<&Pass> { self.passes.iter().find(|p| p.name == name) }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pass {
+    pub name:           String,
+    pub annotations:    Vec<Annotation>,
+    /// `SetVertexShader(...)`, `SetBlendState(...)` and so on, in order.
+    pub assignments:    Vec<Assignment>,
+}
+
+/// `state[index] = value`, in a pass or state block.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Assignment {
+    pub state:  State,
+    /// Render target for blend states, otherwise 0.
+    pub index:  u32,
+    pub value:  AssignmentValue,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AssignmentValue {
+    /// Usually one, four for colors.  `NULL` objects are a single 0.
+    Constants(Vec<Constant>),
+    /// A variable by name.
+    Variable(String),
+    /// `array[index]`
+    ConstIndex { array: String, index: u32 },
+    /// `array[index_variable]`
+    VariableIndex { array: String, index_variable: String },
+    /// `array[expression]`, as FXLVM code.
+    ExpressionIndex { array: String, code: Vec<u8> },
+    /// FXLVM code.
+    Expression(Vec<u8>),
+    /// `CompileShader(...)` inline in a pass.
+    Shader(Shader),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Constant {
+    Float(f32),
+    Int(i32),
+    UInt(u32),
+    Bool(bool),
+}
+
+impl Constant {
+    pub fn as_u32 (self) -> u32 {
+        match self {
+            Constant::Float(f)  => f as u32,
+            Constant::Int(i)    => i as u32,
+            Constant::UInt(u)   => u,
+            Constant::Bool(b)   => b as u32,
+        }
+    }
+
+    pub fn as_i32 (self) -> i32 {
+        match self {
+            Constant::Float(f)  => f as i32,
+            Constant::Int(i)    => i,
+            Constant::UInt(u)   => u as i32,
+            Constant::Bool(b)   => b as i32,
+        }
+    }
+
+    pub fn as_f32 (self) -> f32 {
+        match self {
+            Constant::Float(f)  => f,
+            Constant::Int(i)    => i as f32,
+            Constant::UInt(u)   => u as f32,
+            Constant::Bool(b)   => if b { 1.0 } else { 0.0 },
+        }
+    }
+
+    pub fn as_bool (self) -> bool {
+        match self {
+            Constant::Float(f)  => f != 0.0,
+            Constant::Int(i)    => i != 0,
+            Constant::UInt(u)   => u != 0,
+            Constant::Bool(b)   => b,
+        }
+    }
+}
+
+/// A shader variable element, or an inline `CompileShader(...)`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Shader {
+    /// DXBC, or `None` for `NULL`.
+    pub bytecode:           Option<Vec<u8>>,
+    /// `ConstructGSWithSO` declarations, one per stream, e.g. `"SV_POSITION.xyz; TEXCOORD0.xy"`.
+    pub so_declarations:    Vec<String>,
+    pub rasterized_stream:  u32,
+    /// `fx_5_0` only.
+    pub interface_bindings: Vec<InterfaceInitializer>,
+}
+
+/// What an `Assignment` assigns to.  Variants are in the order of the binary format's state indices.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[allow(clippy::enum_variant_names)] // Effects11's names for them
+pub enum State {
+    // Passes
+    RasterizerState,
+    DepthStencilState,
+    BlendState,
+    RenderTargetView,
+    DepthStencilView,
+    GenerateMips,
+    VertexShader,
+    PixelShader,
+    GeometryShader,
+    StencilRef,
+    BlendFactor,
+    SampleMask,
+
+    // RasterizerState { ... }
+    FillMode,
+    CullMode,
+    FrontCounterClockwise,
+    DepthBias,
+    DepthBiasClamp,
+    SlopeScaledDepthBias,
+    DepthClipEnable,
+    ScissorEnable,
+    MultisampleEnable,
+    AntialiasedLineEnable,
+
+    // DepthStencilState { ... }
+    DepthEnable,
+    DepthWriteMask,
+    DepthFunc,
+    StencilEnable,
+    StencilReadMask,
+    StencilWriteMask,
+    FrontFaceStencilFail,
+    FrontFaceStencilDepthFail,
+    FrontFaceStencilPass,
+    FrontFaceStencilFunc,
+    BackFaceStencilFail,
+    BackFaceStencilDepthFail,
+    BackFaceStencilPass,
+    BackFaceStencilFunc,
+
+    // BlendState { ... }
+    AlphaToCoverageEnable,
+    BlendEnable,
+    SrcBlend,
+    DestBlend,
+    BlendOp,
+    SrcBlendAlpha,
+    DestBlendAlpha,
+    BlendOpAlpha,
+    RenderTargetWriteMask,
+
+    // SamplerState { ... }
+    Filter,
+    AddressU,
+    AddressV,
+    AddressW,
+    MipLODBias,
+    MaxAnisotropy,
+    ComparisonFunc,
+    BorderColor,
+    MinLOD,
+    MaxLOD,
+    Texture,
+
+    // Passes, fx_5_0
+    HullShader,
+    DomainShader,
+    ComputeShader,
+}
+
+impl State {
+    const ALL : [State; 59] = [
+        State::RasterizerState, State::DepthStencilState, State::BlendState, State::RenderTargetView,
+        State::DepthStencilView, State::GenerateMips, State::VertexShader, State::PixelShader, State::GeometryShader,
+        State::StencilRef, State::BlendFactor, State::SampleMask,
+
+        State::FillMode, State::CullMode, State::FrontCounterClockwise, State::DepthBias, State::DepthBiasClamp,
+        State::SlopeScaledDepthBias, State::DepthClipEnable, State::ScissorEnable, State::MultisampleEnable,
+        State::AntialiasedLineEnable,
+
+        State::DepthEnable, State::DepthWriteMask, State::DepthFunc, State::StencilEnable, State::StencilReadMask,
+        State::StencilWriteMask, State::FrontFaceStencilFail, State::FrontFaceStencilDepthFail,
+        State::FrontFaceStencilPass, State::FrontFaceStencilFunc, State::BackFaceStencilFail,
+        State::BackFaceStencilDepthFail, State::BackFaceStencilPass, State::BackFaceStencilFunc,
+
+        State::AlphaToCoverageEnable, State::BlendEnable, State::SrcBlend, State::DestBlend, State::BlendOp,
+        State::SrcBlendAlpha, State::DestBlendAlpha, State::BlendOpAlpha, State::RenderTargetWriteMask,
+
+        State::Filter, State::AddressU, State::AddressV, State::AddressW, State::MipLODBias, State::MaxAnisotropy,
+        State::ComparisonFunc, State::BorderColor, State::MinLOD, State::MaxLOD, State::Texture,
+
+        State::HullShader, State::DomainShader, State::ComputeShader,
+    ];
+
+    pub fn from_index (index: u32) -> Option<State> { Self::ALL.get(index as usize).cloned() }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Type {
+    /// e.g. `"float4"`, `"BlendState"`, or a struct's name.
+    pub name:           String,
+    pub class:          TypeClass,
+    /// Array length, or 0 if not an array.
+    pub elements:       u32,
+    pub total_size:     u32,
+    pub stride:         u32,
+    pub packed_size:    u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TypeClass {
+    Numeric(NumericType),
+    Object(ObjectType),
+    Struct(Vec<Member>),
+    Interface,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NumericType {
+    pub layout:         NumericLayout,
+    pub scalar:         ScalarType,
+    pub rows:           u8,
+    pub columns:        u8,
+    pub column_major:   bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum NumericLayout {
+    Scalar,
+    Vector,
+    Matrix,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ScalarType {
+    Float,
+    Int,
+    UInt,
+    Bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Member {
+    pub name:       String,
+    pub semantic:   Option<String>,
+    /// In bytes, from the start of the enclosing struct.
+    pub offset:     u32,
+    pub ty:         Type,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ObjectType {
+    String,
+    Blend,
+    DepthStencil,
+    Rasterizer,
+    PixelShader,
+    VertexShader,
+    GeometryShader,
+    GeometryShaderSO,
+    Texture,
+    Texture1D,
+    Texture1DArray,
+    Texture2D,
+    Texture2DArray,
+    Texture2DMS,
+    Texture2DMSArray,
+    Texture3D,
+    TextureCube,
+    ConstantBuffer,
+    RenderTargetView,
+    DepthStencilView,
+    Sampler,
+    Buffer,
+    TextureCubeArray,
+    PixelShader5,
+    VertexShader5,
+    GeometryShader5,
+    ComputeShader5,
+    HullShader5,
+    DomainShader5,
+    RWTexture1D,
+    RWTexture1DArray,
+    RWTexture2D,
+    RWTexture2DArray,
+    RWTexture3D,
+    RWBuffer,
+    ByteAddressBuffer,
+    RWByteAddressBuffer,
+    StructuredBuffer,
+    RWStructuredBuffer,
+    RWStructuredBufferAlloc,
+    RWStructuredBufferConsume,
+    AppendStructuredBuffer,
+    ConsumeStructuredBuffer,
+}
+
+impl ObjectType {
+    fn from_u32 (value: u32) -> Option<ObjectType> {
+        use ObjectType::*;
+        const BEFORE_5 : [ObjectType; 23] = [
+            String, Blend, DepthStencil, Rasterizer, PixelShader, VertexShader, GeometryShader, GeometryShaderSO,
+            Texture, Texture1D, Texture1DArray, Texture2D, Texture2DArray, Texture2DMS, Texture2DMSArray, Texture3D,
+            TextureCube, ConstantBuffer, RenderTargetView, DepthStencilView, Sampler, Buffer, TextureCubeArray,
+        ];
+        const FROM_5 : [ObjectType; 20] = [
+            PixelShader5, VertexShader5, GeometryShader5, ComputeShader5, HullShader5, DomainShader5, RWTexture1D,
+            RWTexture1DArray, RWTexture2D, RWTexture2DArray, RWTexture3D, RWBuffer, ByteAddressBuffer,
+            RWByteAddressBuffer, StructuredBuffer, RWStructuredBuffer, RWStructuredBufferAlloc,
+            RWStructuredBufferConsume, AppendStructuredBuffer, ConsumeStructuredBuffer,
+        ];
+        match value {
+            1 ..= 23    => Some(BEFORE_5[value as usize - 1]),
+            25 ..= 44   => Some(FROM_5[value as usize - 25]), // 24 was EOT_Count before fx_5_0
+            _           => None,
+        }
+    }
+
+    /// The stage a shader type is for, `None` if it isn't a shader.
+    pub fn program_type (self) -> Option<ProgramType> {
+        match self {
+            ObjectType::PixelShader | ObjectType::PixelShader5                                  => Some(ProgramType::Pixel),
+            ObjectType::VertexShader | ObjectType::VertexShader5                                => Some(ProgramType::Vertex),
+            ObjectType::GeometryShader | ObjectType::GeometryShaderSO | ObjectType::GeometryShader5 => Some(ProgramType::Geometry),
+            ObjectType::HullShader5                                                             => Some(ProgramType::Hull),
+            ObjectType::DomainShader5                                                           => Some(ProgramType::Domain),
+            ObjectType::ComputeShader5                                                          => Some(ProgramType::Compute),
+            _                                                                                   => None,
+        }
+    }
+}
+
+/// Reads the structured stream in order, and the unstructured blob by offset.  All offsets in errors are from the
+/// start of the chunk.
+struct Parser<'a> {
+    data:       &'a [u8],
+    blob_start: usize,
+    blob_end:   usize,
+    position:   usize,
+}
+
+impl<'a> Parser<'a> {
+    const MAX_TYPE_DEPTH : usize = 32;
+
+    fn next (&mut self) -> Result<u32> {
+        let value = u32_at(self.data, self.position)?;
+        self.position += 4;
+        Ok(value)
+    }
+
+    fn next_string (&mut self) -> Result<String> { let offset = self.next()?; self.string(offset) }
+
+    fn next_optional_string (&mut self) -> Result<Option<String>> { let offset = self.next()?; self.optional_string(offset) }
+
+    fn next_type (&mut self) -> Result<Type> { let offset = self.next()?; self.ty(offset, 0) }
+
+    fn blob (&self) -> &'a [u8] { &self.data[..self.blob_end] }
+
+    /// The `index`th `u32` of the blob at `offset`.
+    fn blob_u32 (&self, offset: u32, index: usize) -> Result<u32> {
+        u32_at(self.blob(), self.blob_start + offset as usize + 4 * index)
+    }
+
+    fn string (&self, offset: u32) -> Result<String> {
+        Ok(str_at(self.blob(), self.blob_start + offset as usize)?.to_string())
+    }
+
+    /// Offset 0 is reserved for "none".
+    fn optional_string (&self, offset: u32) -> Result<Option<String>> {
+        if offset == 0 { Ok(None) } else { self.string(offset).map(Some) }
+    }
+
+    fn bytes (&self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let start = self.blob_start + offset;
+        start.checked_add(len).and_then(|end| self.blob().get(start..end)).map(|b| b.to_vec()).ok_or(Error::Truncated { offset: start })
+    }
+
+    /// A `u32` size followed by that many bytes.
+    fn block (&self, offset: u32) -> Result<Vec<u8>> {
+        let size = self.blob_u32(offset, 0)? as usize;
+        self.bytes(offset as usize + 4, size)
+    }
+
+    fn ty (&self, offset: u32, depth: usize) -> Result<Type> {
+        if depth > Self::MAX_TYPE_DEPTH { return Err(Error::Unsupported("types nested too deeply (cyclic?)")); }
+        let class = match self.blob_u32(offset, 1)? {
+            1 => {
+                let bits = self.blob_u32(offset, 6)?;
+                TypeClass::Numeric(NumericType {
+                    layout: match bits & 0x7 {
+                        1 => NumericLayout::Scalar,
+                        2 => NumericLayout::Vector,
+                        3 => NumericLayout::Matrix,
+                        _ => return Err(Error::Unsupported("unknown numeric layout")),
+                    },
+                    scalar: match (bits >> 3) & 0x1F {
+                        1 => ScalarType::Float,
+                        2 => ScalarType::Int,
+                        3 => ScalarType::UInt,
+                        4 => ScalarType::Bool,
+                        _ => return Err(Error::Unsupported("unknown scalar type")),
+                    },
+                    rows:           ((bits >> 8) & 0x7) as u8,
+                    columns:        ((bits >> 11) & 0x7) as u8,
+                    column_major:   (bits >> 14) & 1 != 0,
+                })
+            },
+            2 => TypeClass::Object(ObjectType::from_u32(self.blob_u32(offset, 6)?).ok_or(Error::Unsupported("unknown object type"))?),
+            3 => {
+                let count = self.blob_u32(offset, 6)? as usize;
+                TypeClass::Struct((0..count).map(|m| Ok(Member {
+                    name:       self.string(self.blob_u32(offset, 7 + 4 * m)?)?,
+                    semantic:   self.optional_string(self.blob_u32(offset, 8 + 4 * m)?)?,
+                    offset:     self.blob_u32(offset, 9 + 4 * m)?,
+                    ty:         self.ty(self.blob_u32(offset, 10 + 4 * m)?, depth + 1)?,
+                })).collect::<Result<Vec<_>>>()?)
+            },
+            4 => TypeClass::Interface,
+            _ => return Err(Error::Unsupported("unknown variable type")),
+        };
+        Ok(Type {
+            name:           self.string(self.blob_u32(offset, 0)?)?,
+            class,
+            elements:       self.blob_u32(offset, 2)?,
+            total_size:     self.blob_u32(offset, 3)?,
+            stride:         self.blob_u32(offset, 4)?,
+            packed_size:    self.blob_u32(offset, 5)?,
+        })
+    }
+
+    fn annotations (&mut self) -> Result<Vec<Annotation>> {
+        let count = self.next()?;
+        (0..count).map(|_| {
+            let name = self.next_string()?;
+            let ty = self.next_type()?;
+            let value = match ty.class {
+                TypeClass::Numeric(_)                   => { let offset = self.next()?; AnnotationValue::Numeric(self.bytes(offset as usize, ty.packed_size as usize)?) },
+                TypeClass::Object(ObjectType::String)   => AnnotationValue::Strings((0..ty.elements.max(1)).map(|_| self.next_string()).collect::<Result<Vec<_>>>()?),
+                _                                       => return Err(Error::Unsupported("annotations other than numbers and strings")),
+            };
+            Ok(Annotation { name, ty, value })
+        }).collect()
+    }
+
+    fn constant_buffer (&mut self) -> Result<ConstantBuffer> {
+        let name            = self.next_string()?;
+        let size            = self.next()?;
+        let flags           = self.next()?;
+        let variable_count  = self.next()?;
+        let bind_point      = self.next()?;
+        let annotations     = self.annotations()?;
+        let variables       = (0..variable_count).map(|_| self.numeric_variable()).collect::<Result<Vec<_>>>()?;
+        Ok(ConstantBuffer {
+            name,
+            size,
+            tbuffer:                flags & 1 != 0,
+            explicit_bind_point:    if bind_point == !0 { None } else { Some(bind_point) },
+            annotations,
+            variables,
+        })
+    }
+
+    fn numeric_variable (&mut self) -> Result<NumericVariable> {
+        let name            = self.next_string()?;
+        let ty              = self.next_type()?;
+        let semantic        = self.next_optional_string()?;
+        let offset          = self.next()?;
+        let default_value   = self.next()?;
+        let flags           = self.next()?;
+        let default_value   = if default_value == 0 { None } else { Some(self.bytes(default_value as usize, ty.packed_size as usize)?) };
+        let annotations     = self.annotations()?;
+        Ok(NumericVariable { name, semantic, ty, offset, default_value, flags, annotations })
+    }
+
+    fn object_variable (&mut self) -> Result<ObjectVariable> {
+        let name        = self.next_string()?;
+        let ty          = self.next_type()?;
+        let semantic    = self.next_optional_string()?;
+        let bind_point  = self.next()?;
+        let object_type = match ty.class { TypeClass::Object(object_type) => object_type, _ => return Err(Error::Unsupported("object variable of non-object type")) };
+
+        let elements = 0..ty.elements.max(1);
+        let initializer = match object_type {
+            ObjectType::String => ObjectInitializer::Strings(elements.map(|_| self.next_string()).collect::<Result<Vec<_>>>()?),
+            ObjectType::Blend | ObjectType::DepthStencil | ObjectType::Rasterizer | ObjectType::Sampler => {
+                ObjectInitializer::States(elements.map(|_| {
+                    let count = self.next()?;
+                    (0..count).map(|_| self.assignment()).collect::<Result<Vec<_>>>()
+                }).collect::<Result<Vec<_>>>()?)
+            },
+            ObjectType::PixelShader | ObjectType::VertexShader | ObjectType::GeometryShader => {
+                ObjectInitializer::Shaders(elements.map(|_| { let bytecode = self.next()?; self.shader_4(bytecode, 0) }).collect::<Result<Vec<_>>>()?)
+            },
+            ObjectType::GeometryShaderSO => {
+                ObjectInitializer::Shaders(elements.map(|_| {
+                    let shader = self.next()?;
+                    let so_declaration = self.next()?;
+                    self.shader_4(shader, so_declaration)
+                }).collect::<Result<Vec<_>>>()?)
+            },
+            ObjectType::PixelShader5 | ObjectType::VertexShader5 | ObjectType::GeometryShader5 |
+            ObjectType::ComputeShader5 | ObjectType::HullShader5 | ObjectType::DomainShader5 => {
+                ObjectInitializer::Shaders(elements.map(|_| {
+                    let mut fields = [0u32; 9];
+                    for field in fields.iter_mut() { *field = self.next()?; }
+                    self.shader_5(&fields)
+                }).collect::<Result<Vec<_>>>()?)
+            },
+            _ => ObjectInitializer::None,
+        };
+        let annotations = self.annotations()?;
+        Ok(ObjectVariable {
+            name,
+            semantic,
+            ty,
+            explicit_bind_point: if bind_point == !0 { None } else { Some(bind_point) },
+            initializer,
+            annotations,
+        })
+    }
+
+    fn interface_variable (&mut self) -> Result<InterfaceVariable> {
+        let name            = self.next_string()?;
+        let ty              = self.next_type()?;
+        let default_value   = self.next()?;
+        let flags           = self.next()?;
+        let initializers    = if default_value == 0 { Vec::new() } else { self.interface_initializers(default_value, ty.elements.max(1) as usize)? };
+        let annotations     = self.annotations()?;
+        Ok(InterfaceVariable { name, ty, initializers, flags, annotations })
+    }
+
+    /// `SBinaryInterfaceInitializer[count]` at `offset`.
+    fn interface_initializers (&self, offset: u32, count: usize) -> Result<Vec<InterfaceInitializer>> {
+        (0..count).map(|i| Ok(InterfaceInitializer {
+            instance:   self.string(self.blob_u32(offset, 2 * i)?)?,
+            index:      self.blob_u32(offset, 2 * i + 1)?,
+        })).collect()
+    }
+
+    fn group (&mut self) -> Result<Group> {
+        let name            = self.next_optional_string()?;
+        let technique_count = self.next()?;
+        let annotations     = self.annotations()?;
+        let techniques      = (0..technique_count).map(|_| self.technique()).collect::<Result<Vec<_>>>()?;
+        Ok(Group { name, annotations, techniques })
+    }
+
+    fn technique (&mut self) -> Result<Technique> {
+        let name            = self.next_string()?;
+        let pass_count      = self.next()?;
+        let annotations     = self.annotations()?;
+        let passes          = (0..pass_count).map(|_| self.pass()).collect::<Result<Vec<_>>>()?;
+        Ok(Technique { name, annotations, passes })
+    }
+
+    fn pass (&mut self) -> Result<Pass> {
+        let name                = self.next_string()?;
+        let assignment_count    = self.next()?;
+        let annotations         = self.annotations()?;
+        let assignments         = (0..assignment_count).map(|_| self.assignment()).collect::<Result<Vec<_>>>()?;
+        Ok(Pass { name, annotations, assignments })
+    }
+
+    fn assignment (&mut self) -> Result<Assignment> {
+        let state       = State::from_index(self.next()?).ok_or(Error::Unsupported("unknown state"))?;
+        let index       = self.next()?;
+        let kind        = self.next()?;
+        let initializer = self.next()?;
+        let value = match kind {
+            1 => {
+                let count = self.blob_u32(initializer, 0)? as usize;
+                AssignmentValue::Constants((0..count).map(|i| {
+                    let value = self.blob_u32(initializer, 2 + 2 * i)?;
+                    Ok(match self.blob_u32(initializer, 1 + 2 * i)? {
+                        1 => Constant::Float(f32::from_bits(value)),
+                        2 => Constant::Int(value as i32),
+                        3 => Constant::UInt(value),
+                        4 => Constant::Bool(value != 0),
+                        _ => return Err(Error::Unsupported("unknown constant type")),
+                    })
+                }).collect::<Result<Vec<_>>>()?)
+            },
+            2 => AssignmentValue::Variable(self.string(initializer)?),
+            3 => AssignmentValue::ConstIndex {
+                array:          self.string(self.blob_u32(initializer, 0)?)?,
+                index:          self.blob_u32(initializer, 1)?,
+            },
+            4 => AssignmentValue::VariableIndex {
+                array:          self.string(self.blob_u32(initializer, 0)?)?,
+                index_variable: self.string(self.blob_u32(initializer, 1)?)?,
+            },
+            5 => AssignmentValue::ExpressionIndex {
+                array:          self.string(self.blob_u32(initializer, 0)?)?,
+                code:           self.block(self.blob_u32(initializer, 1)?)?,
+            },
+            6 => AssignmentValue::Expression(self.block(initializer)?),
+            7 => AssignmentValue::Shader(self.shader_4(self.blob_u32(initializer, 0)?, self.blob_u32(initializer, 1)?)?),
+            8 => {
+                let mut fields = [0u32; 9];
+                for (i, field) in fields.iter_mut().enumerate() { *field = self.blob_u32(initializer, i)?; }
+                AssignmentValue::Shader(self.shader_5(&fields)?)
+            },
+            _ => return Err(Error::Unsupported("unknown assignment type")),
+        };
+        Ok(Assignment { state, index, value })
+    }
+
+    /// Bytecode at `offset`, or `None` for `NULL` shaders.
+    fn bytecode (&self, offset: u32) -> Result<Option<Vec<u8>>> {
+        if offset == 0 { return Ok(None); }
+        let bytecode = self.block(offset)?;
+        Ok(if bytecode.is_empty() { None } else { Some(bytecode) })
+    }
+
+    /// An `fx_4_*` shader:  bytecode, and an optional stream output declaration.
+    fn shader_4 (&self, bytecode: u32, so_declaration: u32) -> Result<Shader> {
+        Ok(Shader {
+            bytecode:           self.bytecode(bytecode)?,
+            so_declarations:    self.optional_string(so_declaration)?.into_iter().collect(),
+            rasterized_stream:  0,
+            interface_bindings: Vec::new(),
+        })
+    }
+
+    /// `SBinaryShaderData5`:  bytecode, 4 stream output declarations, how many of those are used, the rasterized
+    /// stream, and interface bindings' count and offset.
+    fn shader_5 (&self, fields: &[u32; 9]) -> Result<Shader> {
+        let so_count = (fields[5] as usize).min(4);
+        Ok(Shader {
+            bytecode:           self.bytecode(fields[0])?,
+            so_declarations:    fields[1..1+so_count].iter().map(|&o| self.string(o)).collect::<Result<Vec<_>>>()?,
+            rasterized_stream:  fields[6],
+            interface_bindings: if fields[7] == 0 { Vec::new() } else { self.interface_initializers(fields[8], fields[7] as usize)? },
+        })
+    }
+}
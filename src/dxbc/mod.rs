@@ -0,0 +1,149 @@
+//! Pure Rust parsing of DXBC containers, the format `D3DCompile` produces for shader models 4 and 5.
+//!
+//! Nothing in here touches `winapi`, so it works the same on every platform.  A container is a header followed by
+//! a table of chunks, each identified by a `FourCC`:  `RDEF` (resource definitions), `ISGN` / `OSGN` (signatures),
+//! `SHDR` / `SHEX` (bytecode), and so on.
+//!
+//! `parts` rebuilds containers with chunks stripped or replaced, and `checksum` re-signs them so D3D still accepts
+//! them.  `disasm` prints the bytecode as `fxc` style assembly.  `effect` parses compiled `fx_*` effects, which keep
+//! their own namespace since their types and variables overlap `reflect`'s.
+
+mod checksum;
+mod disasm;
+pub mod effect;
+mod parts;
+mod reflect;
+pub use checksum::*;
+pub use disasm::*;
+pub use parts::*;
+pub use reflect::*;
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FourCC(pub [u8; 4]);
+
+impl FourCC {
+    pub const DXBC : FourCC = FourCC(*b"DXBC");
+    pub const AON9 : FourCC = FourCC(*b"Aon9");
+    pub const RDEF : FourCC = FourCC(*b"RDEF");
+    pub const ISGN : FourCC = FourCC(*b"ISGN");
+    pub const ISG1 : FourCC = FourCC(*b"ISG1");
+    pub const OSGN : FourCC = FourCC(*b"OSGN");
+    pub const OSG5 : FourCC = FourCC(*b"OSG5");
+    pub const OSG1 : FourCC = FourCC(*b"OSG1");
+    pub const PCSG : FourCC = FourCC(*b"PCSG");
+    pub const PSG1 : FourCC = FourCC(*b"PSG1");
+    pub const SHDR : FourCC = FourCC(*b"SHDR");
+    pub const SHEX : FourCC = FourCC(*b"SHEX");
+    pub const STAT : FourCC = FourCC(*b"STAT");
+    pub const SDBG : FourCC = FourCC(*b"SDBG");
+    pub const SPDB : FourCC = FourCC(*b"SPDB");
+    pub const ILDB : FourCC = FourCC(*b"ILDB");
+    pub const ILDN : FourCC = FourCC(*b"ILDN");
+    pub const PRIV : FourCC = FourCC(*b"PRIV");
+    pub const RTS0 : FourCC = FourCC(*b"RTS0");
+    pub const XNAP : FourCC = FourCC(*b"XNAP");
+    pub const XNAS : FourCC = FourCC(*b"XNAS");
+    pub const FX10 : FourCC = FourCC(*b"FX10");
+}
+
+impl fmt::Debug for FourCC {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0[..]))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// Doesn't start with `"DXBC"`.
+    NotDxbc,
+    /// Something at `offset` runs past the end of the container or chunk it's in.
+    Truncated { offset: usize },
+    /// A string at `offset` isn't NUL terminated UTF-8.
+    BadString { offset: usize },
+    /// A required chunk is missing.
+    MissingChunk(FourCC),
+    /// Something the parser doesn't understand.
+    Unsupported(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub(crate) fn u8_at (data: &[u8], offset: usize) -> Result<u8> {
+    data.get(offset).cloned().ok_or(Error::Truncated { offset })
+}
+
+pub(crate) fn u16_at (data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data.get(offset..offset+2).ok_or(Error::Truncated { offset })?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub(crate) fn u32_at (data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data.get(offset..offset+4).ok_or(Error::Truncated { offset })?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+pub(crate) fn str_at (data: &[u8], offset: usize) -> Result<&str> {
+    let tail = data.get(offset..).ok_or(Error::Truncated { offset })?;
+    let len = tail.iter().position(|&b| b == 0).ok_or(Error::BadString { offset })?;
+    std::str::from_utf8(&tail[..len]).map_err(|_| Error::BadString { offset })
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Chunk<'a> {
+    pub fourcc: FourCC,
+    /// The chunk's contents, not including its 8 byte header.
+    pub data:   &'a [u8],
+}
+
+/// A parsed view of a DXBC container.  Borrows the bytes it was parsed from.
+#[derive(Clone, Debug)]
+pub struct Container<'a> {
+    bytes:  &'a [u8],
+    chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> Container<'a> {
+    /// Size of the fixed part of the header:  magic, checksum, version, total size, and chunk count.
+    pub const HEADER_SIZE : usize = 32;
+
+    pub fn parse (bytes: &'a [u8]) -> Result<Self> {
+        if bytes.get(0..4) != Some(&FourCC::DXBC.0[..]) { return Err(Error::NotDxbc); }
+        let total_size = u32_at(bytes, 24)? as usize;
+        let bytes = bytes.get(..total_size).ok_or(Error::Truncated { offset: total_size })?;
+        let chunk_count = u32_at(bytes, 28)? as usize;
+
+        let mut chunks = Vec::with_capacity(chunk_count.min(64));
+        for index in 0..chunk_count {
+            let offset = u32_at(bytes, Self::HEADER_SIZE + 4 * index)? as usize;
+            let fourcc = bytes.get(offset..offset+4).ok_or(Error::Truncated { offset })?;
+            let size = u32_at(bytes, offset + 4)? as usize;
+            let data = bytes.get(offset+8..offset+8+size).ok_or(Error::Truncated { offset: offset+8 })?;
+            chunks.push(Chunk { fourcc: FourCC([fourcc[0], fourcc[1], fourcc[2], fourcc[3]]), data });
+        }
+        Ok(Self { bytes, chunks })
+    }
+
+    /// The whole container, trimmed to the size its header claims.
+    pub fn bytes (&self) -> &'a [u8] { self.bytes }
+
+    /// The 16 byte hash stored in the header, see `verify_checksum`.
+    pub fn checksum (&self) -> [u8; 16] {
+        let mut checksum = [0u8; 16];
+        checksum.copy_from_slice(&self.bytes[4..20]);
+        checksum
+    }
+
+    pub fn chunks (&self) -> &[Chunk<'a>] { &self.chunks[..] }
+
+    /// The first chunk with `fourcc`, if any.
+    pub fn chunk (&self, fourcc: FourCC) -> Option<&Chunk<'a>> {
+        self.chunks.iter().find(|c| c.fourcc == fourcc)
+    }
+
+    /// The first chunk with any of `fourccs`, in order of preference.
+    pub fn chunk_any (&self, fourccs: &[FourCC]) -> Option<&Chunk<'a>> {
+        fourccs.iter().filter_map(|&fourcc| self.chunk(fourcc)).next()
+    }
+}
@@ -0,0 +1,138 @@
+//! Pure Rust `D3DStripShader`, `D3DGetBlobPart`, and `D3DSetBlobPart`:  rebuilding containers with chunks removed,
+//! extracted, or replaced.
+//!
+//! Rebuilt containers keep the original's header version, and are re-signed, so `Create*Shader` accepts them.
+
+use super::*;
+
+/// `D3DCOMPILER_STRIP_FLAGS`
+pub const STRIP_REFLECTION_DATA : u32 = 0x01;
+pub const STRIP_DEBUG_INFO      : u32 = 0x02;
+pub const STRIP_TEST_BLOBS      : u32 = 0x04;
+pub const STRIP_PRIVATE_DATA    : u32 = 0x08;
+pub const STRIP_ROOT_SIGNATURE  : u32 = 0x10;
+
+/// `D3D_BLOB_PART`, minus the `TEST_*` parts, which aren't documented.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BlobPart {
+    InputSignature,
+    OutputSignature,
+    InputAndOutputSignature,
+    PatchConstantSignature,
+    AllSignature,
+    DebugInfo,
+    LegacyShader,
+    XnaPrepassShader,
+    XnaShader,
+    Pdb,
+    PrivateData,
+    RootSignature,
+}
+
+impl BlobPart {
+    /// The chunks this part is made of, in order.  Each slot lists the alternatives a container might use instead.
+    fn chunks (self) -> &'static [&'static [FourCC]] {
+        const INPUT     : &[FourCC] = &[FourCC::ISGN, FourCC::ISG1];
+        const OUTPUT    : &[FourCC] = &[FourCC::OSGN, FourCC::OSG5, FourCC::OSG1];
+        const PATCH     : &[FourCC] = &[FourCC::PCSG, FourCC::PSG1];
+        match self {
+            BlobPart::InputSignature            => &[INPUT],
+            BlobPart::OutputSignature           => &[OUTPUT],
+            BlobPart::InputAndOutputSignature   => &[INPUT, OUTPUT],
+            BlobPart::PatchConstantSignature    => &[PATCH],
+            BlobPart::AllSignature              => &[INPUT, OUTPUT, PATCH],
+            BlobPart::DebugInfo                 => &[&[FourCC::SDBG]],
+            BlobPart::LegacyShader              => &[&[FourCC::AON9]],
+            BlobPart::XnaPrepassShader          => &[&[FourCC::XNAP]],
+            BlobPart::XnaShader                 => &[&[FourCC::XNAS]],
+            BlobPart::Pdb                       => &[&[FourCC::SPDB]],
+            BlobPart::PrivateData               => &[&[FourCC::PRIV]],
+            BlobPart::RootSignature             => &[&[FourCC::RTS0]],
+        }
+    }
+
+    /// Signature parts are returned as containers of just their chunks (which `CreateInputLayout` accepts), other
+    /// parts as the chunk's raw data.
+    fn is_signature (self) -> bool {
+        matches!(self,
+            BlobPart::InputSignature | BlobPart::OutputSignature | BlobPart::InputAndOutputSignature |
+            BlobPart::PatchConstantSignature | BlobPart::AllSignature
+        )
+    }
+}
+
+/// Build a container of `chunks`.  `version` is the header field after the checksum, 1 for everything `D3DCompile`
+/// currently produces.
+pub fn write_container (version: u32, chunks: &[Chunk]) -> Vec<u8> {
+    let header_size = Container::HEADER_SIZE + 4 * chunks.len();
+    let total_size = header_size + chunks.iter().map(|c| 8 + c.data.len()).sum::<usize>();
+
+    let mut bytes = Vec::with_capacity(total_size);
+    bytes.extend_from_slice(&FourCC::DXBC.0[..]);
+    bytes.extend_from_slice(&[0u8; 16]);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&(total_size as u32).to_le_bytes());
+    bytes.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    let mut offset = header_size;
+    for chunk in chunks.iter() {
+        bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += 8 + chunk.data.len();
+    }
+    for chunk in chunks.iter() {
+        bytes.extend_from_slice(&chunk.fourcc.0[..]);
+        bytes.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(chunk.data);
+    }
+    sign(&mut bytes[..]).unwrap(); // Just wrote a valid header
+    bytes
+}
+
+fn version (container: &Container) -> u32 {
+    u32_at(container.bytes(), 20).unwrap() // parse already read past this
+}
+
+/// Whether `STRIP_*` `flags` remove `fourcc`.
+fn stripped (fourcc: FourCC, flags: u32) -> bool {
+    let by = |flag: u32, fourccs: &[FourCC]| flags & flag != 0 && fourccs.contains(&fourcc);
+    by(STRIP_REFLECTION_DATA, &[FourCC::RDEF, FourCC::STAT])
+        || by(STRIP_DEBUG_INFO, &[FourCC::SDBG, FourCC::SPDB, FourCC::ILDB, FourCC::ILDN])
+        || by(STRIP_PRIVATE_DATA, &[FourCC::PRIV])
+        || by(STRIP_ROOT_SIGNATURE, &[FourCC::RTS0])
+}
+
+/// `D3DStripShader`:  `bytes` without the chunks `STRIP_*` `flags` select.  `STRIP_TEST_BLOBS` is accepted, but
+/// removes nothing.
+pub fn strip (bytes: &[u8], flags: u32) -> Result<Vec<u8>> {
+    let container = Container::parse(bytes)?;
+    let kept = container.chunks().iter().cloned().filter(|c| !stripped(c.fourcc, flags)).collect::<Vec<_>>();
+    Ok(write_container(version(&container), &kept[..]))
+}
+
+/// `D3DGetBlobPart`:  extract `part` from `bytes`.  Fails with `MissingChunk` if `bytes` doesn't have it.  Like
+/// `D3DGetBlobPart`, multi-chunk signature parts need every one of their chunks:  `AllSignature` fails on a vertex
+/// shader, which has no patch constant signature.
+pub fn get_part (bytes: &[u8], part: BlobPart) -> Result<Vec<u8>> {
+    let container = Container::parse(bytes)?;
+    let chunks = part.chunks().iter().map(|&alternatives| {
+        container.chunk_any(alternatives).cloned().ok_or(Error::MissingChunk(alternatives[0]))
+    }).collect::<Result<Vec<_>>>()?;
+    if part.is_signature() {
+        Ok(write_container(version(&container), &chunks[..]))
+    } else {
+        Ok(chunks[0].data.to_vec())
+    }
+}
+
+/// `D3DSetBlobPart`:  `bytes` with `part` replaced by `data` (or appended if it's missing.)  Like `D3DSetBlobPart`,
+/// only `PrivateData` and `RootSignature` can be set.
+pub fn set_part (bytes: &[u8], part: BlobPart, data: &[u8]) -> Result<Vec<u8>> {
+    let fourcc = match part {
+        BlobPart::PrivateData   => FourCC::PRIV,
+        BlobPart::RootSignature => FourCC::RTS0,
+        _                       => return Err(Error::Unsupported("only private data and root signatures can be set")),
+    };
+    let container = Container::parse(bytes)?;
+    let mut chunks = container.chunks().iter().cloned().filter(|c| c.fourcc != fourcc).collect::<Vec<_>>();
+    chunks.push(Chunk { fourcc, data });
+    Ok(write_container(version(&container), &chunks[..]))
+}
@@ -0,0 +1,339 @@
+//! Shader reflection:  what a compiled shader binds, and how its constant buffers are laid out.
+//!
+//! `reflect` reads this straight out of the `RDEF`, signature, and `SHDR` / `SHEX` chunks.  On Windows,
+//! `d3d::reflect` gets the same information from `D3DReflect` instead.  Enum-like fields hold the raw values of the
+//! corresponding `D3D_*` enums, so the two can be compared directly.
+
+use super::*;
+
+/// Which pipeline stage a shader is for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ProgramType {
+    Pixel,
+    Vertex,
+    Geometry,
+    Hull,
+    Domain,
+    Compute,
+}
+
+/// A shader's stage and model, e.g. `vs_5_0`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ShaderVersion {
+    pub program:    ProgramType,
+    pub major:      u8,
+    pub minor:      u8,
+}
+
+impl ShaderVersion {
+    /// Decode the version token that starts `SHDR` / `SHEX` chunks (also `D3D11_SHADER_DESC::Version`.)
+    pub fn from_token (token: u32) -> Option<Self> {
+        let program = match token >> 16 {
+            0 => ProgramType::Pixel,
+            1 => ProgramType::Vertex,
+            2 => ProgramType::Geometry,
+            3 => ProgramType::Hull,
+            4 => ProgramType::Domain,
+            5 => ProgramType::Compute,
+            _ => return None,
+        };
+        Some(Self { program, major: ((token >> 4) & 0xF) as u8, minor: (token & 0xF) as u8 })
+    }
+
+    /// Read the version token of `container`'s bytecode chunk.
+    pub fn from_container (container: &Container) -> Result<Self> {
+        let shader = container.chunk_any(&[FourCC::SHEX, FourCC::SHDR]).ok_or(Error::MissingChunk(FourCC::SHDR))?;
+        Self::from_token(u32_at(shader.data, 0)?).ok_or(Error::Unsupported("unknown program type"))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ShaderReflection {
+    pub version:            ShaderVersion,
+    pub creator:            String,
+    pub constant_buffers:   Vec<ConstantBuffer>,
+    pub bound_resources:    Vec<BoundResource>,
+    pub input_signature:    Vec<SignatureElement>,
+    pub output_signature:   Vec<SignatureElement>,
+    /// `[numthreads(x, y, z)]`, for compute shaders.
+    pub thread_group_size:  Option<[u32; 3]>,
+}
+
+impl ShaderReflection {
+    pub fn constant_buffer (&self, name: &str) -> Option<&ConstantBuffer> {
+        self.constant_buffers.iter().find(|cb| cb.name == name)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ConstantBuffer {
+    pub name:       String,
+    /// `D3D_CBUFFER_TYPE`
+    pub kind:       u32,
+    /// In bytes, including padding out to a multiple of 16.
+    pub size:       u32,
+    pub flags:      u32,
+    pub variables:  Vec<Variable>,
+}
+
+impl ConstantBuffer {
+    pub fn variable (&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Variable {
+    pub name:   String,
+    /// In bytes, from the start of the constant buffer.
+    pub offset: u32,
+    pub size:   u32,
+    /// `D3D_SHADER_VARIABLE_FLAGS`, e.g. `D3D_SVF_USED`.
+    pub flags:  u32,
+    pub ty:     Type,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Type {
+    /// `D3D_SHADER_VARIABLE_CLASS`, e.g. `D3D_SVC_VECTOR`.
+    pub class:      u32,
+    /// `D3D_SHADER_VARIABLE_TYPE`, e.g. `D3D_SVT_FLOAT`.
+    pub kind:       u32,
+    pub rows:       u32,
+    pub columns:    u32,
+    /// Array length, or 0 if not an array.
+    pub elements:   u32,
+    /// For structs.
+    pub members:    Vec<Member>,
+    /// Shader model 5 only, e.g. `"float4x4"`.
+    pub name:       Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Member {
+    pub name:   String,
+    /// In bytes, from the start of the enclosing struct.
+    pub offset: u32,
+    pub ty:     Type,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BoundResource {
+    pub name:           String,
+    /// `D3D_SHADER_INPUT_TYPE`, e.g. `D3D_SIT_TEXTURE`.
+    pub kind:           u32,
+    /// `D3D_RESOURCE_RETURN_TYPE`
+    pub return_type:    u32,
+    /// `D3D_SRV_DIMENSION`
+    pub dimension:      u32,
+    pub samples:        u32,
+    pub bind_point:     u32,
+    pub bind_count:     u32,
+    /// `D3D_SHADER_INPUT_FLAGS`
+    pub flags:          u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SignatureElement {
+    pub semantic_name:      String,
+    pub semantic_index:     u32,
+    /// `D3D_NAME`, e.g. `D3D_NAME_POSITION` for `SV_Position`.
+    pub system_value:       u32,
+    /// `D3D_REGISTER_COMPONENT_TYPE`
+    pub component_type:     u32,
+    pub register:           u32,
+    pub mask:               u8,
+    pub read_write_mask:    u8,
+    /// Geometry shader output stream.
+    pub stream:             u32,
+    /// `D3D_MIN_PRECISION`
+    pub min_precision:      u32,
+}
+
+/// Reflect a compiled shader without `D3DReflect`.
+pub fn reflect (bytecode: &[u8]) -> Result<ShaderReflection> {
+    let container = Container::parse(bytecode)?;
+    let version = ShaderVersion::from_container(&container)?;
+    let rdef = container.chunk(FourCC::RDEF).ok_or(Error::MissingChunk(FourCC::RDEF))?;
+    let rdef = Rdef::parse(rdef.data)?;
+
+    let signature = |fourccs: &[FourCC]| match container.chunk_any(fourccs) {
+        Some(chunk) => parse_signature(chunk.fourcc, chunk.data),
+        None        => Ok(Vec::new()),
+    };
+
+    Ok(ShaderReflection {
+        version,
+        creator:            rdef.creator()?,
+        constant_buffers:   rdef.constant_buffers()?,
+        bound_resources:    rdef.bound_resources()?,
+        input_signature:    signature(&[FourCC::ISG1, FourCC::ISGN])?,
+        output_signature:   signature(&[FourCC::OSG1, FourCC::OSG5, FourCC::OSGN])?,
+        thread_group_size:  thread_group_size(&container)?,
+    })
+}
+
+/// The `RDEF` chunk.  Every offset in it is relative to the start of the chunk's data.
+struct Rdef<'a> {
+    data:           &'a [u8],
+    variable_size:  usize,
+    type_size:      usize,
+}
+
+impl<'a> Rdef<'a> {
+    const CBUFFER_SIZE  : usize = 24;
+    const BINDING_SIZE  : usize = 32;
+    const MEMBER_SIZE   : usize = 12;
+    const MAX_TYPE_DEPTH : usize = 32;
+
+    fn parse (data: &'a [u8]) -> Result<Self> {
+        let major = u8_at(data, 17)?;
+        // Shader model 5 added an "RD11" header describing larger variable and type descriptions.
+        let (variable_size, type_size) = if major >= 5 && data.get(28..32) == Some(&b"RD11"[..]) {
+            (u32_at(data, 44)? as usize, u32_at(data, 48)? as usize)
+        } else {
+            (24, 16)
+        };
+        Ok(Self { data, variable_size, type_size })
+    }
+
+    fn string (&self, offset: u32) -> Result<String> { Ok(str_at(self.data, offset as usize)?.to_string()) }
+
+    fn creator (&self) -> Result<String> { self.string(u32_at(self.data, 24)?) }
+
+    fn constant_buffers (&self) -> Result<Vec<ConstantBuffer>> {
+        let count  = u32_at(self.data, 0)? as usize;
+        let offset = u32_at(self.data, 4)? as usize;
+        (0..count).map(|index| {
+            let cb = offset + index * Self::CBUFFER_SIZE;
+            let variable_count  = u32_at(self.data, cb +  4)? as usize;
+            let variable_offset = u32_at(self.data, cb +  8)? as usize;
+            let variables = (0..variable_count)
+                .map(|v| self.variable(variable_offset + v * self.variable_size))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(ConstantBuffer {
+                name:   self.string(u32_at(self.data, cb)?)?,
+                size:   u32_at(self.data, cb + 12)?,
+                flags:  u32_at(self.data, cb + 16)?,
+                kind:   u32_at(self.data, cb + 20)?,
+                variables,
+            })
+        }).collect()
+    }
+
+    fn variable (&self, offset: usize) -> Result<Variable> {
+        Ok(Variable {
+            name:   self.string(u32_at(self.data, offset)?)?,
+            offset: u32_at(self.data, offset +  4)?,
+            size:   u32_at(self.data, offset +  8)?,
+            flags:  u32_at(self.data, offset + 12)?,
+            ty:     self.ty(u32_at(self.data, offset + 16)? as usize, 0)?,
+        })
+    }
+
+    fn ty (&self, offset: usize, depth: usize) -> Result<Type> {
+        if depth > Self::MAX_TYPE_DEPTH { return Err(Error::Unsupported("types nested too deeply (cyclic?)")); }
+        let member_count  = u16_at(self.data, offset + 10)? as usize;
+        let member_offset = u32_at(self.data, offset + 12)? as usize;
+        let members = (0..member_count).map(|m| {
+            let member = member_offset + m * Self::MEMBER_SIZE;
+            Ok(Member {
+                name:   self.string(u32_at(self.data, member)?)?,
+                ty:     self.ty(u32_at(self.data, member + 4)? as usize, depth + 1)?,
+                offset: u32_at(self.data, member + 8)?,
+            })
+        }).collect::<Result<Vec<_>>>()?;
+
+        let name = if self.type_size >= 36 {
+            match u32_at(self.data, offset + 32)? {
+                0           => None,
+                name_offset => Some(self.string(name_offset)?),
+            }
+        } else {
+            None
+        };
+
+        Ok(Type {
+            class:      u16_at(self.data, offset    )? as u32,
+            kind:       u16_at(self.data, offset + 2)? as u32,
+            rows:       u16_at(self.data, offset + 4)? as u32,
+            columns:    u16_at(self.data, offset + 6)? as u32,
+            elements:   u16_at(self.data, offset + 8)? as u32,
+            members,
+            name,
+        })
+    }
+
+    fn bound_resources (&self) -> Result<Vec<BoundResource>> {
+        let count  = u32_at(self.data,  8)? as usize;
+        let offset = u32_at(self.data, 12)? as usize;
+        (0..count).map(|index| {
+            let rb = offset + index * Self::BINDING_SIZE;
+            Ok(BoundResource {
+                name:           self.string(u32_at(self.data, rb)?)?,
+                kind:           u32_at(self.data, rb +  4)?,
+                return_type:    u32_at(self.data, rb +  8)?,
+                dimension:      u32_at(self.data, rb + 12)?,
+                samples:        u32_at(self.data, rb + 16)?,
+                bind_point:     u32_at(self.data, rb + 20)?,
+                bind_count:     u32_at(self.data, rb + 24)?,
+                flags:          u32_at(self.data, rb + 28)?,
+            })
+        }).collect()
+    }
+}
+
+/// Parse an `ISGN` / `OSGN` / `PCSG` chunk, or one of their `OSG5` / `*SG1` variants.
+pub fn parse_signature (fourcc: FourCC, data: &[u8]) -> Result<Vec<SignatureElement>> {
+    // (element size, has stream, has min precision)
+    let (size, stream, min_precision) = match fourcc {
+        FourCC::ISGN | FourCC::OSGN | FourCC::PCSG  => (24, false, false),
+        FourCC::OSG5                                => (28, true,  false),
+        FourCC::ISG1 | FourCC::OSG1 | FourCC::PSG1  => (32, true,  true ),
+        _ => return Err(Error::Unsupported("not a signature chunk")),
+    };
+
+    let count = u32_at(data, 0)? as usize;
+    (0..count).map(|index| {
+        let element = 8 + index * size;
+        let (stream, fields) = if stream { (u32_at(data, element)?, element + 4) } else { (0, element) };
+        Ok(SignatureElement {
+            semantic_name:      str_at(data, u32_at(data, fields)? as usize)?.to_string(),
+            semantic_index:     u32_at(data, fields +  4)?,
+            system_value:       u32_at(data, fields +  8)?,
+            component_type:     u32_at(data, fields + 12)?,
+            register:           u32_at(data, fields + 16)?,
+            mask:               u8_at (data, fields + 20)?,
+            read_write_mask:    u8_at (data, fields + 21)?,
+            stream,
+            min_precision:      if min_precision { u32_at(data, fields + 24)? } else { 0 },
+        })
+    }).collect()
+}
+
+/// The D3D9 version token of the shader in `container`'s `Aon9` chunk, which `_level_9_*` targets add (e.g.
+/// `0xFFFF0200` for `ps_2_0`, `0xFFFF0201` for `ps_2_x`), or `None` without one.
+pub fn legacy_shader_version (container: &Container) -> Result<Option<u32>> {
+    let chunk = match container.chunk(FourCC::AON9) {
+        Some(chunk) => chunk,
+        None        => return Ok(None),
+    };
+    // Aon9:  u32 (unused here), u32 lowest version token, u32 shader size, u32 shader offset, ...
+    let offset = u32_at(chunk.data, 12)? as usize;
+    Ok(Some(u32_at(chunk.data, offset)?))
+}
+
+/// Find the `dcl_thread_group` declaration in `container`'s bytecode, if any.
+pub fn thread_group_size (container: &Container) -> Result<Option<[u32; 3]>> {
+    let program = match Program::from_container(container) {
+        Ok(program)                     => program,
+        Err(Error::MissingChunk(_))     => return Ok(None),
+        Err(e)                          => return Err(e),
+    };
+    for instruction in program.instructions()? {
+        if let (OPCODE_DCL_THREAD_GROUP, &[_, x, y, z]) = (instruction.opcode, instruction.tokens) {
+            return Ok(Some([x, y, z]));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,296 @@
+//! Decides when to render, how to present, and how long the event loop may sleep in between.
+//!
+//! Deliberately free of D3D and winit types:  the event loop feeds in present results and maps `Wake` to a
+//! `ControlFlow`.  All timing goes through `Clock`, so the pacing logic can be driven by a fake clock.
+
+use std::time::{Duration, Instant};
+
+/// A monotonic time source.
+pub trait Clock {
+    /// Time elapsed since some arbitrary, fixed epoch.
+    fn now (&self) -> Duration;
+}
+
+/// The real clock, measured from when it was created.
+pub struct SystemClock {
+    epoch: Instant,
+}
+
+impl SystemClock {
+    pub fn new () -> Self { Self { epoch: Instant::now() } }
+
+    /// Convert a `Clock::now`-relative time back into an `Instant`, e.g. for `ControlFlow::WaitUntil`.
+    pub fn instant (&self, time: Duration) -> Instant { self.epoch + time }
+}
+
+impl Default for SystemClock {
+    fn default () -> Self { Self::new() }
+}
+
+impl Clock for SystemClock {
+    fn now (&self) -> Duration { self.epoch.elapsed() }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PresentMode {
+    /// Present on vertical blank (`sync_interval = 1`.)
+    VSync,
+    /// Present immediately, but no more than `hz` times per second.
+    Capped { hz: u32 },
+    /// Present immediately, as often as possible.
+    Uncapped,
+}
+
+impl PresentMode {
+    pub fn sync_interval (self) -> u32 {
+        match self {
+            PresentMode::VSync => 1,
+            _                  => 0,
+        }
+    }
+
+    /// The minimum time between the start of two frames, if any.
+    pub fn frame_time (self) -> Option<Duration> {
+        match self {
+            PresentMode::Capped { hz } if hz > 0 => Some(Duration::from_secs(1) / hz),
+            _                                    => None,
+        }
+    }
+}
+
+/// What the event loop should do once it runs out of events.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wake {
+    /// Keep polling, the next frame is due immediately.
+    Immediately,
+    /// Sleep until `Clock::now` reaches this time, or an event arrives.
+    At(Duration),
+    /// Sleep until an event arrives.
+    OnEvent,
+}
+
+pub struct FramePacer<C: Clock> {
+    clock:      C,
+    mode:       PresentMode,
+    continuous: bool,
+    dirty:      bool,
+    occluded:   bool,
+    /// While visible, the earliest time the next frame may start.  While occluded, the next time to test for visibility.
+    next_frame: Duration,
+}
+
+impl<C: Clock> FramePacer<C> {
+    /// How often to check if an occluded window has become visible again.
+    pub const OCCLUSION_POLL_INTERVAL : Duration = Duration::from_millis(100);
+
+    pub fn new (clock: C, mode: PresentMode) -> Self {
+        let next_frame = clock.now();
+        Self { clock, mode, continuous: true, dirty: true, occluded: false, next_frame }
+    }
+
+    pub fn clock (&self) -> &C { &self.clock }
+    pub fn mode (&self) -> PresentMode { self.mode }
+    pub fn set_mode (&mut self, mode: PresentMode) { self.mode = mode; self.next_frame = self.clock.now(); }
+
+    /// If `false`, only render after `invalidate`, and let the event loop sleep in between.
+    pub fn set_continuous (&mut self, continuous: bool) { self.continuous = continuous; }
+
+    /// Request that the next frame be rendered, e.g. because the window was resized or the scene changed.
+    pub fn invalidate (&mut self) { self.dirty = true; }
+
+    /// If `true`, don't render:  `present` with `DXGI_PRESENT_TEST`, and feed the result to `occlusion_tested`.
+    pub fn is_occluded (&self) -> bool { self.occluded }
+
+    pub fn sync_interval (&self) -> u32 { self.mode.sync_interval() }
+
+    /// Returns `true` if it's time to render (or, while occluded, to test for visibility.)
+    pub fn frame_due (&self) -> bool {
+        let idle = !self.occluded && !self.continuous && !self.dirty;
+        !idle && self.clock.now() >= self.next_frame
+    }
+
+    /// Call after presenting a rendered frame.  `occluded` should be `true` if present returned `DXGI_STATUS_OCCLUDED`.
+    pub fn frame_presented (&mut self, occluded: bool) {
+        let now = self.clock.now();
+        if occluded {
+            // Keep `dirty` set:  whatever we just rendered was never seen.
+            self.occluded   = true;
+            self.next_frame = now + Self::OCCLUSION_POLL_INTERVAL;
+            return;
+        }
+
+        self.dirty = false;
+        self.next_frame = match self.mode.frame_time() {
+            // Schedule against the previous deadline to avoid drift, but don't try to "catch up" after a long frame.
+            Some(frame_time) => (self.next_frame + frame_time).max(now),
+            None             => now,
+        };
+    }
+
+    /// Call after a `DXGI_PRESENT_TEST` present while occluded.
+    pub fn occlusion_tested (&mut self, occluded: bool) {
+        let now = self.clock.now();
+        if occluded {
+            self.next_frame = now + Self::OCCLUSION_POLL_INTERVAL;
+        } else {
+            self.occluded   = false;
+            self.dirty      = true;
+            self.next_frame = now;
+        }
+    }
+
+    /// How long the event loop may sleep before the next call to `frame_due` could return `true`.
+    pub fn wake (&self) -> Wake {
+        if self.occluded {
+            Wake::At(self.next_frame)
+        } else if !self.continuous && !self.dirty {
+            Wake::OnEvent
+        } else if self.clock.now() < self.next_frame {
+            Wake::At(self.next_frame)
+        } else {
+            Wake::Immediately
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Only moves when told to.
+    #[derive(Default)]
+    struct FakeClock(Cell<Duration>);
+
+    impl FakeClock {
+        fn set (&self, ms: u64) { self.0.set(Duration::from_millis(ms)); }
+    }
+
+    impl Clock for FakeClock {
+        fn now (&self) -> Duration { self.0.get() }
+    }
+
+    fn ms (ms: u64) -> Duration { Duration::from_millis(ms) }
+
+    fn pacer (mode: PresentMode) -> FramePacer<FakeClock> { FramePacer::new(FakeClock::default(), mode) }
+
+    #[test]
+    fn present_modes () {
+        assert_eq!(PresentMode::VSync.sync_interval(), 1);
+        assert_eq!(PresentMode::Uncapped.sync_interval(), 0);
+        assert_eq!(PresentMode::Capped { hz: 50 }.sync_interval(), 0);
+        assert_eq!(PresentMode::VSync.frame_time(), None);
+        assert_eq!(PresentMode::Uncapped.frame_time(), None);
+        assert_eq!(PresentMode::Capped { hz: 50 }.frame_time(), Some(ms(20)));
+        assert_eq!(PresentMode::Capped { hz: 0 }.frame_time(), None);
+    }
+
+    #[test]
+    fn vsync_and_uncapped_render_continuously () {
+        for mode in [PresentMode::VSync, PresentMode::Uncapped].iter().cloned() {
+            let mut pacer = pacer(mode);
+            for t in 0..3 {
+                pacer.clock().set(t);
+                assert!(pacer.frame_due());
+                assert_eq!(pacer.wake(), Wake::Immediately);
+                pacer.frame_presented(false);
+                assert!(pacer.frame_due(), "{:?} waits for present itself, not the pacer", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn capped_schedules_against_the_previous_deadline () {
+        let mut pacer = pacer(PresentMode::Capped { hz: 50 });
+        assert!(pacer.frame_due());
+        pacer.frame_presented(false);
+        assert!(!pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::At(ms(20)));
+
+        pacer.clock().set(19);
+        assert!(!pacer.frame_due());
+        pacer.clock().set(20);
+        assert!(pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::Immediately);
+
+        // Presenting a little late doesn't push the schedule back...
+        pacer.clock().set(25);
+        pacer.frame_presented(false);
+        assert_eq!(pacer.wake(), Wake::At(ms(40)));
+
+        // ...but a long frame doesn't cause a burst of catch-up frames either.
+        pacer.clock().set(100);
+        pacer.frame_presented(false);
+        assert_eq!(pacer.wake(), Wake::Immediately);
+        pacer.frame_presented(false);
+        assert_eq!(pacer.wake(), Wake::At(ms(120)));
+    }
+
+    #[test]
+    fn set_mode_restarts_the_schedule () {
+        let mut pacer = pacer(PresentMode::Capped { hz: 10 });
+        pacer.frame_presented(false);
+        assert_eq!(pacer.wake(), Wake::At(ms(100)));
+        pacer.clock().set(5);
+        pacer.set_mode(PresentMode::VSync);
+        assert_eq!(pacer.mode(), PresentMode::VSync);
+        assert!(pacer.frame_due());
+    }
+
+    #[test]
+    fn on_demand_renders_only_when_invalidated () {
+        let mut pacer = pacer(PresentMode::VSync);
+        pacer.set_continuous(false);
+        assert!(pacer.frame_due(), "the first frame is always due");
+        pacer.frame_presented(false);
+        assert!(!pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::OnEvent);
+
+        pacer.clock().set(1000);
+        assert!(!pacer.frame_due());
+        pacer.invalidate();
+        assert!(pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::Immediately);
+        pacer.frame_presented(false);
+        assert_eq!(pacer.wake(), Wake::OnEvent);
+    }
+
+    #[test]
+    fn occluded_backs_off () {
+        let mut pacer = pacer(PresentMode::VSync);
+        pacer.set_continuous(false);
+        pacer.clock().set(10);
+        pacer.frame_presented(true);
+        assert!(pacer.is_occluded());
+        assert!(!pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::At(ms(10) + FramePacer::<FakeClock>::OCCLUSION_POLL_INTERVAL));
+
+        // Still occluded:  test again one interval later, even though nothing was invalidated.
+        pacer.clock().set(110);
+        assert!(pacer.frame_due());
+        pacer.occlusion_tested(true);
+        assert!(pacer.is_occluded());
+        assert!(!pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::At(ms(210)));
+
+        // Visible again:  redraw right away, since the occluded frame was never seen.
+        pacer.clock().set(210);
+        assert!(pacer.frame_due());
+        pacer.occlusion_tested(false);
+        assert!(!pacer.is_occluded());
+        assert!(pacer.frame_due());
+        assert_eq!(pacer.wake(), Wake::Immediately);
+        pacer.frame_presented(false);
+        assert_eq!(pacer.wake(), Wake::OnEvent);
+    }
+
+    #[test]
+    fn occluded_keeps_the_frame_dirty () {
+        let mut pacer = pacer(PresentMode::Capped { hz: 50 });
+        pacer.set_continuous(false);
+        pacer.frame_presented(true);
+        pacer.clock().set(100);
+        pacer.occlusion_tested(false);
+        assert!(pacer.frame_due(), "the frame rendered while occluded still needs to be seen");
+    }
+}
@@ -0,0 +1,295 @@
+//! Records a frame's draw batches on worker threads, and replays them on the immediate context in a deterministic
+//! order.
+//!
+//! Batches are split into contiguous chunks of roughly equal cost (`partition`), one per worker, and each worker
+//! records its chunk into its own context.  Results are replayed sorted by chunk, so the batches execute in the order
+//! they were submitted - no matter which worker finished first.
+//!
+//! Recorded results never leave the recorder:  it owns both the worker contexts and the immediate context they're
+//! replayed on, so a command list can't be executed on the wrong device's context.
+//!
+//! Generic over `RecordingContext` so the threading and ordering don't depend on D3D.  `DeferredContext` is the real
+//! implementation:  record with its `DeviceContext` methods, and `FrameRecorder::create` pairs deferred contexts with
+//! the immediate context of the same device.
+
+#[cfg(windows)] use crate::com::Wrapper;
+#[cfg(windows)] use crate::com::d3d11::{CommandList, DeferredContext, DeviceChild, DeviceContext};
+#[cfg(windows)] use crate::win32::*;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Something draw batches can be recorded into, one chunk at a time.
+pub trait RecordingContext : Send + 'static {
+    type Recorded : Send + 'static;
+    type Error : Send + 'static;
+    /// What recorded chunks are replayed on.
+    type Immediate;
+
+    /// Wrap up everything recorded since the last call.
+    fn finish (&mut self) -> Result<Self::Recorded, Self::Error>;
+
+    /// Replay `recorded` on `immediate`.
+    ///
+    /// `unsafe`:  `recorded` must have been finished by a context that can be replayed on `immediate`.
+    unsafe fn execute (immediate: &Self::Immediate, recorded: Self::Recorded);
+}
+
+#[cfg(windows)]
+impl RecordingContext for DeferredContext {
+    type Recorded   = CommandList;
+    type Error      = HRESULT;
+    type Immediate  = DeviceContext;
+
+    fn finish (&mut self) -> Result<CommandList, HRESULT> { self.finish_command_list(false) }
+
+    unsafe fn execute (immediate: &DeviceContext, recorded: CommandList) { immediate.execute_command_list(&recorded, false) }
+}
+
+/// One batch of draws, and a rough estimate of how expensive it is to record (e.g. its draw call count.)
+pub struct Batch<C> {
+    pub cost:   u32,
+    pub record: Box<dyn FnOnce(&mut C) + Send>,
+}
+
+impl<C> Batch<C> {
+    pub fn new<F: FnOnce(&mut C) + Send + 'static> (cost: u32, record: F) -> Self { Self { cost, record: Box::new(record) } }
+}
+
+/// Split batches with the given `costs` into at most `max_chunks` contiguous, non-empty ranges of roughly equal total
+/// cost.  Chunk `k` ends at the first batch that brings the running total to at least `(k+1)/max_chunks` of the whole.
+pub fn partition (costs: &[u32], max_chunks: usize) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    if costs.is_empty() || max_chunks == 0 { return chunks; }
+
+    let total : u64 = costs.iter().map(|&c| c as u64).sum();
+    let mut running = 0u64;
+    let mut start = 0;
+    for (index, &cost) in costs.iter().enumerate() {
+        running += cost as u64;
+        let last_chunk = chunks.len() + 1 == max_chunks;
+        if !last_chunk && running * max_chunks as u64 >= total * (chunks.len() as u64 + 1) {
+            chunks.push(start..index+1);
+            start = index+1;
+        }
+    }
+    if start < costs.len() { chunks.push(start..costs.len()); }
+    chunks
+}
+
+/// A finished chunk, and its index.  `Err` if one of its batches (or finishing it) panicked.
+type Chunk<C> = (usize, thread::Result<Result<<C as RecordingContext>::Recorded, <C as RecordingContext>::Error>>);
+
+struct Worker<C: RecordingContext> {
+    jobs:   Option<Sender<(usize, Vec<Batch<C>>)>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// A pool of worker threads, each owning one context for the lifetime of the recorder, and the immediate context
+/// their recordings are replayed on.
+pub struct FrameRecorder<C: RecordingContext> {
+    immediate:  C::Immediate,
+    workers:    Vec<Worker<C>>,
+    results:    Receiver<Chunk<C>>,
+}
+
+#[cfg(windows)]
+impl FrameRecorder<DeferredContext> {
+    /// Record on `workers` deferred contexts of `immediate`'s device, replaying on `immediate`.  Takes the immediate
+    /// context by value, since only one thread may use it at a time.
+    pub fn create (immediate: DeviceContext, workers: usize) -> Result<Self, HRESULT> {
+        let device = immediate.upcast::<DeviceChild>().get_device();
+        let contexts = (0..workers).map(|_| device.create_deferred_context()).collect::<Result<Vec<_>, _>>()?;
+        Ok(unsafe { Self::new(immediate, contexts) })
+    }
+}
+
+impl<C: RecordingContext> FrameRecorder<C> {
+    /// Spawn a worker thread per context.  Panics if `contexts` is empty.
+    ///
+    /// `unsafe`:  whatever `contexts` record must be replayable on `immediate` (e.g. deferred contexts of the device
+    /// `immediate` belongs to.)  See `create` for a safe constructor.
+    pub unsafe fn new (immediate: C::Immediate, contexts: Vec<C>) -> Self {
+        assert!(!contexts.is_empty(), "FrameRecorder::new: no contexts to record with");
+        let (result_sender, results) = channel();
+        let workers = contexts.into_iter().map(|mut context| {
+            let (jobs, job_receiver) = channel::<(usize, Vec<Batch<C>>)>();
+            let result_sender = result_sender.clone();
+            let thread = thread::spawn(move || {
+                for (chunk, batches) in job_receiver {
+                    // A panic must still produce a result, or `record` would wait for it forever.  Whatever a
+                    // panicking chunk got to record is finished and thrown away, so the next frame starts clean.
+                    let recorded = panic::catch_unwind(AssertUnwindSafe(|| for batch in batches { (batch.record)(&mut context); }));
+                    let finished = panic::catch_unwind(AssertUnwindSafe(|| context.finish()));
+                    if result_sender.send((chunk, recorded.and(finished))).is_err() { break; }
+                }
+            });
+            Worker { jobs: Some(jobs), thread: Some(thread) }
+        }).collect();
+        Self { immediate, workers, results }
+    }
+
+    /// The context recorded batches are replayed on.
+    pub fn immediate (&self) -> &C::Immediate { &self.immediate }
+
+    /// Record `batches` across the workers, blocking until they're all done, then replay them on the immediate
+    /// context in batch order.  If any chunk fails to finish, nothing is replayed and the first chunk's error is
+    /// returned.  If a batch panics, nothing is replayed either, and the panic is resumed on this thread once every
+    /// chunk is done.  The workers survive it.
+    pub fn record (&mut self, batches: Vec<Batch<C>>) -> Result<(), C::Error> {
+        let costs : Vec<u32> = batches.iter().map(|b| b.cost).collect();
+        let chunks = partition(&costs[..], self.workers.len());
+
+        let mut batches = batches.into_iter();
+        for (chunk, range) in chunks.iter().enumerate() {
+            let chunk_batches = batches.by_ref().take(range.len()).collect();
+            let jobs = self.workers[chunk].jobs.as_ref().unwrap();
+            jobs.send((chunk, chunk_batches)).expect("FrameRecorder::record: worker thread died");
+        }
+
+        let mut recorded : Vec<Chunk<C>> = (0..chunks.len())
+            .map(|_| self.results.recv().expect("FrameRecorder::record: worker thread died"))
+            .collect();
+        recorded.sort_by_key(|(chunk, _)| *chunk);
+        let recorded = match recorded.into_iter().map(|(_, r)| r).collect::<thread::Result<Vec<_>>>() {
+            Ok(recorded) => recorded,
+            Err(panic) => panic::resume_unwind(panic),
+        };
+        let recorded = recorded.into_iter().collect::<Result<Vec<_>, _>>()?;
+        for r in recorded { unsafe { C::execute(&self.immediate, r) }; }
+        Ok(())
+    }
+}
+
+impl<C: RecordingContext> Drop for FrameRecorder<C> {
+    fn drop (&mut self) {
+        for worker in self.workers.iter_mut() { worker.jobs = None; } // Ends the workers' job loops
+        for worker in self.workers.iter_mut() {
+            if let Some(thread) = worker.thread.take() { let _ = thread.join(); }
+        }
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::sync::{Arc, Condvar, Mutex};
+
+    /// Which workers have finished a chunk, in the order they did.
+    type Finished = Arc<(Mutex<Vec<usize>>, Condvar)>;
+
+    /// Records batch indices instead of draws.
+    struct Mock {
+        worker:     usize,
+        draws:      Vec<usize>,
+        finished:   Finished,
+        fail:       bool,
+    }
+
+    impl RecordingContext for Mock {
+        type Recorded   = Vec<usize>;
+        type Error      = usize;
+        type Immediate  = RefCell<Vec<usize>>;
+
+        fn finish (&mut self) -> Result<Vec<usize>, usize> {
+            let (finished, changed) = &*self.finished;
+            finished.lock().unwrap().push(self.worker);
+            changed.notify_all();
+            let draws = std::mem::take(&mut self.draws);
+            if self.fail { Err(self.worker) } else { Ok(draws) }
+        }
+
+        unsafe fn execute (immediate: &RefCell<Vec<usize>>, recorded: Vec<usize>) { immediate.borrow_mut().extend(recorded) }
+    }
+
+    fn recorder (workers: usize, failing: &[usize]) -> (FrameRecorder<Mock>, Finished) {
+        let finished = Finished::default();
+        let contexts = (0..workers).map(|worker| Mock { worker, draws: Vec::new(), finished: finished.clone(), fail: failing.contains(&worker) }).collect();
+        (unsafe { FrameRecorder::new(RefCell::new(Vec::new()), contexts) }, finished)
+    }
+
+    fn draw (index: usize) -> Batch<Mock> { Batch::new(1, move |c: &mut Mock| c.draws.push(index)) }
+
+    #[allow(clippy::single_range_in_vec_init)]
+    #[test] fn partition_table () {
+        type Case = (&'static [u32], usize, &'static [Range<usize>]);
+        let cases : &[Case] = &[
+            (&[],               4, &[]),
+            (&[1, 2, 3],        0, &[]),
+            (&[1, 1, 1, 1],     2, &[0..2, 2..4]),
+            (&[1, 1, 1, 1],     4, &[0..1, 1..2, 2..3, 3..4]),
+            (&[1, 1],           4, &[0..1, 1..2]),
+            (&[9, 1, 1, 1],     2, &[0..1, 1..4]),
+            (&[1, 1, 1, 9],     2, &[0..4]),
+            (&[0, 0, 0],        2, &[0..1, 1..3]),
+            (&[5],              3, &[0..1]),
+        ];
+        for &(costs, max_chunks, expected) in cases {
+            assert_eq!(partition(costs, max_chunks), expected, "partition({:?}, {})", costs, max_chunks);
+        }
+    }
+
+    #[test] fn partition_covers_everything () {
+        let costs : Vec<u32> = (0..37).map(|i| (i * 7919) % 13).collect();
+        for max_chunks in 1..10 {
+            let chunks = partition(&costs, max_chunks);
+            assert!(!chunks.is_empty() && chunks.len() <= max_chunks);
+            assert_eq!(chunks.first().unwrap().start, 0);
+            assert_eq!(chunks.last().unwrap().end, costs.len());
+            for pair in chunks.windows(2) { assert_eq!(pair[0].end, pair[1].start); }
+            for chunk in chunks.iter() { assert!(!chunk.is_empty()); }
+        }
+    }
+
+    #[test] fn replays_in_batch_order () {
+        let (mut recorder, finished) = recorder(4, &[]);
+
+        // Hold the first chunk back until every other worker has finished, so results arrive out of order.
+        let mut batches = vec![{
+            let finished = finished.clone();
+            Batch::new(1, move |c: &mut Mock| {
+                let (done, changed) = &*finished;
+                drop(changed.wait_while(done.lock().unwrap(), |done| done.len() < 3).unwrap());
+                c.draws.push(0);
+            })
+        }];
+        batches.extend((1..8).map(draw));
+
+        recorder.record(batches).unwrap();
+        assert_eq!(*recorder.immediate().borrow(), (0..8).collect::<Vec<_>>());
+        assert_eq!(finished.0.lock().unwrap().last(), Some(&0), "chunk 0 finished last");
+
+        recorder.record((8..10).map(draw).collect()).unwrap();
+        assert_eq!(*recorder.immediate().borrow(), (0..10).collect::<Vec<_>>(), "workers are reused across frames");
+    }
+
+    #[test] fn failure_replays_nothing () {
+        let (mut recorder, _) = recorder(3, &[1, 2]);
+        assert_eq!(recorder.record((0..6).map(draw).collect()), Err(1), "first failing chunk");
+        assert!(recorder.immediate().borrow().is_empty());
+    }
+
+    #[test] fn panicking_batch () {
+        let (mut recorder, _) = recorder(3, &[]);
+        let mut batches : Vec<_> = (0..6).map(draw).collect();
+        batches[3] = Batch::new(1, |c: &mut Mock| { c.draws.push(3); panic!("batch 3") });
+
+        let panic = panic::catch_unwind(AssertUnwindSafe(|| recorder.record(batches))).unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"batch 3"));
+        assert!(recorder.immediate().borrow().is_empty(), "nothing replayed");
+
+        recorder.record((0..6).map(draw).collect()).unwrap();
+        assert_eq!(*recorder.immediate().borrow(), (0..6).collect::<Vec<_>>(), "workers survive, and start clean");
+    }
+
+    #[test] fn no_batches () {
+        let (mut recorder, finished) = recorder(2, &[]);
+        recorder.record(Vec::new()).unwrap();
+        assert!(finished.0.lock().unwrap().is_empty());
+    }
+
+    #[test] #[should_panic(expected = "no contexts")] fn no_contexts () {
+        let _ = unsafe { FrameRecorder::<Mock>::new(RefCell::new(Vec::new()), Vec::new()) };
+    }
+}
@@ -0,0 +1,418 @@
+//! Switching between windowed, borderless fullscreen, and exclusive fullscreen.
+//!
+//! `FullscreenState::plan` works out the `Step`s of a switch, and `FullscreenState::switch` runs them - undoing them
+//! again if one fails - through a callback, which `apply` implements with DXGI and Win32.  Resizing the swap chain's
+//! buffers isn't a step:  every transition resizes the window, and the event loop already handles `Resized`.
+//!
+//! Everything but `apply` is free of Windows types, so it can be tested anywhere.
+
+#[cfg(windows)] use crate::com::dxgi::*;
+#[cfg(windows)] use crate::win32::*;
+#[cfg(windows)] use std::mem;
+#[cfg(windows)] use winit::platform::windows::WindowExtWindows;
+#[cfg(windows)] use winit::window::Window;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WindowMode {
+    Windowed,
+    /// An undecorated window covering the entire monitor.  Alt+Tab friendly, but no control over the display mode.
+    Borderless,
+    /// `SetFullscreenState(TRUE, ...)` with a display mode picked by `closest_mode`.
+    Exclusive,
+}
+
+/// A window or monitor rectangle, in physical pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub left:   i32,
+    pub top:    i32,
+    pub right:  i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn width  (&self) -> i32 { self.right - self.left }
+    pub fn height (&self) -> i32 { self.bottom - self.top }
+}
+
+#[cfg(windows)]
+impl From<RECT> for Rect {
+    fn from (rect: RECT) -> Self { Self { left: rect.left, top: rect.top, right: rect.right, bottom: rect.bottom } }
+}
+
+/// A `DXGI_MODE_DESC` without the Windows types.  As in DXGI, zero means "unspecified" for every field:
+/// `DXGI_FORMAT_UNKNOWN`, `DXGI_MODE_SCANLINE_ORDER_UNSPECIFIED`, `DXGI_MODE_SCALING_UNSPECIFIED`, and a zero refresh
+/// rate numerator.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct DisplayMode {
+    pub width:              u32,
+    pub height:             u32,
+    /// Hz, as a `(numerator, denominator)` rational.
+    pub refresh_rate:       (u32, u32),
+    /// `DXGI_FORMAT`
+    pub format:             u32,
+    /// `DXGI_MODE_SCANLINE_ORDER`
+    pub scanline_ordering:  u32,
+    /// `DXGI_MODE_SCALING`
+    pub scaling:            u32,
+}
+
+impl DisplayMode {
+    fn refresh_millihertz (&self) -> u64 {
+        let (numerator, denominator) = self.refresh_rate;
+        if denominator == 0 { 0 } else { 1000 * numerator as u64 / denominator as u64 }
+    }
+}
+
+#[cfg(windows)]
+impl From<DXGI_MODE_DESC> for DisplayMode {
+    fn from (mode: DXGI_MODE_DESC) -> Self {
+        Self {
+            width:              mode.Width,
+            height:             mode.Height,
+            refresh_rate:       (mode.RefreshRate.Numerator, mode.RefreshRate.Denominator),
+            format:             mode.Format,
+            scanline_ordering:  mode.ScanlineOrdering,
+            scaling:            mode.Scaling,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<DisplayMode> for DXGI_MODE_DESC {
+    fn from (mode: DisplayMode) -> Self {
+        Self {
+            Width:              mode.width,
+            Height:             mode.height,
+            RefreshRate:        DXGI_RATIONAL { Numerator: mode.refresh_rate.0, Denominator: mode.refresh_rate.1 },
+            Format:             mode.format,
+            ScanlineOrdering:   mode.scanline_ordering,
+            Scaling:            mode.scaling,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Step {
+    /// `Window::set_decorations`
+    SetDecorations(bool),
+    /// `SetWindowPos`
+    MoveWindow(Rect),
+    /// `SwapChain::resize_target`
+    ResizeTarget(DisplayMode),
+    /// `SwapChain::set_fullscreen_state`
+    SetFullscreenState(bool),
+}
+
+fn distance (a: u32, b: u32) -> u64 {
+    (a as i64 - b as i64).unsigned_abs()
+}
+
+/// Lexicographically ordered "badness" of `mode` as a substitute for `wanted`.
+fn mode_distance (mode: &DisplayMode, wanted: &DisplayMode) -> (bool, u64, u64, bool) {
+    let format = wanted.format != 0 && mode.format != wanted.format;
+    let dx = if wanted.width  == 0 { 0 } else { distance(mode.width,  wanted.width ) };
+    let dy = if wanted.height == 0 { 0 } else { distance(mode.height, wanted.height) };
+    let refresh = if wanted.refresh_rate.0 == 0 { 0 } else { mode.refresh_millihertz().abs_diff(wanted.refresh_millihertz()) };
+    let scanline = wanted.scanline_ordering != 0 && mode.scanline_ordering != wanted.scanline_ordering;
+    let scaling  = wanted.scaling           != 0 && mode.scaling           != wanted.scaling;
+    (format, dx*dx + dy*dy, refresh, scanline || scaling)
+}
+
+/// Pick the mode from `modes` closest to `wanted`, like `IDXGIOutput::FindClosestMatchingMode` but without needing a
+/// device.  Zeroed / unspecified fields of `wanted` match anything.  In order of priority, prefers:  the exact format,
+/// the closest resolution, the closest refresh rate, and finally matching scanline ordering and scaling.  Ties go to
+/// whichever mode comes first.
+pub fn closest_mode<'m> (modes: &'m [DisplayMode], wanted: &DisplayMode) -> Option<&'m DisplayMode> {
+    modes.iter().min_by_key(|mode| mode_distance(mode, wanted))
+}
+
+pub struct FullscreenState {
+    mode:           WindowMode,
+    windowed_rect:  Option<Rect>,
+    /// What Alt+Enter switches to from `WindowMode::Windowed`.
+    pub alt_enter:  WindowMode,
+}
+
+impl Default for FullscreenState {
+    fn default () -> Self { Self::new() }
+}
+
+impl FullscreenState {
+    pub fn new () -> Self { Self { mode: WindowMode::Windowed, windowed_rect: None, alt_enter: WindowMode::Borderless } }
+
+    pub fn mode (&self) -> WindowMode { self.mode }
+
+    /// What Alt+Enter should switch to from the current mode.
+    pub fn toggled (&self) -> WindowMode {
+        if self.mode == WindowMode::Windowed { self.alt_enter } else { WindowMode::Windowed }
+    }
+
+    /// Plan the steps to switch to `to`.  Changes nothing:  see `switch` to carry them out.
+    ///
+    /// * `window` - The current window rectangle, remembered when leaving `Windowed` so it can be restored later.
+    /// * `monitor` - The desktop rectangle of the output containing the window.
+    /// * `exclusive` - The display mode to switch to, if `to` is `Exclusive`.  `None` keeps the current display mode.
+    pub fn plan (&self, to: WindowMode, window: Rect, monitor: Rect, exclusive: Option<DisplayMode>) -> Vec<Step> {
+        let mut steps = Vec::new();
+        if to == self.mode { return steps; }
+
+        if self.mode == WindowMode::Exclusive { steps.push(Step::SetFullscreenState(false)); }
+
+        let restore = self.restore_rect(window);
+        match to {
+            WindowMode::Windowed => {
+                steps.push(Step::SetDecorations(true));
+                steps.push(Step::MoveWindow(restore));
+            },
+            WindowMode::Borderless => {
+                steps.push(Step::SetDecorations(false));
+                steps.push(Step::MoveWindow(monitor));
+            },
+            WindowMode::Exclusive => {
+                // DXGI restores the pre-fullscreen window when leaving exclusive mode, so make that a regular window.
+                if self.mode == WindowMode::Borderless {
+                    steps.push(Step::SetDecorations(true));
+                    steps.push(Step::MoveWindow(restore));
+                }
+                if let Some(exclusive) = exclusive {
+                    steps.push(Step::ResizeTarget(exclusive));
+                }
+                steps.push(Step::SetFullscreenState(true));
+            },
+        }
+        steps
+    }
+
+    /// Switch to `to`, carrying out each of `plan`'s steps with `run`.  `mode` only changes once every step has
+    /// succeeded.  If one fails, the steps already taken are undone (as far as possible - a `ResizeTarget` can't be)
+    /// and its error is returned, leaving the window in its previous mode.
+    pub fn switch<E> (&mut self, to: WindowMode, window: Rect, monitor: Rect, exclusive: Option<DisplayMode>, mut run: impl FnMut(Step) -> Result<(), E>) -> Result<(), E> {
+        let steps = self.plan(to, window, monitor, exclusive);
+        for (i, step) in steps.iter().enumerate() {
+            if let Err(error) = run(*step) {
+                for undo in steps[..i].iter().rev().filter_map(|done| self.undo(*done, window)) {
+                    let _ = run(undo); // Best effort, the original error is the interesting one
+                }
+                return Err(error);
+            }
+        }
+
+        if self.mode == WindowMode::Windowed && to != WindowMode::Windowed { self.windowed_rect = Some(window); }
+        self.mode = to;
+        Ok(())
+    }
+
+    /// The window rectangle to go back to when returning to `Windowed`.
+    fn restore_rect (&self, window: Rect) -> Rect {
+        if self.mode == WindowMode::Windowed { window } else { self.windowed_rect.unwrap_or(window) }
+    }
+
+    /// The step reverting `step`, given the window rectangle at the start of the switch.
+    fn undo (&self, step: Step, window: Rect) -> Option<Step> {
+        match step {
+            Step::SetDecorations(_)         => Some(Step::SetDecorations(self.mode != WindowMode::Borderless)),
+            Step::MoveWindow(_)             => Some(Step::MoveWindow(window)),
+            Step::ResizeTarget(_)           => None,
+            Step::SetFullscreenState(full)  => Some(Step::SetFullscreenState(!full)),
+        }
+    }
+
+    /// DXGI leaves exclusive fullscreen on its own when the window loses focus or the swap chain is recreated.  Call
+    /// this when `SwapChain::get_fullscreen_state` no longer agrees with `mode`.
+    pub fn exclusive_lost (&mut self) {
+        if self.mode == WindowMode::Exclusive { self.mode = WindowMode::Windowed; }
+    }
+}
+
+/// Switch `window` (and `swap_chain`, which must be presenting to it) to `to`.  On failure, `state` and the window are
+/// left in their previous mode.
+#[cfg(windows)]
+pub fn apply (window: &Window, swap_chain: &SwapChain, state: &mut FullscreenState, to: WindowMode) -> Result<(), HRESULT> {
+    let hwnd = window.hwnd() as HWND;
+    let output = swap_chain.get_containing_output()?;
+    let monitor = Rect::from(output.get_desc()?.DesktopCoordinates);
+
+    let mut window_rect = unsafe { mem::zeroed() };
+    unsafe { GetWindowRect(hwnd, &mut window_rect) };
+
+    let exclusive = if to == WindowMode::Exclusive {
+        let wanted = DisplayMode {
+            width:  monitor.width()  as u32,
+            height: monitor.height() as u32,
+            format: swap_chain.get_desc()?.BufferDesc.Format,
+            ..DisplayMode::default()
+        };
+        let modes = output.get_display_mode_list(wanted.format, 0)?.into_iter().map(DisplayMode::from).collect::<Vec<_>>();
+        closest_mode(&modes[..], &wanted).cloned()
+    } else {
+        None
+    };
+
+    state.switch(to, Rect::from(window_rect), monitor, exclusive, |step| {
+        match step {
+            Step::SetDecorations(decorations)   => window.set_decorations(decorations),
+            Step::MoveWindow(r)                 => unsafe { SetWindowPos(hwnd, HWND_TOP, r.left, r.top, r.width(), r.height(), SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED); },
+            Step::ResizeTarget(mode)            => swap_chain.resize_target(&mode.into())?,
+            Step::SetFullscreenState(full)      => swap_chain.set_fullscreen_state(full, None)?,
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW  : Rect = Rect { left: 100, top: 100, right: 900, bottom: 700 };
+    const MONITOR : Rect = Rect { left: 0, top: 0, right: 1920, bottom: 1080 };
+    const R8G8B8A8_UNORM : u32 = 28;
+    const B8G8R8A8_UNORM : u32 = 87;
+
+    fn mode (width: u32, height: u32, hz: (u32, u32), format: u32) -> DisplayMode {
+        DisplayMode { width, height, refresh_rate: hz, format, ..DisplayMode::default() }
+    }
+
+    fn modes () -> Vec<DisplayMode> {
+        vec![
+            mode( 800,  600, (60, 1),          R8G8B8A8_UNORM),
+            mode(1280,  720, (60, 1),          R8G8B8A8_UNORM),
+            mode(1920, 1080, (59940, 1000),    R8G8B8A8_UNORM),
+            mode(1920, 1080, (60, 1),          R8G8B8A8_UNORM),
+            mode(1920, 1080, (144, 1),         R8G8B8A8_UNORM),
+            mode(2560, 1440, (60, 1),          B8G8R8A8_UNORM),
+        ]
+    }
+
+    #[test]
+    fn closest_mode_exact () {
+        let modes = modes();
+        for m in modes.iter() { assert_eq!(closest_mode(&modes, m), Some(m)); }
+    }
+
+    #[test]
+    fn closest_mode_priorities () {
+        let modes = modes();
+        // Format beats resolution.
+        assert_eq!(closest_mode(&modes, &mode(2560, 1440, (0, 0), R8G8B8A8_UNORM)), Some(&modes[2]));
+        assert_eq!(closest_mode(&modes, &mode(800, 600, (0, 0), B8G8R8A8_UNORM)), Some(&modes[5]));
+        // Resolution beats refresh rate.
+        assert_eq!(closest_mode(&modes, &mode(1280, 720, (144, 1), R8G8B8A8_UNORM)), Some(&modes[1]));
+        // Closest resolution, then closest refresh rate.
+        assert_eq!(closest_mode(&modes, &mode(1900, 1000, (120, 1), R8G8B8A8_UNORM)), Some(&modes[4]));
+        assert_eq!(closest_mode(&modes, &mode(1920, 1080, (5994, 100), R8G8B8A8_UNORM)), Some(&modes[2]));
+        assert_eq!(closest_mode(&modes, &mode(1920, 1080, (61, 1), R8G8B8A8_UNORM)), Some(&modes[3]));
+    }
+
+    #[test]
+    fn closest_mode_unspecified () {
+        let modes = modes();
+        // Unspecified fields match anything, so ties go to the first mode.
+        assert_eq!(closest_mode(&modes, &DisplayMode::default()), Some(&modes[0]));
+        assert_eq!(closest_mode(&modes, &mode(1920, 1080, (0, 0), 0)), Some(&modes[2]));
+        assert_eq!(closest_mode(&modes, &mode(0, 0, (144, 1), 0)), Some(&modes[4]));
+        assert_eq!(closest_mode(&modes, &mode(0, 0, (0, 0), B8G8R8A8_UNORM)), Some(&modes[5]));
+        assert_eq!(closest_mode(&[], &DisplayMode::default()), None);
+    }
+
+    #[test]
+    fn closest_mode_scanline_and_scaling () {
+        let progressive = DisplayMode { scanline_ordering: 1, ..mode(1920, 1080, (60, 1), R8G8B8A8_UNORM) };
+        let stretched   = DisplayMode { scaling: 2, ..progressive };
+        let modes = [progressive, stretched];
+        assert_eq!(closest_mode(&modes, &DisplayMode { scaling: 2, ..mode(1920, 1080, (0, 0), 0) }), Some(&stretched));
+        assert_eq!(closest_mode(&modes, &DisplayMode { scaling: 1, ..mode(1920, 1080, (0, 0), 0) }), Some(&progressive));
+        // Only a tie breaker:  never preferred over a closer resolution.
+        let small = DisplayMode { scaling: 2, ..mode(1280, 720, (60, 1), R8G8B8A8_UNORM) };
+        assert_eq!(closest_mode(&[small, progressive], &DisplayMode { scaling: 2, ..mode(1920, 1080, (0, 0), 0) }), Some(&progressive));
+    }
+
+    #[test]
+    fn plans () {
+        let exclusive = mode(1920, 1080, (60, 1), R8G8B8A8_UNORM);
+        let state = FullscreenState::new();
+        assert!(state.plan(WindowMode::Windowed, WINDOW, MONITOR, None).is_empty());
+        assert_eq!(state.plan(WindowMode::Borderless, WINDOW, MONITOR, None), vec![Step::SetDecorations(false), Step::MoveWindow(MONITOR)]);
+        assert_eq!(state.plan(WindowMode::Exclusive, WINDOW, MONITOR, Some(exclusive)), vec![Step::ResizeTarget(exclusive), Step::SetFullscreenState(true)]);
+        assert_eq!(state.plan(WindowMode::Exclusive, WINDOW, MONITOR, None), vec![Step::SetFullscreenState(true)]);
+    }
+
+    #[test]
+    fn switch_round_trip () {
+        let mut state = FullscreenState::new();
+        let mut ran = Vec::new();
+        state.switch(WindowMode::Borderless, WINDOW, MONITOR, None, |step| { ran.push(step); Ok::<(), ()>(()) }).unwrap();
+        assert_eq!(state.mode(), WindowMode::Borderless);
+        assert_eq!(state.toggled(), WindowMode::Windowed);
+
+        // The borderless window now covers the monitor, but the original window rectangle is what's restored.
+        ran.clear();
+        state.switch(WindowMode::Exclusive, MONITOR, MONITOR, None, |step| { ran.push(step); Ok::<(), ()>(()) }).unwrap();
+        assert_eq!(ran, vec![Step::SetDecorations(true), Step::MoveWindow(WINDOW), Step::SetFullscreenState(true)]);
+        assert_eq!(state.mode(), WindowMode::Exclusive);
+
+        ran.clear();
+        state.switch(WindowMode::Windowed, MONITOR, MONITOR, None, |step| { ran.push(step); Ok::<(), ()>(()) }).unwrap();
+        assert_eq!(ran, vec![Step::SetFullscreenState(false), Step::SetDecorations(true), Step::MoveWindow(WINDOW)]);
+        assert_eq!(state.mode(), WindowMode::Windowed);
+        assert_eq!(state.toggled(), WindowMode::Borderless);
+    }
+
+    #[test]
+    fn failed_switch_rolls_back () {
+        let exclusive = mode(1920, 1080, (60, 1), R8G8B8A8_UNORM);
+        let mut state = FullscreenState::new();
+        state.alt_enter = WindowMode::Exclusive;
+
+        // Fails on the final SetFullscreenState(true), after the display mode was already changed.
+        let mut ran = Vec::new();
+        let result = state.switch(WindowMode::Exclusive, WINDOW, MONITOR, Some(exclusive), |step| {
+            ran.push(step);
+            if step == Step::SetFullscreenState(true) { Err("DXGI_ERROR_NOT_CURRENTLY_AVAILABLE") } else { Ok(()) }
+        });
+        assert_eq!(result, Err("DXGI_ERROR_NOT_CURRENTLY_AVAILABLE"));
+        assert_eq!(ran, vec![Step::ResizeTarget(exclusive), Step::SetFullscreenState(true)]);
+        assert_eq!(state.mode(), WindowMode::Windowed);
+        assert_eq!(state.toggled(), WindowMode::Exclusive);
+
+        // Borderless failing to move the window:  decorations are put back.
+        ran.clear();
+        let result = state.switch(WindowMode::Borderless, WINDOW, MONITOR, None, |step| {
+            ran.push(step);
+            if let Step::MoveWindow(_) = step { Err(()) } else { Ok(()) }
+        });
+        assert!(result.is_err());
+        assert_eq!(ran, vec![Step::SetDecorations(false), Step::MoveWindow(MONITOR), Step::SetDecorations(true)]);
+        assert_eq!(state.mode(), WindowMode::Windowed);
+    }
+
+    #[test]
+    fn failed_switch_from_exclusive_reenters_it () {
+        let mut state = FullscreenState::new();
+        state.switch(WindowMode::Exclusive, WINDOW, MONITOR, None, |_| Ok::<(), ()>(())).unwrap();
+
+        let mut ran = Vec::new();
+        let result = state.switch(WindowMode::Windowed, MONITOR, MONITOR, None, |step| {
+            ran.push(step);
+            if let Step::MoveWindow(_) = step { Err(()) } else { Ok(()) }
+        });
+        assert!(result.is_err());
+        assert_eq!(ran, vec![
+            Step::SetFullscreenState(false), Step::SetDecorations(true), Step::MoveWindow(WINDOW),
+            Step::SetDecorations(true), Step::SetFullscreenState(true),
+        ]);
+        assert_eq!(state.mode(), WindowMode::Exclusive);
+    }
+
+    #[test]
+    fn exclusive_lost () {
+        let mut state = FullscreenState::new();
+        state.switch(WindowMode::Exclusive, WINDOW, MONITOR, None, |_| Ok::<(), ()>(())).unwrap();
+        state.exclusive_lost();
+        assert_eq!(state.mode(), WindowMode::Windowed);
+
+        state.switch(WindowMode::Borderless, WINDOW, MONITOR, None, |_| Ok::<(), ()>(())).unwrap();
+        state.exclusive_lost();
+        assert_eq!(state.mode(), WindowMode::Borderless);
+    }
+}
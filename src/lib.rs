@@ -0,0 +1,28 @@
+//! The sample's D3D11 plumbing, shared by the sample itself (`main.rs`) and the `hlslc` / `assetpack` tools.
+//!
+//! Modules that only wrap `winapi` or `winit` are Windows-only.  Everything else - bytecode parsing, archives,
+//! pacing, permutations, validation - builds and is tested on every platform.
+
+#![allow(non_snake_case)] // WinAPI style
+#![allow(clippy::missing_safety_doc)] // Contracts are spelled out in `unsafe`: paragraphs instead
+
+#[macro_use] mod macros;
+
+pub mod archive;
+pub mod com;
+pub mod compute;
+pub mod constant_buffer;
+pub mod debug;
+pub mod depth;
+pub mod dxbc;
+pub mod frame_pacing;
+pub mod frame_recorder;
+pub mod fullscreen;
+#[cfg(windows)] pub mod mapped_file;
+pub mod permutation;
+pub mod pipeline;
+pub mod recovery;
+pub mod shader_manifest;
+#[cfg(windows)] pub mod shaders;
+#[cfg(windows)] pub mod win32;
+pub mod window_registry;
@@ -1,7 +1,8 @@
+#[macro_export]
 macro_rules! input_layout {
     ($({ $semantic_name:expr , $semantic_index:expr , $format:expr , $input_slot:expr , $aligned_byte_offset:expr , $input_slot_class:expr , $instance_data_step_rate:expr }),+ $(,)?) => {
         [
-            $(crate::d3d11::InputElementDesc(D3D11_INPUT_ELEMENT_DESC {
+            $(unsafe { $crate::com::d3d11::InputElementDesc::from_raw($crate::win32::D3D11_INPUT_ELEMENT_DESC {
                 SemanticName:           concat!($semantic_name, "\0").as_ptr() as *const _,
                 SemanticIndex:          $semantic_index,
                 Format:                 $format,
@@ -9,7 +10,7 @@ macro_rules! input_layout {
                 AlignedByteOffset:      $aligned_byte_offset,
                 InputSlotClass:         $input_slot_class,
                 InstanceDataStepRate:   $instance_data_step_rate,
-            }, PhantomData)),+
+            }) }),+
         ]
     };
 }
@@ -18,44 +19,73 @@ macro_rules! input_layout {
 macro_rules! expect {
     ($expr:expr) => {{
         if !($expr) {
-            #[allow(unused_unsafe)]
-            unsafe {
-                crate::win32::OutputDebugStringA(concat!(stringify!($expr), "\n... was false\n\0").as_ptr() as *const _);
-                if crate::win32::IsDebuggerPresent() != 0 { crate::win32::DebugBreak(); }
-                panic!(concat!("expect!(", stringify!($expr), ") failed"));
-            }
+            crate::debug::output(concat!(stringify!($expr), "\n... was false\n"));
+            crate::debug::break_if_debugger();
+            panic!(concat!("expect!(", stringify!($expr), ") failed"));
         }
     }};
 }
 
+#[allow(unused_macros)]
 macro_rules! expect_eq {
     ($left:expr, $right:expr) => {{
         let left = $left;
         let right = $right;
         if left != right {
-            #[allow(unused_unsafe)]
-            unsafe {
-                let msg = format!("expect_eq!({}, {}) failed.\nleft:  {:?}\nright: {:?}\n\0", stringify!($left), stringify!($right), &left, &right);
-                crate::win32::OutputDebugStringA(msg.as_ptr() as *const _);
-                if crate::win32::IsDebuggerPresent() != 0 { crate::win32::DebugBreak(); }
-                panic!(msg);
-            }
+            let msg = format!("expect_eq!({}, {}) failed.\nleft:  {:?}\nright: {:?}\n", stringify!($left), stringify!($right), &left, &right);
+            crate::debug::output(&msg);
+            crate::debug::break_if_debugger();
+            panic!("{}", msg);
         }
     }};
 }
 
+#[allow(unused_macros)]
 macro_rules! expect_ne {
     ($left:expr, $right:expr) => {{
         let left = $left;
         let right = $right;
         if left == right {
-            #[allow(unused_unsafe)]
-            unsafe {
-                let msg = format!("expect_ne!({}, {}) failed.\nleft:  {:?}\nright: {:?}\n\0", stringify!($left), stringify!($right), &left, &right);
-                crate::win32::OutputDebugStringA(msg.as_ptr() as *const _);
-                if crate::win32::IsDebuggerPresent() != 0 { crate::win32::DebugBreak(); }
-                panic!(msg);
-            }
+            let msg = format!("expect_ne!({}, {}) failed.\nleft:  {:?}\nright: {:?}\n", stringify!($left), stringify!($right), &left, &right);
+            crate::debug::output(&msg);
+            crate::debug::break_if_debugger();
+            panic!("{}", msg);
         }
     }};
 }
+
+/// Declare a struct usable as a constant buffer (or as a struct within one.)  Every field must implement `HlslType`.
+///
+/// A `macro_rules!` rather than `#[derive(ConstantBuffer)]`:  a derive needs its own proc-macro crate (and, in
+/// practice, `syn` and `quote`), where this keeps the sample a single dependency-free package.
+#[macro_export]
+macro_rules! constant_buffer {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident { $($field_vis:vis $field:ident : $ty:ty),+ $(,)? }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug)]
+        $vis struct $name { $($field_vis $field : $ty),+ }
+
+        impl $crate::constant_buffer::HlslType for $name {
+            fn hlsl_type () -> String { String::from(stringify!($name)) }
+            fn size () -> usize { <Self as $crate::constant_buffer::ConstantBuffer>::layout().end() }
+            fn starts_register () -> bool { true }
+            fn ends_register () -> bool { true }
+            fn write (&self, out: &mut [u8]) {
+                let layout = <Self as $crate::constant_buffer::ConstantBuffer>::layout();
+                let mut fields = layout.fields.iter();
+                $($crate::constant_buffer::HlslType::write(&self.$field, &mut out[fields.next().unwrap().offset..]);)+
+            }
+        }
+
+        impl $crate::constant_buffer::ConstantBuffer for $name {
+            fn name () -> &'static str { stringify!($name) }
+            fn fields () -> Vec<$crate::constant_buffer::FieldDesc> {
+                vec![$($crate::constant_buffer::FieldDesc::of::<$ty>(stringify!($field))),+]
+            }
+            fn layout () -> &'static $crate::constant_buffer::Layout {
+                static LAYOUT : std::sync::OnceLock<$crate::constant_buffer::Layout> = std::sync::OnceLock::new();
+                LAYOUT.get_or_init(|| $crate::constant_buffer::Layout::pack(&<Self as $crate::constant_buffer::ConstantBuffer>::fields()[..]))
+            }
+        }
+    };
+}
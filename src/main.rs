@@ -4,29 +4,27 @@
 //     2) https://docs.microsoft.com/en-us/previous-versions//ff729719(v=vs.85)
 //     3) https://docs.microsoft.com/en-us/previous-versions//ff729720(v=vs.85)
 
-#![windows_subsystem = "windows"]
+#![cfg_attr(windows, windows_subsystem = "windows")]
 #![allow(non_snake_case)] // WinAPI style
 
-#[macro_use] mod macros;
-mod com;
-mod debug;
-mod win32;
-
-use com::d3d;
-use com::d3d11;
-use com::*;
-use win32::*;
-use std::fs;
-use std::mem;
-use std::marker::{PhantomData};
-use std::path::{Path, PathBuf};
-use std::ffi::{CStr};
-use std::convert::AsRef;
-use winit::window::*;
-use winit::event::*;
-use winit::event_loop::*;
-use winit::platform::windows::WindowExtWindows;
+#[cfg(windows)] use rust_win32_d3d11::*;
+#[cfg(windows)] use rust_win32_d3d11::com::{d3d, d3d11};
+#[cfg(windows)] use rust_win32_d3d11::frame_pacing::*;
+#[cfg(windows)] use rust_win32_d3d11::recovery::*;
+#[cfg(windows)] use rust_win32_d3d11::win32::*;
+#[cfg(windows)] use std::mem;
+#[cfg(windows)] use std::convert::AsRef;
+#[cfg(windows)] use winit::window::*;
+#[cfg(windows)] use winit::event::*;
+#[cfg(windows)] use winit::event_loop::*;
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("The sample renders with Direct3D 11, and only runs on Windows.");
+    std::process::exit(1);
+}
 
+#[cfg(windows)]
 #[repr(C, align(16))]
 #[derive(Clone, Copy, Debug)]
 struct Vector {
@@ -36,16 +34,19 @@ struct Vector {
     pub w: f32,
 }
 
+#[cfg(windows)]
 impl Vector {
     fn new (x: f32, y: f32, z: f32, w: f32) -> Self { Self {x, y, z, w} }
 }
 
+#[cfg(windows)]
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct SimpleVertex {
     pub pos: Vector,
 }
 
+#[cfg(windows)]
 impl SimpleVertex {
     fn new (pos: Vector) -> Self { Self { pos } }
 
@@ -57,81 +58,35 @@ impl SimpleVertex {
     }
 }
 
-fn require_shader (name: &Path, target: d3d::Target) -> Vec<u8> {
-    let src_path = PathBuf::from("res").join(name);
-    let bin_path = PathBuf::from(r"target\assets").join(name).with_extension("bin");
-    let bin_dir = bin_path.parent().unwrap();
-    if !bin_dir.exists() {
-        fs::create_dir_all(&bin_dir).unwrap();
-    }
-    if !bin_path.exists() {
-        let result = unsafe { d3d::compile(
-            &fs::read(&src_path).unwrap()[..],
-            Some(&src_path),
-            None, // Defines
-            Some(d3d::COMPILE_STANDARD_FILE_INCLUDE),
-            Some(CStr::from_bytes_with_nul(b"main\0").unwrap()),
-            target.to_cstr(),
-            0, // flags1
-            0  // flags2
-        )}.unwrap();
-        
-        result.shader.write_file(&bin_path, false).unwrap();
-    }
-    fs::read(bin_path).unwrap()
-}
-
+#[cfg(windows)]
 fn main() {
     debug::init();
 
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
-        .with_title("Rust Win32 Sample")
-        .with_inner_size((800, 600).into())
-        .build(&event_loop)
-        .unwrap();
-    let client = window.inner_size();
-
-    let swap_chain_desc = DXGI_SWAP_CHAIN_DESC {
-        BufferDesc: DXGI_MODE_DESC {
-            Width:  client.width as UINT,
-            Height: client.height as UINT,
-            RefreshRate: DXGI_RATIONAL { Numerator: 60, Denominator: 1 },
-            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
-            ScanlineOrdering: DXGI_MODE_SCANLINE_ORDER_UNSPECIFIED,
-            Scaling: DXGI_MODE_SCALING_CENTERED,
-        },
-        SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
-        BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
-        BufferCount: 1,
-        OutputWindow: window.hwnd() as HWND,
-        Windowed: 1,
-        SwapEffect: DXGI_SWAP_EFFECT_DISCARD,
-        Flags: 0,
-    };
-
-    let DeviceAndSwapChain { swap_chain, device, device_context, .. } = unsafe { DeviceAndSwapChain::create(
-        None, // adapter
+    let mut graphics = Graphics::create(
         d3d11::DriverType::Hardware,
-        None, // software
         0, // flags
         Some(&[d3d11::FeatureLevel::_11_0]),
-        &swap_chain_desc
-    )}.unwrap();
-
-    let back_buffer = swap_chain.get_buffer::<d3d11::Texture2D>(0).unwrap();
-    let rtv = device.create_render_target_view(&back_buffer, None).unwrap();
-
-    device_context.om_set_render_targets(&[rtv.as_ref()], None);
+    ).unwrap();
 
-    let vp = D3D11_VIEWPORT { Width: client.width as f32, Height: client.height as f32, MinDepth: 0.0, MaxDepth: 1.0, TopLeftX: 0.0, TopLeftY: 0.0 };
-    device_context.rs_set_viewports(&[vp]);
-
-    let vs_bin = require_shader(Path::new("vs.hlsl"), d3d::Target::vs_5_0);
-    let ps_bin = require_shader(Path::new("ps.hlsl"), d3d::Target::ps_5_0);
-    let vs = device.create_vertex_shader(&vs_bin[..], None).unwrap();
-    let ps = device.create_pixel_shader(&ps_bin[..], None).unwrap();
-    let input_layout = device.create_input_layout(SimpleVertex::layout(), &vs_bin[..]).unwrap();
+    let windows : [(&str, (u32, u32), [f32; 4]); 2] = [
+        ("Rust Win32 Sample - Scene",   (800, 600), [0.1, 0.2, 0.3, 1.0]),
+        ("Rust Win32 Sample - Preview", (320, 240), [0.3, 0.1, 0.2, 1.0]),
+    ];
+    let clear_colors = windows.iter().map(|(title, size, clear_color)| {
+        let window = WindowBuilder::new()
+            .with_title(*title)
+            .with_inner_size((*size).into())
+            .build(&event_loop)
+            .unwrap();
+        (graphics.add_window(window).unwrap(), *clear_color)
+    }).collect::<Vec<_>>();
+
+    let vs_bin : d3d::VertexBytecode = shaders::load("vs").unwrap_or_else(|e| panic!("vs: {}", e));
+    let ps_bin : d3d::PixelBytecode  = shaders::load("ps").unwrap_or_else(|e| panic!("ps: {}", e));
+    let input_layout = graphics.register::<d3d11::InputLayout>(ResourceDesc::InputLayout { elements: SimpleVertex::layout(), bytecode: vs_bin.clone() }).unwrap();
+    let vs = graphics.register::<d3d11::VertexShader>(ResourceDesc::VertexShader { bytecode: vs_bin }).unwrap();
+    let ps = graphics.register::<d3d11::PixelShader>(ResourceDesc::PixelShader { bytecode: ps_bin }).unwrap();
 
     let verticies = [
         SimpleVertex::new(Vector::new( 0.0,  0.5, 0.5, 0.0)),
@@ -148,31 +103,101 @@ fn main() {
         StructureByteStride:    0,
     };
 
-    let init_data = D3D11_SUBRESOURCE_DATA {
-        pSysMem:            verticies.as_ptr() as *const _,
-        SysMemPitch:        0,
-        SysMemSlicePitch:   0,
-    };
+    let init_data = unsafe { std::slice::from_raw_parts(verticies.as_ptr() as *const u8, mem::size_of_val(&verticies)) }.to_vec();
 
-    let vertex_buffer = unsafe { device.create_buffer(&bd, Some(&init_data)) }.unwrap();
+    let vertex_buffer = graphics.register::<d3d11::Buffer>(ResourceDesc::Buffer { desc: bd, initial_data: Some(init_data) }).unwrap();
 
-    loop {
-        event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Poll;
-            match event {
-                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => *control_flow = ControlFlow::Exit,
-                Event::EventsCleared => {
-                    device_context.clear_render_target_view(&rtv, &[0.1, 0.2, 0.3, 1.0]);
-                    device_context.ia_set_input_layout(&input_layout);
+    let default_stencil_op = D3D11_DEPTH_STENCILOP_DESC {
+        StencilFailOp:      D3D11_STENCIL_OP_KEEP,
+        StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
+        StencilPassOp:      D3D11_STENCIL_OP_KEEP,
+        StencilFunc:        D3D11_COMPARISON_ALWAYS,
+    };
+    let dsd = D3D11_DEPTH_STENCIL_DESC {
+        DepthEnable:        TRUE,
+        DepthWriteMask:     D3D11_DEPTH_WRITE_MASK_ALL,
+        DepthFunc:          D3D11_COMPARISON_LESS,
+        StencilEnable:      FALSE,
+        StencilReadMask:    D3D11_DEFAULT_STENCIL_READ_MASK as UINT8,
+        StencilWriteMask:   D3D11_DEFAULT_STENCIL_WRITE_MASK as UINT8,
+        FrontFace:          default_stencil_op,
+        BackFace:           default_stencil_op,
+    };
+    let depth_stencil_state = graphics.register::<d3d11::DepthStencilState>(ResourceDesc::DepthStencilState { desc: dsd }).unwrap();
+
+    let mut pacer = FramePacer::new(SystemClock::new(), PresentMode::VSync);
+    pacer.set_continuous(false); // Nothing animates, so only redraw when something changes.
+
+    event_loop.run(move |event, _, control_flow| {
+        if let Some((id, input)) = window_registry::winit_input(&event) {
+            if let Some(surface_event) = graphics.windows.route(id, &input) {
+                if !window_registry::dispatch(&mut graphics, id, surface_event) {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                pacer.invalidate();
+            }
+        }
+
+        match event {
+            Event::EventsCleared if pacer.frame_due() && pacer.is_occluded() => {
+                let mut occluded = true;
+                for id in graphics.windows.ids() {
+                    occluded &= match graphics.present(id, 0, DXGI_PRESENT_TEST) {
+                        // A lost device keeps backing off like an occluded window, retrying recovery every test.
+                        Ok(Presented::Occluded) | Ok(Presented::Lost { .. }) => true,
+                        Ok(Presented::Visible) | Ok(Presented::Recovered { .. }) => false,
+                        Err(hresult) => { debug::output(&format!("Present failed: HRESULT={:X}\n", hresult)); false },
+                    };
+                }
+                pacer.occlusion_tested(occluded);
+            },
+            Event::EventsCleared if pacer.frame_due() => {
+                let ids = graphics.windows.ids();
+                let mut occluded = true;
+                let mut recovered = false;
+                let mut lost = false;
+                for (i, id) in ids.iter().cloned().enumerate() {
+                    let Graphics { device_context, resources, windows, .. } = &graphics;
+                    let surface = windows.get(id).unwrap();
+                    let rtv = match surface.render_target_view() {
+                        Some(rtv) => rtv,
+                        None => continue, // Its last resize failed, see `Surface::resize`
+                    };
+                    let clear_color = clear_colors.iter().find(|(w, _)| *w == id).map_or([0.0, 0.0, 0.0, 1.0], |(_, c)| *c);
+                    device_context.om_set_render_targets(&[rtv.as_ref()], surface.depth_stencil_view());
+                    device_context.om_set_depth_stencil_state(Some(resources.get(depth_stencil_state)), 0);
+                    device_context.rs_set_viewports(&[surface.viewport()]);
+                    device_context.clear_render_target_view(rtv, &clear_color);
+                    if let Some(dsv) = surface.depth_stencil_view() {
+                        device_context.clear_depth_stencil_view(dsv, D3D11_CLEAR_DEPTH | D3D11_CLEAR_STENCIL, 1.0, 0);
+                    }
+                    device_context.ia_set_input_layout(resources.get(input_layout));
                     device_context.ia_set_primitive_topology(d3d11::PrimitiveTopology::TriangleList);
-                    device_context.ia_set_vertex_buffers(0, &[vertex_buffer.as_ref()], &[mem::size_of::<SimpleVertex>() as UINT], &[0]);
-                    device_context.vs_set_shader(&vs, &[]);
-                    device_context.ps_set_shader(&ps, &[]);
+                    device_context.ia_set_vertex_buffers(0, &[resources.get(vertex_buffer).as_ref()], &[mem::size_of::<SimpleVertex>() as UINT], &[0]);
+                    device_context.vs_set_shader(resources.get(vs), &[]);
+                    device_context.ps_set_shader(resources.get(ps), &[]);
                     device_context.draw(3, 0);
-                    swap_chain.present(0, 0).unwrap();
-                },
-                _ => {},
-            }
-        });
-    }
+
+                    // Only wait for vsync once per frame, not once per window.
+                    let sync_interval = if i + 1 == ids.len() { pacer.sync_interval() } else { 0 };
+                    match graphics.present(id, sync_interval, 0) {
+                        Ok(Presented::Visible)          => occluded = false,
+                        Ok(Presented::Occluded)         => {},
+                        Ok(Presented::Recovered { .. }) => { recovered = true; break; },
+                        Ok(Presented::Lost { .. })      => { lost = true; break; },
+                        Err(hresult) => debug::output(&format!("Present failed: HRESULT={:X}\n", hresult)),
+                    }
+                }
+                if recovered { pacer.invalidate(); } else { pacer.frame_presented(occluded || lost); }
+            },
+            _ => {},
+        }
+
+        *control_flow = match pacer.wake() {
+            Wake::Immediately   => ControlFlow::Poll,
+            Wake::At(time)      => ControlFlow::WaitUntil(pacer.clock().instant(time)),
+            Wake::OnEvent       => ControlFlow::Wait,
+        };
+    });
 }
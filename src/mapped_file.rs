@@ -0,0 +1,74 @@
+//! Read-only memory mapped files, so archives (or any other file) can be handed to `archive::Archive` or
+//! `Blob::from_data` without reading them in first.
+
+use crate::win32::*;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr::{null, null_mut};
+
+/// A whole file mapped read-only.  Other processes can still read it, but not write to it, while it's mapped.
+pub struct MappedFile {
+    view:   *const u8,
+    len:    usize,
+}
+
+// The view is read-only, and only unmapped on drop.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// MSDN: [CreateFileMappingW](https://docs.microsoft.com/en-us/windows/desktop/api/memoryapi/nf-memoryapi-createfilemappingw),
+    /// [MapViewOfFile](https://docs.microsoft.com/en-us/windows/desktop/api/memoryapi/nf-memoryapi-mapviewoffile)
+    pub fn open<P: AsRef<Path>> (path: &P) -> io::Result<MappedFile> {
+        let mut path : Vec<u16> = path.as_ref().as_os_str().encode_wide().collect();
+        path.push(0u16);
+
+        unsafe {
+            let file = CreateFileW(path.as_ptr(), GENERIC_READ, FILE_SHARE_READ, null_mut(), OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, null_mut());
+            if file == INVALID_HANDLE_VALUE { return Err(io::Error::last_os_error()); }
+
+            let mut size : LARGE_INTEGER = std::mem::zeroed();
+            if GetFileSizeEx(file, &mut size) == FALSE { return Err(close_after_error(file)); }
+            let len = *size.QuadPart() as usize;
+            if len == 0 {
+                // CreateFileMappingW refuses empty files.
+                CloseHandle(file);
+                return Ok(MappedFile { view: null(), len: 0 });
+            }
+
+            // The mapping keeps the file open, and the view keeps the mapping open.
+            let mapping = CreateFileMappingW(file, null_mut(), PAGE_READONLY, 0, 0, null());
+            if mapping.is_null() { return Err(close_after_error(file)); }
+            CloseHandle(file);
+
+            let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+            if view.is_null() { return Err(close_after_error(mapping)); }
+            CloseHandle(mapping);
+
+            Ok(MappedFile { view: view as *const u8, len })
+        }
+    }
+
+    pub fn as_bytes (&self) -> &[u8] {
+        if self.view.is_null() { return &[]; }
+        unsafe { std::slice::from_raw_parts(self.view, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref (&self) -> &[u8] { self.as_bytes() }
+}
+
+impl Drop for MappedFile {
+    fn drop (&mut self) {
+        if !self.view.is_null() { unsafe { UnmapViewOfFile(self.view as LPCVOID) }; }
+    }
+}
+
+/// The last error, before `CloseHandle` gets a chance to overwrite it.
+unsafe fn close_after_error (handle: HANDLE) -> io::Error {
+    let error = io::Error::last_os_error();
+    CloseHandle(handle);
+    error
+}
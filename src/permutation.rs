@@ -0,0 +1,469 @@
+//! Shader permutations:  one source compiled once per combination of keyword values (`SKINNED`, `FOG`, ...)
+//!
+//! Keywords become `D3D_SHADER_MACRO` defines, every keyword always defined to its value (so test them with `#if FOG`,
+//! not `#ifdef FOG`.)  Each combination packs into a `Key`, a bitmask with a field per keyword, which is what the
+//! runtime looks compiled variants up by:
+//!
+//! ```ignore
+//! let permutation = Permutation::new(vec![
+//!     Keyword::toggle("SKINNED"),
+//!     Keyword::toggle("ALPHA_TEST"),
+//!     Keyword::new("FOG", &["NONE", "LINEAR", "EXP"]),
+//! ], vec![
+//!     Rule::exclude(&[("SKINNED", "1"), ("FOG", "EXP")]),
+//! ])?;
+//! let shaders = permutation.compile_all(4, move |variant| compile(variant.defines()))?;
+//! let key = permutation.key(&[("SKINNED", "1"), ("FOG", "LINEAR")])?;
+//! let shader = shaders.get(key).unwrap();
+//!
+//! // Or a keyword at a time, checking the rules once they're all combined:
+//! let key = permutation.check(permutation.field("SKINNED", "1")? | permutation.field("FOG", "LINEAR")?)?;
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::BitOr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Keyword {
+    pub name:   String,
+    /// The first value is the default, for keywords a `Key` doesn't mention.
+    pub values: Vec<String>,
+}
+
+impl Keyword {
+    pub fn new (name: &str, values: &[&str]) -> Keyword {
+        Keyword { name: name.to_string(), values: values.iter().map(|v| v.to_string()).collect() }
+    }
+
+    /// A keyword that's off (`0`) by default, or on (`1`).
+    pub fn toggle (name: &str) -> Keyword { Keyword::new(name, &["0", "1"]) }
+
+    /// Bits needed to store any value's index.
+    fn bits (&self) -> u32 {
+        let max_index = self.values.len().saturating_sub(1) as u64;
+        64 - max_index.leading_zeros()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Rule {
+    /// These keyword values never appear together.
+    Exclude(Vec<(String, String)>),
+    /// If `when` is set, `then` must also be set.
+    Requires { when: (String, String), then: (String, String) },
+}
+
+impl Rule {
+    pub fn exclude (values: &[(&str, &str)]) -> Rule {
+        Rule::Exclude(values.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    pub fn requires (when: (&str, &str), then: (&str, &str)) -> Rule {
+        Rule::Requires { when: (when.0.to_string(), when.1.to_string()), then: (then.0.to_string(), then.1.to_string()) }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    NoKeywords,
+    DuplicateKeyword(String),
+    NoValues(String),
+    DuplicateValue { keyword: String, value: String },
+    /// Keywords must be valid HLSL identifiers, and values can't contain NULs or line breaks.
+    BadName(String),
+    /// The keywords' fields don't fit in a 64-bit `Key`.
+    TooManyBits(u32),
+    UnknownKeyword(String),
+    UnknownValue { keyword: String, value: String },
+    /// Every combination is excluded by the rules.
+    NoVariants,
+    /// The keyword values in a `Key` are excluded by the rules, or aren't values of the keywords at all.
+    Excluded(Key),
+}
+
+impl fmt::Display for Error {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoKeywords                               => write!(f, "no keywords"),
+            Error::DuplicateKeyword(ref k)                  => write!(f, "keyword {} declared twice", k),
+            Error::NoValues(ref k)                          => write!(f, "keyword {} has no values", k),
+            Error::DuplicateValue { ref keyword, ref value } => write!(f, "keyword {} lists {} twice", keyword, value),
+            Error::BadName(ref name)                        => write!(f, "{:?} isn't an identifier", name),
+            Error::TooManyBits(bits)                        => write!(f, "keywords need {} bits, limit is 64", bits),
+            Error::UnknownKeyword(ref k)                    => write!(f, "unknown keyword {}", k),
+            Error::UnknownValue { ref keyword, ref value }  => write!(f, "keyword {} has no value {}", keyword, value),
+            Error::NoVariants                               => write!(f, "every variant is excluded"),
+            Error::Excluded(key)                            => write!(f, "variant {:?} is excluded", key),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A combination of keyword values, packed into a bitfield per keyword.  Get one from `Permutation::key`, or combine
+/// single keyword `Key`s from `Permutation::field` with `|` and `Permutation::check` the result.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default)]
+pub struct Key(pub u64);
+
+impl BitOr for Key {
+    type Output = Key;
+    fn bitor (self, other: Key) -> Key { Key(self.0 | other.0) }
+}
+
+/// One valid combination of keyword values.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Variant {
+    pub key:        Key,
+    /// `(keyword, value)` for every keyword, in declaration order.
+    pub defines:    Vec<(String, String)>,
+}
+
+impl Variant {
+    pub fn defines (&self) -> &[(String, String)] { &self.defines[..] }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Permutation {
+    keywords:   Vec<Keyword>,
+    /// Bit offset of each keyword's field in a `Key`.
+    offsets:    Vec<u32>,
+    /// `Rule`s with names resolved:  `(keyword index, value index)`.
+    excludes:   Vec<Vec<(usize, usize)>>,
+    requires:   Vec<((usize, usize), (usize, usize))>,
+}
+
+impl Permutation {
+    pub fn new (keywords: Vec<Keyword>, rules: Vec<Rule>) -> Result<Permutation> {
+        if keywords.is_empty() { return Err(Error::NoKeywords); }
+        let mut offsets = Vec::with_capacity(keywords.len());
+        let mut bits = 0;
+        for (index, keyword) in keywords.iter().enumerate() {
+            if !is_identifier(&keyword.name) { return Err(Error::BadName(keyword.name.clone())); }
+            if keywords[..index].iter().any(|k| k.name == keyword.name) { return Err(Error::DuplicateKeyword(keyword.name.clone())); }
+            if keyword.values.is_empty() { return Err(Error::NoValues(keyword.name.clone())); }
+            for (v, value) in keyword.values.iter().enumerate() {
+                if !is_define_value(value) { return Err(Error::BadName(value.clone())); }
+                if keyword.values[..v].contains(value) { return Err(Error::DuplicateValue { keyword: keyword.name.clone(), value: value.clone() }); }
+            }
+            offsets.push(bits);
+            bits += keyword.bits();
+        }
+        if bits > 64 { return Err(Error::TooManyBits(bits)); }
+
+        let mut permutation = Permutation { keywords, offsets, excludes: Vec::new(), requires: Vec::new() };
+        for rule in rules.iter() {
+            match *rule {
+                Rule::Exclude(ref values) => {
+                    let values = values.iter().map(|(k, v)| permutation.find(k, v)).collect::<Result<Vec<_>>>()?;
+                    permutation.excludes.push(values);
+                },
+                Rule::Requires { ref when, ref then } => {
+                    let rule = (permutation.find(&when.0, &when.1)?, permutation.find(&then.0, &then.1)?);
+                    permutation.requires.push(rule);
+                },
+            }
+        }
+        if !permutation.any_allowed() { return Err(Error::NoVariants); }
+        Ok(permutation)
+    }
+
+    pub fn keywords (&self) -> &[Keyword] { &self.keywords[..] }
+
+    /// The `Key` for `values`, with unmentioned keywords at their defaults.  Fails if the combination is excluded.
+    pub fn key (&self, values: &[(&str, &str)]) -> Result<Key> {
+        let mut indices = vec![0; self.keywords.len()];
+        for &(keyword, value) in values.iter() {
+            let (k, v) = self.find(keyword, value)?;
+            indices[k] = v;
+        }
+        let key = self.pack(&indices[..]);
+        if self.allows(&indices[..]) { Ok(key) } else { Err(Error::Excluded(key)) }
+    }
+
+    /// The bits of `keyword`'s `value` alone.  Unlike `key`, other keywords' defaults aren't checked against the rules:
+    /// combine fields with `|`, then `check` the result.
+    pub fn field (&self, keyword: &str, value: &str) -> Result<Key> {
+        let (k, v) = self.find(keyword, value)?;
+        Ok(Key((v as u64) << self.offsets[k]))
+    }
+
+    /// `key`, if it's a variant the rules allow.
+    pub fn check (&self, key: Key) -> Result<Key> {
+        match self.unpack(key) {
+            Some(_) => Ok(key),
+            None    => Err(Error::Excluded(key)),
+        }
+    }
+
+    /// The index of each keyword's value in `key`, or `None` if `key` isn't a valid variant.
+    pub fn unpack (&self, key: Key) -> Option<Vec<usize>> {
+        let mut indices = Vec::with_capacity(self.keywords.len());
+        let mut used = 0u64;
+        for (keyword, &offset) in self.keywords.iter().zip(self.offsets.iter()) {
+            let mask = (1u64 << keyword.bits()) - 1;
+            let index = ((key.0 >> offset) & mask) as usize;
+            if index >= keyword.values.len() { return None; }
+            used |= mask << offset;
+            indices.push(index);
+        }
+        if key.0 & !used != 0 || !self.allows(&indices[..]) { return None; }
+        Some(indices)
+    }
+
+    /// Every combination the rules allow, in `Key` order.
+    pub fn variants (&self) -> Vec<Variant> {
+        let mut variants = Vec::new();
+        let mut indices = vec![0; self.keywords.len()];
+        loop {
+            if self.allows(&indices[..]) { variants.push(self.variant(&indices[..])); }
+            if !self.advance(&mut indices[..]) { break; }
+        }
+        variants.sort_by_key(|v| v.key);
+        variants
+    }
+
+    /// Compile every variant on `threads` threads, stopping at the first failure.
+    pub fn compile_all<T, E, F> (&self, threads: usize, compile: F) -> std::result::Result<PermutationSet<T>, (Variant, E)>
+        where T: Send + 'static, E: Send + 'static, F: Fn(&Variant) -> std::result::Result<T, E> + Send + Sync + 'static
+    {
+        let queue = Arc::new(Mutex::new(self.variants()));
+        let failed = Arc::new(Mutex::new(false));
+        let compile = Arc::new(compile);
+
+        let workers = (0..threads.max(1)).map(|_| {
+            let (queue, failed, compile) = (queue.clone(), failed.clone(), compile.clone());
+            thread::spawn(move || {
+                let mut compiled = Vec::new();
+                loop {
+                    if *failed.lock().unwrap() { return Ok(compiled); }
+                    let variant = match queue.lock().unwrap().pop() { Some(v) => v, None => return Ok(compiled) };
+                    match compile(&variant) {
+                        Ok(shader) => compiled.push((variant.key, shader)),
+                        Err(e) => { *failed.lock().unwrap() = true; return Err((variant, e)); },
+                    }
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        let mut shaders = HashMap::new();
+        let mut error = None;
+        for worker in workers {
+            match worker.join().unwrap() {
+                Ok(compiled) => shaders.extend(compiled),
+                Err(e) => if error.is_none() { error = Some(e); },
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None    => Ok(PermutationSet { permutation: self.clone(), shaders }),
+        }
+    }
+
+    fn find (&self, keyword: &str, value: &str) -> Result<(usize, usize)> {
+        let k = self.keywords.iter().position(|k| k.name == keyword).ok_or_else(|| Error::UnknownKeyword(keyword.to_string()))?;
+        let v = self.keywords[k].values.iter().position(|v| v == value).ok_or_else(|| Error::UnknownValue { keyword: keyword.to_string(), value: value.to_string() })?;
+        Ok((k, v))
+    }
+
+    /// Step `indices` to the next combination, the first keyword changing fastest.  `false` after the last.
+    fn advance (&self, indices: &mut [usize]) -> bool {
+        for (index, keyword) in indices.iter_mut().zip(self.keywords.iter()) {
+            *index += 1;
+            if *index < keyword.values.len() { return true; }
+            *index = 0;
+        }
+        false
+    }
+
+    /// Whether the rules allow any combination, stopping at the first.
+    fn any_allowed (&self) -> bool {
+        let mut indices = vec![0; self.keywords.len()];
+        loop {
+            if self.allows(&indices[..]) { return true; }
+            if !self.advance(&mut indices[..]) { return false; }
+        }
+    }
+
+    fn allows (&self, indices: &[usize]) -> bool {
+        let set = |&(k, v): &(usize, usize)| indices[k] == v;
+        !self.excludes.iter().any(|values| values.iter().all(set))
+            && self.requires.iter().all(|(when, then)| !set(when) || set(then))
+    }
+
+    fn pack (&self, indices: &[usize]) -> Key {
+        Key(indices.iter().zip(self.offsets.iter()).fold(0, |key, (&index, &offset)| key | (index as u64) << offset))
+    }
+
+    fn variant (&self, indices: &[usize]) -> Variant {
+        Variant {
+            key:        self.pack(indices),
+            defines:    self.keywords.iter().zip(indices.iter()).map(|(k, &v)| (k.name.clone(), k.values[v].clone())).collect(),
+        }
+    }
+}
+
+/// Compiled variants of a `Permutation`, by `Key`.
+pub struct PermutationSet<T> {
+    permutation:    Permutation,
+    shaders:        HashMap<Key, T>,
+}
+
+impl<T> PermutationSet<T> {
+    pub fn permutation (&self) -> &Permutation { &self.permutation }
+    pub fn get (&self, key: Key) -> Option<&T> { self.shaders.get(&key) }
+    pub fn len (&self) -> usize { self.shaders.len() }
+    pub fn is_empty (&self) -> bool { self.shaders.is_empty() }
+    pub fn iter (&self) -> impl Iterator<Item = (Key, &T)> { self.shaders.iter().map(|(&k, t)| (k, t)) }
+
+    /// Look up by keyword values, as `Permutation::key`.
+    pub fn get_values (&self, values: &[(&str, &str)]) -> Result<&T> {
+        let key = self.permutation.key(values)?;
+        self.shaders.get(&key).ok_or(Error::Excluded(key))
+    }
+}
+
+/// Values become `#define` bodies:  a NUL would end one early, and a line break would end the `#define`.
+fn is_define_value (value: &str) -> bool {
+    !value.contains(['\0', '\n', '\r'])
+}
+
+fn is_identifier (name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn example () -> Permutation {
+        Permutation::new(vec![
+            Keyword::toggle("SKINNED"),
+            Keyword::toggle("ALPHA_TEST"),
+            Keyword::new("FOG", &["NONE", "LINEAR", "EXP"]),
+        ], vec![
+            Rule::exclude(&[("SKINNED", "1"), ("FOG", "EXP")]),
+            Rule::requires(("ALPHA_TEST", "1"), ("FOG", "NONE")),
+        ]).unwrap()
+    }
+
+    #[test] fn keys () {
+        let p = example();
+        // SKINNED at bit 0, ALPHA_TEST at bit 1, FOG in bits 2..4
+        assert_eq!(p.key(&[]),                                          Ok(Key(0)));
+        assert_eq!(p.key(&[("SKINNED", "1")]),                          Ok(Key(0b0001)));
+        assert_eq!(p.key(&[("FOG", "LINEAR"), ("SKINNED", "1")]),       Ok(Key(0b0101)));
+        assert_eq!(p.key(&[("FOG", "EXP")]),                            Ok(Key(0b1000)));
+        assert_eq!(p.key(&[("FOG", "LINEAR"), ("FOG", "NONE")]),        Ok(Key(0)));  // Last wins
+        assert_eq!(p.field("SKINNED", "1").unwrap() | p.field("FOG", "LINEAR").unwrap(), Key(0b0101));
+        assert_eq!(p.field("FOG", "EXP"),       Ok(Key(0b1000)));
+        assert_eq!(p.field("SKINNED", "0"),     Ok(Key(0)));
+        assert_eq!(p.field("FOG", "FOGGY"),     Err(Error::UnknownValue { keyword: "FOG".into(), value: "FOGGY".into() }));
+        assert_eq!(p.key(&[("FOG", "FOGGY")]),  Err(Error::UnknownValue { keyword: "FOG".into(), value: "FOGGY".into() }));
+        assert_eq!(p.key(&[("SHADOW", "1")]),   Err(Error::UnknownKeyword("SHADOW".into())));
+    }
+
+    #[test] fn rules () {
+        let p = example();
+        assert_eq!(p.key(&[("SKINNED", "1"), ("FOG", "EXP")]),          Err(Error::Excluded(Key(0b1001))));
+        assert_eq!(p.key(&[("ALPHA_TEST", "1"), ("FOG", "LINEAR")]),    Err(Error::Excluded(Key(0b0110))));
+        assert_eq!(p.key(&[("ALPHA_TEST", "1")]),                       Ok(Key(0b0010)));
+
+        // 2 * 2 * 3 = 12, less SKINNED + EXP (2), less ALPHA_TEST without NONE (4, one of them already excluded)
+        let variants = p.variants();
+        assert_eq!(variants.len(), 7);
+        assert!(variants.windows(2).all(|w| w[0].key < w[1].key));
+        assert!(variants.iter().all(|v| !(v.defines.contains(&("SKINNED".into(), "1".into())) && v.defines.contains(&("FOG".into(), "EXP".into())))));
+        assert_eq!(variants[0].defines, vec![("SKINNED".into(), "0".into()), ("ALPHA_TEST".into(), "0".into()), ("FOG".into(), "NONE".into())]);
+    }
+
+    #[test] fn fields_only_checked_combined () {
+        let p = Permutation::new(vec![
+            Keyword::toggle("SKINNED"),
+            Keyword::new("FOG", &["NONE", "LINEAR"]),
+        ], vec![
+            Rule::requires(("SKINNED", "1"), ("FOG", "LINEAR")),
+        ]).unwrap();
+        assert_eq!(p.key(&[("SKINNED", "1")]), Err(Error::Excluded(Key(0b01))), "FOG defaults to NONE");
+        let skinned = p.field("SKINNED", "1").unwrap();
+        assert_eq!(p.check(skinned | p.field("FOG", "LINEAR").unwrap()), Ok(Key(0b11)));
+        assert_eq!(p.check(skinned), Err(Error::Excluded(Key(0b01))));
+        assert_eq!(p.check(Key(0b100)), Err(Error::Excluded(Key(0b100))), "unused bits");
+    }
+
+    #[test] fn unpack () {
+        let p = example();
+        for variant in p.variants() {
+            let indices = p.unpack(variant.key).unwrap();
+            let values = p.keywords().iter().zip(indices.iter()).map(|(k, &v)| (&k.name[..], &k.values[v][..])).collect::<Vec<_>>();
+            assert_eq!(p.key(&values[..]), Ok(variant.key));
+        }
+        assert_eq!(p.unpack(Key(0b1100)),   None);  // FOG index 3
+        assert_eq!(p.unpack(Key(0b1001)),   None);  // Excluded
+        assert_eq!(p.unpack(Key(1 << 4)),   None);  // Unused bits
+    }
+
+    #[test] fn errors () {
+        let new = |keywords: Vec<Keyword>, rules: Vec<Rule>| Permutation::new(keywords, rules).err();
+        assert_eq!(new(vec![], vec![]),                                                     Some(Error::NoKeywords));
+        assert_eq!(new(vec![Keyword::toggle("A"), Keyword::toggle("A")], vec![]),           Some(Error::DuplicateKeyword("A".into())));
+        assert_eq!(new(vec![Keyword::new("A", &[])], vec![]),                               Some(Error::NoValues("A".into())));
+        assert_eq!(new(vec![Keyword::new("A", &["X", "X"])], vec![]),                       Some(Error::DuplicateValue { keyword: "A".into(), value: "X".into() }));
+        assert_eq!(new(vec![Keyword::toggle("1A")], vec![]),                                Some(Error::BadName("1A".into())));
+        assert_eq!(new(vec![Keyword::new("A", &["X", "Y\0"])], vec![]),                     Some(Error::BadName("Y\0".into())));
+        assert_eq!(new(vec![Keyword::new("A", &["X\nY"])], vec![]),                         Some(Error::BadName("X\nY".into())));
+        assert!(new(vec![Keyword::new("A", &["", "0.5", "float4(1, 2, 3, 4)"])], vec![]).is_none(), "any single line value");
+        assert_eq!(new((0..65).map(|i| Keyword::toggle(&format!("K{}", i))).collect(), vec![]), Some(Error::TooManyBits(65)));
+        assert_eq!(new(vec![Keyword::toggle("A")], vec![Rule::exclude(&[("B", "1")])]),     Some(Error::UnknownKeyword("B".into())));
+        assert_eq!(new(vec![Keyword::toggle("A")], vec![Rule::exclude(&[("A", "0")]), Rule::exclude(&[("A", "1")])]), Some(Error::NoVariants));
+        assert_eq!(new(vec![Keyword::toggle("A")], vec![Rule::requires(("A", "0"), ("A", "1")), Rule::exclude(&[("A", "1")])]), Some(Error::NoVariants));
+    }
+
+    #[test] fn new_stops_at_first_variant () {
+        // 2^64 combinations:  enumerating them all would never finish.
+        let keywords = (0..64).map(|i| Keyword::toggle(&format!("K{}", i))).collect();
+        let p = Permutation::new(keywords, vec![Rule::exclude(&[("K0", "0")])]).unwrap();
+        assert_eq!(p.key(&[("K0", "1"), ("K63", "1")]), Ok(Key(1 | 1 << 63)));
+    }
+
+    #[test] fn compile_all () {
+        let p = example();
+        let set = p.compile_all(4, |variant: &Variant| -> std::result::Result<u64, ()> { Ok(variant.key.0) }).unwrap();
+        assert_eq!(set.len(), 7);
+        assert!(!set.is_empty());
+        assert!(set.iter().all(|(key, &value)| key.0 == value));
+        assert_eq!(set.get_values(&[("FOG", "LINEAR")]), Ok(&0b0100));
+        assert_eq!(set.get_values(&[("SKINNED", "1"), ("FOG", "EXP")]), Err(Error::Excluded(Key(0b1001))));
+        assert_eq!(set.get(Key(0b1001)), None);
+    }
+
+    #[test] fn compile_all_failure () {
+        let p = example();
+        let failing = p.key(&[("SKINNED", "1"), ("FOG", "LINEAR")]).unwrap();
+        for &threads in [0, 1, 4].iter() {
+            let compiled = Arc::new(AtomicUsize::new(0));
+            let counter = compiled.clone();
+            let result = p.compile_all(threads, move |variant: &Variant| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                if variant.key == failing { Err(format!("{:?} failed", variant.key)) } else { Ok(()) }
+            });
+            let (variant, error) = result.err().unwrap();
+            assert_eq!((variant.key, error), (failing, format!("{:?} failed", failing)), "{} threads", threads);
+            assert!(compiled.load(Ordering::SeqCst) <= p.variants().len());
+        }
+
+        // One thread pops variants highest key first, so a failure there stops everything else.
+        let last = p.variants().last().unwrap().key;
+        let compiled = Arc::new(AtomicUsize::new(0));
+        let counter = compiled.clone();
+        let result = p.compile_all(1, move |variant: &Variant| { counter.fetch_add(1, Ordering::SeqCst); if variant.key == last { Err(()) } else { Ok(()) } });
+        assert_eq!(result.err().map(|(variant, _)| variant.key), Some(last));
+        assert_eq!(compiled.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! Checks that bound shader stages, a primitive topology, and stream output declarations fit together.
+//!
+//! D3D only complains about a mismatched pipeline through the debug layer, at draw time - and draws nothing.  These
+//! rules catch the common mistakes up front, without a device:  describe what's bound as `Stages`, then `validate`.
+//! `com::d3d11` converts `PrimitiveTopology` and `SoDeclarationEntry` into the types here.
+
+use crate::dxbc::{ProgramType, ShaderVersion};
+use std::fmt;
+
+/// `D3D11_SO_STREAM_COUNT`
+pub const STREAM_COUNT : u32 = 4;
+/// `D3D11_SO_BUFFER_SLOT_COUNT`
+pub const BUFFER_SLOT_COUNT : u8 = 4;
+/// `D3D11_SO_BUFFER_MAX_STRIDE_IN_BYTES`
+pub const MAX_BUFFER_STRIDE : u32 = 2048;
+/// `D3D11_SO_STREAM_COUNT * D3D11_SO_OUTPUT_COMPONENT_COUNT`
+pub const MAX_ENTRIES : usize = 4 * 128;
+
+/// What the input assembler feeds the pipeline, ignoring list vs strip.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Primitive {
+    Points,
+    Lines,
+    Triangles,
+    LinesAdj,
+    TrianglesAdj,
+    /// A patch of 1 ..= 32 control points, for the hull shader.
+    Patches(u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct HullStage {
+    /// `InputPatch<T, N>`'s `N`, if known (e.g. from reflection.)
+    pub input_control_points:   Option<u32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GeometryStage {
+    /// The `point` / `line` / `triangle` / `lineadj` / `triangleadj` input primitive, if known.
+    pub input:                  Option<Primitive>,
+}
+
+/// Which shader stages are bound.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Stages {
+    pub vertex:     bool,
+    pub hull:       Option<HullStage>,
+    pub domain:     bool,
+    pub geometry:   Option<GeometryStage>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    NoVertexShader,
+    /// A patch list topology is only valid with a hull shader to consume it.
+    PatchesWithoutHullShader,
+    /// Hull shaders only accept patch list topologies.
+    HullShaderWithoutPatches(Primitive),
+    /// Hull and domain shaders must be bound together.
+    HullWithoutDomain,
+    DomainWithoutHull,
+    /// The topology's control points per patch don't match the hull shader's `InputPatch`.
+    ControlPointMismatch { topology: u32, hull_shader: u32 },
+    /// The topology doesn't match the geometry shader's input primitive.
+    GeometryInputMismatch { topology: Primitive, geometry_shader: Primitive },
+    /// More than `MAX_ENTRIES` stream output entries.
+    TooManyEntries(usize),
+    /// More than `BUFFER_SLOT_COUNT` stream output buffer strides.
+    TooManyStrides(usize),
+    /// Entry `entry` writes to stream `stream`, but there are only `STREAM_COUNT`.
+    BadStream { entry: usize, stream: u32 },
+    /// Entry `entry` writes to output slot `slot`, but there are only `BUFFER_SLOT_COUNT`.
+    BadSlot { entry: usize, slot: u8 },
+    /// Entry `entry` writes components `start .. start + count`, which isn't a non-empty subset of `xyzw` (or is an
+    /// empty gap.)
+    BadComponents { entry: usize, start: u8, count: u8 },
+    /// Output slot `slot` is written by more than one stream.
+    SlotSharedByStreams { slot: u8 },
+    /// Output slot `slot` is written, but has no stride.
+    MissingStride { slot: u8 },
+    /// Output slot `slot` needs `needed` bytes per vertex, but its stride is `stride` (or `stride` is over
+    /// `MAX_BUFFER_STRIDE`, or not a multiple of 4.)
+    BadStride { slot: u8, needed: u32, stride: u32 },
+    /// The rasterized stream must be one of the `STREAM_COUNT` streams, or none.
+    BadRasterizedStream(u32),
+    /// Only `gs_5_0` shaders can write or rasterize a stream other than 0.
+    StreamRequiresGs5(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::NoVertexShader                                   => write!(f, "no vertex shader bound"),
+            Error::PatchesWithoutHullShader                         => write!(f, "patch list topology without a hull shader"),
+            Error::HullShaderWithoutPatches(p)                      => write!(f, "hull shader bound, but topology is {:?}", p),
+            Error::HullWithoutDomain                                => write!(f, "hull shader bound without a domain shader"),
+            Error::DomainWithoutHull                                => write!(f, "domain shader bound without a hull shader"),
+            Error::ControlPointMismatch { topology, hull_shader }   => write!(f, "topology has {} control points per patch, hull shader expects {}", topology, hull_shader),
+            Error::GeometryInputMismatch { topology, geometry_shader } => write!(f, "topology is {:?}, geometry shader expects {:?}", topology, geometry_shader),
+            Error::TooManyEntries(n)                                => write!(f, "{} stream output entries, limit is {}", n, MAX_ENTRIES),
+            Error::TooManyStrides(n)                                => write!(f, "{} stream output strides, limit is {}", n, BUFFER_SLOT_COUNT),
+            Error::BadStream { entry, stream }                      => write!(f, "entry {} writes stream {}", entry, stream),
+            Error::BadSlot { entry, slot }                          => write!(f, "entry {} writes output slot {}", entry, slot),
+            Error::BadComponents { entry, start, count }            => write!(f, "entry {} writes {} components starting at {}", entry, count, start),
+            Error::SlotSharedByStreams { slot }                     => write!(f, "output slot {} is written by multiple streams", slot),
+            Error::MissingStride { slot }                           => write!(f, "output slot {} has no stride", slot),
+            Error::BadStride { slot, needed, stride }               => write!(f, "output slot {} needs {} bytes, stride is {}", slot, needed, stride),
+            Error::BadRasterizedStream(stream)                      => write!(f, "rasterized stream {} doesn't exist", stream),
+            Error::StreamRequiresGs5(stream)                        => write!(f, "stream {} requires a gs_5_0 shader", stream),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Check that `stages` can draw `topology`.
+pub fn validate (stages: &Stages, topology: Primitive) -> Result<()> {
+    if !stages.vertex { return Err(Error::NoVertexShader); }
+
+    match (stages.hull.is_some(), stages.domain) {
+        (true, false) => return Err(Error::HullWithoutDomain),
+        (false, true) => return Err(Error::DomainWithoutHull),
+        _ => {},
+    }
+
+    match (topology, stages.hull) {
+        (Primitive::Patches(_), None) => return Err(Error::PatchesWithoutHullShader),
+        (Primitive::Patches(topology), Some(HullStage { input_control_points: Some(hull_shader) })) if topology != hull_shader => {
+            return Err(Error::ControlPointMismatch { topology, hull_shader });
+        },
+        (Primitive::Patches(_), Some(_)) => {},
+        (other, Some(_)) => return Err(Error::HullShaderWithoutPatches(other)),
+        (_, None) => {},
+    }
+
+    // With tessellation, the geometry shader sees the domain shader's output instead, which isn't described here.
+    if let (None, Some(GeometryStage { input: Some(geometry_shader) })) = (stages.hull, stages.geometry) {
+        if topology != geometry_shader { return Err(Error::GeometryInputMismatch { topology, geometry_shader }); }
+    }
+
+    Ok(())
+}
+
+/// One `D3D11_SO_DECLARATION_ENTRY`, minus its semantic.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StreamOutputEntry {
+    pub stream:             u32,
+    /// Skips `component_count` components of the output slot instead of writing a semantic.  `start_component` is
+    /// ignored.
+    pub gap:                bool,
+    pub start_component:    u8,
+    pub component_count:    u8,
+    pub output_slot:        u8,
+}
+
+/// Check stream output declarations for `CreateGeometryShaderWithStreamOutput`, streaming out of a `version` shader.
+/// `rasterized_stream` is `None` for `D3D11_SO_NO_RASTERIZED_STREAM`.
+pub fn validate_stream_output (version: ShaderVersion, entries: &[StreamOutputEntry], buffer_strides: &[u32], rasterized_stream: Option<u32>) -> Result<()> {
+    // Vertex and domain shaders, and geometry shaders before SM5, only have stream 0.
+    let streams = version.program == ProgramType::Geometry && version.major >= 5;
+
+    if entries.len() > MAX_ENTRIES { return Err(Error::TooManyEntries(entries.len())); }
+    if buffer_strides.len() > BUFFER_SLOT_COUNT as usize { return Err(Error::TooManyStrides(buffer_strides.len())); }
+    if let Some(stream) = rasterized_stream {
+        if stream >= STREAM_COUNT { return Err(Error::BadRasterizedStream(stream)); }
+        if stream != 0 && !streams { return Err(Error::StreamRequiresGs5(stream)); }
+    }
+
+    let mut slot_streams = [None; BUFFER_SLOT_COUNT as usize];
+    let mut slot_bytes = [0u32; BUFFER_SLOT_COUNT as usize];
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.stream >= STREAM_COUNT { return Err(Error::BadStream { entry: index, stream: entry.stream }); }
+        if entry.stream != 0 && !streams { return Err(Error::StreamRequiresGs5(entry.stream)); }
+        if entry.output_slot >= BUFFER_SLOT_COUNT { return Err(Error::BadSlot { entry: index, slot: entry.output_slot }); }
+        let (start, count) = (entry.start_component, entry.component_count);
+        let fits = entry.gap || start as u32 + count as u32 <= 4; // Gaps may skip more than one register's worth
+        if count == 0 || !fits { return Err(Error::BadComponents { entry: index, start, count }); }
+
+        let slot = entry.output_slot as usize;
+        match slot_streams[slot] {
+            Some(stream) if stream != entry.stream => return Err(Error::SlotSharedByStreams { slot: entry.output_slot }),
+            _ => slot_streams[slot] = Some(entry.stream),
+        }
+        slot_bytes[slot] += 4 * count as u32;
+    }
+
+    for slot in 0..BUFFER_SLOT_COUNT {
+        let needed = slot_bytes[slot as usize];
+        if needed == 0 { continue; }
+        let stride = *buffer_strides.get(slot as usize).ok_or(Error::MissingStride { slot })?;
+        if stride < needed || stride > MAX_BUFFER_STRIDE || !stride.is_multiple_of(4) { return Err(Error::BadStride { slot, needed, stride }); }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    const VS_5_0 : ShaderVersion = ShaderVersion { program: ProgramType::Vertex,   major: 5, minor: 0 };
+    const GS_4_1 : ShaderVersion = ShaderVersion { program: ProgramType::Geometry, major: 4, minor: 1 };
+    const GS_5_0 : ShaderVersion = ShaderVersion { program: ProgramType::Geometry, major: 5, minor: 0 };
+
+    const VS            : Stages = Stages { vertex: true, hull: None, domain: false, geometry: None };
+    const TESSELLATED   : Stages = Stages { vertex: true, hull: Some(HullStage { input_control_points: Some(3) }), domain: true, geometry: None };
+
+    fn entry (stream: u32, output_slot: u8, start_component: u8, component_count: u8) -> StreamOutputEntry {
+        StreamOutputEntry { stream, gap: false, start_component, component_count, output_slot }
+    }
+
+    #[test] fn stages () {
+        assert_eq!(validate(&VS, Primitive::Triangles), Ok(()));
+        assert_eq!(validate(&Stages { vertex: false, ..VS }, Primitive::Triangles), Err(Error::NoVertexShader));
+        assert_eq!(validate(&Stages { domain: true, ..VS }, Primitive::Triangles), Err(Error::DomainWithoutHull));
+        assert_eq!(validate(&Stages { domain: false, ..TESSELLATED }, Primitive::Patches(3)), Err(Error::HullWithoutDomain));
+    }
+
+    #[test] fn patches () {
+        assert_eq!(validate(&VS, Primitive::Patches(3)), Err(Error::PatchesWithoutHullShader));
+        assert_eq!(validate(&TESSELLATED, Primitive::Patches(3)), Ok(()));
+        assert_eq!(validate(&TESSELLATED, Primitive::Patches(4)), Err(Error::ControlPointMismatch { topology: 4, hull_shader: 3 }));
+        assert_eq!(validate(&TESSELLATED, Primitive::Triangles), Err(Error::HullShaderWithoutPatches(Primitive::Triangles)));
+
+        let unknown = Stages { hull: Some(HullStage::default()), ..TESSELLATED };
+        assert_eq!(validate(&unknown, Primitive::Patches(32)), Ok(()));
+    }
+
+    #[test] fn geometry_input () {
+        let gs = |input| Stages { geometry: Some(GeometryStage { input: Some(input) }), ..VS };
+        assert_eq!(validate(&gs(Primitive::TrianglesAdj), Primitive::TrianglesAdj), Ok(()));
+        assert_eq!(validate(&gs(Primitive::Triangles), Primitive::Lines), Err(Error::GeometryInputMismatch { topology: Primitive::Lines, geometry_shader: Primitive::Triangles }));
+        assert_eq!(validate(&Stages { geometry: Some(GeometryStage::default()), ..VS }, Primitive::Points), Ok(()));
+
+        // The domain shader's output isn't described, so anything goes after tessellation.
+        assert_eq!(validate(&Stages { geometry: Some(GeometryStage { input: Some(Primitive::Points) }), ..TESSELLATED }, Primitive::Patches(3)), Ok(()));
+    }
+
+    #[test] fn stream_output () {
+        let position_color = [entry(0, 0, 0, 4), entry(0, 0, 0, 4)];
+        assert_eq!(validate_stream_output(VS_5_0, &position_color, &[32], Some(0)), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &position_color, &[32], None), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &[], &[], None), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &[entry(4, 0, 0, 4)], &[16], None), Err(Error::BadStream { entry: 0, stream: 4 }));
+        assert_eq!(validate_stream_output(VS_5_0, &[entry(0, 4, 0, 4)], &[16], None), Err(Error::BadSlot { entry: 0, slot: 4 }));
+        assert_eq!(validate_stream_output(VS_5_0, &[entry(0, 0, 0, 1); MAX_ENTRIES + 1], &[16], None), Err(Error::TooManyEntries(MAX_ENTRIES + 1)));
+        assert_eq!(validate_stream_output(VS_5_0, &position_color, &[32; 5], None), Err(Error::TooManyStrides(5)));
+        assert_eq!(validate_stream_output(VS_5_0, &position_color, &[32], Some(4)), Err(Error::BadRasterizedStream(4)));
+    }
+
+    #[test] fn components () {
+        assert_eq!(validate_stream_output(VS_5_0, &[entry(0, 0, 3, 1)], &[4], None), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &[entry(0, 0, 3, 2)], &[8], None), Err(Error::BadComponents { entry: 0, start: 3, count: 2 }));
+        assert_eq!(validate_stream_output(VS_5_0, &[entry(0, 0, 0, 0)], &[4], None), Err(Error::BadComponents { entry: 0, start: 0, count: 0 }));
+
+        let gap = |component_count| StreamOutputEntry { gap: true, ..entry(0, 0, 0, component_count) };
+        assert_eq!(validate_stream_output(VS_5_0, &[gap(8), entry(0, 0, 0, 4)], &[48], None), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &[gap(0)], &[4], None), Err(Error::BadComponents { entry: 0, start: 0, count: 0 }));
+    }
+
+    #[test] fn streams_require_gs_5_0 () {
+        let two_streams = [entry(0, 0, 0, 4), entry(1, 1, 0, 4)];
+        assert_eq!(validate_stream_output(GS_5_0, &two_streams, &[16, 16], Some(1)), Ok(()));
+        assert_eq!(validate_stream_output(GS_4_1, &two_streams, &[16, 16], None), Err(Error::StreamRequiresGs5(1)));
+        assert_eq!(validate_stream_output(VS_5_0, &two_streams, &[16, 16], None), Err(Error::StreamRequiresGs5(1)));
+        assert_eq!(validate_stream_output(GS_4_1, &two_streams[..1], &[16], Some(1)), Err(Error::StreamRequiresGs5(1)));
+        assert_eq!(validate_stream_output(GS_4_1, &two_streams[..1], &[16], Some(0)), Ok(()));
+    }
+
+    #[test] fn slots_shared_by_streams () {
+        assert_eq!(validate_stream_output(GS_5_0, &[entry(0, 0, 0, 4), entry(1, 0, 0, 4)], &[32], None), Err(Error::SlotSharedByStreams { slot: 0 }));
+        assert_eq!(validate_stream_output(GS_5_0, &[entry(2, 1, 0, 4), entry(2, 1, 0, 4)], &[0, 32], None), Ok(()));
+    }
+
+    #[test] fn strides () {
+        let slot_1 = [entry(0, 1, 0, 3)];
+        assert_eq!(validate_stream_output(VS_5_0, &slot_1, &[0, 12], None), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &slot_1, &[0, 64], None), Ok(()));
+        assert_eq!(validate_stream_output(VS_5_0, &slot_1, &[12], None), Err(Error::MissingStride { slot: 1 }));
+        assert_eq!(validate_stream_output(VS_5_0, &slot_1, &[0, 8], None), Err(Error::BadStride { slot: 1, needed: 12, stride: 8 }));
+        assert_eq!(validate_stream_output(VS_5_0, &slot_1, &[0, 14], None), Err(Error::BadStride { slot: 1, needed: 12, stride: 14 }));
+        assert_eq!(validate_stream_output(VS_5_0, &slot_1, &[0, MAX_BUFFER_STRIDE + 4], None), Err(Error::BadStride { slot: 1, needed: 12, stride: MAX_BUFFER_STRIDE + 4 }));
+    }
+}
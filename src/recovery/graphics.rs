@@ -0,0 +1,262 @@
+use super::*;
+use crate::com::DeviceAndContext;
+use crate::com::d3d::{Bytecode, PixelBytecode, VertexBytecode};
+use crate::com::d3d11::*;
+use crate::com::dxgi::*;
+use crate::depth::DepthPolicy;
+use crate::fullscreen::{FullscreenState, WindowMode};
+use crate::win32::*;
+use crate::window_registry::*;
+use winit::dpi::LogicalSize;
+use winit::window::{Window, WindowId};
+
+/// Everything needed to recreate a resource from scratch on a new device.
+pub enum ResourceDesc {
+    /// `initial_data` is required for `D3D11_USAGE_IMMUTABLE` buffers, and must be at least `desc.ByteWidth` bytes.
+    Buffer { desc: D3D11_BUFFER_DESC, initial_data: Option<Vec<u8>> },
+    DepthStencilState { desc: D3D11_DEPTH_STENCIL_DESC },
+    VertexShader { bytecode: VertexBytecode },
+    PixelShader { bytecode: PixelBytecode },
+    InputLayout { elements: &'static [InputElementDesc<'static>], bytecode: VertexBytecode },
+}
+
+impl Recreate for ResourceDesc {
+    type Device     = Device;
+    type Resource   = Resource;
+    type Error      = HRESULT;
+
+    fn stage (&self) -> Stage {
+        match self {
+            ResourceDesc::Buffer { .. }                     => Stage::Resources,
+            ResourceDesc::DepthStencilState { .. }          => Stage::States,
+            ResourceDesc::VertexShader { .. }               => Stage::Shaders,
+            ResourceDesc::PixelShader { .. }                => Stage::Shaders,
+            ResourceDesc::InputLayout { .. }                => Stage::InputLayouts,
+        }
+    }
+
+    fn create (&self, device: &Device) -> Result<Resource, HRESULT> {
+        Ok(match self {
+            ResourceDesc::Buffer { desc, initial_data } => {
+                if let Some(initial_data) = initial_data.as_ref() {
+                    expect!(initial_data.len() >= desc.ByteWidth as usize);
+                }
+                let initial_data = initial_data.as_ref().map(|data| D3D11_SUBRESOURCE_DATA {
+                    pSysMem:            data.as_ptr() as *const _,
+                    SysMemPitch:        0,
+                    SysMemSlicePitch:   0,
+                });
+                Resource::Buffer(unsafe { device.create_buffer(desc, initial_data.as_ref()) }?)
+            },
+            ResourceDesc::DepthStencilState { desc } => Resource::DepthStencilState(device.create_depth_stencil_state(desc)?),
+            ResourceDesc::VertexShader { bytecode } => Resource::VertexShader(device.create_vertex_shader(bytecode, None)?),
+            ResourceDesc::PixelShader  { bytecode } => Resource::PixelShader (device.create_pixel_shader (bytecode, None)?),
+            ResourceDesc::InputLayout  { elements, bytecode } => Resource::InputLayout(device.create_input_layout(*elements, bytecode.as_bytes())?),
+        })
+    }
+}
+
+pub enum Resource {
+    Buffer(Buffer),
+    DepthStencilState(DepthStencilState),
+    VertexShader(VertexShader),
+    PixelShader(PixelShader),
+    InputLayout(InputLayout),
+}
+
+macro_rules! registered {
+    ($($variant:ident),+ $(,)?) => {$(
+        impl Registered<Resource> for $variant {
+            fn from_resource (resource: &Resource) -> Option<&Self> {
+                match resource {
+                    Resource::$variant(r) => Some(r),
+                    _ => None,
+                }
+            }
+        }
+    )+};
+}
+
+registered!(Buffer, DepthStencilState, VertexShader, PixelShader, InputLayout);
+
+/// A device, its immediate context, the windows it presents to, and the resources created from them - recreated as a
+/// unit whenever the device is lost.
+pub struct Graphics {
+    pub feature_level:  FeatureLevel,
+    pub device:         Device,
+    pub device_context: DeviceContext,
+    pub factory:        Factory,
+    pub resources:      ResourceRegistry<ResourceDesc>,
+    pub windows:        WindowRegistry<WindowId, Surface>,
+    /// Depth buffer formats for windows added from now on.  `None` to add windows without depth buffers.
+    pub depth_policy:   Option<DepthPolicy>,
+
+    driver_type:        DriverType,
+    flags:              UINT,
+    feature_levels:     Option<Vec<FeatureLevel>>,
+    /// Why the device was lost, until it's successfully recreated.
+    lost:               Option<HRESULT>,
+}
+
+/// A device and the objects created alongside it, before `Graphics` switches over to them.
+pub struct NewDevice {
+    feature_level:  FeatureLevel,
+    device:         Device,
+    device_context: DeviceContext,
+    factory:        Factory,
+}
+
+impl Graphics {
+    pub fn create (
+        driver_type:        DriverType,
+        flags:              UINT, // D3D11_CREATE_DEVICE_FLAG
+        feature_levels:     Option<&[FeatureLevel]>,
+    ) -> Result<Self, HRESULT> {
+        let feature_levels = feature_levels.map(|fl| fl.to_vec());
+        let NewDevice { feature_level, device, device_context, factory } = create_device(driver_type, flags, feature_levels.as_ref())?;
+        Ok(Self {
+            feature_level,
+            device,
+            device_context,
+            factory,
+            resources:          ResourceRegistry::new(),
+            windows:            WindowRegistry::new(),
+            depth_policy:       Some(DepthPolicy::default()),
+            driver_type,
+            flags,
+            feature_levels,
+            lost:               None,
+        })
+    }
+
+    pub fn register<T: Registered<Resource>> (&mut self, desc: ResourceDesc) -> Result<ResourceId<T>, HRESULT> {
+        self.resources.register(&self.device, desc)
+    }
+
+    /// Create a swap chain (and depth buffer, per `depth_policy`) for `window` and start tracking it.
+    pub fn add_window (&mut self, window: Window) -> Result<WindowId, HRESULT> {
+        let surface = Surface::new(window, &self.device, self.feature_level, &self.factory, self.depth_policy)?;
+        let id = surface.id();
+        self.windows.insert(id, surface);
+        Ok(id)
+    }
+
+    /// Stop tracking `id`, destroying its swap chain and window.
+    pub fn remove_window (&mut self, id: WindowId) {
+        self.device_context.clear_state(); // Unbind its back buffer
+        self.windows.remove(id);
+    }
+
+    /// Resize the back buffers of `id` to match a new client area.
+    pub fn resize_window (&mut self, id: WindowId, size: LogicalSize) -> Result<(), HRESULT> {
+        match self.windows.get_mut(id) {
+            Some(surface)   => surface.resize(&self.device, &self.device_context, size),
+            None            => Ok(()),
+        }
+    }
+
+    /// Why the device was lost, if it has been and couldn't be recreated yet.
+    pub fn lost (&self) -> Option<HRESULT> { self.lost }
+
+    /// `SwapChain::present` for window `id`, recovering from device removal instead of returning it as an error.  While
+    /// the device is lost, presents nothing and retries recovery instead.
+    pub fn present (&mut self, id: WindowId, sync_interval: UINT, flags: UINT) -> Result<Presented, HRESULT> {
+        if let Some(reason) = self.lost { return Ok(self.recover(reason)); }
+
+        let status = match self.windows.get(id) {
+            Some(surface)   => surface.swap_chain().present(sync_interval, flags),
+            None            => return Ok(Presented::Occluded),
+        };
+        match status {
+            Ok(PresentStatus::Presented) => Ok(Presented::Visible),
+            Ok(PresentStatus::Occluded)  => Ok(Presented::Occluded),
+            Err(hresult) if is_device_lost(hresult) => {
+                let reason = self.device.get_device_removed_reason();
+                crate::debug::output(&format!("Device lost (HRESULT={:X}, reason={:X}), recreating device\n", hresult, reason));
+                Ok(self.recover(reason))
+            },
+            Err(hresult) => Err(hresult),
+        }
+    }
+
+    /// Recreate the device, every window's swap chain, and every registered resource - or, failing that, stay lost
+    /// with everything as it was.
+    fn recover (&mut self, reason: HRESULT) -> Presented {
+        let presented = try_recover(self, reason);
+        self.lost = match presented { Presented::Lost { reason } => Some(reason), _ => None };
+        presented
+    }
+}
+
+fn create_device (driver_type: DriverType, flags: UINT, feature_levels: Option<&Vec<FeatureLevel>>) -> Result<NewDevice, HRESULT> {
+    let DeviceAndContext { feature_level, device, device_context } = unsafe { DeviceAndContext::create(
+        None, // adapter
+        driver_type,
+        None, // software
+        flags,
+        feature_levels.map(|fl| &fl[..])
+    )}?;
+    let factory = Factory::from_device(&device)?;
+    Ok(NewDevice { feature_level, device, device_context, factory })
+}
+
+impl SurfaceHost for Graphics {
+    type Id     = WindowId;
+    type Error  = HRESULT;
+
+    fn remove_window (&mut self, id: WindowId) { Graphics::remove_window(self, id) }
+
+    fn window_count (&self) -> usize { self.windows.len() }
+
+    fn resize_window (&mut self, id: WindowId, width: f64, height: f64) -> Result<(), HRESULT> {
+        Graphics::resize_window(self, id, LogicalSize::new(width, height))
+    }
+
+    fn fullscreen (&mut self, id: WindowId) -> Option<&mut FullscreenState> {
+        self.windows.get_mut(id).map(|surface| &mut surface.fullscreen)
+    }
+
+    fn set_window_mode (&mut self, id: WindowId, to: WindowMode) -> Result<(), HRESULT> {
+        match self.windows.get_mut(id) {
+            Some(surface)   => surface.set_window_mode(to),
+            None            => Ok(()),
+        }
+    }
+
+    fn is_exclusive (&self, id: WindowId) -> bool {
+        self.windows.get(id).is_some_and(|surface| surface.swap_chain().get_fullscreen_state().unwrap_or(false))
+    }
+}
+
+impl Recover for Graphics {
+    type Device     = NewDevice;
+    type Surfaces   = Vec<SurfaceBuffers>;
+    type Desc       = ResourceDesc;
+    type Error      = HRESULT;
+
+    fn create_device (&self) -> Result<NewDevice, HRESULT> {
+        create_device(self.driver_type, self.flags, self.feature_levels.as_ref())
+    }
+
+    fn resource_device (device: &NewDevice) -> &Device { &device.device }
+
+    fn create_surfaces (&self, device: &NewDevice) -> Result<Vec<SurfaceBuffers>, HRESULT> {
+        self.windows.iter().map(|surface| surface.rebuild(&device.device, device.feature_level, &device.factory)).collect()
+    }
+
+    fn resources (&self) -> &ResourceRegistry<ResourceDesc> { &self.resources }
+
+    fn replace (&mut self, device: NewDevice, surfaces: Vec<SurfaceBuffers>, resources: Vec<Resource>) {
+        self.device_context.clear_state();
+        self.device_context.flush();
+
+        self.resources.replace(resources);
+        for (surface, buffers) in self.windows.iter_mut().zip(surfaces) { surface.replace(buffers); }
+
+        let NewDevice { feature_level, device, device_context, factory } = device;
+        self.feature_level  = feature_level;
+        self.device         = device;
+        self.device_context = device_context;
+        self.factory        = factory;
+    }
+}
@@ -0,0 +1,389 @@
+//! Device-removed detection and recovery.
+//!
+//! Driver updates, TDRs, and GPU hot-unplugs all invalidate the `ID3D11Device` and every object created from it.
+//! `SwapChain::present` reports this with `DXGI_ERROR_DEVICE_REMOVED` / `DXGI_ERROR_DEVICE_RESET`, at which point the
+//! only way forward is to tear everything down and recreate it.  To make that possible, resources are created through
+//! a `ResourceRegistry`, which remembers enough about each one to create it again on a brand new device.  Swap chains
+//! and their views belong to `Surface`s, which know how to recreate themselves.
+//!
+//! Recovery is all or nothing:  `recover` builds a complete replacement - device, surfaces, resources - before
+//! touching the old one.  If any step fails (the GPU may still be resetting), the old objects stay in place, lost but
+//! never missing, and recovery is retried later.  The ordering lives here, free of D3D types, so it can be tested
+//! against a recording backend.  `Graphics` (Windows only) is the D3D11 implementation.
+
+use crate::com::abi::HRESULT;
+
+#[cfg(windows)] mod graphics;
+#[cfg(windows)] pub use graphics::*;
+
+// Spelled out here rather than taken from winapi, so the recovery logic builds everywhere.
+pub const DXGI_ERROR_DEVICE_REMOVED : HRESULT = 0x887A_0005_u32 as HRESULT;
+pub const DXGI_ERROR_DEVICE_RESET   : HRESULT = 0x887A_0007_u32 as HRESULT;
+
+/// Returns `true` if `hresult` means the device (and everything created from it) must be recreated.
+pub fn is_device_lost (hresult: HRESULT) -> bool {
+    hresult == DXGI_ERROR_DEVICE_REMOVED || hresult == DXGI_ERROR_DEVICE_RESET
+}
+
+/// Resources are recreated in ascending `Stage` order, and released in descending `Stage` order.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Stage {
+    Resources,
+    States,
+    Shaders,
+    InputLayouts,
+}
+
+/// Returns the indices of `stages` in the order they should be recreated:  sorted by `Stage`, with ties broken by
+/// registration order (so a resource is never recreated before anything registered ahead of it in the same stage.)
+pub fn recreation_order<I: IntoIterator<Item = Stage>> (stages: I) -> Vec<usize> {
+    let mut order : Vec<(Stage, usize)> = stages.into_iter().enumerate().map(|(index, stage)| (stage, index)).collect();
+    order.sort();
+    order.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Everything needed to recreate a resource from scratch on a new device, e.g. `ResourceDesc`.
+pub trait Recreate {
+    type Device;
+    type Resource;
+    type Error;
+
+    fn stage (&self) -> Stage;
+    fn create (&self, device: &Self::Device) -> Result<Self::Resource, Self::Error>;
+}
+
+/// Implemented by every type a `ResourceRegistry` of `R`s can hand out.
+pub trait Registered<R> : Sized {
+    fn from_resource (resource: &R) -> Option<&Self>;
+}
+
+/// A typed handle to a resource owned by a `ResourceRegistry`.  Stays valid across device recovery.
+pub struct ResourceId<T> {
+    index:      usize,
+    phantom:    std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ResourceId<T> { fn clone (&self) -> Self { *self } }
+impl<T> Copy  for ResourceId<T> {}
+
+/// Resources, and the descriptions to recreate them from.  Always holds a complete set created on the same device.
+pub struct ResourceRegistry<D: Recreate> {
+    descs:      Vec<D>,
+    resources:  Vec<D::Resource>,
+}
+
+impl<D: Recreate> Default for ResourceRegistry<D> {
+    fn default () -> Self { Self { descs: Vec::new(), resources: Vec::new() } }
+}
+
+impl<D: Recreate> ResourceRegistry<D> {
+    pub fn new () -> Self { Self::default() }
+
+    pub fn len (&self) -> usize { self.descs.len() }
+    pub fn is_empty (&self) -> bool { self.descs.is_empty() }
+
+    /// Create a resource from `desc` and remember `desc` so it can be recreated later.
+    pub fn register<T: Registered<D::Resource>> (&mut self, device: &D::Device, desc: D) -> Result<ResourceId<T>, D::Error> {
+        let resource = desc.create(device)?;
+        expect!(T::from_resource(&resource).is_some());
+        let index = self.descs.len();
+        self.descs.push(desc);
+        self.resources.push(resource);
+        Ok(ResourceId { index, phantom: std::marker::PhantomData })
+    }
+
+    /// Get the current incarnation of a registered resource.  Don't hold onto it across `Graphics::present`, which
+    /// may replace it.
+    pub fn get<T: Registered<D::Resource>> (&self, id: ResourceId<T>) -> &T {
+        T::from_resource(&self.resources[id.index]).unwrap()
+    }
+
+    pub fn recreation_order (&self) -> Vec<usize> {
+        recreation_order(self.descs.iter().map(|d| d.stage()))
+    }
+
+    /// Create every resource again on `device`, in recreation order, without touching the current ones.  Stops at the
+    /// first failure, releasing whatever was already created.  See `replace` to swap the results in.
+    pub fn rebuild (&self, device: &D::Device) -> Result<Vec<D::Resource>, D::Error> {
+        let mut rebuilt : Vec<Option<D::Resource>> = self.descs.iter().map(|_| None).collect();
+        for index in self.recreation_order() {
+            rebuilt[index] = Some(self.descs[index].create(device)?);
+        }
+        Ok(rebuilt.into_iter().map(|r| r.unwrap()).collect())
+    }
+
+    /// Swap in resources from `rebuild`, releasing the old ones in reverse recreation order.
+    pub fn replace (&mut self, resources: Vec<D::Resource>) {
+        expect_eq!(resources.len(), self.descs.len());
+        let mut old : Vec<Option<D::Resource>> = std::mem::replace(&mut self.resources, resources).into_iter().map(Some).collect();
+        for index in self.recreation_order().into_iter().rev() {
+            old[index] = None;
+        }
+    }
+
+    /// `rebuild` + `replace`:  recreate every resource on `device`, or change nothing.
+    pub fn recreate_all (&mut self, device: &D::Device) -> Result<(), D::Error> {
+        let resources = self.rebuild(device)?;
+        self.replace(resources);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Presented {
+    Visible,
+    /// The window is occluded (`DXGI_STATUS_OCCLUDED`), nothing was presented.
+    Occluded,
+    /// The device was lost for `reason` (per `GetDeviceRemovedReason`) and has been successfully recreated.
+    Recovered { reason: HRESULT },
+    /// The device was lost for `reason` and couldn't be recreated yet.  Nothing was presented, the next present will
+    /// try again.  Until then the old objects are still there to render with, to no effect.
+    Lost { reason: HRESULT },
+}
+
+/// The steps of recovering from a lost device.  `Graphics` implements these with D3D11, the tests with a recording
+/// backend.
+pub trait Recover {
+    /// Whatever device creation produces, e.g. a device, its immediate context, and a factory.
+    type Device;
+    /// Everything tied to the device that isn't in `resources`, e.g. every window's swap chain and views.
+    type Surfaces;
+    type Desc : Recreate<Error = Self::Error>;
+    type Error : std::fmt::Debug;
+
+    fn create_device (&self) -> Result<Self::Device, Self::Error>;
+    /// The part of `device` that resources are created on.
+    fn resource_device (device: &Self::Device) -> &<Self::Desc as Recreate>::Device;
+    fn create_surfaces (&self, device: &Self::Device) -> Result<Self::Surfaces, Self::Error>;
+    fn resources (&self) -> &ResourceRegistry<Self::Desc>;
+
+    /// Swap in everything `recover` built, releasing the old objects.  Only called once every step has succeeded.
+    fn replace (&mut self, device: Self::Device, surfaces: Self::Surfaces, resources: Vec<<Self::Desc as Recreate>::Resource>);
+}
+
+/// Build a new device, then new surfaces, then every registered resource in `recreation_order` - and only then swap
+/// them all in.  If any step fails nothing is replaced, and `recover` can simply be tried again later.
+pub fn recover<R: Recover> (target: &mut R) -> Result<(), R::Error> {
+    let device      = target.create_device()?;
+    let surfaces    = target.create_surfaces(&device)?;
+    let resources   = target.resources().rebuild(R::resource_device(&device))?;
+    target.replace(device, surfaces, resources);
+    Ok(())
+}
+
+/// `recover` from losing the device for `reason`, reporting failure as `Presented::Lost` to be retried later rather
+/// than as an error.
+pub fn try_recover<R: Recover> (target: &mut R, reason: HRESULT) -> Presented {
+    match recover(target) {
+        Ok(()) => Presented::Recovered { reason },
+        Err(error) => {
+            crate::debug::output(&format!("Recreating the device (lost for reason={:X}) failed: {:?}, will retry\n", reason, error));
+            Presented::Lost { reason }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// A "resource" that logs its creation and release.
+    struct Recorded {
+        name:   String,
+        device: u32,
+        log:    Log,
+    }
+
+    impl Drop for Recorded {
+        fn drop (&mut self) { self.log.borrow_mut().push(format!("release {}@{}", self.name, self.device)); }
+    }
+
+    impl Registered<Recorded> for Recorded {
+        fn from_resource (resource: &Recorded) -> Option<&Self> { Some(resource) }
+    }
+
+    struct RecordedDevice {
+        generation: u32,
+        /// Resource creation on this device fails from the `fail_at`th resource on.
+        fail_at:    Option<usize>,
+        created:    Cell<usize>,
+        log:        Log,
+    }
+
+    struct Desc {
+        name:   &'static str,
+        stage:  Stage,
+    }
+
+    impl Recreate for Desc {
+        type Device     = RecordedDevice;
+        type Resource   = Recorded;
+        type Error      = String;
+
+        fn stage (&self) -> Stage { self.stage }
+
+        fn create (&self, device: &RecordedDevice) -> Result<Recorded, String> {
+            let n = device.created.get();
+            device.created.set(n + 1);
+            if device.fail_at.is_some_and(|f| n >= f) { return Err(format!("create {}@{}", self.name, device.generation)); }
+            device.log.borrow_mut().push(format!("create {}@{}", self.name, device.generation));
+            Ok(Recorded { name: self.name.to_string(), device: device.generation, log: device.log.clone() })
+        }
+    }
+
+    /// Logs every step.  Device creation fails while `device_failures` > 0, the resources of the next
+    /// `resource_failures` devices fail partway through.
+    struct Backend {
+        log:                Log,
+        device:             RecordedDevice,
+        surfaces:           Vec<Recorded>,
+        resources:          ResourceRegistry<Desc>,
+        generations:        Cell<u32>,
+        device_failures:    Cell<u32>,
+        resource_failures:  Cell<u32>,
+    }
+
+    impl Backend {
+        fn new () -> Self {
+            let log = Log::default();
+            let device = RecordedDevice { generation: 0, fail_at: None, created: Cell::new(0), log: log.clone() };
+            let surfaces = vec![Recorded { name: "surface".to_string(), device: 0, log: log.clone() }];
+            let mut resources = ResourceRegistry::new();
+            for (name, stage) in [("layout", Stage::InputLayouts), ("vs", Stage::Shaders), ("vb", Stage::Resources), ("depth", Stage::States), ("ib", Stage::Resources)].iter() {
+                let _ : ResourceId<Recorded> = resources.register(&device, Desc { name, stage: *stage }).unwrap();
+            }
+            log.borrow_mut().clear();
+            Self { log, device, surfaces, resources, generations: Cell::new(0), device_failures: Cell::new(0), resource_failures: Cell::new(0) }
+        }
+
+        fn take_log (&self) -> Vec<String> { std::mem::take(&mut *self.log.borrow_mut()) }
+    }
+
+    impl Recover for Backend {
+        type Device     = RecordedDevice;
+        type Surfaces   = Vec<Recorded>;
+        type Desc       = Desc;
+        type Error      = String;
+
+        fn resource_device (device: &RecordedDevice) -> &RecordedDevice { device }
+
+        fn create_device (&self) -> Result<RecordedDevice, String> {
+            if self.device_failures.get() > 0 {
+                self.device_failures.set(self.device_failures.get() - 1);
+                return Err("create device".to_string());
+            }
+            let generation = self.generations.get() + 1;
+            self.generations.set(generation);
+            let fail_at = if self.resource_failures.get() > 0 { self.resource_failures.set(self.resource_failures.get() - 1); Some(2) } else { None };
+            self.log.borrow_mut().push(format!("create device@{}", generation));
+            Ok(RecordedDevice { generation, fail_at, created: Cell::new(0), log: self.log.clone() })
+        }
+
+        fn create_surfaces (&self, device: &RecordedDevice) -> Result<Vec<Recorded>, String> {
+            self.log.borrow_mut().push(format!("create surface@{}", device.generation));
+            Ok(vec![Recorded { name: "surface".to_string(), device: device.generation, log: self.log.clone() }])
+        }
+
+        fn resources (&self) -> &ResourceRegistry<Desc> { &self.resources }
+
+        fn replace (&mut self, device: RecordedDevice, surfaces: Vec<Recorded>, resources: Vec<Recorded>) {
+            self.resources.replace(resources);
+            self.surfaces = surfaces;
+            self.device = device;
+        }
+    }
+
+    fn current (backend: &Backend) -> Vec<String> {
+        (0..backend.resources.len()).map(|index| {
+            let r = backend.resources.get::<Recorded>(ResourceId { index, phantom: std::marker::PhantomData });
+            format!("{}@{}", r.name, r.device)
+        }).collect()
+    }
+
+    #[test]
+    fn device_lost () {
+        assert!(is_device_lost(DXGI_ERROR_DEVICE_REMOVED));
+        assert!(is_device_lost(DXGI_ERROR_DEVICE_RESET));
+        assert!(!is_device_lost(0));
+        assert!(!is_device_lost(0x887A_0001_u32 as HRESULT)); // DXGI_ERROR_INVALID_CALL
+    }
+
+    #[test]
+    fn order () {
+        assert_eq!(recreation_order(vec![Stage::InputLayouts, Stage::Shaders, Stage::Resources, Stage::States, Stage::Resources]), vec![2, 4, 3, 1, 0]);
+        assert_eq!(recreation_order(vec![]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn recover_builds_everything_before_releasing_anything () {
+        let mut backend = Backend::new();
+        assert_eq!(try_recover(&mut backend, DXGI_ERROR_DEVICE_REMOVED), Presented::Recovered { reason: DXGI_ERROR_DEVICE_REMOVED });
+        assert_eq!(backend.take_log(), vec![
+            "create device@1", "create surface@1",
+            "create vb@1", "create ib@1", "create depth@1", "create vs@1", "create layout@1",
+            "release layout@0", "release vs@0", "release depth@0", "release ib@0", "release vb@0",
+            "release surface@0",
+        ]);
+        assert_eq!(current(&backend), vec!["layout@1", "vs@1", "vb@1", "depth@1", "ib@1"]);
+        assert_eq!(backend.surfaces[0].device, 1);
+        assert_eq!(backend.device.generation, 1);
+    }
+
+    #[test]
+    fn failed_device_creation_changes_nothing () {
+        let mut backend = Backend::new();
+        backend.device_failures.set(2);
+        assert_eq!(try_recover(&mut backend, DXGI_ERROR_DEVICE_RESET), Presented::Lost { reason: DXGI_ERROR_DEVICE_RESET });
+        assert_eq!(try_recover(&mut backend, DXGI_ERROR_DEVICE_RESET), Presented::Lost { reason: DXGI_ERROR_DEVICE_RESET });
+        assert!(backend.take_log().is_empty());
+        assert_eq!(current(&backend), vec!["layout@0", "vs@0", "vb@0", "depth@0", "ib@0"]);
+
+        assert_eq!(try_recover(&mut backend, DXGI_ERROR_DEVICE_RESET), Presented::Recovered { reason: DXGI_ERROR_DEVICE_RESET });
+        assert_eq!(current(&backend), vec!["layout@1", "vs@1", "vb@1", "depth@1", "ib@1"]);
+    }
+
+    #[test]
+    fn failed_resource_creation_keeps_the_old_resources () {
+        let mut backend = Backend::new();
+        backend.resource_failures.set(1);
+        assert_eq!(try_recover(&mut backend, DXGI_ERROR_DEVICE_REMOVED), Presented::Lost { reason: DXGI_ERROR_DEVICE_REMOVED });
+        assert_eq!(backend.take_log(), vec![
+            "create device@1", "create surface@1",
+            "create vb@1", "create ib@1",
+            // The partial rebuild is thrown away, the surface with the device it was built on.
+            "release vb@1", "release ib@1", "release surface@1",
+        ]);
+        assert_eq!(current(&backend), vec!["layout@0", "vs@0", "vb@0", "depth@0", "ib@0"]);
+        assert_eq!(backend.surfaces[0].device, 0);
+        assert_eq!(backend.device.generation, 0);
+
+        // Retried later, on the next device.
+        assert_eq!(try_recover(&mut backend, DXGI_ERROR_DEVICE_REMOVED), Presented::Recovered { reason: DXGI_ERROR_DEVICE_REMOVED });
+        assert_eq!(current(&backend), vec!["layout@2", "vs@2", "vb@2", "depth@2", "ib@2"]);
+        assert_eq!(backend.device.generation, 2);
+    }
+
+    #[test]
+    fn registry_recreate_all () {
+        let log = Log::default();
+        let device = |generation| RecordedDevice { generation, fail_at: None, created: Cell::new(0), log: log.clone() };
+        let mut registry = ResourceRegistry::new();
+        let vs : ResourceId<Recorded> = registry.register(&device(0), Desc { name: "vs", stage: Stage::Shaders }).unwrap();
+        let vb : ResourceId<Recorded> = registry.register(&device(0), Desc { name: "vb", stage: Stage::Resources }).unwrap();
+        log.borrow_mut().clear();
+
+        let failing = RecordedDevice { fail_at: Some(1), ..device(1) };
+        assert_eq!(registry.recreate_all(&failing).err(), Some("create vs@1".to_string()));
+        assert_eq!(registry.get(vs).device, 0);
+        assert_eq!(registry.get(vb).device, 0);
+
+        registry.recreate_all(&device(2)).unwrap();
+        assert_eq!(registry.get(vs).device, 2);
+        assert_eq!(registry.get(vb).device, 2);
+        assert_eq!(*log.borrow(), vec!["create vb@1", "release vb@1", "create vb@2", "create vs@2", "release vs@0", "release vb@0"]);
+    }
+}
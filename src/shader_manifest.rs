@@ -0,0 +1,233 @@
+//! `res/shaders.manifest`:  which shaders `build.rs` compiles and embeds, and how.
+//!
+//! Shared by `build.rs` (through `#[path]`) and the runtime loader in `shaders.rs`, so it only uses `std`.  One shader
+//! per line, a name followed by `key=value` fields, `#` starting a comment:
+//!
+//! ```text
+//! # name    fields...
+//! vs        source=vs.hlsl  entry=main  target=vs_5_0
+//! ps_fog    source=ps.hlsl  entry=main  target=ps_5_0  define=FOG  define=FOG_DENSITY=0.5  options=/Zpr,/WX
+//! ```
+//!
+//! * `source` - Path relative to `res/`.  Required.
+//! * `entry` - Entrypoint function name.  Defaults to `main`.
+//! * `target` - Compiler target, e.g. `ps_5_0`.  Required.
+//! * `define` - A preprocessor define, `NAME` or `NAME=VALUE`.  May be repeated.
+//! * `options` - Comma separated `fxc` style switches (see `d3d::CompileOptions`), applied on top of the build
+//!   profile's preset (`debug` or `release`.)
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    pub name:       String,
+    pub source:     String,
+    pub entrypoint: String,
+    pub target:     String,
+    pub defines:    Vec<(String, String)>,
+    pub options:    Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Error {
+    /// 1-based.
+    pub line:       usize,
+    pub message:    String,
+}
+
+impl fmt::Display for Error {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "shaders.manifest({}): {}", self.line, self.message)
+    }
+}
+
+pub fn parse (text: &str) -> Result<Vec<Entry>, Error> {
+    let mut entries : Vec<Entry> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let err = |message: String| Error { line: line_no, message };
+        let line = line.split('#').next().unwrap().trim();
+        let mut fields = line.split_whitespace();
+        let name = match fields.next() { Some(name) => name, None => continue };
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') { return Err(err(format!("invalid name {:?}", name))); }
+        if entries.iter().any(|e| e.name == name) { return Err(err(format!("duplicate name {:?}", name))); }
+
+        let mut entry = Entry { name: name.to_string(), source: String::new(), entrypoint: String::from("main"), target: String::new(), defines: Vec::new(), options: Vec::new() };
+        for field in fields {
+            let eq = field.find('=').ok_or_else(|| err(format!("expected key=value, found {:?}", field)))?;
+            let (key, value) = (&field[..eq], &field[eq+1..]);
+            match key {
+                "source"    => entry.source = value.to_string(),
+                "entry"     => entry.entrypoint = value.to_string(),
+                "target"    => entry.target = value.to_string(),
+                "define"    => {
+                    let mut define = value.splitn(2, '=');
+                    let name = define.next().unwrap();
+                    if name.is_empty() { return Err(err(String::from("empty define"))); }
+                    entry.defines.push((name.to_string(), define.next().unwrap_or("1").to_string()));
+                },
+                "options"   => entry.options.extend(value.split(',').filter(|o| !o.is_empty()).map(String::from)),
+                other       => return Err(err(format!("unknown key {:?}", other))),
+            }
+        }
+        if entry.source.is_empty() { return Err(err(format!("{} has no source=", name))); }
+        if entry.target.is_empty() { return Err(err(format!("{} has no target=", name))); }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Identifies what `entry` was compiled from:  FNV-1a 64 (as `archive::hash`) of its fields and `source`.  Carriage
+/// returns are skipped, so a CRLF checkout hashes like an LF one.  `#include`d files aren't covered.
+pub fn source_hash (entry: &Entry, source: &[u8]) -> u64 {
+    let fields = format!("{} {} {} {:?} {:?}\n", entry.source, entry.entrypoint, entry.target, entry.defines, entry.options);
+    let mut hash = 0xcbf29ce484222325u64;
+    for &byte in fields.as_bytes().iter().chain(source.iter()).filter(|&&b| b != b'\r') {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// `sources.txt`:  a `{name} {source_hash:016x}` line per entry, written next to compiled bytecode by `build.rs`, and
+/// checked in alongside `res/precompiled`.
+pub fn sources_txt (hashes: &[(&str, u64)]) -> String {
+    hashes.iter().map(|(name, hash)| format!("{} {:016x}\n", name, hash)).collect()
+}
+
+/// Turns a manifest entry into bytecode at build time.
+pub trait Compiler {
+    /// Compile `entry`, whose source was read from `source_path`.
+    fn compile (&self, entry: &Entry, source_path: &Path, source: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// Reads `{dir}/{name}.dxbc` instead of compiling, for building where `D3DCompiler` isn't available.  Fails if
+/// `{dir}/sources.txt` says it was compiled from anything but the current source and manifest entry.
+pub struct Precompiled {
+    pub dir: PathBuf,
+}
+
+impl Compiler for Precompiled {
+    fn compile (&self, entry: &Entry, source_path: &Path, source: &[u8]) -> Result<Vec<u8>, String> {
+        let sources_path = self.dir.join("sources.txt");
+        let sources = fs::read_to_string(&sources_path).map_err(|e| format!("{}: {}", sources_path.display(), e))?;
+        let recorded = sources.lines().filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(name), Some(hash)) if name == entry.name => Some(hash),
+                _ => None,
+            }
+        }).next();
+        let path = self.dir.join(&entry.name).with_extension("dxbc");
+        if recorded != Some(&format!("{:016x}", source_hash(entry, source))[..]) {
+            return Err(format!("{} wasn't compiled from the current {} and manifest entry:  regenerate it with D3DCompile (see README.md next to it)", path.display(), source_path.display()));
+        }
+        fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Embeds nothing, leaving every shader to the runtime fallback.  Lets the rest of the build proceed anywhere.
+pub struct Fake;
+
+impl Compiler for Fake {
+    fn compile (&self, _entry: &Entry, _source_path: &Path, _source: &[u8]) -> Result<Vec<u8>, String> {
+        Ok(Vec::new())
+    }
+}
+
+/// Rust source for the `EMBEDDED` table `shaders.rs` `include!`s, referencing each entry's bytecode at
+/// `bytecode_dir/{name}.dxbc` with `include_bytes!`.
+pub fn embed (entries: &[Entry], bytecode_dir: &Path) -> String {
+    let mut rust = String::from("pub static EMBEDDED : &[EmbeddedShader] = &[\n");
+    for entry in entries {
+        let path = bytecode_dir.join(&entry.name).with_extension("dxbc");
+        rust.push_str(&format!(
+            "    EmbeddedShader {{ name: {:?}, target: {:?}, bytecode: include_bytes!({:?}) }},\n",
+            entry.name, entry.target, path.display().to_string()
+        ));
+    }
+    rust.push_str("];\n");
+    rust
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn entry (name: &str, source: &str, target: &str) -> Entry {
+        Entry { name: name.into(), source: source.into(), entrypoint: "main".into(), target: target.into(), defines: Vec::new(), options: Vec::new() }
+    }
+
+    fn error (text: &str) -> (usize, String) {
+        let e = parse(text).unwrap_err();
+        (e.line, e.message)
+    }
+
+    #[test] fn checked_in_manifest () {
+        let entries = parse(include_str!("../res/shaders.manifest")).unwrap();
+        assert_eq!(entries, vec![entry("vs", "vs.hlsl", "vs_5_0"), entry("ps", "ps.hlsl", "ps_5_0")]);
+    }
+
+    #[test] fn fields () {
+        let entries = parse("# comment\n\n  ps_fog source=fog.hlsl entry=fog target=ps_5_0 define=FOG define=DENSITY=0.5 options=/Zpr,,/WX # trailing\n").unwrap();
+        assert_eq!(entries, vec![Entry {
+            name:       "ps_fog".into(),
+            source:     "fog.hlsl".into(),
+            entrypoint: "fog".into(),
+            target:     "ps_5_0".into(),
+            defines:    vec![("FOG".into(), "1".into()), ("DENSITY".into(), "0.5".into())],
+            options:    vec!["/Zpr".into(), "/WX".into()],
+        }]);
+        assert_eq!(parse(""), Ok(Vec::new()));
+    }
+
+    #[test] fn errors () {
+        assert_eq!(error("vs source=a target=vs_5_0\nvs source=b target=vs_5_0"),    (2, String::from("duplicate name \"vs\"")));
+        assert_eq!(error("\n\nv-s source=a target=vs_5_0"),                          (3, String::from("invalid name \"v-s\"")));
+        assert_eq!(error("vs source=a target=vs_5_0 junk"),                          (1, String::from("expected key=value, found \"junk\"")));
+        assert_eq!(error("vs source=a target=vs_5_0 flags=1"),                       (1, String::from("unknown key \"flags\"")));
+        assert_eq!(error("vs source=a target=vs_5_0 define==1"),                     (1, String::from("empty define")));
+        assert_eq!(error("vs target=vs_5_0"),                                        (1, String::from("vs has no source=")));
+        assert_eq!(error("vs source=a"),                                             (1, String::from("vs has no target=")));
+        assert_eq!(parse("vs source=a").unwrap_err().to_string(), "shaders.manifest(1): vs has no target=");
+    }
+
+    #[test] fn backends () {
+        let vs = entry("vs", "vs.hlsl", "vs_5_0");
+        let source_path = Path::new("vs.hlsl");
+        assert_eq!(Fake.compile(&vs, source_path, b"float4 main () : SV_POSITION { return 0; }"), Ok(Vec::new()));
+
+        let res = Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
+        let precompiled = Precompiled { dir: res.join("precompiled") };
+        let source = fs::read(res.join("vs.hlsl")).unwrap();
+        assert_eq!(precompiled.compile(&vs, source_path, &source[..]), Ok(fs::read(res.join("precompiled/vs.dxbc")).unwrap()));
+        assert!(precompiled.compile(&vs, source_path, b"").unwrap_err().contains("wasn't compiled from the current vs.hlsl"), "stale");
+        assert!(precompiled.compile(&entry("missing", "missing.hlsl", "vs_5_0"), source_path, b"").unwrap_err().contains("missing.dxbc"));
+    }
+
+    #[test] fn source_hashes () {
+        let vs = entry("vs", "vs.hlsl", "vs_5_0");
+        let hash = source_hash(&vs, b"a\nb\n");
+        assert_eq!(hash, crate::archive::hash(b"vs.hlsl main vs_5_0 [] []\na\nb\n"));
+        assert_eq!(source_hash(&vs, b"a\r\nb\r\n"), hash, "line endings");
+        assert_ne!(source_hash(&vs, b"a\nb\nc\n"), hash);
+        assert_ne!(source_hash(&Entry { defines: vec![("FOG".into(), "1".into())], ..vs.clone() }, b"a\nb\n"), hash);
+        assert_ne!(source_hash(&Entry { target: "vs_4_0".into(), ..vs.clone() }, b"a\nb\n"), hash);
+        assert_eq!(source_hash(&Entry { name: "other".into(), ..vs }, b"a\nb\n"), hash, "name isn't part of it");
+        assert_eq!(sources_txt(&[("vs", 1), ("ps", u64::MAX)]), "vs 0000000000000001\nps ffffffffffffffff\n");
+    }
+
+    #[test] fn embedding () {
+        let dir = Path::new("out").join("shaders");
+        let vs_path = dir.join("vs.dxbc").display().to_string();
+        let ps_path = dir.join("ps.dxbc").display().to_string();
+        assert_eq!(embed(&[entry("vs", "vs.hlsl", "vs_5_0"), entry("ps", "ps.hlsl", "ps_5_0")], &dir), format!(concat!(
+            "pub static EMBEDDED : &[EmbeddedShader] = &[\n",
+            "    EmbeddedShader {{ name: \"vs\", target: \"vs_5_0\", bytecode: include_bytes!({:?}) }},\n",
+            "    EmbeddedShader {{ name: \"ps\", target: \"ps_5_0\", bytecode: include_bytes!({:?}) }},\n",
+            "];\n",
+        ), vs_path, ps_path));
+        assert_eq!(embed(&[], &dir), "pub static EMBEDDED : &[EmbeddedShader] = &[\n];\n");
+    }
+}
@@ -0,0 +1,112 @@
+//! Shaders from `res/shaders.manifest`, as compiled and embedded by `build.rs`.
+//!
+//! Release builds only use the embedded bytecode.  Debug builds fall back to compiling `res/` at runtime when
+//! `build.rs` couldn't (e.g. `SHADER_COMPILER=fake`), so shader edits don't require a D3DCompiler at build time.
+//! `load_from_archive` loads them from an `assetpack`ed archive instead, for swapping shaders without a rebuild.
+
+use crate::archive::{self, Archive};
+use crate::com::d3d::{self, Bytecode, BytecodeError, Target};
+use crate::shader_manifest::{self, Entry};
+use crate::win32::*;
+use std::ffi::CString;
+use std::fmt;
+use std::path::PathBuf;
+use std::ptr::null;
+
+pub struct EmbeddedShader {
+    pub name:       &'static str,
+    pub target:     &'static str,
+    /// Empty if `build.rs` didn't compile this shader.
+    pub bytecode:   &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
+
+const MANIFEST : &str = include_str!("../res/shaders.manifest");
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// No shader by that name in `res/shaders.manifest`.
+    UnknownShader(String),
+    Manifest(shader_manifest::Error),
+    /// The manifest's `target=` isn't a `d3d::Target`.
+    BadTarget(String),
+    /// Not embedded, and this build doesn't compile shaders at runtime.
+    NotEmbedded(String),
+    Io(std::io::Error),
+    /// The runtime fallback failed to compile.
+    Compile { hresult: HRESULT, errors: String },
+    Bytecode(BytecodeError),
+    Archive(archive::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::UnknownShader(ref name)          => write!(f, "no shader named {:?} in shaders.manifest", name),
+            LoadError::Manifest(ref e)                  => write!(f, "{}", e),
+            LoadError::BadTarget(ref target)            => write!(f, "unknown target {:?}", target),
+            LoadError::NotEmbedded(ref name)            => write!(f, "shader {:?} wasn't embedded at build time", name),
+            LoadError::Io(ref e)                        => write!(f, "{}", e),
+            LoadError::Compile { hresult, ref errors }  => write!(f, "D3DCompile failed with HRESULT={:X}\n{}", hresult, errors),
+            LoadError::Bytecode(ref e)                  => write!(f, "{}", e),
+            LoadError::Archive(ref e)                   => write!(f, "{}", e),
+        }
+    }
+}
+
+/// The bytecode `build.rs` embedded for `name`, if it compiled it.
+pub fn embedded (name: &str) -> Option<&'static EmbeddedShader> {
+    EMBEDDED.iter().find(|s| s.name == name && !s.bytecode.is_empty())
+}
+
+/// Load shader `name` from `res/shaders.manifest`, preferring the embedded bytecode.
+pub fn load<B: Bytecode> (name: &str) -> Result<B, LoadError> {
+    if let Some(shader) = embedded(name) {
+        return B::from_slice(shader.bytecode).map_err(LoadError::Bytecode);
+    }
+    if !cfg!(debug_assertions) { return Err(LoadError::NotEmbedded(name.to_string())); }
+
+    let entries = shader_manifest::parse(MANIFEST).map_err(LoadError::Manifest)?;
+    let entry = entries.iter().find(|e| e.name == name).ok_or_else(|| LoadError::UnknownShader(name.to_string()))?;
+    compile(entry)
+}
+
+/// Load shader `name` from `archive`, where `assetpack` stores `build.rs`'s `shaders/` output as `shaders/{name}.dxbc`.
+pub fn load_from_archive<B: Bytecode, D: AsRef<[u8]>> (archive: &Archive<D>, name: &str) -> Result<B, LoadError> {
+    let contents = archive.load(&format!("shaders/{}.dxbc", name)).map_err(LoadError::Archive)?;
+    B::from_slice(contents.as_ref()).map_err(LoadError::Bytecode)
+}
+
+/// Compile `entry` from the source tree's `res/` directory, as `build.rs` would for a debug build.
+fn compile<B: Bytecode> (entry: &Entry) -> Result<B, LoadError> {
+    let target = Target::parse(&entry.target).ok_or_else(|| LoadError::BadTarget(entry.target.clone()))?;
+    let mut options = d3d::CompileOptions::debug();
+    for switch in entry.options.iter() {
+        if !options.apply_switch(switch) { return Err(LoadError::Manifest(shader_manifest::Error { line: 0, message: format!("{}: unknown option {:?}", entry.name, switch) })); }
+    }
+
+    let source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res").join(&entry.source);
+    let source = std::fs::read(&source_path).map_err(LoadError::Io)?;
+    let entrypoint = CString::new(entry.entrypoint.clone()).unwrap();
+    let defines : Vec<(CString, CString)> = entry.defines.iter().map(|(name, value)| (CString::new(name.clone()).unwrap(), CString::new(value.clone()).unwrap())).collect();
+    let mut macros : Vec<D3D_SHADER_MACRO> = defines.iter().map(|(name, value)| D3D_SHADER_MACRO { Name: name.as_ptr(), Definition: value.as_ptr() }).collect();
+    macros.push(D3D_SHADER_MACRO { Name: null(), Definition: null() });
+
+    let result = unsafe { d3d::compile::<B, _>(
+        &source[..],
+        Some(&source_path),
+        Some(&macros[..]),
+        Some(d3d::COMPILE_STANDARD_FILE_INCLUDE),
+        &entrypoint,
+        target,
+        &options
+    )};
+    match result {
+        Ok(compiled) => Ok(compiled.shader),
+        Err(e) => Err(LoadError::Compile {
+            hresult:    e.hresult,
+            errors:     e.errors.map_or(String::new(), |blob| blob.to_string_lossy()),
+        }),
+    }
+}
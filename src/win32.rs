@@ -1,18 +1,25 @@
 #![cfg(windows)]
+#![allow(ambiguous_glob_reexports)] // winapi's modules redefine a few of each other's names
 
 pub use winapi::*;
 pub use winapi::shared::basetsd::*;
 pub use winapi::shared::dxgi::*;
 pub use winapi::shared::dxgiformat::*;
 pub use winapi::shared::dxgitype::*;
+pub use winapi::shared::guiddef::*;
 pub use winapi::shared::minwindef::*;
 pub use winapi::shared::windef::*;
 pub use winapi::shared::winerror::*;
 pub use winapi::um::d3d11::*;
+pub use winapi::um::d3d11shader::*;
 pub use winapi::um::d3dcommon::*;
 pub use winapi::um::d3dcompiler::*;
 pub use winapi::um::debugapi::*;
+pub use winapi::um::fileapi::*;
+pub use winapi::um::handleapi::*;
 pub use winapi::um::libloaderapi::*;
+pub use winapi::um::memoryapi::*;
 pub use winapi::um::unknwnbase::*;
 pub use winapi::um::wingdi::*;
+pub use winapi::um::winnt::{FILE_ATTRIBUTE_NORMAL, FILE_SHARE_READ, GENERIC_READ, HANDLE, LARGE_INTEGER, PAGE_READONLY};
 pub use winapi::um::winuser::*;
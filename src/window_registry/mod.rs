@@ -0,0 +1,317 @@
+//! Several windows, each presenting through its own swap chain, all rendered by one shared device.
+//!
+//! `WindowRegistry` is generic over both window IDs and what it stores per window, and `route` takes `WindowInput`
+//! rather than a windowing library's events, so event routing and window lifetimes don't depend on `winit` or having
+//! a device.  `dispatch` then acts on the routed `SurfaceEvent`s through a `SurfaceHost` - `Graphics` on Windows, a
+//! recording backend in tests.  `Surface` (Windows only) is what `Graphics` actually stores:  a window, its swap
+//! chain, and its back buffer view.
+
+use crate::fullscreen::{FullscreenState, WindowMode};
+use std::fmt::Debug;
+
+#[cfg(windows)] mod surface;
+#[cfg(windows)] pub use surface::*;
+
+/// The keys surfaces care about.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Return,
+    Other,
+}
+
+/// A window event, as far as surfaces are concerned.  Backends translate their own events into these (see
+/// `winit_input`) before handing them to `WindowRegistry::route`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WindowInput {
+    /// The new client area size, in logical pixels.
+    Resized { width: f64, height: f64 },
+    CloseRequested,
+    RedrawRequested,
+    Focused(bool),
+    KeyPressed { key: Key, alt: bool },
+}
+
+/// What a `WindowInput` means for the surface of the window it was sent to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SurfaceEvent {
+    /// The new client area size, in logical pixels.
+    Resized { width: f64, height: f64 },
+    CloseRequested,
+    /// The window's contents were lost or are otherwise out of date.
+    Redraw,
+    /// The window gained or lost focus, possibly knocking it out of exclusive fullscreen.
+    Focused(bool),
+    /// Alt+Enter was pressed:  toggle fullscreen.
+    AltEnter,
+}
+
+/// Per-window state, kept in the order windows were added.
+pub struct WindowRegistry<Id, S> {
+    windows: Vec<(Id, S)>,
+}
+
+impl<Id: Copy + Eq, S> Default for WindowRegistry<Id, S> {
+    fn default () -> Self { Self::new() }
+}
+
+impl<Id: Copy + Eq, S> WindowRegistry<Id, S> {
+    pub fn new () -> Self { Self { windows: Vec::new() } }
+
+    pub fn len (&self) -> usize { self.windows.len() }
+    pub fn is_empty (&self) -> bool { self.windows.is_empty() }
+
+    /// Add or replace the state for `id`, returning the replaced state (if any.)
+    pub fn insert (&mut self, id: Id, surface: S) -> Option<S> {
+        match self.windows.iter_mut().find(|(w, _)| *w == id) {
+            Some((_, existing)) => Some(std::mem::replace(existing, surface)),
+            None                => { self.windows.push((id, surface)); None },
+        }
+    }
+
+    pub fn remove (&mut self, id: Id) -> Option<S> {
+        let index = self.windows.iter().position(|(w, _)| *w == id)?;
+        Some(self.windows.remove(index).1)
+    }
+
+    pub fn get (&self, id: Id) -> Option<&S> {
+        self.windows.iter().find(|(w, _)| *w == id).map(|(_, s)| s)
+    }
+
+    pub fn get_mut (&mut self, id: Id) -> Option<&mut S> {
+        self.windows.iter_mut().find(|(w, _)| *w == id).map(|(_, s)| s)
+    }
+
+    pub fn ids (&self) -> Vec<Id> {
+        self.windows.iter().map(|(w, _)| *w).collect()
+    }
+
+    pub fn iter (&self) -> impl Iterator<Item = &S> {
+        self.windows.iter().map(|(_, s)| s)
+    }
+
+    pub fn iter_mut (&mut self) -> impl Iterator<Item = &mut S> {
+        self.windows.iter_mut().map(|(_, s)| s)
+    }
+
+    /// What `input`, sent to window `id`, means for that window's surface.  Returns `None` if `id` isn't registered,
+    /// or for input that surfaces don't care about.
+    pub fn route (&self, id: Id, input: &WindowInput) -> Option<SurfaceEvent> {
+        self.get(id)?;
+        Some(match *input {
+            WindowInput::Resized { width, height }                  => SurfaceEvent::Resized { width, height },
+            WindowInput::CloseRequested                             => SurfaceEvent::CloseRequested,
+            WindowInput::RedrawRequested                            => SurfaceEvent::Redraw,
+            WindowInput::Focused(focused)                           => SurfaceEvent::Focused(focused),
+            WindowInput::KeyPressed { key: Key::Return, alt: true } => SurfaceEvent::AltEnter,
+            WindowInput::KeyPressed { .. }                          => return None,
+        })
+    }
+}
+
+/// Whatever owns the windows `dispatch` acts on.
+pub trait SurfaceHost {
+    type Id: Copy + Eq + Debug;
+    type Error: Debug;
+
+    /// Stop tracking `id`, destroying its swap chain and window.
+    fn remove_window (&mut self, id: Self::Id);
+    fn window_count (&self) -> usize;
+    /// Resize the back buffers of `id` to match a new client area, in logical pixels.
+    fn resize_window (&mut self, id: Self::Id, width: f64, height: f64) -> Result<(), Self::Error>;
+    fn fullscreen (&mut self, id: Self::Id) -> Option<&mut FullscreenState>;
+    /// Switch `id` to `to`.  On failure, the window must be left in its previous mode.
+    fn set_window_mode (&mut self, id: Self::Id, to: WindowMode) -> Result<(), Self::Error>;
+    /// Whether the swap chain of `id` is actually in exclusive fullscreen right now.
+    fn is_exclusive (&self, id: Self::Id) -> bool;
+}
+
+/// Act on `event`, which `WindowRegistry::route` produced for window `id`.  Errors are logged rather than returned:
+/// a window that failed to resize or switch modes is still usable.  Returns `false` once the last window is closed.
+pub fn dispatch<H: SurfaceHost> (host: &mut H, id: H::Id, event: SurfaceEvent) -> bool {
+    match event {
+        SurfaceEvent::CloseRequested => {
+            host.remove_window(id);
+            if host.window_count() == 0 { return false; }
+        },
+        SurfaceEvent::Resized { width, height } => if let Err(error) = host.resize_window(id, width, height) {
+            crate::debug::output(&format!("Resizing the back buffers of {:?} failed: {:?}\n", id, error));
+        },
+        SurfaceEvent::Redraw => {},
+        SurfaceEvent::AltEnter => {
+            let to = match host.fullscreen(id) { Some(fullscreen) => fullscreen.toggled(), None => return true };
+            if let Err(error) = host.set_window_mode(id, to) {
+                // `set_window_mode` rolled back, so the window is still in its previous mode.
+                crate::debug::output(&format!("Switching {:?} to {:?} failed: {:?}\n", id, to, error));
+            }
+        },
+        SurfaceEvent::Focused(_) => {
+            let exclusive = host.is_exclusive(id);
+            if let Some(fullscreen) = host.fullscreen(id) {
+                if fullscreen.mode() == WindowMode::Exclusive && !exclusive { fullscreen.exclusive_lost(); }
+            }
+        },
+    }
+    true
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::fullscreen::{Rect, Step};
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Call {
+        Remove(u32),
+        Resize(u32, f64, f64),
+        Step(u32, Step),
+    }
+
+    struct Window {
+        fullscreen: FullscreenState,
+        exclusive:  bool,
+    }
+
+    /// Records what `dispatch` asks of it, with windows that fail to resize or switch modes on demand.
+    #[derive(Default)]
+    struct Recording {
+        windows:        WindowRegistry<u32, Window>,
+        calls:          Vec<Call>,
+        fail_resize:    bool,
+        /// Fail the step with this index, once.
+        fail_step:      Option<usize>,
+    }
+
+    const WINDOW  : Rect = Rect { left: 100, top: 100, right: 740, bottom: 580 };
+    const MONITOR : Rect = Rect { left: 0, top: 0, right: 1920, bottom: 1080 };
+
+    impl Recording {
+        fn with_windows (ids: &[u32]) -> Self {
+            let mut r = Self::default();
+            for &id in ids { r.windows.insert(id, Window { fullscreen: FullscreenState::new(), exclusive: false }); }
+            r
+        }
+
+        fn mode (&self, id: u32) -> WindowMode { self.windows.get(id).unwrap().fullscreen.mode() }
+    }
+
+    impl SurfaceHost for Recording {
+        type Id     = u32;
+        type Error  = &'static str;
+
+        fn remove_window (&mut self, id: u32) {
+            self.calls.push(Call::Remove(id));
+            self.windows.remove(id);
+        }
+
+        fn window_count (&self) -> usize { self.windows.len() }
+
+        fn resize_window (&mut self, id: u32, width: f64, height: f64) -> Result<(), &'static str> {
+            self.calls.push(Call::Resize(id, width, height));
+            if self.fail_resize { Err("resize failed") } else { Ok(()) }
+        }
+
+        fn fullscreen (&mut self, id: u32) -> Option<&mut FullscreenState> {
+            self.windows.get_mut(id).map(|w| &mut w.fullscreen)
+        }
+
+        fn set_window_mode (&mut self, id: u32, to: WindowMode) -> Result<(), &'static str> {
+            let Self { windows, calls, fail_step, .. } = self;
+            let window = windows.get_mut(id).unwrap();
+            let exclusive = &mut window.exclusive;
+            window.fullscreen.switch(to, WINDOW, MONITOR, None, |step| {
+                let taken = calls.iter().filter(|c| matches!(c, Call::Step(..))).count();
+                if *fail_step == Some(taken) { *fail_step = None; return Err("step failed"); }
+                calls.push(Call::Step(id, step));
+                if let Step::SetFullscreenState(full) = step { *exclusive = full; }
+                Ok(())
+            })
+        }
+
+        fn is_exclusive (&self, id: u32) -> bool { self.windows.get(id).unwrap().exclusive }
+    }
+
+    fn route_and_dispatch (host: &mut Recording, id: u32, input: WindowInput) -> Option<bool> {
+        let event = host.windows.route(id, &input)?;
+        Some(dispatch(host, id, event))
+    }
+
+    #[test] fn registry () {
+        let mut windows = WindowRegistry::<u32, &str>::new();
+        assert!(windows.is_empty());
+        assert_eq!(windows.insert(1, "one"), None);
+        assert_eq!(windows.insert(2, "two"), None);
+        assert_eq!(windows.insert(1, "uno"), Some("one"));
+        assert_eq!(windows.ids(), vec![1, 2]);
+        assert_eq!(windows.get(1), Some(&"uno"));
+        assert_eq!(windows.remove(1), Some("uno"));
+        assert_eq!(windows.remove(1), None);
+        assert_eq!(windows.get(1), None);
+        assert_eq!(windows.iter().collect::<Vec<_>>(), vec![&"two"]);
+        assert_eq!(windows.len(), 1);
+    }
+
+    #[test] fn route () {
+        let windows = Recording::with_windows(&[1]).windows;
+        let resized = WindowInput::Resized { width: 800.0, height: 600.0 };
+        assert_eq!(windows.route(1, &resized),                                                  Some(SurfaceEvent::Resized { width: 800.0, height: 600.0 }));
+        assert_eq!(windows.route(1, &WindowInput::CloseRequested),                              Some(SurfaceEvent::CloseRequested));
+        assert_eq!(windows.route(1, &WindowInput::RedrawRequested),                             Some(SurfaceEvent::Redraw));
+        assert_eq!(windows.route(1, &WindowInput::Focused(false)),                              Some(SurfaceEvent::Focused(false)));
+        assert_eq!(windows.route(1, &WindowInput::KeyPressed { key: Key::Return, alt: true }),  Some(SurfaceEvent::AltEnter));
+        assert_eq!(windows.route(1, &WindowInput::KeyPressed { key: Key::Return, alt: false }), None);
+        assert_eq!(windows.route(1, &WindowInput::KeyPressed { key: Key::Other,  alt: true }),  None);
+        assert_eq!(windows.route(2, &resized),                                                  None, "unregistered window");
+    }
+
+    #[test] fn close_exits_after_last_window () {
+        let mut host = Recording::with_windows(&[1, 2]);
+        assert_eq!(route_and_dispatch(&mut host, 1, WindowInput::CloseRequested), Some(true));
+        assert_eq!(route_and_dispatch(&mut host, 1, WindowInput::CloseRequested), None, "already closed");
+        assert_eq!(route_and_dispatch(&mut host, 2, WindowInput::CloseRequested), Some(false));
+        assert_eq!(host.calls, vec![Call::Remove(1), Call::Remove(2)]);
+    }
+
+    #[test] fn resize () {
+        let mut host = Recording::with_windows(&[1, 2]);
+        assert_eq!(route_and_dispatch(&mut host, 2, WindowInput::Resized { width: 320.0, height: 240.0 }), Some(true));
+        host.fail_resize = true;
+        assert_eq!(route_and_dispatch(&mut host, 1, WindowInput::Resized { width: 0.0, height: 0.0 }), Some(true), "failures are logged, not fatal");
+        assert_eq!(host.calls, vec![Call::Resize(2, 320.0, 240.0), Call::Resize(1, 0.0, 0.0)]);
+    }
+
+    #[test] fn alt_enter_toggles () {
+        let mut host = Recording::with_windows(&[1, 2]);
+        let alt_enter = WindowInput::KeyPressed { key: Key::Return, alt: true };
+
+        route_and_dispatch(&mut host, 1, alt_enter);
+        assert_eq!(host.mode(1), WindowMode::Borderless);
+        assert_eq!(host.mode(2), WindowMode::Windowed, "other windows are left alone");
+        assert_eq!(host.calls, vec![Call::Step(1, Step::SetDecorations(false)), Call::Step(1, Step::MoveWindow(MONITOR))]);
+
+        host.calls.clear();
+        route_and_dispatch(&mut host, 1, alt_enter);
+        assert_eq!(host.mode(1), WindowMode::Windowed);
+        assert_eq!(host.calls, vec![Call::Step(1, Step::SetDecorations(true)), Call::Step(1, Step::MoveWindow(WINDOW))]);
+    }
+
+    #[test] fn alt_enter_failure_stays_put () {
+        let mut host = Recording::with_windows(&[1]);
+        host.fail_step = Some(1);
+        assert_eq!(route_and_dispatch(&mut host, 1, WindowInput::KeyPressed { key: Key::Return, alt: true }), Some(true));
+        assert_eq!(host.mode(1), WindowMode::Windowed);
+        assert_eq!(host.calls, vec![Call::Step(1, Step::SetDecorations(false)), Call::Step(1, Step::SetDecorations(true))], "undone");
+    }
+
+    #[test] fn focus_loss_drops_exclusive () {
+        let mut host = Recording::with_windows(&[1]);
+        host.windows.get_mut(1).unwrap().fullscreen.alt_enter = WindowMode::Exclusive;
+        route_and_dispatch(&mut host, 1, WindowInput::KeyPressed { key: Key::Return, alt: true });
+        assert_eq!(host.mode(1), WindowMode::Exclusive);
+
+        route_and_dispatch(&mut host, 1, WindowInput::Focused(false));
+        assert_eq!(host.mode(1), WindowMode::Exclusive, "DXGI still agrees");
+
+        host.windows.get_mut(1).unwrap().exclusive = false; // DXGI left exclusive fullscreen on its own
+        route_and_dispatch(&mut host, 1, WindowInput::Focused(false));
+        assert_eq!(host.mode(1), WindowMode::Windowed);
+    }
+}
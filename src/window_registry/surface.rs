@@ -0,0 +1,191 @@
+//! `Surface` and the `winit` side of `WindowRegistry::route`.
+
+use super::*;
+use crate::com::d3d11::*;
+use crate::com::dxgi::*;
+use crate::depth::*;
+use crate::fullscreen;
+use crate::win32::*;
+use winit::dpi::LogicalSize;
+use winit::event::*;
+use winit::platform::windows::WindowExtWindows;
+use winit::window::{Window, WindowId};
+
+/// Translate a winit event into the window it was sent to and what it means for surfaces, for
+/// `WindowRegistry::route`.  `None` for events that aren't for a window, or that surfaces don't care about.
+pub fn winit_input<T> (event: &Event<T>) -> Option<(WindowId, WindowInput)> {
+    let (id, event) = match event {
+        Event::WindowEvent { window_id, event } => (*window_id, event),
+        _ => return None,
+    };
+
+    let input = match event {
+        WindowEvent::Resized(size)      => WindowInput::Resized { width: size.width, height: size.height },
+        WindowEvent::CloseRequested     => WindowInput::CloseRequested,
+        WindowEvent::RedrawRequested    => WindowInput::RedrawRequested,
+        WindowEvent::Focused(focused)   => WindowInput::Focused(*focused),
+        WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Pressed, virtual_keycode, modifiers, .. }, .. } => {
+            let key = if *virtual_keycode == Some(VirtualKeyCode::Return) { Key::Return } else { Key::Other };
+            WindowInput::KeyPressed { key, alt: modifiers.alt }
+        },
+        _ => return None,
+    };
+    Some((id, input))
+}
+
+/// A window, the swap chain presenting to it, and a depth buffer that's resized alongside it.
+pub struct Surface {
+    pub window:         Window,
+    pub fullscreen:     FullscreenState,
+    swap_chain_desc:    DXGI_SWAP_CHAIN_DESC,
+    depth_policy:       Option<DepthPolicy>,
+    feature_level:      FeatureLevel,
+    swap_chain:         SwapChain,
+    // `None` while the back buffers are being resized, and after a resize that failed to recreate them.
+    render_target_view: Option<RenderTargetView>,
+    depth_buffer:       Option<DepthBuffer>,
+}
+
+/// A `Surface`'s swap chain and views, built on a new device by `Surface::rebuild` for `Surface::replace` to swap in.
+pub struct SurfaceBuffers {
+    feature_level:      FeatureLevel,
+    swap_chain:         SwapChain,
+    render_target_view: RenderTargetView,
+    depth_buffer:       Option<DepthBuffer>,
+}
+
+impl Surface {
+    /// Create a swap chain for `window`.  Takes ownership of `window` so it's guaranteed to outlive the swap chain.
+    /// If `depth_policy` is set, also create a depth buffer to go with it.
+    pub fn new (window: Window, device: &Device, feature_level: FeatureLevel, factory: &Factory, depth_policy: Option<DepthPolicy>) -> Result<Self, HRESULT> {
+        let client = window.inner_size().to_physical(window.hidpi_factor());
+        let swap_chain_desc = DXGI_SWAP_CHAIN_DESC {
+            BufferDesc: DXGI_MODE_DESC {
+                Width:  client.width as UINT,
+                Height: client.height as UINT,
+                RefreshRate: DXGI_RATIONAL { Numerator: 60, Denominator: 1 },
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                ScanlineOrdering: DXGI_MODE_SCANLINE_ORDER_UNSPECIFIED,
+                Scaling: DXGI_MODE_SCALING_CENTERED,
+            },
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            BufferCount: 1,
+            OutputWindow: window.hwnd() as HWND,
+            Windowed: 1,
+            SwapEffect: DXGI_SWAP_EFFECT_DISCARD,
+            Flags: 0,
+        };
+
+        let SurfaceBuffers { feature_level, swap_chain, render_target_view, depth_buffer } = build(&swap_chain_desc, depth_policy, device, feature_level, factory)?;
+        Ok(Self {
+            window,
+            fullscreen:         FullscreenState::new(),
+            swap_chain_desc,
+            depth_policy,
+            feature_level,
+            swap_chain,
+            render_target_view: Some(render_target_view),
+            depth_buffer,
+        })
+    }
+
+    pub fn id (&self) -> WindowId { self.window.id() }
+
+    pub fn swap_chain (&self) -> &SwapChain { &self.swap_chain }
+
+    /// `None` if the last `resize` failed and left the surface without back buffer views.  Skip rendering to it
+    /// until a later `resize` or `replace` succeeds.
+    pub fn render_target_view (&self) -> Option<&RenderTargetView> {
+        self.render_target_view.as_ref()
+    }
+
+    /// `None` if the surface was created without a `DepthPolicy`.
+    pub fn depth_stencil_view (&self) -> Option<&DepthStencilView> {
+        self.depth_buffer.as_ref().map(|db| &db.dsv)
+    }
+
+    /// A viewport covering the entire back buffer.
+    pub fn viewport (&self) -> D3D11_VIEWPORT {
+        let buffer_desc = &self.swap_chain_desc.BufferDesc;
+        D3D11_VIEWPORT { Width: buffer_desc.Width as f32, Height: buffer_desc.Height as f32, MinDepth: 0.0, MaxDepth: 1.0, TopLeftX: 0.0, TopLeftY: 0.0 }
+    }
+
+    /// Resize the back buffers (and depth buffer) to match a new client area.  `device_context`'s state is cleared to
+    /// unbind the old ones.  If resizing fails, views of the old size are recreated if possible.
+    pub fn resize (&mut self, device: &Device, device_context: &DeviceContext, size: LogicalSize) -> Result<(), HRESULT> {
+        let size = size.to_physical(self.window.hidpi_factor());
+        if size.width < 1.0 || size.height < 1.0 { return Ok(()); } // Minimized
+
+        device_context.clear_state();
+        // `ResizeBuffers` fails while anything still references the back buffers, so the old views can't be kept.
+        self.render_target_view = None;
+        self.depth_buffer       = None;
+        if let Err(hresult) = self.swap_chain.resize_buffers(0, size.width as UINT, size.height as UINT, DXGI_FORMAT_UNKNOWN, self.swap_chain_desc.Flags) {
+            if let Ok((render_target_view, depth_buffer)) = create_views(&self.swap_chain, &self.swap_chain_desc, self.depth_policy, device, self.feature_level) {
+                self.render_target_view = Some(render_target_view);
+                self.depth_buffer       = depth_buffer;
+            }
+            return Err(hresult);
+        }
+        self.swap_chain_desc.BufferDesc.Width  = size.width as UINT;
+        self.swap_chain_desc.BufferDesc.Height = size.height as UINT;
+        let (render_target_view, depth_buffer) = create_views(&self.swap_chain, &self.swap_chain_desc, self.depth_policy, device, self.feature_level)?;
+        self.render_target_view = Some(render_target_view);
+        self.depth_buffer       = depth_buffer;
+        Ok(())
+    }
+
+    pub fn set_window_mode (&mut self, to: WindowMode) -> Result<(), HRESULT> {
+        fullscreen::apply(&self.window, &self.swap_chain, &mut self.fullscreen, to)
+    }
+
+    /// Build a new swap chain and views on `device`, which must have been created by `factory`'s adapter.  This
+    /// surface keeps its current ones until `replace`.
+    pub fn rebuild (&self, device: &Device, feature_level: FeatureLevel, factory: &Factory) -> Result<SurfaceBuffers, HRESULT> {
+        // The old swap chain's device is gone, and exclusive fullscreen with it.  Let go of the output so the new
+        // swap chain can be created for the same window.  (`DXGI_SWAP_EFFECT_DISCARD` allows several per window.)
+        let _ = self.swap_chain.set_fullscreen_state(false, None);
+        build(&self.swap_chain_desc, self.depth_policy, device, feature_level, factory)
+    }
+
+    /// Switch to buffers from `rebuild`, releasing the old ones.
+    pub fn replace (&mut self, buffers: SurfaceBuffers) {
+        let SurfaceBuffers { feature_level, swap_chain, render_target_view, depth_buffer } = buffers;
+        self.render_target_view = Some(render_target_view);
+        self.depth_buffer       = depth_buffer;
+        self.swap_chain         = swap_chain;
+        self.feature_level      = feature_level;
+        self.fullscreen.exclusive_lost();
+    }
+}
+
+impl Drop for Surface {
+    fn drop (&mut self) {
+        // DXGI requires leaving exclusive fullscreen before a swap chain is released.
+        self.render_target_view = None;
+        self.depth_buffer       = None;
+        let _ = self.swap_chain.set_fullscreen_state(false, None);
+    }
+}
+
+fn build (swap_chain_desc: &DXGI_SWAP_CHAIN_DESC, depth_policy: Option<DepthPolicy>, device: &Device, feature_level: FeatureLevel, factory: &Factory) -> Result<SurfaceBuffers, HRESULT> {
+    let swap_chain = unsafe { factory.create_swap_chain(device, swap_chain_desc) }?;
+    // DXGI's own Alt+Enter handling only knows about exclusive fullscreen, route it through `FullscreenState` instead.
+    unsafe { factory.make_window_association(swap_chain_desc.OutputWindow, DXGI_MWA_NO_ALT_ENTER) }?;
+    let (render_target_view, depth_buffer) = create_views(&swap_chain, swap_chain_desc, depth_policy, device, feature_level)?;
+    Ok(SurfaceBuffers { feature_level, swap_chain, render_target_view, depth_buffer })
+}
+
+fn create_views (swap_chain: &SwapChain, swap_chain_desc: &DXGI_SWAP_CHAIN_DESC, depth_policy: Option<DepthPolicy>, device: &Device, feature_level: FeatureLevel) -> Result<(RenderTargetView, Option<DepthBuffer>), HRESULT> {
+    let back_buffer = swap_chain.get_buffer::<Texture2D>(0)?;
+    let render_target_view = device.create_render_target_view(&back_buffer, None)?;
+    let depth_buffer = match depth_policy {
+        Some(policy) => {
+            let buffer_desc = &swap_chain_desc.BufferDesc;
+            Some(DepthBuffer::create(device, policy.select(feature_level), buffer_desc.Width, buffer_desc.Height, swap_chain_desc.SampleDesc)?)
+        },
+        None => None,
+    };
+    Ok((render_target_view, depth_buffer))
+}
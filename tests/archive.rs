@@ -0,0 +1,157 @@
+//! `archive::Writer` round trips through `Archive` and `StreamReader`, and how both handle archives that aren't.
+
+use rust_win32_d3d11::archive::{self, Archive, Compression, Contents, Error, Options, StreamReader, Writer};
+use std::io::Cursor;
+
+const VS_9_1 : &[u8] = include_bytes!("data/fxc/sdl_vs_9_1.dxbc");
+
+/// xorshift, for incompressible input.
+fn noise (len: usize) -> Vec<u8> {
+    let mut state = 0x2545F491u32;
+    (0..len).map(|_| { state ^= state << 13; state ^= state >> 17; state ^= state << 5; state as u8 }).collect()
+}
+
+fn stored () -> Options { Options { compress: false, align: 16 } }
+
+/// Name, contents, how they're added, and how they should end up stored.
+fn assets () -> Vec<(&'static str, Vec<u8>, Options, Compression)> {
+    vec![
+        ("empty",               Vec::new(),             Options::default(),                         Compression::None),
+        ("shaders/vs.dxbc",     VS_9_1.to_vec(),        Options::default(),                         Compression::Lz4),
+        ("shaders/raw.dxbc",    VS_9_1.to_vec(),        stored(),                                   Compression::None),
+        ("zeros",               vec![0u8; 100_000],     Options { compress: true, align: 4096 },    Compression::Lz4),
+        ("noise",               noise(1000),            Options { compress: true, align: 1 },       Compression::None),
+        ("mapped/noise",        noise(333),             Options { compress: false, align: 4096 },   Compression::None),
+    ]
+}
+
+fn pack () -> Vec<u8> {
+    let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+    for (name, data, options, _) in assets() { writer.add(name, &data[..], &options).unwrap(); }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test] fn archive_round_trip () {
+    let archive = Archive::parse(pack()).unwrap();
+    assert_eq!(archive.entries().len(), assets().len());
+    for (name, data, options, compression) in assets() {
+        let entry = archive.entry(name).unwrap();
+        assert_eq!(entry.compression, compression, "{}", name);
+        assert_eq!(entry.size, data.len() as u64);
+        assert_eq!(entry.hash, archive::hash(&data[..]));
+        assert_eq!(entry.offset % options.align as u64, 0, "{} alignment", name);
+
+        let contents = archive.load(name).unwrap();
+        assert_eq!(contents.as_ref(), &data[..], "{}", name);
+        // Uncompressed entries are borrowed from the archive, not copied
+        match contents {
+            Contents::Stored { .. }     => assert_eq!(compression, Compression::None),
+            Contents::Unpacked(_)       => assert_eq!(compression, Compression::Lz4),
+        }
+    }
+    archive.verify().unwrap();
+    assert!(matches!(archive.load("missing"), Err(Error::NotFound(ref name)) if name == "missing"));
+}
+
+#[test] fn stream_reader_round_trip () {
+    let bytes = pack();
+    let mut reader = StreamReader::new(Cursor::new(&bytes[..])).unwrap();
+    assert_eq!(reader.entries(), Archive::parse(&bytes[..]).unwrap().entries());
+    for (name, data, _, _) in assets().into_iter().rev() {
+        assert_eq!(reader.read(name).unwrap(), data, "{}", name);
+    }
+    assert!(matches!(reader.read("missing"), Err(Error::NotFound(_))));
+}
+
+#[test] fn empty_archive () {
+    let bytes = Writer::new(Cursor::new(Vec::new())).unwrap().finish().unwrap().into_inner();
+    assert!(Archive::parse(&bytes[..]).unwrap().entries().is_empty());
+    assert!(StreamReader::new(Cursor::new(&bytes[..])).unwrap().entries().is_empty());
+}
+
+#[test] fn writer_errors () {
+    let mut writer = Writer::new(Cursor::new(Vec::new())).unwrap();
+    writer.add("a", b"a", &Options::default()).unwrap();
+    assert!(matches!(writer.add("a", b"again", &Options::default()), Err(Error::DuplicateName(ref name)) if name == "a"));
+    assert!(matches!(writer.add("b", b"b", &Options { compress: true, align: 3 }), Err(Error::BadAlignment(3))));
+    assert!(matches!(writer.add("b", b"b", &Options { compress: true, align: 0 }), Err(Error::BadAlignment(0))));
+    assert!(matches!(writer.add(&"n".repeat(65536), b"b", &Options::default()), Err(Error::NameTooLong(_))));
+    writer.add(&"n".repeat(65535), b"b", &Options::default()).unwrap();
+    assert_eq!(writer.entries().len(), 2);
+
+    let archive = Archive::parse(writer.finish().unwrap().into_inner()).unwrap();
+    assert_eq!(archive.load(&"n".repeat(65535)).unwrap().as_ref(), b"b");
+}
+
+/// Parse `bytes` both ways, expecting both to fail the same way.
+fn both_fail (bytes: &[u8], expected: fn(&Error) -> bool) {
+    let archive = Archive::parse(bytes).err().expect("Archive::parse succeeded");
+    assert!(expected(&archive), "Archive: {:?}", archive);
+    let stream = StreamReader::new(Cursor::new(bytes)).err().expect("StreamReader::new succeeded");
+    assert!(expected(&stream), "StreamReader: {:?}", stream);
+}
+
+#[test] fn bad_header () {
+    let bytes = pack();
+
+    let mut magic = bytes.clone();
+    magic[0..4].copy_from_slice(b"DXBC");
+    both_fail(&magic[..], |e| matches!(e, Error::NotArchive));
+
+    let mut version = bytes.clone();
+    version[4..8].copy_from_slice(&(archive::VERSION + 1).to_le_bytes());
+    both_fail(&version[..], |e| matches!(e, Error::UnsupportedVersion(v) if *v == archive::VERSION + 1));
+    version[4..8].copy_from_slice(&0u32.to_le_bytes());
+    both_fail(&version[..], |e| matches!(e, Error::UnsupportedVersion(0)));
+
+    both_fail(&bytes[..31], |e| matches!(e, Error::Truncated { .. }));
+    both_fail(&bytes[..bytes.len() - 1], |e| matches!(e, Error::Truncated { .. }));
+
+    // An index offset that overflows, rather than allocating or panicking
+    let mut huge = bytes.clone();
+    huge[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    both_fail(&huge[..], |e| matches!(e, Error::Truncated { .. }));
+}
+
+fn index_offset (bytes: &[u8]) -> usize {
+    let mut le = [0u8; 8];
+    le.copy_from_slice(&bytes[8..16]);
+    u64::from_le_bytes(le) as usize
+}
+
+#[test] fn corrupt_index () {
+    let bytes = pack();
+    for offset in index_offset(&bytes)..bytes.len() {
+        let mut flipped = bytes.clone();
+        flipped[offset] ^= 0x01;
+        both_fail(&flipped[..], |e| matches!(e, Error::CorruptIndex));
+    }
+
+    // The index hash matches, but `shaders/vs.dxbc` runs into the index
+    let mut overlapping = bytes.clone();
+    let index = index_offset(&bytes);
+    let offset = index + 4 + (2 + "empty".len() + 1 + 32) + (2 + "shaders/vs.dxbc".len() + 1);
+    assert_eq!(&overlapping[offset..offset+8], &Archive::parse(&bytes[..]).unwrap().entries()[1].offset.to_le_bytes()[..]);
+    overlapping[offset..offset+8].copy_from_slice(&(index as u64 - 1).to_le_bytes());
+    let hash = archive::hash(&overlapping[index..]);
+    overlapping[24..32].copy_from_slice(&hash.to_le_bytes());
+    both_fail(&overlapping[..], |e| matches!(e, Error::CorruptIndex));
+}
+
+#[test] fn corrupt_entries () {
+    let bytes = pack();
+    let archive = Archive::parse(&bytes[..]).unwrap();
+    for entry in archive.entries().iter().filter(|e| e.stored_size > 0) {
+        for &at in [0, entry.stored_size / 2, entry.stored_size - 1].iter() {
+            let mut flipped = bytes.clone();
+            flipped[(entry.offset + at) as usize] ^= 0x01;
+
+            // The index is fine, so parsing succeeds, and only loading the entry fails
+            let archive = Archive::parse(&flipped[..]).unwrap();
+            let corrupt = |e: &Error| matches!(e, Error::CorruptEntry(ref name) if *name == entry.name);
+            assert!(corrupt(&archive.load(&entry.name).err().unwrap()), "{} + {}", entry.name, at);
+            assert!(corrupt(&archive.verify().unwrap_err()));
+            assert!(corrupt(&StreamReader::new(Cursor::new(&flipped[..])).unwrap().read(&entry.name).unwrap_err()));
+        }
+    }
+}
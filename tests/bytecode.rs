@@ -0,0 +1,61 @@
+//! `d3d::bytecode_target` and the stage checks of `d3d::Bytecode`, against bytecode from `fxc` (see `data/README.md`).
+
+use rust_win32_d3d11::com::d3d::{self, Bytecode, BytecodeError, ShaderStage, Target};
+use rust_win32_d3d11::dxbc::{self, Chunk, Container, FourCC};
+
+const VS_9_1        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_1.dxbc");
+const VS_9_3        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_3.dxbc");
+const PS_COLORS     : &[u8] = include_bytes!("data/fxc/sdl_ps_colors_9_1.dxbc");
+const PS_TEXTURES   : &[u8] = include_bytes!("data/fxc/sdl_ps_textures_9_1.dxbc");
+const PS_YUV        : &[u8] = include_bytes!("data/fxc/sdl_ps_yuv_bt601_9_1.dxbc");
+const PS_NV12       : &[u8] = include_bytes!("data/fxc/sdl_ps_nv12_bt601_9_3.dxbc");
+
+#[test] fn level_9_targets () {
+    assert_eq!(d3d::bytecode_target(VS_9_1),        Ok(Target::vs_4_0_level_9_1));
+    assert_eq!(d3d::bytecode_target(VS_9_3),        Ok(Target::vs_4_0_level_9_3));
+    assert_eq!(d3d::bytecode_target(PS_COLORS),     Ok(Target::ps_4_0_level_9_1));
+    assert_eq!(d3d::bytecode_target(PS_TEXTURES),   Ok(Target::ps_4_0_level_9_1));
+    assert_eq!(d3d::bytecode_target(PS_YUV),        Ok(Target::ps_4_0_level_9_1));
+    assert_eq!(d3d::bytecode_target(PS_NV12),       Ok(Target::ps_4_0_level_9_3));
+}
+
+#[test] fn legacy_shader_version () {
+    let version = |bytecode| dxbc::legacy_shader_version(&Container::parse(bytecode).unwrap()).unwrap();
+    assert_eq!(version(VS_9_1),     Some(0xFFFE_0200));
+    assert_eq!(version(VS_9_3),     Some(0xFFFE_0201));
+    assert_eq!(version(PS_COLORS),  Some(0xFFFF_0200));
+    assert_eq!(version(PS_NV12),    Some(0xFFFF_0201));
+}
+
+/// `SHEX` with nothing but a version token, and whatever else `chunks` adds.
+fn shader (version: u32, chunks: &[Chunk]) -> Vec<u8> {
+    let shex = [version, 2].iter().flat_map(|dword: &u32| dword.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+    let mut all = vec![Chunk { fourcc: FourCC::SHEX, data: &shex[..] }];
+    all.extend_from_slice(chunks);
+    dxbc::write_container(1, &all[..])
+}
+
+#[test] fn d3d10_targets () {
+    assert_eq!(d3d::bytecode_target(&shader(0x0005_0050, &[])), Ok(Target::cs_5_0));
+    assert_eq!(d3d::bytecode_target(&shader(0x0003_0050, &[])), Ok(Target::hs_5_0));
+    assert_eq!(d3d::bytecode_target(&shader(0x0002_0041, &[])), Ok(Target::gs_4_1));
+    assert_eq!(d3d::bytecode_target(&shader(0x0000_0040, &[])), Ok(Target::ps_4_0));
+}
+
+#[test] fn unknown_versions () {
+    let hs_4_0 = shader(0x0003_0040, &[]);
+    assert!(matches!(d3d::bytecode_target(&hs_4_0), Err(BytecodeError::UnknownVersion(_))));
+
+    // An Aon9 chunk claiming a ps_3_0 shader, which no _level_9_* target produces.
+    let aon9 = [0u32, 0xFFFF_0300, 4, 16, 0xFFFF_0300].iter().flat_map(|dword| dword.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+    let ps_3_0 = shader(0x0000_0040, &[Chunk { fourcc: FourCC::AON9, data: &aon9[..] }]);
+    assert!(matches!(d3d::bytecode_target(&ps_3_0), Err(BytecodeError::UnknownVersion(_))));
+
+    assert!(matches!(d3d::bytecode_target(b"not a shader"), Err(BytecodeError::Dxbc(_))));
+}
+
+#[test] fn stages () {
+    assert_eq!(d3d::VertexBytecode::from_slice(VS_9_3).unwrap().target(), Target::vs_4_0_level_9_3);
+    assert_eq!(d3d::PixelBytecode::from_slice(VS_9_1), Err(BytecodeError::WrongStage { expected: ShaderStage::Pixel, found: ShaderStage::Vertex }));
+    assert_eq!(d3d::ComputeBytecode::from_bytes(shader(0x0005_0050, &[])).unwrap().target(), Target::cs_5_0);
+}
@@ -0,0 +1,90 @@
+//! `dxbc::verify_checksum`, `dxbc::compute_checksum`, and `dxbc::sign`, against bytecode from `fxc` (see
+//! `data/README.md`) and containers checksummed by an independent implementation.
+
+use rust_win32_d3d11::dxbc::{self, Chunk, Error, FourCC};
+
+const VS_9_1        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_1.dxbc");
+const VS_9_3        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_3.dxbc");
+const PS_COLORS     : &[u8] = include_bytes!("data/fxc/sdl_ps_colors_9_1.dxbc");
+const PS_TEXTURES   : &[u8] = include_bytes!("data/fxc/sdl_ps_textures_9_1.dxbc");
+const PS_YUV        : &[u8] = include_bytes!("data/fxc/sdl_ps_yuv_bt601_9_1.dxbc");
+const PS_NV12       : &[u8] = include_bytes!("data/fxc/sdl_ps_nv12_bt601_9_3.dxbc");
+const PS_NV21       : &[u8] = include_bytes!("data/fxc/sdl_ps_nv21_bt601_9_1.dxbc");
+const ALL           : &[&[u8]] = &[VS_9_1, VS_9_3, PS_COLORS, PS_TEXTURES, PS_YUV, PS_NV12, PS_NV21];
+
+fn checksum (bytes: &[u8]) -> [u8; 16] {
+    let mut checksum = [0u8; 16];
+    checksum.copy_from_slice(&bytes[4..20]);
+    checksum
+}
+
+#[test] fn fxc_blobs_verify () {
+    for &bytecode in ALL {
+        assert_eq!(dxbc::verify_checksum(bytecode), Ok(true));
+        assert_eq!(dxbc::compute_checksum(bytecode), Ok(checksum(bytecode)));
+    }
+    assert_eq!((PS_NV21.len() - 20) % 64, 0, "no tail block");
+}
+
+#[test] fn flipped_bytes_fail () {
+    for &bytecode in ALL {
+        // One byte in each of:  the header, the first chunk, and the last chunk
+        let first = u32::from_le_bytes([bytecode[32], bytecode[33], bytecode[34], bytecode[35]]) as usize;
+        for &offset in [20, first + 12, bytecode.len() - 1].iter() {
+            let mut flipped = bytecode.to_vec();
+            flipped[offset] ^= 0x01;
+            assert_eq!(dxbc::verify_checksum(&flipped), Ok(false), "offset: {}", offset);
+
+            dxbc::sign(&mut flipped).unwrap();
+            assert_eq!(dxbc::verify_checksum(&flipped), Ok(true), "offset: {}", offset);
+            assert_ne!(checksum(&flipped), checksum(bytecode));
+        }
+
+        // Flipping the checksum itself
+        let mut flipped = bytecode.to_vec();
+        flipped[4] ^= 0x80;
+        assert_eq!(dxbc::verify_checksum(&flipped), Ok(false));
+        dxbc::sign(&mut flipped).unwrap();
+        assert_eq!(flipped, bytecode);
+    }
+}
+
+#[test] fn trailing_bytes_ignored () {
+    let mut padded = PS_COLORS.to_vec();
+    padded.extend_from_slice(&[0xCC; 7]);
+    assert_eq!(dxbc::verify_checksum(&padded), Ok(true));
+    assert_eq!(dxbc::compute_checksum(&padded), Ok(checksum(PS_COLORS)));
+}
+
+#[test] fn errors () {
+    assert_eq!(dxbc::verify_checksum(b"not a container"), Err(Error::NotDxbc));
+    assert_eq!(dxbc::verify_checksum(&PS_COLORS[..100]), Err(Error::Truncated { offset: PS_COLORS.len() }));
+    let mut short = [0u8; 8];
+    short[..4].copy_from_slice(b"DXBC");
+    assert_eq!(dxbc::sign(&mut short[..]), Err(Error::Truncated { offset: 24 }));
+}
+
+/// A container of one `PRIV` chunk of `size` bytes `0, 1, 2, ...`:  `24 + size` bytes are checksummed.
+fn private (size: usize) -> Vec<u8> {
+    let data = (0..size).map(|i| i as u8).collect::<Vec<u8>>();
+    dxbc::write_container(1, &[Chunk { fourcc: FourCC::PRIV, data: &data[..] }])
+}
+
+/// The `fxc` blobs' tail blocks are all under 56 bytes, so the other padding path is checked against checksums from
+/// a separate (Python) implementation of the same algorithm, which agrees with `fxc` on every SDL blob.
+#[test] fn tail_blocks () {
+    let hex = |checksum: [u8; 16]| checksum.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    let expected = [
+        (28, 52, "a9d9fa04667743db8c5357084b157c5f"),
+        (32, 56, "7934ac19105f814e71841e8dbb7deee2"),
+        (36, 60, "99b02fbbf26f39fa7b858e318bcf35cd"),
+        (40,  0, "ee6df05df04edeb0aaab5fbd5da87fa3"),
+        (96, 56, "462f00ab15e37f1c78050658e167cf10"),
+    ];
+    for &(size, tail, sum) in expected.iter() {
+        let bytes = private(size);
+        assert_eq!((bytes.len() - 20) % 64, tail);
+        assert_eq!(hex(checksum(&bytes)), sum, "size: {}", size);
+        assert_eq!(dxbc::verify_checksum(&bytes), Ok(true));
+    }
+}
@@ -0,0 +1,24 @@
+//! `d3d::compile_raw` / `d3d::preprocess` against the real `D3DCompiler_47.dll`.
+
+#![cfg(windows)]
+
+use rust_win32_d3d11::com::d3d::{self, MagicConstantInclude};
+use std::ffi::CStr;
+use std::path::Path;
+
+const BROKEN : &[u8] = b"float4 main () : SV_TARGET { return undeclared; }\n";
+
+#[test] fn source_name_in_diagnostics () {
+    let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let ps_5_0 = CStr::from_bytes_with_nul(b"ps_5_0\0").unwrap();
+    let failed = unsafe { d3d::compile_raw::<MagicConstantInclude>(BROKEN, Some(Path::new("shaders/broken.hlsl")), None, None, Some(main), ps_5_0, 0, 0) }.err().unwrap();
+    let errors = failed.errors.unwrap().to_string_lossy();
+    assert!(errors.starts_with("shaders/broken.hlsl(1,"), "{}", errors);
+}
+
+#[test] fn preprocess_source_name () {
+    let source = b"#line 10\n#error stop\n";
+    let failed = unsafe { d3d::preprocess::<MagicConstantInclude>(source, Some(Path::new("pre.hlsl")), None, None) }.err().unwrap();
+    let errors = failed.errors.unwrap().to_string_lossy();
+    assert!(errors.starts_with("pre.hlsl(10"), "{}", errors);
+}
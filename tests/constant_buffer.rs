@@ -0,0 +1,70 @@
+//! `constant_buffer!` layouts against the offsets `fxc` reflects for the same `cbuffer`s.
+
+#![allow(non_snake_case)] // Field names match the HLSL
+
+use rust_win32_d3d11::constant_buffer;
+use rust_win32_d3d11::constant_buffer::*;
+use rust_win32_d3d11::dxbc;
+
+constant_buffer! {
+    /// `VertexShaderConstants` from `data/fxc/sdl_vs_*.dxbc`.
+    pub struct VertexShaderConstants {
+        pub model:              Float4x4,
+        pub projectionAndView:  Float4x4,
+    }
+}
+
+/// `(name, offset)` of every field, and the buffer size.
+fn layout<C: ConstantBuffer> () -> (Vec<(String, usize)>, usize) {
+    let layout = C::layout();
+    (layout.fields.iter().map(|f| (f.name.to_string(), f.offset)).collect(), layout.size)
+}
+
+fn reflected (cb: &dxbc::ConstantBuffer) -> (Vec<(String, usize)>, usize) {
+    (cb.variables.iter().map(|v| (v.name.clone(), v.offset as usize)).collect(), cb.size as usize)
+}
+
+#[test] fn sdl_vertex_shader () {
+    for &bytecode in [&include_bytes!("data/fxc/sdl_vs_9_1.dxbc")[..], &include_bytes!("data/fxc/sdl_vs_9_3.dxbc")[..]].iter() {
+        let reflection = dxbc::reflect(bytecode).unwrap();
+        let cb = reflection.constant_buffer("VertexShaderConstants").unwrap();
+        assert_eq!(layout::<VertexShaderConstants>(), reflected(cb));
+        for (field, variable) in VertexShaderConstants::layout().fields.iter().zip(cb.variables.iter()) {
+            assert_eq!(field.size, variable.size as usize, "{}", field.name);
+        }
+    }
+}
+
+/// Compile each `hlsl_cbuffer` with `D3DCompile`, and compare every field's offset with what it reflects.
+#[cfg(windows)]
+mod d3dcompile {
+    use super::*;
+    use rust_win32_d3d11::com::d3d::{self, CompileOptions, Target};
+    use std::ffi::CStr;
+
+    constant_buffer! { pub struct Light { pub color: Float3, pub intensity: f32, pub direction: Float3 } }
+    constant_buffer! { pub struct Scalars { pub a: f32, pub b: Float3, pub c: Float2, pub d: Float2, pub e: Float3 } }
+    constant_buffer! { pub struct Matrices { pub a: f32, pub b: Float3x3, pub c: f32, pub d: Float4x3, pub e: Float2 } }
+    constant_buffer! { pub struct Arrays { pub a: [f32; 3], pub b: f32, pub c: [Float2; 2], pub d: Float2, pub e: [Float4x4; 2], pub f: f32 } }
+    constant_buffer! { pub struct Structs { pub a: f32, pub b: Light, pub c: f32, pub d: [Light; 2], pub e: f32, pub f: Uint3, pub g: i32 } }
+
+    fn fxc<C: ConstantBuffer> () -> Vec<(String, usize)> {
+        // Unused constant buffers are stripped, so read something.  Appended, so it can't move anything else.
+        let cbuffer = C::hlsl_cbuffer(Some(0)).replace("};\n", "    float used_;\n};\n");
+        let source = format!("{}{}float4 main () : SV_TARGET {{ return used_; }}\n", Light::hlsl_struct(), cbuffer);
+        let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        let compiled = unsafe { d3d::compile::<d3d::PixelBytecode, d3d::MagicConstantInclude>(source.as_bytes(), None, None, None, main, Target::ps_5_0, &CompileOptions::default()) }
+            .unwrap_or_else(|e| panic!("{}\n{}", source, e.errors.map_or(String::new(), |blob| blob.to_string_lossy())));
+        let reflection = dxbc::reflect(d3d::Bytecode::as_bytes(&compiled.shader)).unwrap();
+        let (mut fields, _) = reflected(reflection.constant_buffer(C::name()).unwrap());
+        assert_eq!(fields.pop().map(|(name, _)| name), Some(String::from("used_")));
+        fields
+    }
+
+    #[test] fn matches_fxc () {
+        assert_eq!(layout::<Scalars>().0,   fxc::<Scalars>());
+        assert_eq!(layout::<Matrices>().0,  fxc::<Matrices>());
+        assert_eq!(layout::<Arrays>().0,    fxc::<Arrays>());
+        assert_eq!(layout::<Structs>().0,   fxc::<Structs>());
+    }
+}
@@ -0,0 +1,411 @@
+//! Assembles the `fx_*.fxo` next to this file from the layouts in Effects11's `EffectBinaryFormat.h` (see
+//! `../README.md`).  `tests/effect.rs` checks the checked in files still match, and rewrites them with
+//! `cargo test --test effect -- --ignored reassemble`.
+
+use rust_win32_d3d11::dxbc::{self, Chunk, FourCC};
+use std::collections::HashMap;
+
+pub const VS_9_1        : &[u8] = include_bytes!("../fxc/sdl_vs_9_1.dxbc");
+pub const PS_COLORS     : &[u8] = include_bytes!("../fxc/sdl_ps_colors_9_1.dxbc");
+pub const VS_5_0        : &[u8] = include_bytes!("../../../res/precompiled/vs.dxbc");
+pub const PS_5_0        : &[u8] = include_bytes!("../../../res/precompiled/ps.dxbc");
+
+const CREATOR : &str = "rust_win32_d3d11 tests/data/fx (hand assembled)";
+
+fn dwords (values: &[u32]) -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect() }
+
+/// Append `text` and a NUL to `bytes`, padded to a dword with `0xAB`s like `fxc` pads its strings.
+fn push_padded_str (bytes: &mut Vec<u8>, text: &str) {
+    bytes.extend_from_slice(text.as_bytes());
+    bytes.push(0);
+    while !bytes.len().is_multiple_of(4) { bytes.push(0xAB); }
+}
+
+/// An `RDEF` without constant buffers or bindings, for `program` (e.g. `0x4753` for geometry shaders), `major`.`minor`.
+fn rdef (program: u32, major: u32, minor: u32) -> Vec<u8> {
+    let header = if major >= 5 { 60 } else { 28 };
+    let mut rdef = dwords(&[0, header, 0, header, program << 16 | major << 8 | minor, 0x100, header]);
+    if major >= 5 { rdef.extend(b"RD11".iter().cloned().chain(dwords(&[header, 24, 32, 40, 36, 12, 0]))); }
+    push_padded_str(&mut rdef, CREATOR);
+    rdef
+}
+
+/// A signature of `(stream, semantic name, semantic index, system value, register, mask, read/write mask)`s of floats,
+/// with streams only for `OSG5`.
+fn signature (fourcc: FourCC, elements: &[(u32, &str, u32, u32, u32, u8, u8)]) -> Vec<u8> {
+    let element_size = if fourcc == FourCC::OSG5 { 28 } else { 24 };
+    let mut strings = Vec::new();
+    let mut offsets = HashMap::new();
+    for &(_, name, ..) in elements {
+        if !offsets.contains_key(name) {
+            offsets.insert(name, (8 + element_size * elements.len() + strings.len()) as u32);
+            push_padded_str(&mut strings, name);
+        }
+    }
+
+    let mut chunk = dwords(&[elements.len() as u32, 8]);
+    for &(stream, name, index, system_value, register, mask, read_write) in elements {
+        if fourcc == FourCC::OSG5 { chunk.extend(dwords(&[stream])); }
+        chunk.extend(dwords(&[offsets[name], index, system_value, 3, register]));
+        chunk.extend_from_slice(&[mask, read_write, 0, 0]);
+    }
+    chunk.extend(strings);
+    chunk
+}
+
+/// A container of `RDEF`, signature, and `SHEX` chunks, laid out the way `fxc` lays them out.  `SHEX` is `version`,
+/// a length token, and `tokens`.
+fn shader (program: u32, major: u32, minor: u32, signatures: &[(FourCC, Vec<u8>)], version: u32, tokens: &[u32]) -> Vec<u8> {
+    let rdef = rdef(program, major, minor);
+    let shex = dwords(&[&[version, 2 + tokens.len() as u32][..], tokens].concat());
+    let mut chunks = vec![Chunk { fourcc: FourCC::RDEF, data: &rdef[..] }];
+    chunks.extend(signatures.iter().map(|&(fourcc, ref data)| Chunk { fourcc, data: &data[..] }));
+    chunks.push(Chunk { fourcc: FourCC::SHEX, data: &shex[..] });
+    dxbc::write_container(1, &chunks[..])
+}
+
+/// What every geometry shader here reads:  one point of `SV_POSITION` and `TEXCOORD0.xy`.
+fn gs_input () -> (FourCC, Vec<u8>) {
+    (FourCC::ISGN, signature(FourCC::ISGN, &[(0, "SV_POSITION", 0, 1, 0, 0xF, 0xF), (0, "TEXCOORD", 0, 0, 1, 0x3, 0x3)]))
+}
+
+/// `gs_4_0` passing a point through:
+///
+/// ```text
+/// gs_4_0
+/// dcl_input_siv v[1][0].xyzw, position
+/// dcl_input v[1][1].xy
+/// dcl_inputprimitive point
+/// dcl_outputtopology pointlist
+/// dcl_output_siv o0.xyzw, position
+/// dcl_output o1.xy
+/// dcl_maxout 1
+/// mov o0.xyzw, v[0][0].xyzw
+/// mov o1.xy, v[0][1].xyxx
+/// emit
+/// ret
+/// ```
+pub fn gs_4_0 () -> Vec<u8> {
+    let output = signature(FourCC::OSGN, &[(0, "SV_POSITION", 0, 1, 0, 0xF, 0), (0, "TEXCOORD", 0, 0, 1, 0x3, 0)]);
+    shader(0x4753, 4, 0, &[gs_input(), (FourCC::OSGN, output)], 0x0002_0040, &[
+        0x0500_0061, 0x0020_10F2, 1, 0, 1,
+        0x0400_005F, 0x0020_1032, 1, 1,
+        0x0100_085D,
+        0x0100_085C,
+        0x0400_0067, 0x0010_20F2, 0, 1,
+        0x0300_0065, 0x0010_2032, 1,
+        0x0200_005E, 1,
+        0x0600_0036, 0x0010_20F2, 0, 0x0020_1E46, 0, 0,
+        0x0600_0036, 0x0010_2032, 1, 0x0020_1046, 0, 1,
+        0x0100_0013,
+        0x0100_003E,
+    ])
+}
+
+/// `gs_5_0` passing a point through to streams 0 and 1:
+///
+/// ```text
+/// gs_5_0
+/// dcl_globalFlags refactoringAllowed
+/// dcl_input_siv v[1][0].xyzw, position
+/// dcl_input v[1][1].xy
+/// dcl_inputprimitive point
+/// dcl_stream m0
+/// dcl_outputtopology pointlist
+/// dcl_output_siv o0.xyzw, position
+/// dcl_stream m1
+/// dcl_outputtopology pointlist
+/// dcl_output o1.xy
+/// dcl_maxout 1
+/// mov o0.xyzw, v[0][0].xyzw
+/// emit_stream m0
+/// mov o1.xy, v[0][1].xyxx
+/// emit_stream m1
+/// ret
+/// ```
+pub fn gs_5_0 () -> Vec<u8> {
+    let output = signature(FourCC::OSG5, &[(0, "SV_POSITION", 0, 1, 0, 0xF, 0), (1, "TEXCOORD", 0, 0, 1, 0x3, 0)]);
+    shader(0x4753, 5, 0, &[gs_input(), (FourCC::OSG5, output)], 0x0002_0050, &[
+        0x0100_086A,
+        0x0500_0061, 0x0020_10F2, 1, 0, 1,
+        0x0400_005F, 0x0020_1032, 1, 1,
+        0x0100_085D,
+        0x0300_008F, 0x0011_0000, 0,
+        0x0100_085C,
+        0x0400_0067, 0x0010_20F2, 0, 1,
+        0x0300_008F, 0x0011_0000, 1,
+        0x0100_085C,
+        0x0300_0065, 0x0010_2032, 1,
+        0x0200_005E, 1,
+        0x0600_0036, 0x0010_20F2, 0, 0x0020_1E46, 0, 0,
+        0x0300_0075, 0x0011_0000, 0,
+        0x0600_0036, 0x0010_2032, 1, 0x0020_1046, 0, 1,
+        0x0300_0075, 0x0011_0000, 1,
+        0x0100_003E,
+    ])
+}
+
+/// `cs_5_0` with `[numthreads(8, 8, 1)]` that does nothing.
+pub fn cs_5_0 () -> Vec<u8> {
+    let empty = || dwords(&[0, 8]);
+    shader(0x4353, 5, 0, &[(FourCC::ISGN, empty()), (FourCC::OSGN, empty())], 0x0005_0050, &[
+        0x0100_086A,
+        0x0400_009B, 8, 8, 1,
+        0x0100_003E,
+    ])
+}
+
+/// The unstructured section of an effect:  strings, types, default values, shaders, and constants, which the
+/// structured section refers to by offset.  Offset 0 means "none", so it starts with a dword of padding.
+struct Blob {
+    data:       Vec<u8>,
+    strings:    HashMap<String, u32>,
+}
+
+const FLOAT : u32 = 1;
+const INT   : u32 = 2;
+const UINT  : u32 = 3;
+const BOOL  : u32 = 4;
+
+impl Blob {
+    fn new () -> Self { Self { data: vec![0; 4], strings: HashMap::new() } }
+
+    fn add (&mut self, raw: &[u8]) -> u32 {
+        while !self.data.len().is_multiple_of(4) { self.data.push(0); }
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(raw);
+        offset
+    }
+
+    fn words (&mut self, values: &[u32]) -> u32 { self.add(&dwords(values)) }
+
+    fn s (&mut self, text: &str) -> u32 {
+        if let Some(&offset) = self.strings.get(text) { return offset; }
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(text.as_bytes());
+        self.data.push(0);
+        self.strings.insert(text.to_string(), offset);
+        offset
+    }
+
+    /// A non-array numeric type.  `layout` is 1 for scalars, 2 for vectors, and 3 for matrices.
+    fn numeric (&mut self, name: &str, layout: u32, scalar: u32, rows: u32, columns: u32, column_major: bool) -> u32 {
+        let size = 4 * rows * columns;
+        let bits = layout | scalar << 3 | rows << 8 | columns << 11 | (column_major as u32) << 14;
+        let name = self.s(name);
+        self.words(&[name, 1, 0, size, size, size, bits])
+    }
+
+    fn object (&mut self, name: &str, object_type: u32, elements: u32) -> u32 {
+        let name = self.s(name);
+        self.words(&[name, 2, elements, 0, 0, 0, object_type])
+    }
+
+    fn structure (&mut self, name: &str, size: u32, members: &[(&str, Option<&str>, u32, u32)]) -> u32 {
+        let mut words = vec![self.s(name), 3, 0, size, size, size, members.len() as u32];
+        for &(member, semantic, offset, ty) in members {
+            words.extend_from_slice(&[self.s(member), semantic.map_or(0, |s| self.s(s)), offset, ty]);
+        }
+        self.words(&words)
+    }
+
+    fn interface (&mut self, name: &str) -> u32 {
+        let name = self.s(name);
+        self.words(&[name, 4, 0, 0, 0, 0])
+    }
+
+    fn shader (&mut self, bytecode: &[u8]) -> u32 {
+        let mut raw = dwords(&[bytecode.len() as u32]);
+        raw.extend_from_slice(bytecode);
+        self.add(&raw)
+    }
+
+    /// `(scalar type, bits)` pairs.
+    fn constants (&mut self, constants: &[(u32, u32)]) -> u32 {
+        let mut words = vec![constants.len() as u32];
+        for &(ty, value) in constants { words.extend_from_slice(&[ty, value]); }
+        self.words(&words)
+    }
+}
+
+/// A container of a single `FX10` chunk:  `tag`, `header` (whose unstructured size is filled in), `blob`, then
+/// `structured`.
+fn effect (tag: u32, header: &[u32], mut blob: Blob, structured: &[u32]) -> Vec<u8> {
+    while !blob.data.len().is_multiple_of(4) { blob.data.push(0); }
+    let mut header = [&[tag][..], header].concat();
+    header[8] = blob.data.len() as u32;
+    let mut fx10 = dwords(&header);
+    fx10.extend(blob.data);
+    fx10.extend(dwords(structured));
+    dxbc::write_container(1, &[Chunk { fourcc: FourCC::FX10, data: &fx10[..] }])
+}
+
+fn f32_bits (value: f32) -> u32 { value.to_bits() }
+
+// Assignment states, numbered as in `dxbc::effect::State`
+const RASTERIZER        : u32 =  0;
+const DEPTH_STENCIL     : u32 =  1;
+const BLEND             : u32 =  2;
+const VS                : u32 =  6;
+const PS                : u32 =  7;
+const GS                : u32 =  8;
+const STENCIL_REF       : u32 =  9;
+const BLEND_FACTOR      : u32 = 10;
+const SAMPLE_MASK       : u32 = 11;
+const FILL_MODE         : u32 = 12;
+const CULL_MODE         : u32 = 13;
+const DEPTH_ENABLE      : u32 = 22;
+const DEPTH_WRITE_MASK  : u32 = 23;
+const BLEND_ENABLE      : u32 = 37;
+const SRC_BLEND         : u32 = 38;
+const DEST_BLEND        : u32 = 39;
+const FILTER            : u32 = 45;
+const ADDRESS_U         : u32 = 46;
+const HS                : u32 = 56;
+const DS                : u32 = 57;
+const CS                : u32 = 58;
+
+pub fn fx_4_0 () -> Vec<u8> {
+    let mut b = Blob::new();
+    let float4      = b.numeric("float4", 2, FLOAT, 1, 4, false);
+    let float4x4    = b.numeric("float4x4", 3, FLOAT, 4, 4, true);
+    let blend       = b.object("BlendState", 2, 0);
+    let raster      = b.object("RasterizerState", 4, 0);
+    let vs          = b.object("VertexShader", 6, 0);
+    let _ps         = b.object("PixelShader", 5, 0);
+    let texture     = b.object("Texture2D", 12, 0);
+    let sampler     = b.object("SamplerState", 21, 0);
+    let string      = b.object("string", 1, 0);
+    let tint        = b.words(&[f32_bits(1.0), f32_bits(0.5), f32_bits(0.25), f32_bits(1.0)]);
+    let ui_scale    = b.words(&[f32_bits(2.0)]);
+    let vs_code     = b.shader(VS_9_1);
+    let ps_code     = b.shader(PS_COLORS);
+
+    let mut s = Vec::new();
+    // cbuffer Constants { float4x4 world; float4 tint : COLOR < float UIScale = 2; > = { 1, 0.5, 0.25, 1 }; }
+    s.extend_from_slice(&[b.s("Constants"), 80, 0, 2, 0xFFFF_FFFF, 0]);
+    s.extend_from_slice(&[b.s("world"), float4x4, 0, 0, 0, 0, 0]);
+    let float = b.numeric("float", 1, FLOAT, 1, 1, false);
+    s.extend_from_slice(&[b.s("tint"), float4, b.s("COLOR"), 64, tint, 0, 1, b.s("UIScale"), float, ui_scale]);
+    // BlendState AlphaBlend { BlendEnable[0] = TRUE; SrcBlend[0] = SRC_ALPHA; DestBlend[0] = INV_SRC_ALPHA; };
+    s.extend_from_slice(&[b.s("AlphaBlend"), blend, 0, 0xFFFF_FFFF, 3,
+        BLEND_ENABLE,   0, 1, b.constants(&[(BOOL, 1)]),
+        SRC_BLEND,      0, 1, b.constants(&[(UINT, 5)]),
+        DEST_BLEND,     0, 1, b.constants(&[(UINT, 6)]), 0]);
+    // RasterizerState Wireframe { FillMode = WIREFRAME; CullMode = NONE; };
+    s.extend_from_slice(&[b.s("Wireframe"), raster, 0, 0xFFFF_FFFF, 2,
+        FILL_MODE,      0, 1, b.constants(&[(UINT, 2)]),
+        CULL_MODE,      0, 1, b.constants(&[(UINT, 1)]), 0]);
+    // VertexShader vs = CompileShader(vs_4_0_level_9_1, ...);
+    s.extend_from_slice(&[b.s("vs"), vs, 0, 0xFFFF_FFFF, vs_code, 0]);
+    // Texture2D diffuse : register(t3);
+    s.extend_from_slice(&[b.s("diffuse"), texture, 0, 3, 0]);
+    // SamplerState linear { Filter = MIN_MAG_MIP_LINEAR; AddressU = WRAP; };
+    s.extend_from_slice(&[b.s("linear"), sampler, 0, 0xFFFF_FFFF, 2,
+        FILTER,         0, 1, b.constants(&[(UINT, 0x15)]),
+        ADDRESS_U,      0, 1, b.constants(&[(UINT, 1)]), 0]);
+    // string Description = "fx_4_0 test effect";
+    s.extend_from_slice(&[b.s("Description"), string, 0, 0xFFFF_FFFF, b.s("fx_4_0 test effect"), 0]);
+    // technique10 Render < string Author = "tests"; > { pass P0 { ... } }
+    s.extend_from_slice(&[b.s("Render"), 1, 1, b.s("Author"), string, b.s("tests")]);
+    let inline_ps = b.words(&[ps_code, 0]);
+    let blend_factor = b.constants(&[(FLOAT, f32_bits(0.0)), (FLOAT, f32_bits(0.5)), (FLOAT, f32_bits(1.0)), (FLOAT, f32_bits(1.0))]);
+    s.extend_from_slice(&[b.s("P0"), 7, 0,
+        VS,             0, 2, b.s("vs"),
+        PS,             0, 7, inline_ps,
+        GS,             0, 1, b.constants(&[(INT, 0)]),
+        BLEND,          0, 2, b.s("AlphaBlend"),
+        BLEND_FACTOR,   0, 1, blend_factor,
+        SAMPLE_MASK,    0, 1, b.constants(&[(UINT, 0xFFFF_FFFF)]),
+        RASTERIZER,     0, 2, b.s("Wireframe")]);
+    effect(0xFEFF_1001, &[1, 2, 6, 0, 0, 0, 1, 0, 0, 1, 0, 1, 1, 1, 0, 0, 2, 1], b, &s)
+}
+
+pub fn fx_4_1 () -> Vec<u8> {
+    let mut b = Blob::new();
+    let ps          = b.object("PixelShader", 5, 2);
+    let gs_so       = b.object("GeometryShader", 8, 0);
+    let ds          = b.object("DepthStencilState", 3, 0);
+    let uint        = b.numeric("uint", 1, UINT, 1, 1, false);
+    let gs_code     = b.shader(&gs_4_0());
+    let ps_code     = b.shader(PS_COLORS);
+    let expression  = b.add(&[&dwords(&[8])[..], b"FXLVM\0\0\0"].concat()); // Placeholder bytes, not real FXLVM code
+
+    let mut s = Vec::new();
+    // cbuffer Selection { uint which; }
+    s.extend_from_slice(&[b.s("Selection"), 16, 0, 1, 0xFFFF_FFFF, 0]);
+    s.extend_from_slice(&[b.s("which"), uint, 0, 0, 0, 0, 0]);
+    // PixelShader shaders[2] = { NULL, CompileShader(...) };
+    s.extend_from_slice(&[b.s("shaders"), ps, 0, 0xFFFF_FFFF, 0, ps_code, 0]);
+    // GeometryShader so = ConstructGSWithSO(CompileShader(gs_4_0, ...), "SV_POSITION.xyzw; TEXCOORD0.xy");
+    s.extend_from_slice(&[b.s("so"), gs_so, 0, 0xFFFF_FFFF, gs_code, b.s("SV_POSITION.xyzw; TEXCOORD0.xy"), 0]);
+    // DepthStencilState NoDepth { DepthEnable = FALSE; DepthWriteMask = ZERO; };
+    s.extend_from_slice(&[b.s("NoDepth"), ds, 0, 0xFFFF_FFFF, 2,
+        DEPTH_ENABLE,       0, 1, b.constants(&[(BOOL, 0)]),
+        DEPTH_WRITE_MASK,   0, 1, b.constants(&[(UINT, 0)]), 0]);
+    // technique10 Indexed { pass Const { ... } pass Variable { ... } pass Expression { ... } }
+    s.extend_from_slice(&[b.s("Indexed"), 3, 0]);
+    let shaders = b.s("shaders");
+    let const_index = b.words(&[shaders, 1]);
+    s.extend_from_slice(&[b.s("Const"), 3, 0,
+        PS,                 0, 3, const_index,
+        DEPTH_STENCIL,      0, 2, b.s("NoDepth"),
+        STENCIL_REF,        0, 1, b.constants(&[(UINT, 7)])]);
+    let which = b.s("which");
+    let variable_index = b.words(&[shaders, which]);
+    s.extend_from_slice(&[b.s("Variable"), 1, 0,
+        PS,                 0, 4, variable_index]);
+    let expression_index = b.words(&[shaders, expression]);
+    s.extend_from_slice(&[b.s("Expression"), 2, 0,
+        PS,                 0, 5, expression_index,
+        STENCIL_REF,        0, 6, expression]);
+    effect(0xFEFF_1011, &[1, 1, 3, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 3, 0], b, &s)
+}
+
+pub fn fx_5_0 () -> Vec<u8> {
+    let mut b = Blob::new();
+    let float3      = b.numeric("float3", 2, FLOAT, 1, 3, false);
+    let uint        = b.numeric("uint", 1, UINT, 1, 1, false);
+    let light       = b.structure("Light", 16, &[("direction", Some("DIRECTION"), 0, float3), ("flags", None, 12, uint)]);
+    let ilight      = b.interface("ILight");
+    let vs5         = b.object("VertexShader", 26, 0);
+    let gs5         = b.object("GeometryShader", 27, 0);
+    let gs_code     = b.shader(&gs_5_0());
+    let vs_code     = b.shader(VS_5_0);
+    let ps_code     = b.shader(PS_5_0);
+    let cs_code     = b.shader(&cs_5_0());
+    let (ambient, suns) = (b.s("ambient"), b.s("suns"));
+    let bindings    = b.words(&[ambient, 0, suns, 2]);
+    let sun_default = b.words(&[suns, 1]);
+
+    let mut s = Vec::new();
+    // cbuffer Lights : register(b2) { Light light; }
+    s.extend_from_slice(&[b.s("Lights"), 16, 0, 1, 2, 0]);
+    s.extend_from_slice(&[b.s("light"), light, 0, 0, 0, 0, 0]);
+    // VertexShader vs = BindInterfaces(CompileShader(vs_5_0, ...), ambient, suns[2]);
+    s.extend_from_slice(&[b.s("vs"), vs5, 0, 0xFFFF_FFFF, vs_code, 0, 0, 0, 0, 0, 0, 2, bindings, 0]);
+    // GeometryShader gs = ConstructGSWithSO(CompileShader(gs_5_0, ...), "SV_POSITION.xyzw", "TEXCOORD0.xy", NULL, NULL, 1);
+    s.extend_from_slice(&[b.s("gs"), gs5, 0, 0xFFFF_FFFF, gs_code, b.s("SV_POSITION.xyzw"), b.s("TEXCOORD0.xy"), 0, 0, 2, 1, 0, 0, 0]);
+    // ILight sun = suns[1];
+    s.extend_from_slice(&[b.s("sun"), ilight, sun_default, 0, 0]);
+    // technique11 Loose { pass P { SetComputeShader(CompileShader(cs_5_0, ...)); } }, outside any fxgroup
+    s.extend_from_slice(&[0, 1, 0]);
+    s.extend_from_slice(&[b.s("Loose"), 1, 0]);
+    let inline_cs = b.words(&[cs_code, 0, 0, 0, 0, 0, 0, 0, 0]);
+    s.extend_from_slice(&[b.s("P"), 1, 0,
+        CS, 0, 8, inline_cs]);
+    // fxgroup Main < int Order = 1; > { technique11 Draw { pass P { ... } } }
+    let int = b.numeric("int", 1, INT, 1, 1, false);
+    let order = b.words(&[1]);
+    s.extend_from_slice(&[b.s("Main"), 1, 1, b.s("Order"), int, order]);
+    s.extend_from_slice(&[b.s("Draw"), 1, 0]);
+    let no_hs = b.constants(&[(INT, 0)]);
+    let no_ds = b.constants(&[(INT, 0)]);
+    let inline_ps = b.words(&[ps_code, 0, 0, 0, 0, 0, 0, 0, 0]); // The last thing in the blob
+    s.extend_from_slice(&[b.s("P"), 4, 0,
+        VS, 0, 2, b.s("vs"),
+        HS, 0, 1, no_hs,
+        DS, 0, 1, no_ds,
+        PS, 0, 8, inline_ps]);
+    effect(0xFEFF_2001, &[1, 1, 2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 2, 2, 0, 1, 1, 0], b, &s)
+}
@@ -0,0 +1,19 @@
+# Regenerates each .asm here from `fxc /dumpbin /Fc` of the .dxbc of the same name (see ../README.md.)  Needs `fxc`
+# from the Windows SDK on PATH.  fxc's listings are trimmed to what `dxbc::disassemble` produces:  no `//` comments,
+# no level 9 bytecode, no trailing spaces, and LF line endings.
+$ErrorActionPreference = 'Stop'
+
+foreach ($dxbc in Get-ChildItem "$PSScriptRoot\*.dxbc") {
+    $fc = [IO.Path]::ChangeExtension($dxbc.FullName, 'fc')
+    & fxc /nologo /dumpbin /Fc $fc $dxbc.FullName | Out-Null
+    if ($LASTEXITCODE -ne 0) { throw "fxc failed on $($dxbc.Name)" }
+
+    $lines = Get-Content $fc | ForEach-Object { $_.TrimEnd() } | Where-Object { $_ -ne '' -and -not $_.StartsWith('//') }
+    $start = 0
+    while ($start -lt $lines.Count -and $lines[$start] -notmatch '^[a-z]s_[45]_[01]$') { $start++ }
+    if ($start -eq $lines.Count) { throw "no shader model 4+ listing for $($dxbc.Name)" }
+
+    $asm = [IO.Path]::ChangeExtension($dxbc.FullName, 'asm')
+    [IO.File]::WriteAllText($asm, (($lines[$start..($lines.Count - 1)] | ForEach-Object { "$_`n" }) -join ''))
+    Remove-Item $fc
+}
@@ -0,0 +1,5 @@
+ps_4_0
+dcl_input_ps linear v2.xyzw
+dcl_output o0.xyzw
+mov o0.xyzw, v2.xyzw
+ret
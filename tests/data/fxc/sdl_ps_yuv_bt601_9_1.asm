@@ -0,0 +1,21 @@
+ps_4_0
+dcl_sampler s0, mode_default
+dcl_resource_texture2d (float,float,float,float) t0
+dcl_resource_texture2d (float,float,float,float) t1
+dcl_resource_texture2d (float,float,float,float) t2
+dcl_input_ps linear v1.xy
+dcl_input_ps linear v2.xyzw
+dcl_output o0.xyzw
+dcl_temps 2
+sample r0.xyzw, v1.xyxx, t0.xyzw, s0
+sample r1.xyzw, v1.xyxx, t1.xyzw, s0
+mov r0.y, r1.x
+sample r1.xyzw, v1.xyxx, t2.xyzw, s0
+mov r0.z, r1.x
+add r0.xyz, r0.xyzx, l(-0.062745, -0.501961, -0.501961, 0.000000)
+dp2 r1.x, r0.xzxx, l(1.164400, 1.596000, 0.000000, 0.000000)
+dp3 r1.y, r0.xyzx, l(1.164400, -0.391800, -0.813000, 0.000000)
+dp2 r1.z, r0.xyxx, l(1.164400, 2.017200, 0.000000, 0.000000)
+mov r1.w, l(1.000000)
+mul o0.xyzw, r1.xyzw, v2.xyzw
+ret
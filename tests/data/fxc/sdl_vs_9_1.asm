@@ -0,0 +1,20 @@
+vs_4_0
+dcl_constantbuffer CB0[8], immediateIndexed
+dcl_input v0.xyz
+dcl_input v1.xy
+dcl_input v2.xyzw
+dcl_output_siv o0.xyzw, position
+dcl_output o1.xy
+dcl_output o2.xyzw
+dcl_temps 2
+mul r0.xyzw, v0.yyyy, cb0[1].xyzw
+mad r0.xyzw, v0.xxxx, cb0[0].xyzw, r0.xyzw
+mad r0.xyzw, v0.zzzz, cb0[2].xyzw, r0.xyzw
+add r0.xyzw, r0.xyzw, cb0[3].xyzw
+mul r1.xyzw, r0.yyyy, cb0[5].xyzw
+mad r1.xyzw, r0.xxxx, cb0[4].xyzw, r1.xyzw
+mad r1.xyzw, r0.zzzz, cb0[6].xyzw, r1.xyzw
+mad o0.xyzw, r0.wwww, cb0[7].xyzw, r1.xyzw
+mov o1.xy, v1.xyxx
+mov o2.xyzw, v2.xyzw
+ret
@@ -0,0 +1,173 @@
+//! `dxbc::disassemble` against listings of bytecode from `fxc` (see `data/README.md`), against `D3DDisassemble` on
+//! Windows, and against hand assembled programs for what `fxc` didn't emit for SDL.
+
+use rust_win32_d3d11::dxbc::{self, Chunk, Container, Error, FourCC, Program};
+
+const LISTINGS : &[(&[u8], &str)] = &[
+    (include_bytes!("data/fxc/sdl_vs_9_1.dxbc"),             include_str!("data/fxc/sdl_vs_9_1.asm")),
+    (include_bytes!("data/fxc/sdl_vs_9_3.dxbc"),             include_str!("data/fxc/sdl_vs_9_3.asm")),
+    (include_bytes!("data/fxc/sdl_ps_colors_9_1.dxbc"),      include_str!("data/fxc/sdl_ps_colors_9_1.asm")),
+    (include_bytes!("data/fxc/sdl_ps_textures_9_1.dxbc"),    include_str!("data/fxc/sdl_ps_textures_9_1.asm")),
+    (include_bytes!("data/fxc/sdl_ps_yuv_bt601_9_1.dxbc"),   include_str!("data/fxc/sdl_ps_yuv_bt601_9_1.asm")),
+    (include_bytes!("data/fxc/sdl_ps_nv12_bt601_9_3.dxbc"),  include_str!("data/fxc/sdl_ps_nv12_bt601_9_3.asm")),
+    (include_bytes!("data/fxc/sdl_ps_nv21_bt601_9_1.dxbc"),  include_str!("data/fxc/sdl_ps_nv21_bt601_9_1.asm")),
+];
+
+#[test] fn fxc_listings () {
+    for &(bytecode, listing) in LISTINGS {
+        assert_eq!(dxbc::disassemble(bytecode).unwrap(), listing);
+    }
+}
+
+/// `D3DDisassemble`'s listing, minus its comments, level 9 bytecode, and trailing spaces (`ret `.)
+#[cfg(windows)]
+fn d3d_disassemble (bytecode: &[u8], version: &str) -> String {
+    let listing = rust_win32_d3d11::com::d3d::disassemble(bytecode, 0, None).unwrap();
+    let lines = listing.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty() && !line.starts_with("//"));
+    lines.skip_while(|&line| line != version).map(|line| format!("{}\n", line)).collect()
+}
+
+#[cfg(windows)]
+#[test] fn matches_d3ddisassemble () {
+    for &(bytecode, listing) in LISTINGS {
+        assert_eq!(d3d_disassemble(bytecode, listing.lines().next().unwrap()), listing);
+    }
+    for bytecode in [blocks(), interfaces()].iter() {
+        let listing = dxbc::disassemble(bytecode).unwrap();
+        assert_eq!(d3d_disassemble(bytecode, listing.lines().next().unwrap()), listing);
+    }
+}
+
+/// `res/`'s shaders, `D3DCompile`d with default and release options, since `res/precompiled` isn't `fxc` output.
+#[cfg(windows)]
+#[test] fn res_matches_d3ddisassemble () {
+    use rust_win32_d3d11::com::d3d::{self, Bytecode, CompileOptions, MagicConstantInclude, PixelBytecode, VertexBytecode, Target};
+    use std::ffi::CStr;
+
+    fn compile<B: Bytecode> (source: &[u8], target: Target, options: &CompileOptions) -> B {
+        let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        unsafe { d3d::compile::<B, MagicConstantInclude>(source, None, None, None, main, target, options) }
+            .unwrap_or_else(|e| panic!("{}", e.errors.map_or(String::new(), |blob| blob.to_string_lossy()))).shader
+    }
+
+    for options in [CompileOptions::default(), CompileOptions::release()].iter() {
+        let vs : VertexBytecode = compile(include_bytes!("../res/vs.hlsl"), Target::vs_5_0, options);
+        let ps : PixelBytecode  = compile(include_bytes!("../res/ps.hlsl"), Target::ps_5_0, options);
+        for bytecode in [vs.as_bytes(), ps.as_bytes()].iter() {
+            let listing = dxbc::disassemble(bytecode).unwrap();
+            assert_eq!(d3d_disassemble(bytecode, listing.lines().next().unwrap()), listing);
+        }
+    }
+}
+
+/// A container with just a `SHEX` chunk of `version`, a length token, and `tokens`.
+fn program (version: u32, tokens: &[u32]) -> Vec<u8> {
+    let mut shex = vec![version, 2 + tokens.len() as u32];
+    shex.extend_from_slice(tokens);
+    let shex = shex.iter().flat_map(|dword| dword.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+    dxbc::write_container(1, &[Chunk { fourcc: FourCC::SHEX, data: &shex[..] }])
+}
+
+fn blocks () -> Vec<u8> {
+    program(0x0000_0040, &[                 // ps_4_0
+        0x0300_1062, 0x0010_1012, 0,        // dcl_input_ps linear v0.x
+        0x0300_0065, 0x0010_20F2, 0,        // dcl_output o0.xyzw
+        0x0304_001F, 0x0010_100A, 0,        // if_nz v0.x
+        0x0800_0036, 0x0010_20F2, 0,        //   mov o0.xyzw, l(1, 0, 0, 1)
+        0x0000_4002, 0x3F80_0000, 0, 0, 0x3F80_0000,
+        0x0100_0012,                        // else
+        0x0A00_0000, 0x0010_20F2, 0,        //   add o0.xyzw, v0.xxxx, l(0, 0, 0, 1)
+        0x0010_1006, 0,
+        0x0000_4002, 0, 0, 0, 0x3F80_0000,
+        0x0100_0015,                        // endif
+        0x0100_003E,                        // ret
+    ])
+}
+
+#[test] fn block_indentation () {
+    assert_eq!(dxbc::disassemble(&blocks()).unwrap(), concat!(
+        "ps_4_0\n",
+        "dcl_input_ps linear v0.x\n",
+        "dcl_output o0.xyzw\n",
+        "if_nz v0.x\n",
+        "  mov o0.xyzw, l(1.000000,0,0,1.000000)\n",
+        "else\n",
+        "  add o0.xyzw, v0.xxxx, l(0.000000, 0.000000, 0.000000, 1.000000)\n",
+        "endif\n",
+        "ret\n",
+    ));
+}
+
+fn interfaces () -> Vec<u8> {
+    program(0x0000_0050, &[                 // ps_5_0
+        0x0100_086A,                        // dcl_globalFlags refactoringAllowed
+        0x0200_0090, 0,                     // dcl_function_body fb0
+        0x0200_0090, 1,                     // dcl_function_body fb1
+        0x0400_0091, 0, 1, 0,               // dcl_function_table ft0 = {fb0}
+        0x0400_0091, 1, 1, 1,               // dcl_function_table ft1 = {fb1}
+        0x0600_0092, 0, 1, 0x0001_0002, 0, 1,   // dcl_interface fp0[1][1] = {ft0, ft1};
+        0x0500_0892, 1, 1, 0x0002_0001, 0,  // dcl_interface_dynamicindexed fp1[2][1] = {ft0};
+        0x0100_003E,                        // ret
+    ])
+}
+
+#[test] fn interface_declarations () {
+    assert_eq!(dxbc::disassemble(&interfaces()).unwrap(), concat!(
+        "ps_5_0\n",
+        "dcl_globalFlags refactoringAllowed\n",
+        "dcl_function_body fb0\n",
+        "dcl_function_body fb1\n",
+        "dcl_function_table ft0 = {fb0}\n",
+        "dcl_function_table ft1 = {fb1}\n",
+        "dcl_interface fp0[1][1] = {ft0, ft1};\n",
+        "dcl_interface_dynamicindexed fp1[2][1] = {ft0};\n",
+        "ret\n",
+    ));
+}
+
+#[test] fn immediate_constant_buffer () {
+    let bytecode = program(0x0005_0050, &[  // cs_5_0
+        0x0000_1835, 2 + 4 * 2,             // customdata (immediate constant buffer), 2 float4s
+        0x3F80_0000, 0, 0, 0,
+        0x4000_0000, 0, 0, 0xFFFF_FFFF,
+        0x0400_009B, 8, 4, 1,               // dcl_thread_group 8, 4, 1
+        0x0100_003E,                        // ret
+    ]);
+    assert_eq!(dxbc::disassemble(&bytecode).unwrap(), concat!(
+        "cs_5_0\n",
+        "dcl_immediateConstantBuffer {\n",
+        "{ 1.000000, 0, 0, 0 },\n",
+        "{ 2.000000, 0, 0, -1 }\n",
+        "}\n",
+        "dcl_thread_group 8, 4, 1\n",
+        "ret\n",
+    ));
+
+    let container = Container::parse(&bytecode).unwrap();
+    let program = Program::from_container(&container).unwrap();
+    let instructions = program.instructions().unwrap();
+    assert_eq!(instructions.iter().map(|i| (i.offset, i.opcode, i.tokens.len())).collect::<Vec<_>>(), [
+        (2,  dxbc::OPCODE_CUSTOMDATA,       10),
+        (12, dxbc::OPCODE_DCL_THREAD_GROUP, 4),
+        (16, 62,                            1),
+    ]);
+}
+
+#[test] fn malformed () {
+    assert_eq!(dxbc::disassemble(b"not a shader"), Err(Error::NotDxbc));
+    assert_eq!(dxbc::disassemble(&dxbc::write_container(1, &[])), Err(Error::MissingChunk(FourCC::SHDR)));
+    assert_eq!(dxbc::disassemble(&program(0x0000_0040, &[0])), Err(Error::Unsupported("zero length instruction")));
+    assert_eq!(dxbc::disassemble(&program(0x0000_0040, &[0x0300_0065, 0x0010_20F2])), Err(Error::Truncated { offset: 8 }));
+
+    // Length token claiming more than the chunk has
+    let mut long = program(0x0000_0040, &[0x0100_003E]);
+    let length = long.len() - 8;
+    long[length..length+4].copy_from_slice(&4u32.to_le_bytes());
+    assert!(matches!(dxbc::disassemble(&long), Err(Error::Truncated { .. })));
+}
+
+#[test] fn bad_operands_are_commented () {
+    // dcl_output with a register operand claiming an index it doesn't have room for
+    let listing = dxbc::disassemble(&program(0x0000_0040, &[0x0200_0065, 0x0010_20F2, 0x0100_003E])).unwrap();
+    assert_eq!(listing, "ps_4_0\n// dcl_output (opcode 101): Truncated { offset: 16 }\nret\n");
+}
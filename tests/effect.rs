@@ -0,0 +1,258 @@
+//! `dxbc::effect::Effect::parse` against the hand assembled effects in `data/fx/` (see `data/README.md`), and against
+//! `D3DCompile`d effects on Windows.
+
+#[path = "data/fx/assemble.rs"] mod assemble;
+
+use assemble::{VS_9_1, PS_COLORS, VS_5_0, PS_5_0};
+use rust_win32_d3d11::dxbc::{self, Container, Error, FourCC, ProgramType};
+use rust_win32_d3d11::dxbc::effect::*;
+
+const FX_4_0 : &[u8] = include_bytes!("data/fx/fx_4_0.fxo");
+const FX_4_1 : &[u8] = include_bytes!("data/fx/fx_4_1.fxo");
+const FX_5_0 : &[u8] = include_bytes!("data/fx/fx_5_0.fxo");
+
+#[test] fn assembled () {
+    assert!(assemble::fx_4_0() == FX_4_0, "data/fx/fx_4_0.fxo is stale:  run `cargo test --test effect -- --ignored reassemble`");
+    assert!(assemble::fx_4_1() == FX_4_1, "data/fx/fx_4_1.fxo is stale:  run `cargo test --test effect -- --ignored reassemble`");
+    assert!(assemble::fx_5_0() == FX_5_0, "data/fx/fx_5_0.fxo is stale:  run `cargo test --test effect -- --ignored reassemble`");
+}
+
+#[test] #[ignore] fn reassemble () {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/fx");
+    std::fs::write(dir.join("fx_4_0.fxo"), assemble::fx_4_0()).unwrap();
+    std::fs::write(dir.join("fx_4_1.fxo"), assemble::fx_4_1()).unwrap();
+    std::fs::write(dir.join("fx_5_0.fxo"), assemble::fx_5_0()).unwrap();
+}
+
+/// `bytecode`'s stage and shader model.
+fn stage (bytecode: &[u8]) -> (ProgramType, u8, u8) {
+    let version = dxbc::reflect(bytecode).unwrap().version;
+    (version.program, version.major, version.minor)
+}
+
+fn constants (value: &AssignmentValue) -> &[Constant] {
+    match *value { AssignmentValue::Constants(ref constants) => &constants[..], ref other => panic!("not constants: {:?}", other) }
+}
+
+fn shader (value: &AssignmentValue) -> &Shader {
+    match *value { AssignmentValue::Shader(ref shader) => shader, ref other => panic!("not a shader: {:?}", other) }
+}
+
+fn states (object: &ObjectVariable) -> Vec<(State, u32)> {
+    match object.initializer {
+        ObjectInitializer::States(ref blocks) => blocks[0].iter().map(|a| (a.state, constants(&a.value)[0].as_u32())).collect(),
+        ref other => panic!("not a state block: {:?}", other),
+    }
+}
+
+fn shaders (object: &ObjectVariable) -> &[Shader] {
+    match object.initializer { ObjectInitializer::Shaders(ref shaders) => &shaders[..], ref other => panic!("not shaders: {:?}", other) }
+}
+
+fn name (s: &str) -> AssignmentValue { AssignmentValue::Variable(s.to_string()) }
+
+#[test] fn fx_4_0 () {
+    assert_eq!(dxbc::verify_checksum(FX_4_0), Ok(true));
+    let effect = Effect::parse(FX_4_0).unwrap();
+    assert_eq!(effect.version, EffectVersion::Fx4_0);
+    assert!(effect.interfaces.is_empty());
+
+    // cbuffer Constants { float4x4 world; float4 tint : COLOR < float UIScale = 2; > = { 1, 0.5, 0.25, 1 }; }
+    assert_eq!(effect.constant_buffers.len(), 1);
+    let cb = &effect.constant_buffers[0];
+    assert_eq!((cb.name.as_str(), cb.size, cb.tbuffer, cb.explicit_bind_point), ("Constants", 80, false, None));
+    assert_eq!(cb.variables.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(), ["world", "tint"]);
+
+    let world = effect.variable("world").unwrap();
+    assert_eq!(world.ty.class, TypeClass::Numeric(NumericType { layout: NumericLayout::Matrix, scalar: ScalarType::Float, rows: 4, columns: 4, column_major: true }));
+    assert_eq!((world.offset, world.ty.total_size, world.default_value.as_ref()), (0, 64, None));
+
+    let tint = effect.variable("tint").unwrap();
+    assert_eq!((tint.ty.name.as_str(), tint.semantic.as_deref(), tint.offset), ("float4", Some("COLOR"), 64));
+    let floats = [1.0f32, 0.5, 0.25, 1.0].iter().flat_map(|f| f.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+    assert_eq!(tint.default_value.as_ref(), Some(&floats));
+    assert_eq!(tint.annotations.len(), 1);
+    assert_eq!(tint.annotations[0].name, "UIScale");
+    assert_eq!(tint.annotations[0].value, AnnotationValue::Numeric(2.0f32.to_le_bytes().to_vec()));
+
+    // Objects
+    assert_eq!(effect.objects.iter().map(|o| o.name.as_str()).collect::<Vec<_>>(), ["AlphaBlend", "Wireframe", "vs", "diffuse", "linear", "Description"]);
+    assert_eq!(states(effect.object("AlphaBlend").unwrap()), [(State::BlendEnable, 1), (State::SrcBlend, 5), (State::DestBlend, 6)]);
+    assert_eq!(states(effect.object("Wireframe").unwrap()), [(State::FillMode, 2), (State::CullMode, 1)]);
+    assert_eq!(states(effect.object("linear").unwrap()), [(State::Filter, 0x15), (State::AddressU, 1)]);
+
+    let vs = effect.object("vs").unwrap();
+    assert_eq!(vs.ty.class, TypeClass::Object(ObjectType::VertexShader));
+    assert_eq!(shaders(vs), [Shader { bytecode: Some(VS_9_1.to_vec()), so_declarations: Vec::new(), rasterized_stream: 0, interface_bindings: Vec::new() }]);
+
+    let diffuse = effect.object("diffuse").unwrap();
+    assert_eq!((diffuse.ty.class.clone(), diffuse.explicit_bind_point, diffuse.initializer.clone()), (TypeClass::Object(ObjectType::Texture2D), Some(3), ObjectInitializer::None));
+    assert_eq!(effect.object("Description").unwrap().initializer, ObjectInitializer::Strings(vec!["fx_4_0 test effect".to_string()]));
+
+    // technique10 Render < string Author = "tests"; > { pass P0 { ... } }
+    assert_eq!(effect.groups.len(), 1);
+    assert_eq!(effect.groups[0].name, None);
+    let render = effect.technique("Render").unwrap();
+    assert_eq!(render.annotations[0].value, AnnotationValue::Strings(vec!["tests".to_string()]));
+    let p0 = render.pass("P0").unwrap();
+    assert_eq!(p0.assignments.iter().map(|a| a.state).collect::<Vec<_>>(), [
+        State::VertexShader, State::PixelShader, State::GeometryShader, State::BlendState, State::BlendFactor, State::SampleMask, State::RasterizerState,
+    ]);
+    let a = &p0.assignments;
+    assert_eq!(a[0].value, name("vs"));
+    assert_eq!(shader(&a[1].value).bytecode.as_deref(), Some(PS_COLORS));
+    assert_eq!(constants(&a[2].value), [Constant::Int(0)]);
+    assert_eq!(a[3].value, name("AlphaBlend"));
+    assert_eq!(constants(&a[4].value), [Constant::Float(0.0), Constant::Float(0.5), Constant::Float(1.0), Constant::Float(1.0)]);
+    assert_eq!(constants(&a[5].value), [Constant::UInt(0xFFFF_FFFF)]);
+    assert_eq!(a[6].value, name("Wireframe"));
+}
+
+#[test] fn fx_4_1 () {
+    assert_eq!(dxbc::verify_checksum(FX_4_1), Ok(true));
+    let effect = Effect::parse(FX_4_1).unwrap();
+    assert_eq!(effect.version, EffectVersion::Fx4_1);
+
+    // PixelShader shaders[2] = { NULL, CompileShader(...) };
+    let array = effect.object("shaders").unwrap();
+    assert_eq!(array.ty.elements, 2);
+    assert_eq!(shaders(array).iter().map(|s| s.bytecode.as_deref()).collect::<Vec<_>>(), [None, Some(PS_COLORS)]);
+
+    // GeometryShader so = ConstructGSWithSO(..., "SV_POSITION.xyzw; TEXCOORD0.xy");
+    let so = effect.object("so").unwrap();
+    assert_eq!(so.ty.class, TypeClass::Object(ObjectType::GeometryShaderSO));
+    assert_eq!(shaders(so)[0].so_declarations, ["SV_POSITION.xyzw; TEXCOORD0.xy"]);
+    assert_eq!(stage(shaders(so)[0].bytecode.as_deref().unwrap()), (ProgramType::Geometry, 4, 0));
+    assert_eq!(states(effect.object("NoDepth").unwrap()), [(State::DepthEnable, 0), (State::DepthWriteMask, 0)]);
+
+    let indexed = effect.technique("Indexed").unwrap();
+    assert_eq!(indexed.passes.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["Const", "Variable", "Expression"]);
+    let value = |pass: &str, i: usize| indexed.pass(pass).unwrap().assignments[i].value.clone();
+    assert_eq!(value("Const", 0), AssignmentValue::ConstIndex { array: "shaders".to_string(), index: 1 });
+    assert_eq!(value("Const", 1), name("NoDepth"));
+    assert_eq!(constants(&value("Const", 2)), [Constant::UInt(7)]);
+    assert_eq!(value("Variable", 0), AssignmentValue::VariableIndex { array: "shaders".to_string(), index_variable: "which".to_string() });
+    assert_eq!(value("Expression", 0), AssignmentValue::ExpressionIndex { array: "shaders".to_string(), code: b"FXLVM\0\0\0".to_vec() });
+    assert_eq!(value("Expression", 1), AssignmentValue::Expression(b"FXLVM\0\0\0".to_vec()));
+}
+
+#[test] fn fx_5_0 () {
+    assert_eq!(dxbc::verify_checksum(FX_5_0), Ok(true));
+    let effect = Effect::parse(FX_5_0).unwrap();
+    assert_eq!(effect.version, EffectVersion::Fx5_0);
+
+    // cbuffer Lights : register(b2) { Light light; }
+    let lights = &effect.constant_buffers[0];
+    assert_eq!((lights.name.as_str(), lights.explicit_bind_point), ("Lights", Some(2)));
+    let light = effect.variable("light").unwrap();
+    match light.ty.class {
+        TypeClass::Struct(ref members) => {
+            assert_eq!(members.iter().map(|m| (m.name.as_str(), m.semantic.as_deref(), m.offset, m.ty.name.as_str())).collect::<Vec<_>>(), [
+                ("direction", Some("DIRECTION"), 0, "float3"),
+                ("flags", None, 12, "uint"),
+            ]);
+        },
+        ref other => panic!("not a struct: {:?}", other),
+    }
+
+    // SBinaryShaderData5 object variables, each followed by the next variable
+    let vs = effect.object("vs").unwrap();
+    assert_eq!(vs.ty.class, TypeClass::Object(ObjectType::VertexShader5));
+    assert_eq!(shaders(vs), [Shader {
+        bytecode:           Some(VS_5_0.to_vec()),
+        so_declarations:    Vec::new(),
+        rasterized_stream:  0,
+        interface_bindings: vec![
+            InterfaceInitializer { instance: "ambient".to_string(), index: 0 },
+            InterfaceInitializer { instance: "suns".to_string(), index: 2 },
+        ],
+    }]);
+    let gs = &shaders(effect.object("gs").unwrap())[0];
+    assert_eq!(gs.so_declarations, ["SV_POSITION.xyzw", "TEXCOORD0.xy"]);
+    assert_eq!(gs.rasterized_stream, 1);
+    assert_eq!(stage(gs.bytecode.as_deref().unwrap()), (ProgramType::Geometry, 5, 0));
+
+    // ILight sun = suns[1];
+    assert_eq!(effect.interfaces.len(), 1);
+    let sun = &effect.interfaces[0];
+    assert_eq!((sun.name.as_str(), sun.ty.class.clone()), ("sun", TypeClass::Interface));
+    assert_eq!(sun.initializers, [InterfaceInitializer { instance: "suns".to_string(), index: 1 }]);
+
+    // A technique outside any fxgroup, then fxgroup Main < int Order = 1; > { technique11 Draw { ... } }
+    assert_eq!(effect.groups.iter().map(|g| g.name.as_deref()).collect::<Vec<_>>(), [None, Some("Main")]);
+    assert_eq!(effect.techniques().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["Loose", "Draw"]);
+    let main = effect.group("Main").unwrap();
+    assert_eq!(main.annotations[0].value, AnnotationValue::Numeric(1i32.to_le_bytes().to_vec()));
+
+    let compute = &effect.technique("Loose").unwrap().passes[0].assignments[0];
+    assert_eq!(compute.state, State::ComputeShader);
+    let cs = shader(&compute.value).bytecode.as_deref().unwrap();
+    assert_eq!(stage(cs), (ProgramType::Compute, 5, 0));
+    let cs = Container::parse(cs).unwrap();
+    assert_eq!(dxbc::thread_group_size(&cs).unwrap(), Some([8, 8, 1]));
+
+    // The inline pixel shader's SBinaryShaderData5 ends the blob, so reading past its 9 dwords would fail
+    let draw = &effect.technique("Draw").unwrap().passes[0].assignments;
+    assert_eq!(draw.iter().map(|a| a.state).collect::<Vec<_>>(), [State::VertexShader, State::HullShader, State::DomainShader, State::PixelShader]);
+    assert_eq!(draw[0].value, name("vs"));
+    assert_eq!(constants(&draw[1].value), [Constant::Int(0)]);
+    assert_eq!(shader(&draw[3].value).bytecode.as_deref(), Some(PS_5_0));
+}
+
+/// What `data/fx/` can't be:  `D3DCompile`'s own `fx_*` output.  Every target is deprecated, but `d3dcompiler_47` still
+/// compiles them.
+#[cfg(windows)]
+#[test] fn matches_d3dcompile () {
+    use rust_win32_d3d11::com::d3d::{self, MagicConstantInclude};
+    use std::ffi::CStr;
+
+    for &(target, shader_model, technique, version) in [
+        ("fx_4_0\0", "4_0", "technique10", EffectVersion::Fx4_0),
+        ("fx_4_1\0", "4_1", "technique10", EffectVersion::Fx4_1),
+        ("fx_5_0\0", "5_0", "technique11", EffectVersion::Fx5_0),
+    ].iter() {
+        let source = format!("
+            cbuffer Constants {{ float4x4 world; float4 tint : COLOR = float4(1, 0.5, 0.25, 1); }};
+            float4 vs_main (float4 pos : POSITION) : SV_POSITION {{ return mul(pos, world); }}
+            float4 ps_main () : SV_Target {{ return tint; }}
+            VertexShader vs = CompileShader(vs_{0}, vs_main());
+            {1} Render {{ pass P0 {{ SetVertexShader(vs); SetPixelShader(CompileShader(ps_{0}, ps_main())); }} }}
+        ", shader_model, technique);
+        let target = CStr::from_bytes_with_nul(target.as_bytes()).unwrap();
+        let compiled = unsafe { d3d::compile_raw::<MagicConstantInclude>(source.as_bytes(), None, None, None, None, target, 0, 0) }
+            .unwrap_or_else(|e| panic!("{:?}: {}", target, e.errors.map_or(String::new(), |blob| blob.to_string_lossy())));
+        let effect = Effect::parse(compiled.shader.as_bytes()).unwrap();
+        assert_eq!(effect.version, version, "{:?}", target);
+
+        let tint = effect.variable("tint").unwrap();
+        assert_eq!((tint.semantic.as_deref(), tint.offset, tint.ty.total_size), (Some("COLOR"), 64, 16), "{:?}", target);
+        let floats = [1.0f32, 0.5, 0.25, 1.0].iter().flat_map(|f| f.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+        assert_eq!(tint.default_value.as_ref(), Some(&floats), "{:?}", target);
+
+        let vs = effect.object("vs").unwrap();
+        let vs = shaders(vs)[0].bytecode.as_deref().unwrap();
+        assert_eq!(stage(vs).0, ProgramType::Vertex, "{:?}", target);
+
+        let p0 = effect.technique("Render").unwrap().pass("P0").unwrap();
+        let ps = p0.assignments.iter().find(|a| a.state == State::PixelShader).unwrap();
+        assert_eq!(stage(shader(&ps.value).bytecode.as_deref().unwrap()).0, ProgramType::Pixel, "{:?}", target);
+    }
+}
+
+#[test] fn errors () {
+    assert_eq!(Effect::parse(b"not an effect"), Err(Error::NotDxbc));
+    assert_eq!(Effect::parse(VS_9_1), Err(Error::MissingChunk(FourCC::FX10)));
+    assert_eq!(Effect::parse_chunk(&0xFEFF_3001u32.to_le_bytes()), Err(Error::Unsupported("not an effect, or an unknown effect version")));
+
+    for &bytecode in [FX_4_0, FX_4_1, FX_5_0].iter() {
+        let chunk = Container::parse(bytecode).unwrap().chunk(FourCC::FX10).unwrap().data;
+        for len in [0, 4, 40, chunk.len() / 2, chunk.len() - 4].iter() {
+            assert!(Effect::parse_chunk(&chunk[..*len]).is_err(), "truncated to {} bytes", len);
+        }
+
+        // Shared variables from an effect pool
+        let mut pool = chunk.to_vec();
+        pool[16..20].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(Effect::parse_chunk(&pool), Err(Error::Unsupported("effect pools")));
+    }
+}
@@ -0,0 +1,74 @@
+//! `leak_tracker` against Rust implemented `Unknown`s.  A separate test binary, so no other test's references show
+//! up in the tracker.  Run with `cargo test --features leak-tracking`.
+
+#![cfg(feature = "leak-tracking")]
+
+use rust_win32_d3d11::com::{Object, Payload, Unknown, Wrapper};
+use rust_win32_d3d11::com::leak_tracker::*;
+use std::panic::catch_unwind;
+use std::sync::Mutex;
+
+/// The tracker is global, so tests that look at it take turns.
+static SERIAL : Mutex<()> = Mutex::new(());
+
+fn serial () -> std::sync::MutexGuard<'static, ()> { SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) }
+
+fn unknown () -> Unknown { Object::new(Payload(())) }
+
+fn live_count (checkpoint: Checkpoint) -> usize { live_since(checkpoint).iter().map(|l| l.count).sum() }
+
+#[test]
+fn checkpoint_and_live_since () {
+    let _serial = serial();
+    let before = unknown();
+    let checkpoint = checkpoint();
+    assert_eq!(live_count(checkpoint), 0);
+
+    let a = unknown();
+    let a2 = a.clone();
+    let b = unknown();
+    assert!(live_since(checkpoint).iter().all(|l| l.type_name == "Unknown"));
+    assert_eq!(live_count(checkpoint), 3);
+
+    drop(a2);
+    assert_eq!(live_count(checkpoint), 2);
+    drop(a);
+    drop(b);
+    assert_eq!(live_count(checkpoint), 0);
+
+    assert!(live().iter().any(|l| l.type_name == "Unknown")); // `before`
+    drop(before);
+}
+
+#[test]
+fn expect_none_live_since_passes () {
+    let _serial = serial();
+    let checkpoint = checkpoint();
+    let a = unknown();
+    let b = a.cast::<Unknown>().unwrap();
+    drop(a);
+    drop(b);
+    expect_none_live_since(checkpoint);
+}
+
+#[test]
+fn expect_none_live_since_reports_leaks () {
+    let _serial = serial();
+    let checkpoint = checkpoint();
+    let leaked = unknown();
+    assert!(catch_unwind(|| expect_none_live_since(checkpoint)).is_err());
+    drop(leaked);
+    expect_none_live_since(checkpoint);
+}
+
+#[test]
+fn untrack_untracked_is_ignored () {
+    let _serial = serial();
+    let checkpoint = checkpoint();
+    untrack("Unknown", 0x1234);
+    let a = unknown();
+    untrack("Unknown", a.as_ptr() as usize + 1);
+    assert_eq!(live_count(checkpoint), 1);
+    drop(a);
+    assert_eq!(live_count(checkpoint), 0);
+}
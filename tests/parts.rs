@@ -0,0 +1,120 @@
+//! `dxbc::strip`, `dxbc::get_part`, and `dxbc::set_part`, against bytecode from `fxc` (see `data/README.md`).
+
+use rust_win32_d3d11::dxbc::{self, BlobPart, Container, Error, FourCC};
+
+const VS_9_1        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_1.dxbc");
+const PS_COLORS     : &[u8] = include_bytes!("data/fxc/sdl_ps_colors_9_1.dxbc");
+const PS_NV12       : &[u8] = include_bytes!("data/fxc/sdl_ps_nv12_bt601_9_3.dxbc");
+const ALL           : &[&[u8]] = &[VS_9_1, PS_COLORS, PS_NV12];
+
+fn fourccs (bytes: &[u8]) -> Vec<FourCC> {
+    Container::parse(bytes).unwrap().chunks().iter().map(|c| c.fourcc).collect()
+}
+
+fn chunk (bytes: &[u8], fourcc: FourCC) -> Vec<u8> {
+    Container::parse(bytes).unwrap().chunk(fourcc).unwrap().data.to_vec()
+}
+
+#[test] fn strip () {
+    for &bytecode in ALL {
+        let stripped = dxbc::strip(bytecode, dxbc::STRIP_REFLECTION_DATA).unwrap();
+        assert_eq!(fourccs(&stripped), [FourCC::AON9, FourCC::SHDR, FourCC::ISGN, FourCC::OSGN]);
+        assert_eq!(dxbc::verify_checksum(&stripped), Ok(true));
+        assert_eq!(chunk(&stripped, FourCC::SHDR), chunk(bytecode, FourCC::SHDR));
+        assert_eq!(&stripped[20..24], &bytecode[20..24], "header version");
+
+        // Nothing to strip:  the same bytes back
+        let debug = dxbc::STRIP_DEBUG_INFO | dxbc::STRIP_PRIVATE_DATA | dxbc::STRIP_TEST_BLOBS | dxbc::STRIP_ROOT_SIGNATURE;
+        assert_eq!(dxbc::strip(bytecode, debug).unwrap(), bytecode);
+        assert_eq!(dxbc::strip(bytecode, 0).unwrap(), bytecode);
+    }
+}
+
+#[test] fn get_signatures () {
+    for &bytecode in ALL {
+        let input = dxbc::get_part(bytecode, BlobPart::InputSignature).unwrap();
+        assert_eq!(fourccs(&input), [FourCC::ISGN]);
+        assert_eq!(chunk(&input, FourCC::ISGN), chunk(bytecode, FourCC::ISGN));
+        assert_eq!(dxbc::verify_checksum(&input), Ok(true));
+
+        let output = dxbc::get_part(bytecode, BlobPart::OutputSignature).unwrap();
+        assert_eq!(fourccs(&output), [FourCC::OSGN]);
+        assert_eq!(dxbc::verify_checksum(&output), Ok(true));
+
+        let both = dxbc::get_part(bytecode, BlobPart::InputAndOutputSignature).unwrap();
+        assert_eq!(fourccs(&both), [FourCC::ISGN, FourCC::OSGN]);
+        assert_eq!(dxbc::verify_checksum(&both), Ok(true));
+    }
+}
+
+#[test] fn get_missing () {
+    for &bytecode in ALL {
+        // Every slot has to be there, not just some of them
+        assert_eq!(dxbc::get_part(bytecode, BlobPart::AllSignature),            Err(Error::MissingChunk(FourCC::PCSG)));
+        assert_eq!(dxbc::get_part(bytecode, BlobPart::PatchConstantSignature),  Err(Error::MissingChunk(FourCC::PCSG)));
+        assert_eq!(dxbc::get_part(bytecode, BlobPart::DebugInfo),               Err(Error::MissingChunk(FourCC::SDBG)));
+        assert_eq!(dxbc::get_part(bytecode, BlobPart::PrivateData),             Err(Error::MissingChunk(FourCC::PRIV)));
+    }
+
+    let no_input = dxbc::write_container(1, &Container::parse(VS_9_1).unwrap().chunks().iter().cloned().filter(|c| c.fourcc != FourCC::ISGN).collect::<Vec<_>>());
+    assert_eq!(dxbc::get_part(&no_input, BlobPart::InputAndOutputSignature), Err(Error::MissingChunk(FourCC::ISGN)));
+    assert_eq!(dxbc::get_part(&no_input, BlobPart::OutputSignature).map(|o| fourccs(&o)), Ok(vec![FourCC::OSGN]));
+
+    assert_eq!(dxbc::get_part(b"not a container", BlobPart::InputSignature), Err(Error::NotDxbc));
+}
+
+#[test] fn get_legacy_shader () {
+    for &bytecode in ALL {
+        // Not a container, just the chunk's data
+        assert_eq!(dxbc::get_part(bytecode, BlobPart::LegacyShader).unwrap(), chunk(bytecode, FourCC::AON9));
+    }
+}
+
+#[test] fn set_private_data () {
+    for &bytecode in ALL {
+        let set = dxbc::set_part(bytecode, BlobPart::PrivateData, b"private").unwrap();
+        assert_eq!(fourccs(&set), [FourCC::AON9, FourCC::SHDR, FourCC::STAT, FourCC::RDEF, FourCC::ISGN, FourCC::OSGN, FourCC::PRIV]);
+        assert_eq!(dxbc::verify_checksum(&set), Ok(true));
+        assert_eq!(dxbc::get_part(&set, BlobPart::PrivateData).unwrap(), b"private");
+
+        // Replaced, not appended twice
+        let replaced = dxbc::set_part(&set, BlobPart::PrivateData, b"replaced").unwrap();
+        assert_eq!(fourccs(&replaced).len(), 7);
+        assert_eq!(dxbc::verify_checksum(&replaced), Ok(true));
+        assert_eq!(dxbc::get_part(&replaced, BlobPart::PrivateData).unwrap(), b"replaced");
+
+        // And stripped again
+        assert_eq!(dxbc::strip(&replaced, dxbc::STRIP_PRIVATE_DATA).unwrap(), bytecode);
+    }
+}
+
+#[test] fn set_root_signature () {
+    let set = dxbc::set_part(PS_COLORS, BlobPart::RootSignature, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(dxbc::verify_checksum(&set), Ok(true));
+    assert_eq!(dxbc::get_part(&set, BlobPart::RootSignature).unwrap(), [1, 2, 3, 4]);
+    assert_eq!(dxbc::strip(&set, dxbc::STRIP_ROOT_SIGNATURE).unwrap(), PS_COLORS);
+}
+
+#[test] fn set_unsupported () {
+    for &part in [BlobPart::InputSignature, BlobPart::AllSignature, BlobPart::DebugInfo, BlobPart::LegacyShader, BlobPart::Pdb].iter() {
+        assert!(matches!(dxbc::set_part(VS_9_1, part, b""), Err(Error::Unsupported(_))), "{:?}", part);
+    }
+}
+
+/// The pure Rust versions against `D3DCompiler_47.dll`'s.
+#[cfg(windows)] #[test] fn matches_d3dcompiler () {
+    use rust_win32_d3d11::com::d3d;
+    for &bytecode in ALL {
+        for &flags in [0, dxbc::STRIP_REFLECTION_DATA, dxbc::STRIP_DEBUG_INFO].iter() {
+            assert_eq!(dxbc::strip(bytecode, flags).unwrap(), d3d::strip_shader(bytecode, flags).unwrap().as_bytes(), "flags: {}", flags);
+        }
+        for &part in [BlobPart::InputSignature, BlobPart::OutputSignature, BlobPart::InputAndOutputSignature, BlobPart::LegacyShader].iter() {
+            assert_eq!(dxbc::get_part(bytecode, part).unwrap(), d3d::get_blob_part(bytecode, part).unwrap().as_bytes(), "{:?}", part);
+        }
+        assert!(dxbc::get_part(bytecode, BlobPart::AllSignature).is_err());
+        assert!(d3d::get_blob_part(bytecode, BlobPart::AllSignature).is_err());
+
+        let private = d3d::set_blob_part(bytecode, BlobPart::PrivateData, b"private").unwrap();
+        assert_eq!(dxbc::set_part(bytecode, BlobPart::PrivateData, b"private").unwrap(), private.as_bytes());
+    }
+}
@@ -0,0 +1,40 @@
+//! `res/precompiled`, which `build.rs` embeds when `D3DCompile` isn't available:  one `.dxbc` per manifest entry, for
+//! the right target, with signatures that link, compiled from the current sources.
+
+use rust_win32_d3d11::com::d3d::{self, Target};
+use rust_win32_d3d11::dxbc;
+use rust_win32_d3d11::shader_manifest;
+use std::fs;
+use std::path::Path;
+
+fn precompiled (name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/precompiled").join(name).with_extension("dxbc");
+    fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+#[test] fn every_entry () {
+    let entries = shader_manifest::parse(include_str!("../res/shaders.manifest")).unwrap();
+    assert!(!entries.is_empty());
+    for entry in entries.iter() {
+        let bytecode = precompiled(&entry.name);
+        assert_eq!(dxbc::verify_checksum(&bytecode), Ok(true), "{}", entry.name);
+        assert_eq!(d3d::bytecode_target(&bytecode), Ok(Target::parse(&entry.target).unwrap()), "{}", entry.name);
+    }
+}
+
+#[test] fn up_to_date () {
+    let res = Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
+    let entries = shader_manifest::parse(include_str!("../res/shaders.manifest")).unwrap();
+    let hashes : Vec<_> = entries.iter().map(|entry| (&entry.name[..], shader_manifest::source_hash(entry, &fs::read(res.join(&entry.source)).unwrap()[..]))).collect();
+    let recorded = fs::read_to_string(res.join("precompiled/sources.txt")).unwrap().replace('\r', "");
+    assert_eq!(recorded, shader_manifest::sources_txt(&hashes[..]), "res/precompiled is stale, see its README.md");
+}
+
+#[test] fn signatures () {
+    let vs = dxbc::reflect(&precompiled("vs")).unwrap();
+    let ps = dxbc::reflect(&precompiled("ps")).unwrap();
+    let semantics = |elements: &[dxbc::SignatureElement]| -> Vec<(String, u32)> { elements.iter().map(|e| (e.semantic_name.to_uppercase(), e.register)).collect() };
+    assert_eq!(semantics(&vs.input_signature),  vec![(String::from("POSITION"), 0)]);
+    assert_eq!(semantics(&vs.output_signature), semantics(&ps.input_signature));
+    assert_eq!(semantics(&ps.output_signature), vec![(String::from("SV_TARGET"), 0)]);
+}
@@ -0,0 +1,187 @@
+//! `dxbc::reflect` against bytecode from `fxc` (see `data/README.md`), and against `D3DReflect` on Windows.
+
+use rust_win32_d3d11::dxbc::{self, Chunk, Container, FourCC, ProgramType, ShaderReflection};
+
+const VS_9_1        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_1.dxbc");
+const VS_9_3        : &[u8] = include_bytes!("data/fxc/sdl_vs_9_3.dxbc");
+const PS_COLORS     : &[u8] = include_bytes!("data/fxc/sdl_ps_colors_9_1.dxbc");
+const PS_TEXTURES   : &[u8] = include_bytes!("data/fxc/sdl_ps_textures_9_1.dxbc");
+const PS_YUV        : &[u8] = include_bytes!("data/fxc/sdl_ps_yuv_bt601_9_1.dxbc");
+const PS_NV12       : &[u8] = include_bytes!("data/fxc/sdl_ps_nv12_bt601_9_3.dxbc");
+const ALL           : &[&[u8]] = &[VS_9_1, VS_9_3, PS_COLORS, PS_TEXTURES, PS_YUV, PS_NV12];
+
+// D3D_* enum values, as stored in the reflection structs.
+const D3D_CT_CBUFFER                  : u32 = 0;
+const D3D_SVC_MATRIX_ROWS             : u32 = 2;
+const D3D_SVT_FLOAT                   : u32 = 3;
+const D3D_SVF_USED                    : u32 = 2;
+const D3D_SIT_CBUFFER                 : u32 = 0;
+const D3D_SIT_TEXTURE                 : u32 = 2;
+const D3D_SIT_SAMPLER                 : u32 = 3;
+const D3D_RETURN_TYPE_FLOAT           : u32 = 5;
+const D3D_SRV_DIMENSION_TEXTURE2D     : u32 = 4;
+const D3D_SIF_USERPACKED              : u32 = 1;
+const D3D_SIF_TEXTURE_COMPONENTS      : u32 = 0xC;
+const D3D_NAME_POSITION               : u32 = 1;
+const D3D_REGISTER_COMPONENT_FLOAT32  : u32 = 3;
+
+fn reflect (bytecode: &[u8]) -> ShaderReflection { dxbc::reflect(bytecode).unwrap() }
+
+/// `(semantic, index, register, mask)`
+fn signature (elements: &[dxbc::SignatureElement]) -> Vec<(&str, u32, u32, u8)> {
+    elements.iter().map(|e| (e.semantic_name.as_str(), e.semantic_index, e.register, e.mask)).collect()
+}
+
+#[test] fn vertex_shader () {
+    for &bytecode in [VS_9_1, VS_9_3].iter() {
+        let r = reflect(bytecode);
+        assert_eq!((r.version.program, r.version.major, r.version.minor), (ProgramType::Vertex, 4, 0));
+        assert!(r.creator.starts_with("Microsoft (R) HLSL Shader Compiler"), "{:?}", r.creator);
+        assert_eq!(r.thread_group_size, None);
+
+        let cb = r.constant_buffer("VertexShaderConstants").unwrap();
+        assert_eq!((cb.kind, cb.size, cb.variables.len()), (D3D_CT_CBUFFER, 128, 2));
+        for (name, offset) in [("model", 0), ("projectionAndView", 64)].iter() {
+            let v = cb.variable(name).unwrap();
+            assert_eq!((v.offset, v.size, v.flags), (*offset, 64, D3D_SVF_USED), "{}", name);
+            assert_eq!((v.ty.class, v.ty.kind, v.ty.rows, v.ty.columns, v.ty.elements), (D3D_SVC_MATRIX_ROWS, D3D_SVT_FLOAT, 4, 4, 0), "{}", name);
+            assert!(v.ty.members.is_empty());
+        }
+
+        assert_eq!(r.bound_resources.len(), 1);
+        let b = &r.bound_resources[0];
+        assert_eq!((b.name.as_str(), b.kind, b.bind_point, b.bind_count, b.flags), ("VertexShaderConstants", D3D_SIT_CBUFFER, 0, 1, D3D_SIF_USERPACKED));
+
+        assert_eq!(signature(&r.input_signature),  vec![("POSITION", 0, 0, 0x7), ("TEXCOORD", 0, 1, 0x3), ("COLOR", 0, 2, 0xF)]);
+        assert_eq!(signature(&r.output_signature), vec![("SV_POSITION", 0, 0, 0xF), ("TEXCOORD", 0, 1, 0x3), ("COLOR", 0, 2, 0xF)]);
+        assert_eq!(r.output_signature[0].system_value, D3D_NAME_POSITION);
+        assert!(r.input_signature.iter().chain(r.output_signature.iter()).all(|e| e.component_type == D3D_REGISTER_COMPONENT_FLOAT32));
+    }
+}
+
+#[test] fn pixel_shaders () {
+    let textures = |r: &ShaderReflection| -> Vec<(String, u32)> {
+        r.bound_resources.iter().filter(|b| b.kind == D3D_SIT_TEXTURE).map(|b| (b.name.clone(), b.bind_point)).collect()
+    };
+    let named = |names: &[(&str, u32)]| -> Vec<(String, u32)> { names.iter().map(|(n, p)| (n.to_string(), *p)).collect() };
+
+    let colors = reflect(PS_COLORS);
+    assert_eq!((colors.version.program, colors.version.major, colors.version.minor), (ProgramType::Pixel, 4, 0));
+    assert!(colors.constant_buffers.is_empty());
+    assert!(colors.bound_resources.is_empty());
+
+    assert_eq!(textures(&reflect(PS_TEXTURES)), named(&[("theTexture", 0)]));
+    assert_eq!(textures(&reflect(PS_YUV)),      named(&[("theTextureY", 0), ("theTextureU", 1), ("theTextureV", 2)]));
+    assert_eq!(textures(&reflect(PS_NV12)),     named(&[("theTextureY", 0), ("theTextureUV", 1)]));
+
+    for &bytecode in [PS_COLORS, PS_TEXTURES, PS_YUV, PS_NV12].iter() {
+        let r = reflect(bytecode);
+        assert_eq!(signature(&r.input_signature),  vec![("SV_POSITION", 0, 0, 0xF), ("TEXCOORD", 0, 1, 0x3), ("COLOR", 0, 2, 0xF)]);
+        assert_eq!(signature(&r.output_signature), vec![("SV_TARGET", 0, 0, 0xF)]);
+
+        for b in r.bound_resources.iter() {
+            match b.kind {
+                D3D_SIT_SAMPLER => assert_eq!((b.name.as_str(), b.bind_point, b.bind_count), ("theSampler", 0, 1)),
+                D3D_SIT_TEXTURE => {
+                    assert_eq!((b.return_type, b.dimension, b.bind_count), (D3D_RETURN_TYPE_FLOAT, D3D_SRV_DIMENSION_TEXTURE2D, 1), "{}", b.name);
+                    assert_eq!(b.flags, D3D_SIF_USERPACKED | D3D_SIF_TEXTURE_COMPONENTS, "{}", b.name);
+                },
+                other => panic!("unexpected resource {:?} of kind {}", b.name, other),
+            }
+        }
+    }
+}
+
+#[test] fn version_from_container () {
+    for &bytecode in ALL.iter() {
+        let container = Container::parse(bytecode).unwrap();
+        assert_eq!(dxbc::ShaderVersion::from_container(&container).unwrap(), reflect(bytecode).version);
+    }
+}
+
+/// `cs_5_0` bytecode with an immediate constant buffer (a `customdata` block) ahead of `dcl_thread_group`, as `fxc`
+/// emits for `static const` arrays indexed at runtime.  Hand assembled:  no compiler available here emits SM5.
+fn compute_shader (numthreads: [u32; 3]) -> Vec<u8> {
+    let mut shex = vec![
+        0x0005_0050,                    // cs_5_0
+        0,                              // Length, patched below
+        0x0100_086A,                    // dcl_globalFlags refactoringAllowed
+        0x0000_1835, 2 + 4 * 2,         // customdata (immediate constant buffer), 2 float4s
+        0x3F80_0000, 0, 0, 0,
+        0x4000_0000, 0, 0, 0,
+        0x0400_009B,                    // dcl_thread_group
+        numthreads[0], numthreads[1], numthreads[2],
+        0x0100_003E,                    // ret
+    ];
+    shex[1] = shex.len() as u32;
+    let shex = shex.iter().flat_map(|dword| dword.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+    dxbc::write_container(1, &[Chunk { fourcc: FourCC::SHEX, data: &shex[..] }])
+}
+
+#[test] fn thread_group_size_after_customdata () {
+    let bytecode = compute_shader([8, 4, 2]);
+    let container = Container::parse(&bytecode).unwrap();
+    assert_eq!(dxbc::thread_group_size(&container).unwrap(), Some([8, 4, 2]));
+}
+
+#[test] fn no_thread_group_size () {
+    for &bytecode in ALL.iter() {
+        assert_eq!(dxbc::thread_group_size(&Container::parse(bytecode).unwrap()).unwrap(), None);
+    }
+    let no_bytecode = dxbc::write_container(1, &[]);
+    assert_eq!(dxbc::thread_group_size(&Container::parse(&no_bytecode).unwrap()).unwrap(), None);
+}
+
+#[test] fn malformed () {
+    assert!(dxbc::reflect(b"not a shader").is_err());
+    for &bytecode in ALL.iter() {
+        for len in [0, 4, 31, bytecode.len() / 2, bytecode.len() - 1].iter() {
+            assert!(dxbc::reflect(&bytecode[..*len]).is_err(), "truncated to {} bytes", len);
+        }
+    }
+}
+
+#[cfg(windows)]
+#[test] fn matches_d3dreflect () {
+    for &bytecode in ALL.iter() {
+        assert_eq!(reflect(bytecode), rust_win32_d3d11::com::d3d::reflect(bytecode).unwrap());
+    }
+}
+
+/// Every blob under `data/fxc` is `level_9`, so shader model 5's `RD11` layout (larger variable and type
+/// descriptions, with type names) is checked against `D3DCompile`d `cs_5_0` here.
+#[cfg(windows)]
+#[test] fn rd11_matches_d3dreflect () {
+    use rust_win32_d3d11::com::d3d::{self, Bytecode, CompileOptions, ComputeBytecode, MagicConstantInclude, Target};
+    use std::ffi::CStr;
+
+    let source = b"
+        struct Light { float3 color; float intensity; float3 direction; };
+        cbuffer Lights : register(b2) { float4x4 view; Light lights[2]; uint count; };
+        RWStructuredBuffer<float4> output : register(u1);
+        [numthreads(8, 4, 1)] void main (uint3 id : SV_DispatchThreadID) {
+            float4 sum = 0;
+            for (uint i = 0; i < count; ++i) { sum += mul(view, float4(lights[i].color * lights[i].intensity, 1)); }
+            output[id.x] = sum;
+        }
+    ";
+    let main = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let compiled = unsafe { d3d::compile::<ComputeBytecode, MagicConstantInclude>(source, None, None, None, main, Target::cs_5_0, &CompileOptions::default()) }
+        .unwrap_or_else(|e| panic!("{}", e.errors.map_or(String::new(), |blob| blob.to_string_lossy())));
+    let bytecode = compiled.shader.as_bytes();
+    let container = Container::parse(bytecode).unwrap();
+    assert_eq!(container.chunk(FourCC::RDEF).map(|rdef| &rdef.data[28..32]), Some(&b"RD11"[..]));
+
+    let reflection = reflect(bytecode);
+    assert_eq!(reflection, d3d::reflect(bytecode).unwrap());
+    assert_eq!((reflection.version.program, reflection.version.major, reflection.version.minor), (ProgramType::Compute, 5, 0));
+    assert_eq!(reflection.thread_group_size, Some([8, 4, 1]));
+
+    let lights = reflection.constant_buffer("Lights").unwrap();
+    let layout : Vec<_> = lights.variables.iter().map(|v| (v.name.as_str(), v.offset, v.size, v.ty.name.as_deref())).collect();
+    assert_eq!(layout, [("view", 0, 64, Some("float4x4")), ("lights", 64, 60, Some("Light")), ("count", 124, 4, Some("uint"))]);
+    let light = &lights.variable("lights").unwrap().ty;
+    assert_eq!(light.elements, 2);
+    let members : Vec<_> = light.members.iter().map(|m| (m.name.as_str(), m.offset, m.ty.name.as_deref())).collect();
+    assert_eq!(members, [("color", 0, Some("float3")), ("intensity", 12, Some("float")), ("direction", 16, Some("float3"))]);
+}